@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+/// One run as seen by the sensitivity analysis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// Numeric panel values the run used.
+    pub inputs: Vec<(String, f64)>,
+    /// The parsed output, `None` for failed runs or output without a number.
+    pub output: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableStats {
+    pub name: String,
+    /// Runs with both this variable and an output.
+    pub runs: usize,
+    /// Pearson correlation with the output, `None` when either is constant.
+    pub correlation: Option<f64>,
+    /// Largest output spread among runs where only this variable changed.
+    pub isolated_effect: Option<f64>,
+    /// How many groups of runs had every other variable fixed.
+    pub isolated_groups: usize,
+    /// `(value, output)` pairs sorted by value, for plotting.
+    pub points: Vec<(f64, f64)>,
+}
+
+impl VariableStats {
+    pub fn is_constant(&self) -> bool {
+        self.points.windows(2).all(|pair| pair[0].0 == pair[1].0)
+    }
+
+    /// Ranking key: the isolated effect when the data has one, otherwise
+    /// the strength of the correlation.
+    fn score(&self) -> f64 {
+        self.isolated_effect
+            .or(self.correlation.map(f64::abs))
+            .unwrap_or(0.0)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Most influential first; constant variables last.
+    pub variables: Vec<VariableStats>,
+    /// Runs left out because they failed or printed no number.
+    pub skipped: usize,
+    /// Why the numbers may mislead, in plain words.
+    pub limitations: Vec<String>,
+}
+
+/// Per-variable statistics over `samples`.
+pub fn sensitivity(samples: &[Sample]) -> Report {
+    let usable: Vec<(&Sample, f64)> = samples
+        .iter()
+        .filter_map(|sample| sample.output.map(|output| (sample, output)))
+        .collect();
+    let mut report = Report {
+        skipped: samples.len() - usable.len(),
+        ..Report::default()
+    };
+    if usable.len() < 2 {
+        report
+            .limitations
+            .push("At least two successful runs are needed.".to_string());
+        return report;
+    }
+
+    let mut names: Vec<&str> = Vec::new();
+    for (sample, _) in &usable {
+        for (name, _) in &sample.inputs {
+            if !names.contains(&name.as_str()) {
+                names.push(name);
+            }
+        }
+    }
+
+    for name in &names {
+        let mut points: Vec<(f64, f64)> = usable
+            .iter()
+            .filter_map(|(sample, output)| Some((input(sample, name)?, *output)))
+            .collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (isolated_effect, isolated_groups) = isolated_effect(&usable, name);
+        report.variables.push(VariableStats {
+            name: name.to_string(),
+            runs: points.len(),
+            correlation: correlation(&points),
+            isolated_effect,
+            isolated_groups,
+            points,
+        });
+    }
+    report.variables.sort_by(|a, b| {
+        a.is_constant()
+            .cmp(&b.is_constant())
+            .then(b.score().total_cmp(&a.score()))
+    });
+
+    for stats in &report.variables {
+        if !stats.is_constant() && stats.isolated_groups == 0 {
+            report.limitations.push(format!(
+                "`{}` never changed on its own, so its correlation may come from \
+                 variables that changed with it.",
+                stats.name
+            ));
+        }
+    }
+    for (index, a) in names.iter().enumerate() {
+        for b in &names[index + 1..] {
+            let pairs: Vec<(f64, f64)> = usable
+                .iter()
+                .filter_map(|(sample, _)| Some((input(sample, a)?, input(sample, b)?)))
+                .collect();
+            if correlation(&pairs).is_some_and(|r| r.abs() > 0.95) {
+                report.limitations.push(format!(
+                    "`{}` and `{}` moved together, so their effects can't be told apart.",
+                    a, b
+                ));
+            }
+        }
+    }
+    if usable.len() < 5 {
+        report.limitations.push(format!(
+            "Only {} runs: treat the ranking as a hint.",
+            usable.len()
+        ));
+    }
+    report
+}
+
+fn input(sample: &Sample, name: &str) -> Option<f64> {
+    sample
+        .inputs
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, value)| *value)
+}
+
+/// Values of every variable but one, as bits so they can be hashed.
+type Others = Vec<(String, u64)>;
+
+/// Groups runs by the values of every other variable and returns the largest
+/// output spread within a group where `name` took different values.
+fn isolated_effect(usable: &[(&Sample, f64)], name: &str) -> (Option<f64>, usize) {
+    let mut groups: HashMap<Others, Vec<(f64, f64)>> = HashMap::new();
+    for (sample, output) in usable {
+        let Some(value) = input(sample, name) else {
+            continue;
+        };
+        let mut others: Others = sample
+            .inputs
+            .iter()
+            .filter(|(n, _)| n != name)
+            .map(|(n, v)| (n.clone(), v.to_bits()))
+            .collect();
+        others.sort();
+        groups.entry(others).or_default().push((value, *output));
+    }
+
+    let mut effect: Option<f64> = None;
+    let mut count = 0;
+    for runs in groups.values() {
+        if runs.iter().all(|(value, _)| *value == runs[0].0) {
+            continue;
+        }
+        count += 1;
+        let (min, max) = runs
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), (_, out)| {
+                (min.min(*out), max.max(*out))
+            });
+        effect = Some(effect.map_or(max - min, |effect| effect.max(max - min)));
+    }
+    (effect, count)
+}
+
+/// Pearson correlation of `(x, y)` pairs, `None` if either side is constant.
+pub fn correlation(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (mut covariance, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        covariance += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return None;
+    }
+    Some(covariance / (var_x * var_y).sqrt())
+}
+
+/// The number printed after the last occurrence of `label`, or the last
+/// number in `output` when `label` is empty.
+pub fn parse_output(output: &str, label: &str) -> Option<f64> {
+    let text = if label.is_empty() {
+        output
+    } else {
+        &output[output.rfind(label)? + label.len()..]
+    };
+    let mut numbers = text
+        .split(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | 'e' | 'E' | '+')))
+        .filter_map(|word| word.parse::<f64>().ok())
+        .filter(|number| number.is_finite());
+    if label.is_empty() {
+        numbers.next_back()
+    } else {
+        numbers.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(inputs: &[(&str, f64)], output: Option<f64>) -> Sample {
+        Sample {
+            inputs: inputs
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect(),
+            output,
+        }
+    }
+
+    /// Every combination of `a` and `b` in 1..=5, with a constant `c`.
+    fn grid(output: impl Fn(f64, f64) -> f64) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        for a in 1..=5 {
+            for b in 1..=5 {
+                let (a, b) = (a as f64, b as f64);
+                samples.push(sample(
+                    &[("a", a), ("b", b), ("c", 7.0)],
+                    Some(output(a, b)),
+                ));
+            }
+        }
+        samples
+    }
+
+    fn stats<'a>(report: &'a Report, name: &str) -> &'a VariableStats {
+        report.variables.iter().find(|v| v.name == name).unwrap()
+    }
+
+    fn close(value: Option<f64>, expected: f64) -> bool {
+        value.is_some_and(|value| (value - expected).abs() < 1e-9)
+    }
+
+    #[test]
+    fn the_variable_that_matters_ranks_first() {
+        let report = sensitivity(&grid(|a, _| 3.0 * a + 1.0));
+        let order: Vec<&str> = report.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(order, vec!["a", "b", "c"]);
+
+        let a = stats(&report, "a");
+        assert_eq!(a.runs, 25);
+        assert!(close(a.correlation, 1.0));
+        assert!(close(a.isolated_effect, 12.0));
+        assert_eq!(a.isolated_groups, 5);
+
+        let b = stats(&report, "b");
+        assert!(close(b.correlation, 0.0));
+        assert!(close(b.isolated_effect, 0.0));
+
+        let c = stats(&report, "c");
+        assert!(c.is_constant());
+        assert_eq!(c.correlation, None);
+        assert_eq!(c.isolated_effect, None);
+        assert!(report.limitations.is_empty(), "{:?}", report.limitations);
+    }
+
+    #[test]
+    fn negative_and_mixed_effects_are_measured() {
+        let report = sensitivity(&grid(|a, b| 10.0 - 2.0 * b + 0.5 * a));
+        assert_eq!(report.variables[0].name, "b");
+        assert!(close(stats(&report, "b").isolated_effect, 8.0));
+        assert!(close(stats(&report, "a").isolated_effect, 2.0));
+        let b = stats(&report, "b").correlation.unwrap();
+        let a = stats(&report, "a").correlation.unwrap();
+        assert!(b < -0.9 && a > 0.0 && a < 0.5, "{} {}", a, b);
+        assert!((a * a + b * b - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn points_are_sorted_for_plotting() {
+        let samples = vec![
+            sample(&[("x", 3.0)], Some(30.0)),
+            sample(&[("x", 1.0)], Some(10.0)),
+            sample(&[("x", 2.0)], Some(20.0)),
+        ];
+        let report = sensitivity(&samples);
+        assert_eq!(
+            report.variables[0].points,
+            vec![(1.0, 10.0), (2.0, 20.0), (3.0, 30.0)]
+        );
+    }
+
+    #[test]
+    fn failed_runs_are_skipped() {
+        let mut samples = grid(|a, _| a);
+        samples.push(sample(&[("a", 100.0), ("b", 1.0), ("c", 7.0)], None));
+        samples.push(sample(&[("a", -100.0), ("b", 1.0), ("c", 7.0)], None));
+        let report = sensitivity(&samples);
+        assert_eq!(report.skipped, 2);
+        assert_eq!(stats(&report, "a").runs, 25);
+        assert!(close(stats(&report, "a").isolated_effect, 4.0));
+    }
+
+    #[test]
+    fn too_few_runs_say_so() {
+        let report = sensitivity(&[
+            sample(&[("x", 1.0)], Some(1.0)),
+            sample(&[("x", 2.0)], None),
+        ]);
+        assert!(report.variables.is_empty());
+        assert_eq!(report.skipped, 1);
+        assert_eq!(
+            report.limitations,
+            vec!["At least two successful runs are needed."]
+        );
+
+        let report = sensitivity(&[
+            sample(&[("x", 1.0)], Some(1.0)),
+            sample(&[("x", 2.0)], Some(3.0)),
+        ]);
+        assert_eq!(
+            report.limitations,
+            vec!["Only 2 runs: treat the ranking as a hint."]
+        );
+    }
+
+    #[test]
+    fn confounded_variables_are_flagged() {
+        let samples: Vec<Sample> = (1..=6)
+            .map(|i| {
+                let i = i as f64;
+                sample(&[("a", i), ("b", 2.0 * i + 1.0)], Some(i * i))
+            })
+            .collect();
+        let report = sensitivity(&samples);
+        assert_eq!(stats(&report, "a").isolated_groups, 0);
+        assert_eq!(stats(&report, "a").isolated_effect, None);
+        assert_eq!(
+            report.limitations,
+            vec![
+                "`a` never changed on its own, so its correlation may come from variables that changed with it.",
+                "`b` never changed on its own, so its correlation may come from variables that changed with it.",
+                "`a` and `b` moved together, so their effects can't be told apart.",
+            ]
+        );
+    }
+
+    #[test]
+    fn variables_missing_from_some_runs_use_the_rest() {
+        let samples = vec![
+            sample(&[("a", 1.0)], Some(1.0)),
+            sample(&[("a", 2.0), ("late", 5.0)], Some(2.0)),
+            sample(&[("a", 3.0), ("late", 6.0)], Some(3.0)),
+        ];
+        let report = sensitivity(&samples);
+        assert_eq!(stats(&report, "late").runs, 2);
+        assert!(close(stats(&report, "late").correlation, 1.0));
+    }
+
+    #[test]
+    fn correlation_needs_variation_on_both_sides() {
+        assert_eq!(correlation(&[]), None);
+        assert_eq!(correlation(&[(1.0, 2.0)]), None);
+        assert_eq!(correlation(&[(1.0, 2.0), (1.0, 3.0)]), None);
+        assert_eq!(correlation(&[(1.0, 2.0), (2.0, 2.0)]), None);
+        assert!(close(
+            correlation(&[(1.0, 5.0), (2.0, 3.0), (3.0, 1.0)]),
+            -1.0
+        ));
+    }
+
+    #[test]
+    fn outputs_are_parsed_after_their_label() {
+        let output = "step 1\nloss: 0.25 (best 0.2)\nloss: -1.5e-3\ndone in 42 ms\n";
+        assert_eq!(parse_output(output, "loss:"), Some(-1.5e-3));
+        assert_eq!(parse_output(output, ""), Some(42.0));
+        assert_eq!(parse_output(output, "accuracy"), None);
+        assert_eq!(parse_output("loss: nan\n", "loss:"), None);
+        assert_eq!(parse_output("", ""), None);
+    }
+}
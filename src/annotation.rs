@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use crate::lexer::{line_of, tokenize, TokenKind};
+
+/// How the panel shows a variable, as given by a `// crowbar: …` comment
+/// at the end of the line it's declared on, like
+/// `let threshold: f64 = 0.5; // crowbar: range(0.0, 1.0)`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Annotation {
+    /// `range(min, max)`: edited with a slider between the two, inclusive.
+    pub range: Option<(f64, f64)>,
+    /// `step(n)`: values are kept to multiples of `n`.
+    pub step: Option<f64>,
+    /// `speed(n)`: how much dragging changes the value per pixel.
+    pub speed: Option<f64>,
+    /// `color`: three or four numbers edited with a color picker.
+    pub color: bool,
+    /// What's wrong with settings that are malformed.
+    pub errors: Vec<String>,
+}
+
+impl Annotation {
+    /// Drag speed: as given, else a step per pixel, else `default`.
+    pub fn speed_or(&self, default: f64) -> f64 {
+        self.speed.or(self.step).unwrap_or(default)
+    }
+
+    /// `value` rounded to the nearest step, if there is one.
+    pub fn snap(&self, value: f64) -> f64 {
+        match self.step {
+            Some(step) => (value / step).round() * step,
+            None => value,
+        }
+    }
+}
+
+/// The annotations in `code`'s line comments, by the line they're on.
+pub fn by_line(code: &str) -> HashMap<usize, Annotation> {
+    tokenize(code)
+        .iter()
+        .filter(|token| token.kind == TokenKind::LineComment)
+        .filter_map(|token| {
+            let text = token.text(code).strip_prefix("//")?.trim();
+            let settings = text.strip_prefix("crowbar:")?;
+            Some((line_of(code, token.span.start), parse(settings)))
+        })
+        .collect()
+}
+
+/// The settings in an annotation, written `name(arguments)`, or just
+/// `name` for flags, one after another, optionally separated by commas.
+/// Unknown ones are left out, and malformed ones are reported in `errors`.
+fn parse(settings: &str) -> Annotation {
+    let mut annotation = Annotation::default();
+    let mut rest = settings.trim();
+    while !rest.is_empty() {
+        let Some((before, after)) = rest.split_once('(') else {
+            flags(&mut annotation, rest);
+            break;
+        };
+        let before = before.trim_end();
+        let (flag_words, name) = before.rsplit_once([' ', ',']).unwrap_or(("", before));
+        flags(&mut annotation, flag_words);
+        let Some((arguments, after)) = after.split_once(')') else {
+            if is_known(name) {
+                annotation.errors.push(format!("`{}(` isn't closed", name));
+            }
+            break;
+        };
+        rest = after.trim_start_matches([',', ' ']);
+        if name == "color" {
+            annotation.errors.push("`color` takes no value".to_string());
+        }
+        if !is_known(name) {
+            continue;
+        }
+        let numbers: Option<Vec<f64>> = arguments
+            .split(',')
+            .map(|argument| argument.trim().replace('_', "").parse().ok())
+            .collect();
+        let positive = |numbers: &[f64]| match numbers {
+            [n] if *n > 0.0 && n.is_finite() => Some(*n),
+            _ => None,
+        };
+        let numbers = numbers.unwrap_or_default();
+        match name {
+            "range" => match numbers.as_slice() {
+                [min, max] if min < max => annotation.range = Some((*min, *max)),
+                _ => annotation.errors.push(format!(
+                    "`range({})` needs a minimum below a maximum",
+                    arguments
+                )),
+            },
+            _ => match positive(&numbers) {
+                Some(n) if name == "step" => annotation.step = Some(n),
+                Some(n) => annotation.speed = Some(n),
+                None => annotation.errors.push(format!(
+                    "`{}({})` needs one number above zero",
+                    name, arguments
+                )),
+            },
+        }
+    }
+    annotation
+}
+
+/// Sets the flags named in `words`, reporting settings that need a value
+/// but were written without one.
+fn flags(annotation: &mut Annotation, words: &str) {
+    for word in words.split([' ', ',']).filter(|word| !word.is_empty()) {
+        if word == "color" {
+            annotation.color = true;
+        } else if is_known(word) {
+            annotation
+                .errors
+                .push(format!("`{}` needs its value in parentheses", word));
+        }
+    }
+}
+
+fn is_known(name: &str) -> bool {
+    matches!(name, "range" | "step" | "speed")
+}
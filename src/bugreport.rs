@@ -0,0 +1,299 @@
+use std::path::Path;
+
+use crate::environment;
+
+/// Values shorter than this aren't scrubbed from reports: they'd match
+/// all over unrelated text.
+const MIN_SECRET_LEN: usize = 4;
+
+/// Everything a bug report can contain, gathered by the caller.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sources {
+    pub version: String,
+    pub os: String,
+    /// `(tool, version)` pairs, the version empty when it couldn't be run.
+    pub toolchain: Vec<(String, String)>,
+    /// `(name, value)` pairs of the settings worth knowing about.
+    pub settings: Vec<(String, String)>,
+    pub log: Vec<String>,
+    pub crash_log: Option<String>,
+    pub buffer: Option<String>,
+    /// Provenance block of the last run.
+    pub provenance: Option<String>,
+}
+
+/// Scrubs reports of the home directory and of secret values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Redactor {
+    home: Option<String>,
+    /// Longest first, so a secret containing another is replaced whole.
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(home: Option<&Path>, secrets: impl IntoIterator<Item = String>) -> Self {
+        let mut secrets: Vec<String> = secrets
+            .into_iter()
+            .filter(|secret| secret.len() >= MIN_SECRET_LEN)
+            .collect();
+        secrets.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        secrets.dedup();
+        Self {
+            home: home
+                .map(|home| {
+                    home.to_string_lossy()
+                        .trim_end_matches(['/', '\\'])
+                        .to_string()
+                })
+                .filter(|home| !home.is_empty()),
+            secrets,
+        }
+    }
+
+    /// `text` with secrets replaced by `<redacted>` and paths under the
+    /// home directory starting with `~` instead. Secrets are replaced in one
+    /// pass, so none can match inside another's replacement.
+    pub fn apply(&self, text: &str) -> String {
+        let mut scrubbed = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            match self
+                .secrets
+                .iter()
+                .find(|secret| rest.starts_with(secret.as_str()))
+            {
+                Some(secret) => {
+                    scrubbed.push_str(environment::REDACTED);
+                    rest = &rest[secret.len()..];
+                }
+                None => {
+                    scrubbed.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        let text = scrubbed;
+        let Some(home) = &self.home else {
+            return text;
+        };
+        let mut redacted = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(at) = rest.find(home.as_str()) {
+            let after = &rest[at + home.len()..];
+            redacted.push_str(&rest[..at]);
+            // Only whole path components: `/home/al` isn't in `/home/alex`.
+            if !starts_word(after) {
+                redacted.push('~');
+            } else {
+                redacted.push_str(home);
+            }
+            rest = after;
+        }
+        redacted.push_str(rest);
+        redacted
+    }
+}
+
+fn starts_word(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Values of `vars` whose names match any of the redaction `patterns`.
+pub fn secret_values(
+    vars: impl IntoIterator<Item = (String, String)>,
+    patterns: &[String],
+) -> Vec<String> {
+    vars.into_iter()
+        .filter(|(name, _)| {
+            patterns
+                .iter()
+                .any(|pattern| environment::glob_match(pattern, name))
+        })
+        .map(|(_, value)| value)
+        .collect()
+}
+
+/// One file of the bundle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub contents: String,
+}
+
+/// The files of the bundle, redacted. The buffer and provenance are only
+/// included with `include_code`, and only when there are any.
+pub fn collect(sources: &Sources, include_code: bool, redactor: &Redactor) -> Vec<Entry> {
+    let mut system = format!("crowbar: {}\nos: {}\n", sources.version, sources.os);
+    for (tool, version) in &sources.toolchain {
+        let version = if version.is_empty() {
+            "not found"
+        } else {
+            version
+        };
+        system.push_str(&format!("{}: {}\n", tool, version));
+    }
+    let settings: String = sources
+        .settings
+        .iter()
+        .map(|(name, value)| format!("{} = {}\n", name, value))
+        .collect();
+    let mut log = sources.log.join("\n");
+    if !log.is_empty() {
+        log.push('\n');
+    }
+
+    let mut entries = vec![
+        ("system.txt", system),
+        ("settings.txt", settings),
+        ("debug.log", log),
+    ];
+    if let Some(crash) = &sources.crash_log {
+        entries.push(("crash.log", crash.clone()));
+    }
+    if include_code {
+        if let Some(buffer) = &sources.buffer {
+            entries.push(("buffer.rs", buffer.clone()));
+        }
+        if let Some(provenance) = &sources.provenance {
+            entries.push(("provenance.toml", provenance.clone()));
+        }
+    }
+    entries
+        .into_iter()
+        .map(|(name, contents)| Entry {
+            name: name.to_string(),
+            contents: redactor.apply(&contents),
+        })
+        .collect()
+}
+
+/// Writes `entries` to a zip archive at `path`.
+pub fn write(path: &Path, entries: &[Entry]) -> std::io::Result<()> {
+    let files: Vec<(String, Vec<u8>)> = entries
+        .iter()
+        .map(|entry| (entry.name.clone(), entry.contents.clone().into_bytes()))
+        .collect();
+    crate::export::write_zip(path, &files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(home: Option<&str>, secrets: &[&str]) -> Redactor {
+        Redactor::new(
+            home.map(Path::new),
+            secrets.iter().map(|secret| secret.to_string()),
+        )
+    }
+
+    fn entry<'a>(entries: &'a [Entry], name: &str) -> &'a str {
+        &entries.iter().find(|e| e.name == name).unwrap().contents
+    }
+
+    #[test]
+    fn secrets_are_replaced_longest_first() {
+        let redactor = redactor(None, &["abcd", "abcdef", "xyz", "abcd"]);
+        assert_eq!(redactor.secrets, vec!["abcdef", "abcd"]);
+        assert_eq!(
+            redactor.apply("token=abcdef other=abcd short=xyz"),
+            "token=<redacted> other=<redacted> short=xyz"
+        );
+    }
+
+    #[test]
+    fn replacements_are_not_redacted_again() {
+        let redactor = redactor(None, &["s3cret", "acted"]);
+        assert_eq!(
+            redactor.apply("key s3cret, acted"),
+            "key <redacted>, <redacted>"
+        );
+    }
+
+    #[test]
+    fn home_is_shortened_only_at_component_boundaries() {
+        let home = redactor(Some("/home/al/"), &[]);
+        assert_eq!(
+            home.apply("/home/al/src/main.rs and /home/alex/x and /home/al"),
+            "~/src/main.rs and /home/alex/x and ~"
+        );
+        assert_eq!(
+            home.apply("at /home/al.bak and /home/al:1"),
+            "at /home/al.bak and ~:1"
+        );
+        // A root home is never shortened.
+        assert_eq!(redactor(Some("/"), &[]).apply("/usr/bin"), "/usr/bin");
+    }
+
+    #[test]
+    fn secret_values_follow_the_redaction_patterns() {
+        let vars = vec![
+            ("API_KEY".to_string(), "k-123".to_string()),
+            ("GITHUB_TOKEN".to_string(), "ghp_x".to_string()),
+            ("HOME".to_string(), "/home/al".to_string()),
+        ];
+        let patterns = vec!["*KEY*".to_string(), "*TOKEN*".to_string()];
+        assert_eq!(secret_values(vars, &patterns), vec!["k-123", "ghp_x"]);
+    }
+
+    #[test]
+    fn collect_redacts_every_file() {
+        let sources = Sources {
+            version: "1.2.3".to_string(),
+            os: "linux".to_string(),
+            toolchain: vec![
+                ("rustc".to_string(), "1.80.0".to_string()),
+                ("rustfmt".to_string(), String::new()),
+            ],
+            settings: vec![("editor".to_string(), "/home/al/bin/ed".to_string())],
+            log: vec![
+                "ran /home/al/p.rs".to_string(),
+                "env API_KEY=k-12345".to_string(),
+            ],
+            crash_log: Some("panicked with k-12345".to_string()),
+            buffer: Some("let key = \"k-12345\";".to_string()),
+            provenance: Some("file = \"/home/al/p.rs\"".to_string()),
+        };
+        let redactor = redactor(Some("/home/al"), &["k-12345"]);
+
+        let entries = collect(&sources, true, &redactor);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "system.txt",
+                "settings.txt",
+                "debug.log",
+                "crash.log",
+                "buffer.rs",
+                "provenance.toml",
+            ]
+        );
+        assert_eq!(
+            entry(&entries, "system.txt"),
+            "crowbar: 1.2.3\nos: linux\nrustc: 1.80.0\nrustfmt: not found\n"
+        );
+        assert_eq!(entry(&entries, "settings.txt"), "editor = ~/bin/ed\n");
+        assert_eq!(
+            entry(&entries, "debug.log"),
+            "ran ~/p.rs\nenv API_KEY=<redacted>\n"
+        );
+        assert_eq!(entry(&entries, "crash.log"), "panicked with <redacted>");
+        assert_eq!(entry(&entries, "buffer.rs"), "let key = \"<redacted>\";");
+        assert_eq!(entry(&entries, "provenance.toml"), "file = \"~/p.rs\"");
+        assert!(entries.iter().all(|e| !e.contents.contains("k-12345")));
+    }
+
+    #[test]
+    fn code_is_left_out_unless_asked_for() {
+        let sources = Sources {
+            buffer: Some("fn main() {}".to_string()),
+            provenance: Some("x".to_string()),
+            ..Sources::default()
+        };
+        let entries = collect(&sources, false, &Redactor::default());
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["system.txt", "settings.txt", "debug.log"]);
+        assert_eq!(entry(&entries, "debug.log"), "");
+    }
+}
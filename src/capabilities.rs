@@ -0,0 +1,101 @@
+/// Subsystems that read persisted state or start external processes. Safe
+/// mode starts with all of them vetoed so a broken one can't stop Crowbar
+/// from opening.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Capability {
+    StoredSettings,
+    UserDictionary,
+    ProcessRecovery,
+    ExternalProcesses,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::StoredSettings,
+        Capability::UserDictionary,
+        Capability::ProcessRecovery,
+        Capability::ExternalProcesses,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Capability::StoredSettings => "stored settings",
+            Capability::UserDictionary => "user dictionary",
+            Capability::ProcessRecovery => "leftover process check",
+            Capability::ExternalProcesses => "compiling and running",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Capabilities {
+    vetoed: Vec<Capability>,
+}
+
+impl Capabilities {
+    pub fn safe_mode() -> Self {
+        Self {
+            vetoed: Capability::ALL.to_vec(),
+        }
+    }
+
+    /// Safe mode is requested with `--safe-mode` on the command line.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        if args.any(|arg| arg == "--safe-mode") {
+            Self::safe_mode()
+        } else {
+            Self::default()
+        }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        !self.vetoed.contains(&capability)
+    }
+
+    pub fn vetoed(&self) -> &[Capability] {
+        &self.vetoed
+    }
+
+    pub fn enable(&mut self, capability: Capability) {
+        self.vetoed.retain(|c| *c != capability);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn safe_mode_comes_from_the_command_line() {
+        assert!(Capabilities::from_args(args(&[])).vetoed().is_empty());
+        assert!(Capabilities::from_args(args(&["demo.rs"]))
+            .vetoed()
+            .is_empty());
+        let safe = Capabilities::from_args(args(&["demo.rs", "--safe-mode"]));
+        assert_eq!(safe.vetoed(), &Capability::ALL);
+        assert!(Capability::ALL.iter().all(|c| !safe.allows(*c)));
+    }
+
+    #[test]
+    fn capabilities_are_enabled_one_at_a_time() {
+        let mut capabilities = Capabilities::safe_mode();
+        capabilities.enable(Capability::UserDictionary);
+        capabilities.enable(Capability::UserDictionary);
+        assert!(capabilities.allows(Capability::UserDictionary));
+        assert_eq!(
+            capabilities.vetoed(),
+            &[
+                Capability::StoredSettings,
+                Capability::ProcessRecovery,
+                Capability::ExternalProcesses,
+            ]
+        );
+    }
+}
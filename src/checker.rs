@@ -0,0 +1,458 @@
+use std::io::{BufRead, BufReader};
+use std::ops::Range;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use eframe::egui;
+
+use crate::processes::{self, Role};
+
+/// How long edits have to pause before a check starts.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Severity {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Severity::Error => "❌",
+            Severity::Warning => "⚠",
+            Severity::Note => "ℹ",
+            Severity::Help => "💡",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Error code like `E0308`, or the lint name for lints.
+    pub code: Option<String>,
+    pub message: String,
+    /// The full diagnostic as rustc prints it.
+    pub rendered: String,
+    /// Byte range of the primary span in the checked source.
+    pub span: Range<usize>,
+    /// Notes and help messages rustc attached, in order.
+    pub children: Vec<(Severity, String)>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// A fix rustc proposed, as replacements in the checked source.
+#[derive(Clone, Debug)]
+pub struct Suggestion {
+    pub message: String,
+    pub edits: Vec<(Range<usize>, String)>,
+}
+
+struct Request {
+    generation: u64,
+    source: String,
+    flags: Vec<String>,
+    ctx: egui::Context,
+}
+
+#[derive(Default)]
+struct Shared {
+    /// Bumped by every request and cancellation; a check only publishes
+    /// results while its generation is still the newest.
+    generation: u64,
+    /// Diagnostics of the last check that ran to completion.
+    complete: Vec<Diagnostic>,
+    /// Source those diagnostics are about.
+    complete_source: Option<String>,
+    /// Diagnostics streamed so far by the check in flight.
+    incoming: Vec<Diagnostic>,
+    in_flight: Option<Child>,
+}
+
+/// Type-checks the buffer on a background thread while the user types.
+/// At most one check runs at a time and the newest request wins: queued
+/// requests are coalesced and a check in flight is killed when superseded.
+///
+/// Only the single file is checked, by `rustc` into a file of this
+/// process's own: Crowbar has no cargo projects, so there's no shared
+/// target directory whose lock a check would have to wait for.
+#[derive(Default)]
+pub struct Checker {
+    requests: Option<Sender<Request>>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Checker {
+    pub fn request(&mut self, ctx: &egui::Context, source: &str, flags: &str) {
+        let generation = self.supersede();
+        let shared = self.shared.clone();
+        let requests = self.requests.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+            std::thread::spawn(move || worker(receiver, shared, check));
+            sender
+        });
+        let _ = requests.send(Request {
+            generation,
+            source: source.to_string(),
+            flags: flags.split_whitespace().map(str::to_string).collect(),
+            ctx: ctx.clone(),
+        });
+    }
+
+    /// Drops pending checks and kills the one in flight, e.g. so a build
+    /// doesn't compete with it.
+    pub fn cancel(&mut self) {
+        self.supersede();
+    }
+
+    fn supersede(&self) -> u64 {
+        let mut shared = self.shared.lock().unwrap();
+        shared.generation += 1;
+        if let Some(child) = &mut shared.in_flight {
+            let _ = child.kill();
+        }
+        shared.generation
+    }
+
+    /// Whether the diagnostics are exactly those of `source`, so their spans
+    /// can be used to edit it.
+    pub fn is_current(&self, source: &str) -> bool {
+        let shared = self.shared.lock().unwrap();
+        shared.in_flight.is_none() && shared.complete_source.as_deref() == Some(source)
+    }
+
+    pub fn is_checking(&self) -> bool {
+        self.shared.lock().unwrap().in_flight.is_some()
+    }
+
+    /// Diagnostics as they stream in, or those of the last complete check
+    /// until the one in flight reports something.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let shared = self.shared.lock().unwrap();
+        if shared.in_flight.is_some() && !shared.incoming.is_empty() {
+            shared.incoming.clone()
+        } else {
+            shared.complete.clone()
+        }
+    }
+}
+
+/// Hands `check` the newest of each burst of requests.
+fn worker(
+    requests: Receiver<Request>,
+    shared: Arc<Mutex<Shared>>,
+    check: impl Fn(&Request, &Mutex<Shared>),
+) {
+    while let Ok(mut request) = requests.recv() {
+        // Let a burst of edits settle, then only check the newest buffer.
+        std::thread::sleep(DEBOUNCE);
+        while let Ok(newer) = requests.try_recv() {
+            request = newer;
+        }
+        check(&request, &shared);
+    }
+}
+
+fn check(request: &Request, shared: &Mutex<Shared>) {
+    // Per instance, so concurrent Crowbar windows don't share a file.
+    let dir = std::env::temp_dir();
+    let source_path = dir.join(format!("crowbar-check-{}.rs", std::process::id()));
+    let output_path = dir.join(format!("crowbar-check-{}.rmeta", std::process::id()));
+    if std::fs::write(&source_path, &request.source).is_err() {
+        return;
+    }
+
+    let (pid, stderr) = {
+        let mut shared = shared.lock().unwrap();
+        if shared.generation != request.generation {
+            return;
+        }
+        let child = Command::new("rustc")
+            .arg(&source_path)
+            .args(["--emit=metadata", "--error-format=json", "-o"])
+            .arg(&output_path)
+            .args(&request.flags)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn();
+        let Ok(mut child) = child else {
+            return;
+        };
+        let stderr = child.stderr.take();
+        let pid = child.id();
+        shared.incoming.clear();
+        shared.in_flight = Some(child);
+        (pid, stderr)
+    };
+    let _tracked = processes::track(pid, Role::Compiler, None, processes::rustc_path());
+    stream(
+        request,
+        shared,
+        stderr
+            .map(BufReader::new)
+            .into_iter()
+            .flat_map(|reader| reader.lines().map_while(Result::ok)),
+    );
+    complete(request, shared);
+}
+
+/// Publishes the diagnostics among `lines` as they arrive, until the
+/// request is superseded.
+fn stream(request: &Request, shared: &Mutex<Shared>, lines: impl Iterator<Item = String>) {
+    for line in lines {
+        if let Some(diagnostic) = parse_diagnostic(&line) {
+            let mut shared = shared.lock().unwrap();
+            if shared.generation != request.generation {
+                break;
+            }
+            shared.incoming.push(diagnostic);
+            request.ctx.request_repaint();
+        }
+    }
+}
+
+/// Makes the streamed diagnostics those of the request's source, unless a
+/// newer request came since.
+fn complete(request: &Request, shared: &Mutex<Shared>) {
+    let mut shared = shared.lock().unwrap();
+    if let Some(mut child) = shared.in_flight.take() {
+        let _ = child.wait();
+    }
+    if shared.generation == request.generation {
+        shared.complete = std::mem::take(&mut shared.incoming);
+        shared.complete_source = Some(request.source.clone());
+    }
+    request.ctx.request_repaint();
+}
+
+/// One line of rustc's `--error-format=json` output. Diagnostics without a
+/// primary span, like the final "aborting due to" summary, are skipped.
+fn parse_diagnostic(line: &str) -> Option<Diagnostic> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    let severity = match value["level"].as_str()? {
+        "error" => Severity::Error,
+        "warning" => Severity::Warning,
+        _ => return None,
+    };
+    let primary = value["spans"]
+        .as_array()?
+        .iter()
+        .find(|span| span["is_primary"].as_bool() == Some(true))?;
+    let children = value["children"].as_array().cloned().unwrap_or_default();
+    Some(Diagnostic {
+        severity,
+        code: value["code"]["code"].as_str().map(str::to_string),
+        message: value["message"].as_str()?.to_string(),
+        rendered: value["rendered"].as_str().unwrap_or_default().to_string(),
+        span: span_range(primary)?,
+        children: children
+            .iter()
+            .filter_map(|child| {
+                let severity = match child["level"].as_str()? {
+                    "help" => Severity::Help,
+                    "warning" => Severity::Warning,
+                    "error" => Severity::Error,
+                    _ => Severity::Note,
+                };
+                Some((severity, child["message"].as_str()?.to_string()))
+            })
+            .collect(),
+        suggestions: children.iter().filter_map(parse_suggestion).collect(),
+    })
+}
+
+/// The replacements of a child diagnostic, if it suggests any.
+fn parse_suggestion(child: &serde_json::Value) -> Option<Suggestion> {
+    let edits: Vec<(Range<usize>, String)> = child["spans"]
+        .as_array()?
+        .iter()
+        .filter_map(|span| {
+            let replacement = span["suggested_replacement"].as_str()?;
+            Some((span_range(span)?, replacement.to_string()))
+        })
+        .collect();
+    (!edits.is_empty()).then(|| Suggestion {
+        message: child["message"].as_str().unwrap_or_default().to_string(),
+        edits,
+    })
+}
+
+fn span_range(span: &serde_json::Value) -> Option<Range<usize>> {
+    Some(span["byte_start"].as_u64()? as usize..span["byte_end"].as_u64()? as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn span(start: u64, end: u64, primary: bool) -> serde_json::Value {
+        json!({
+            "byte_start": start,
+            "byte_end": end,
+            "is_primary": primary,
+            "suggested_replacement": null,
+        })
+    }
+
+    fn mismatch() -> String {
+        json!({
+            "level": "error",
+            "message": "mismatched types",
+            "code": {"code": "E0308", "explanation": "..."},
+            "rendered": "error[E0308]: mismatched types\n",
+            "spans": [span(20, 24, false), span(30, 35, true)],
+            "children": [
+                {"level": "note", "message": "expected `i32`, found `&str`", "spans": []},
+                {
+                    "level": "help",
+                    "message": "try using a conversion method",
+                    "spans": [{
+                        "byte_start": 30,
+                        "byte_end": 35,
+                        "is_primary": true,
+                        "suggested_replacement": "\"5\".parse().unwrap()",
+                    }],
+                },
+            ],
+        })
+        .to_string()
+    }
+
+    fn request(generation: u64, source: &str) -> Request {
+        Request {
+            generation,
+            source: source.to_string(),
+            flags: Vec::new(),
+            ctx: egui::Context::default(),
+        }
+    }
+
+    #[test]
+    fn parses_an_error_with_notes_and_a_suggestion() {
+        let diagnostic = parse_diagnostic(&mismatch()).unwrap();
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.code.as_deref(), Some("E0308"));
+        assert_eq!(diagnostic.message, "mismatched types");
+        assert_eq!(diagnostic.rendered, "error[E0308]: mismatched types\n");
+        assert_eq!(diagnostic.span, 30..35);
+        assert_eq!(
+            diagnostic.children,
+            vec![
+                (Severity::Note, "expected `i32`, found `&str`".to_string()),
+                (Severity::Help, "try using a conversion method".to_string()),
+            ]
+        );
+        assert_eq!(diagnostic.suggestions.len(), 1);
+        assert_eq!(
+            diagnostic.suggestions[0].edits,
+            vec![(30..35, "\"5\".parse().unwrap()".to_string())]
+        );
+    }
+
+    #[test]
+    fn parses_a_lint_warning() {
+        let line = json!({
+            "level": "warning",
+            "message": "unused variable: `x`",
+            "code": {"code": "unused_variables"},
+            "spans": [span(4, 5, true)],
+            "children": [],
+        })
+        .to_string();
+        let diagnostic = parse_diagnostic(&line).unwrap();
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.code.as_deref(), Some("unused_variables"));
+        assert!(diagnostic.rendered.is_empty());
+        assert!(diagnostic.suggestions.is_empty());
+    }
+
+    #[test]
+    fn skips_summaries_other_levels_and_garbage() {
+        let summary = json!({
+            "level": "error",
+            "message": "aborting due to 1 previous error",
+            "spans": [],
+            "children": [],
+        });
+        assert!(parse_diagnostic(&summary.to_string()).is_none());
+        let note = json!({"level": "note", "message": "x", "spans": [span(0, 1, true)]});
+        assert!(parse_diagnostic(&note.to_string()).is_none());
+        let secondary = json!({"level": "error", "message": "x", "spans": [span(0, 1, false)]});
+        assert!(parse_diagnostic(&secondary.to_string()).is_none());
+        assert!(parse_diagnostic("error: not json").is_none());
+        assert!(parse_diagnostic("").is_none());
+    }
+
+    #[test]
+    fn a_burst_of_requests_checks_only_the_newest() {
+        let (sender, receiver) = mpsc::channel();
+        let checked = Arc::new(Mutex::new(Vec::new()));
+        let worker = {
+            let checked = checked.clone();
+            std::thread::spawn(move || {
+                worker(receiver, Arc::default(), |request, _| {
+                    checked.lock().unwrap().push(request.source.clone())
+                })
+            })
+        };
+        for (generation, source) in ["a", "ab", "abc"].iter().enumerate() {
+            sender.send(request(generation as u64, source)).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while checked.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        sender.send(request(3, "abcd")).unwrap();
+        drop(sender);
+        worker.join().unwrap();
+        assert_eq!(*checked.lock().unwrap(), vec!["abc", "abcd"]);
+    }
+
+    #[test]
+    fn a_current_check_publishes_its_diagnostics() {
+        let checker = Checker::default();
+        let generation = checker.supersede();
+        let request = request(generation, "fn main() {}");
+        stream(
+            &request,
+            &checker.shared,
+            [mismatch(), "junk".to_string()].into_iter(),
+        );
+        assert_eq!(checker.shared.lock().unwrap().incoming.len(), 1);
+        assert!(!checker.is_current("fn main() {}"));
+        complete(&request, &checker.shared);
+        assert!(checker.is_current("fn main() {}"));
+        assert!(!checker.is_current("fn main() { }"));
+        assert_eq!(checker.diagnostics().len(), 1);
+    }
+
+    #[test]
+    fn a_superseded_check_publishes_nothing() {
+        let mut checker = Checker::default();
+        let old = request(checker.supersede(), "old");
+        let current = request(checker.supersede(), "current");
+        stream(&current, &checker.shared, std::iter::once(mismatch()));
+        complete(&current, &checker.shared);
+
+        stream(&old, &checker.shared, std::iter::once(mismatch()));
+        complete(&old, &checker.shared);
+        assert!(checker.is_current("current"));
+
+        // Cancelling, as a run does, leaves the last results alone.
+        let cancelled = request(checker.supersede(), "cancelled");
+        checker.cancel();
+        stream(&cancelled, &checker.shared, std::iter::once(mismatch()));
+        complete(&cancelled, &checker.shared);
+        assert!(checker.is_current("current"));
+        assert_eq!(checker.diagnostics().len(), 1);
+    }
+}
@@ -0,0 +1,409 @@
+use std::ops::Range;
+use std::path::PathBuf;
+
+use eframe::egui::Color32;
+
+/// Diffs bigger than this many table cells compare the differing middle as a
+/// single replacement instead of aligning it line by line.
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RowKind {
+    Same,
+    /// Both sides have a line, with different text.
+    Changed,
+    /// Only the left side has a line; the right shows a gap.
+    Removed,
+    /// Only the right side has a line; the left shows a gap.
+    Added,
+}
+
+/// One row of the side-by-side view: a line index into each side, `None`
+/// where that side gets a filler gap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub kind: RowKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Edit script turning `a` into `b` from a longest common subsequence.
+/// The common prefix and suffix are skipped before building the table.
+fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut ops = vec![Op::Equal; prefix];
+    let (n, m) = (a_mid.len(), b_mid.len());
+    if n * m > MAX_CELLS {
+        ops.extend(std::iter::repeat_n(Op::Delete, n));
+        ops.extend(std::iter::repeat_n(Op::Insert, m));
+    } else {
+        // lengths[i][j]: common subsequence length of a_mid[i..] and b_mid[j..].
+        let width = m + 1;
+        let mut lengths = vec![0u32; (n + 1) * width];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * width + j] = if a_mid[i] == b_mid[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && a_mid[i] == b_mid[j] {
+                ops.push(Op::Equal);
+                i += 1;
+                j += 1;
+            } else if j == m
+                || (i < n && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+            {
+                ops.push(Op::Delete);
+                i += 1;
+            } else {
+                ops.push(Op::Insert);
+                j += 1;
+            }
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+    ops
+}
+
+/// Byte ranges of the lines of `text`, without line breaks.
+pub fn line_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut offset = 0;
+    text.split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            start..start + line.trim_end_matches(['\n', '\r']).len()
+        })
+        .collect()
+}
+
+/// Aligns the lines of `left` and `right` into rows. Within each block of
+/// differences, removed and added lines are paired up as changed rows and
+/// whatever is left over faces a gap.
+pub fn rows(left: &str, right: &str) -> Vec<Row> {
+    let left_lines: Vec<&str> = line_ranges(left).into_iter().map(|r| &left[r]).collect();
+    let right_lines: Vec<&str> = line_ranges(right).into_iter().map(|r| &right[r]).collect();
+
+    let mut rows = Vec::new();
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    for op in diff(&left_lines, &right_lines) {
+        match op {
+            Op::Delete => {
+                removed.push(i);
+                i += 1;
+            }
+            Op::Insert => {
+                added.push(j);
+                j += 1;
+            }
+            Op::Equal => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push(Row {
+                    left: Some(i),
+                    right: Some(j),
+                    kind: RowKind::Same,
+                });
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+fn flush(rows: &mut Vec<Row>, removed: &mut Vec<usize>, added: &mut Vec<usize>) {
+    let pairs = removed.len().max(added.len());
+    for k in 0..pairs {
+        let (left, right) = (removed.get(k).copied(), added.get(k).copied());
+        let kind = match (left, right) {
+            (Some(_), Some(_)) => RowKind::Changed,
+            (Some(_), None) => RowKind::Removed,
+            _ => RowKind::Added,
+        };
+        rows.push(Row { left, right, kind });
+    }
+    removed.clear();
+    added.clear();
+}
+
+/// `(changed, removed, added)` row counts.
+pub fn summary(rows: &[Row]) -> (usize, usize, usize) {
+    let count = |kind| rows.iter().filter(|row| row.kind == kind).count();
+    (
+        count(RowKind::Changed),
+        count(RowKind::Removed),
+        count(RowKind::Added),
+    )
+}
+
+/// Byte ranges of the words that differ between two versions of a line,
+/// for each side. Words are runs of identifier characters, runs of
+/// whitespace, or single other characters.
+pub fn word_diff(left: &str, right: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let (left_words, right_words) = (words(left), words(right));
+    let left_text: Vec<&str> = left_words.iter().map(|r| &left[r.clone()]).collect();
+    let right_text: Vec<&str> = right_words.iter().map(|r| &right[r.clone()]).collect();
+
+    let (mut left_changed, mut right_changed) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    for op in diff(&left_text, &right_text) {
+        match op {
+            Op::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Op::Delete => {
+                extend(&mut left_changed, left_words[i].clone());
+                i += 1;
+            }
+            Op::Insert => {
+                extend(&mut right_changed, right_words[j].clone());
+                j += 1;
+            }
+        }
+    }
+    (left_changed, right_changed)
+}
+
+fn words(line: &str) -> Vec<Range<usize>> {
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut words: Vec<Range<usize>> = Vec::new();
+    let mut previous = None;
+    for (offset, c) in line.char_indices() {
+        let current = class(c);
+        match words.last_mut() {
+            Some(last) if previous == Some(current) && current != 2 => {
+                last.end = offset + c.len_utf8()
+            }
+            _ => words.push(offset..offset + c.len_utf8()),
+        }
+        previous = Some(current);
+    }
+    words
+}
+
+/// Appends `range`, merging it into the last range when they touch.
+fn extend(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+/// One side of a comparison.
+pub struct Side {
+    pub title: String,
+    /// The file the text came from, if any.
+    pub path: Option<PathBuf>,
+    pub text: String,
+    pub lines: Vec<Range<usize>>,
+    /// Syntax colors over `text`, empty for plain text.
+    pub sections: Vec<(Range<usize>, Color32)>,
+}
+
+impl Side {
+    pub fn new(
+        title: String,
+        path: Option<PathBuf>,
+        text: String,
+        sections: Vec<(Range<usize>, Color32)>,
+    ) -> Self {
+        Side {
+            title,
+            path,
+            lines: line_ranges(&text),
+            text,
+            sections,
+        }
+    }
+
+    pub fn line(&self, index: usize) -> &str {
+        &self.text[self.lines[index].clone()]
+    }
+}
+
+/// A two-pane read-only view and its scrolling state.
+pub struct Comparison {
+    pub left: Side,
+    pub right: Side,
+    pub rows: Vec<Row>,
+    /// Whether both panes scroll together.
+    pub locked: bool,
+    /// Vertical offset both panes had last frame.
+    pub scroll: f32,
+}
+
+impl Comparison {
+    pub fn new(left: Side, right: Side) -> Self {
+        Comparison {
+            rows: rows(&left.text, &right.text),
+            left,
+            right,
+            locked: true,
+            scroll: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RowKind::{Added, Changed, Removed, Same};
+
+    /// Each side's lines appear once, in order, and same rows really match.
+    fn check(left: &str, right: &str) -> Vec<Row> {
+        let rows = rows(left, right);
+        let lines = |text: &str| -> Vec<String> {
+            line_ranges(text)
+                .into_iter()
+                .map(|r| text[r].to_string())
+                .collect()
+        };
+        let (left_lines, right_lines) = (lines(left), lines(right));
+        let left_seen: Vec<usize> = rows.iter().filter_map(|row| row.left).collect();
+        let right_seen: Vec<usize> = rows.iter().filter_map(|row| row.right).collect();
+        assert_eq!(left_seen, (0..left_lines.len()).collect::<Vec<_>>());
+        assert_eq!(right_seen, (0..right_lines.len()).collect::<Vec<_>>());
+        for row in &rows {
+            match (row.left, row.right, row.kind) {
+                (Some(l), Some(r), Same) => assert_eq!(left_lines[l], right_lines[r]),
+                (Some(_), Some(_), Changed) | (Some(_), None, Removed) | (None, Some(_), Added) => {
+                }
+                other => panic!("inconsistent row {:?}", other),
+            }
+        }
+        rows
+    }
+
+    fn kinds(rows: &[Row]) -> Vec<RowKind> {
+        rows.iter().map(|row| row.kind).collect()
+    }
+
+    #[test]
+    fn identical_outputs_are_all_same() {
+        let text = "a\nb\nc\n";
+        assert_eq!(kinds(&check(text, text)), vec![Same; 3]);
+        assert!(check("", "").is_empty());
+    }
+
+    #[test]
+    fn inserted_lines_face_gaps() {
+        let rows = check("a\nd\n", "a\nb\nc\nd\n");
+        assert_eq!(kinds(&rows), vec![Same, Added, Added, Same]);
+        assert_eq!(
+            rows[3],
+            Row {
+                left: Some(1),
+                right: Some(3),
+                kind: Same
+            }
+        );
+        assert_eq!(summary(&rows), (0, 0, 2));
+    }
+
+    #[test]
+    fn deleted_lines_face_gaps() {
+        let rows = check("1\n2\n3\n4\n5\n", "1\n5\n");
+        assert_eq!(kinds(&rows), vec![Same, Removed, Removed, Removed, Same]);
+        assert_eq!(summary(&rows), (0, 3, 0));
+    }
+
+    #[test]
+    fn replaced_lines_pair_up_and_leftovers_face_gaps() {
+        let rows = check("keep\nx1\nx2\nx3\nend\n", "keep\ny1\nend\n");
+        assert_eq!(kinds(&rows), vec![Same, Changed, Removed, Removed, Same]);
+        assert_eq!(
+            rows[1],
+            Row {
+                left: Some(1),
+                right: Some(1),
+                kind: Changed
+            }
+        );
+        let rows = check("a\nold\nz\n", "a\nnew1\nnew2\nnew3\nz\n");
+        assert_eq!(kinds(&rows), vec![Same, Changed, Added, Added, Same]);
+    }
+
+    #[test]
+    fn everything_replaced() {
+        let rows = check("1\n2\n3\n", "4\n5\n");
+        assert_eq!(kinds(&rows), vec![Changed, Changed, Removed]);
+    }
+
+    #[test]
+    fn one_side_empty() {
+        assert_eq!(kinds(&check("", "a\nb\n")), vec![Added, Added]);
+        assert_eq!(kinds(&check("a\nb\n", "")), vec![Removed, Removed]);
+    }
+
+    #[test]
+    fn interleaved_edits_keep_common_lines_aligned() {
+        let left = "h\n1\n2\nm\n3\nt\n";
+        let right = "h\n2\nm\nx\ny\n3\nt\nextra\n";
+        let rows = check(left, right);
+        assert_eq!(
+            kinds(&rows),
+            vec![Same, Removed, Same, Same, Added, Added, Same, Same, Added]
+        );
+    }
+
+    #[test]
+    fn trailing_newline_and_crlf_dont_make_a_difference() {
+        assert_eq!(kinds(&check("a\r\nb", "a\nb\n")), vec![Same, Same]);
+    }
+
+    #[test]
+    fn repeated_lines_align_with_the_longest_common_run() {
+        let rows = check("x\nx\nx\n", "x\ny\nx\nx\n");
+        assert_eq!(summary(&rows), (0, 0, 1));
+    }
+
+    #[test]
+    fn huge_differences_fall_back_to_one_replacement() {
+        let left: String = (0..2_100).map(|i| format!("l{}\n", i)).collect();
+        let right: String = (0..2_100).map(|i| format!("r{}\n", i)).collect();
+        let rows = check(&format!("same\n{}", left), &format!("same\n{}", right));
+        assert_eq!(rows[0].kind, Same);
+        assert_eq!(summary(&rows), (2_100, 0, 0));
+    }
+
+    #[test]
+    fn word_diff_marks_only_changed_words() {
+        let (left, right) = word_diff("let total = 42;", "let total = 43;");
+        assert_eq!(left, vec![12..14]);
+        assert_eq!(right, vec![12..14]);
+        let (left, right) = word_diff("a b", "a  x b");
+        assert!(left.is_empty());
+        assert_eq!(right, vec![1..4]);
+    }
+}
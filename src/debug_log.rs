@@ -0,0 +1,57 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::paths;
+
+/// Lines kept in memory; older ones are dropped.
+const CAPACITY: usize = 500;
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Adds a line to the in-memory log, for bug reports.
+pub fn record(message: impl AsRef<str>) {
+    let line = format!("[{}] {}", timestamp(), message.as_ref());
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    if log.len() == CAPACITY {
+        log.pop_front();
+    }
+    log.push_back(line);
+}
+
+/// The logged lines, oldest first.
+pub fn recent() -> Vec<String> {
+    let log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    log.iter().cloned().collect()
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn crash_log_path() -> PathBuf {
+    paths::config_dir().join("crash.log")
+}
+
+/// Makes panics also write the panic message and the log to `crash.log`
+/// in the config dir, replacing the previous crash's.
+pub fn install_crash_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        record(format!("panic: {}", info));
+        let path = crash_log_path();
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = std::fs::write(&path, recent().join("\n"));
+        previous(info);
+    }));
+}
+
+/// Contents of the last crash log, if Crowbar ever crashed.
+pub fn last_crash() -> Option<String> {
+    std::fs::read_to_string(crash_log_path()).ok()
+}
@@ -0,0 +1,389 @@
+use syn::visit::{self, Visit};
+use syn::{BinOp, Expr, Lit, UnOp};
+
+use crate::VariableValue;
+
+/// Names of the plain identifiers `expr` reads, in order of first use.
+/// Macro arguments are skipped like everywhere else in the panel.
+pub fn references(expr: &Expr) -> Vec<String> {
+    let mut finder = ReferenceFinder { names: Vec::new() };
+    finder.visit_expr(expr);
+    finder.names
+}
+
+struct ReferenceFinder {
+    names: Vec<String>,
+}
+
+impl<'ast> Visit<'ast> for ReferenceFinder {
+    fn visit_macro(&mut self, _: &'ast syn::Macro) {}
+
+    fn visit_expr_path(&mut self, path: &'ast syn::ExprPath) {
+        if let Some(ident) = path.path.get_ident() {
+            let name = ident.to_string();
+            if !self.names.contains(&name) {
+                self.names.push(name);
+            }
+        }
+        visit::visit_expr_path(self, path);
+    }
+}
+
+/// Which of `names` the variable at `index` means by `name`: the closest
+/// earlier one, as with shadowing, or else the first later one.
+pub fn resolve(names: &[&str], index: usize, name: &str) -> Option<usize> {
+    (0..index)
+        .rev()
+        .chain(index + 1..names.len())
+        .find(|other| names[*other] == name)
+}
+
+/// Whether `expr` is arithmetic on number literals alone, like
+/// `60 * 60 * 24`, whose value doesn't depend on anything else.
+pub fn is_constant_arithmetic(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(lit) => matches!(lit.lit, Lit::Int(_) | Lit::Float(_)),
+        Expr::Paren(paren) => is_constant_arithmetic(&paren.expr),
+        Expr::Group(group) => is_constant_arithmetic(&group.expr),
+        Expr::Unary(unary) => is_constant_arithmetic(&unary.expr),
+        Expr::Binary(binary) => {
+            is_constant_arithmetic(&binary.left) && is_constant_arithmetic(&binary.right)
+        }
+        _ => false,
+    }
+}
+
+/// The value of `expr`, for the arithmetic, comparisons, casts and literals
+/// that can be worked out without running the program. `lookup` gives the
+/// values of referenced variables.
+pub fn evaluate(
+    expr: &Expr,
+    lookup: &dyn Fn(&str) -> Option<VariableValue>,
+) -> Option<VariableValue> {
+    match expr {
+        Expr::Lit(lit) => literal(&lit.lit),
+        Expr::Path(path) => lookup(&path.path.get_ident()?.to_string()),
+        Expr::Paren(paren) => evaluate(&paren.expr, lookup),
+        Expr::Group(group) => evaluate(&group.expr, lookup),
+        Expr::Unary(unary) => match (unary.op, evaluate(&unary.expr, lookup)?) {
+            (UnOp::Neg(_), VariableValue::Int(value)) => {
+                value.checked_neg().map(VariableValue::Int)
+            }
+            (UnOp::Neg(_), VariableValue::Float(value)) => Some(VariableValue::Float(-value)),
+            (UnOp::Not(_), VariableValue::Bool(value)) => Some(VariableValue::Bool(!value)),
+            (UnOp::Not(_), VariableValue::Int(value)) => Some(VariableValue::Int(!value)),
+            _ => None,
+        },
+        Expr::Binary(binary) => {
+            let left = evaluate(&binary.left, lookup)?;
+            let right = evaluate(&binary.right, lookup)?;
+            apply(&binary.op, left, right)
+        }
+        Expr::Cast(cast) => {
+            let syn::Type::Path(ty) = &*cast.ty else {
+                return None;
+            };
+            let ty = ty.path.get_ident()?.to_string();
+            match (evaluate(&cast.expr, lookup)?, ty.as_str()) {
+                (VariableValue::Int(value), "f32" | "f64") => {
+                    Some(VariableValue::Float(value as f64))
+                }
+                (VariableValue::Float(value), "f32" | "f64") => Some(VariableValue::Float(value)),
+                (VariableValue::Float(value), ty) if is_int_type(ty) => {
+                    Some(VariableValue::Int(value as i64))
+                }
+                (VariableValue::Int(value), ty) if is_int_type(ty) => {
+                    Some(VariableValue::Int(value))
+                }
+                (VariableValue::Bool(value), ty) if is_int_type(ty) => {
+                    Some(VariableValue::Int(value as i64))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_int_type(ty: &str) -> bool {
+    matches!(
+        ty,
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize"
+    )
+}
+
+fn literal(lit: &Lit) -> Option<VariableValue> {
+    match lit {
+        Lit::Int(lit) => lit.base10_parse().ok().map(VariableValue::Int),
+        Lit::Float(lit) => lit.base10_parse().ok().map(VariableValue::Float),
+        Lit::Bool(lit) => Some(VariableValue::Bool(lit.value)),
+        Lit::Str(lit) => Some(VariableValue::Str(lit.value())),
+        _ => None,
+    }
+}
+
+/// `left op right` with Rust's rules: both sides the same type, integer
+/// overflow and division by zero give no value rather than a wrong one.
+fn apply(op: &BinOp, left: VariableValue, right: VariableValue) -> Option<VariableValue> {
+    use VariableValue::{Bool, Float, Int};
+    Some(match (left, right) {
+        (Int(l), Int(r)) => match op {
+            BinOp::Add(_) => Int(l.checked_add(r)?),
+            BinOp::Sub(_) => Int(l.checked_sub(r)?),
+            BinOp::Mul(_) => Int(l.checked_mul(r)?),
+            BinOp::Div(_) => Int(l.checked_div(r)?),
+            BinOp::Rem(_) => Int(l.checked_rem(r)?),
+            BinOp::BitAnd(_) => Int(l & r),
+            BinOp::BitOr(_) => Int(l | r),
+            BinOp::BitXor(_) => Int(l ^ r),
+            BinOp::Shl(_) => Int(l.checked_shl(u32::try_from(r).ok()?)?),
+            BinOp::Shr(_) => Int(l.checked_shr(u32::try_from(r).ok()?)?),
+            _ => Bool(compare(op, l.cmp(&r))?),
+        },
+        (Float(l), Float(r)) => match op {
+            BinOp::Add(_) => Float(l + r),
+            BinOp::Sub(_) => Float(l - r),
+            BinOp::Mul(_) => Float(l * r),
+            BinOp::Div(_) => Float(l / r),
+            BinOp::Rem(_) => Float(l % r),
+            _ => Bool(compare(op, l.partial_cmp(&r)?)?),
+        },
+        (Bool(l), Bool(r)) => match op {
+            BinOp::And(_) | BinOp::BitAnd(_) => Bool(l && r),
+            BinOp::Or(_) | BinOp::BitOr(_) => Bool(l || r),
+            BinOp::BitXor(_) => Bool(l ^ r),
+            _ => Bool(compare(op, l.cmp(&r))?),
+        },
+        (VariableValue::Str(l), VariableValue::Str(r)) => Bool(compare(op, l.cmp(&r))?),
+        _ => return None,
+    })
+}
+
+fn compare(op: &BinOp, ordering: std::cmp::Ordering) -> Option<bool> {
+    Some(match op {
+        BinOp::Eq(_) => ordering.is_eq(),
+        BinOp::Ne(_) => ordering.is_ne(),
+        BinOp::Lt(_) => ordering.is_lt(),
+        BinOp::Le(_) => ordering.is_le(),
+        BinOp::Gt(_) => ordering.is_gt(),
+        BinOp::Ge(_) => ordering.is_ge(),
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    New,
+    Visiting,
+    Done,
+}
+
+/// Which variables each variable's initializer depends on, by index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Graph {
+    edges: Vec<Vec<usize>>,
+}
+
+/// Result of ordering a [`Graph`] for evaluation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Evaluation {
+    /// Every variable after everything it depends on.
+    pub order: Vec<usize>,
+    /// Variables on a cycle or depending on one, which have no value.
+    pub cyclic: Vec<usize>,
+}
+
+impl Graph {
+    pub fn new(edges: Vec<Vec<usize>>) -> Self {
+        Self { edges }
+    }
+
+    pub fn dependencies(&self, index: usize) -> &[usize] {
+        self.edges.get(index).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn evaluation(&self) -> Evaluation {
+        let mut states = vec![State::New; self.edges.len()];
+        let mut evaluation = Evaluation::default();
+        for index in 0..self.edges.len() {
+            if states[index] == State::New {
+                self.visit(index, &mut states, &mut evaluation);
+            }
+        }
+        evaluation.cyclic.sort_unstable();
+        evaluation
+    }
+
+    /// Depth-first; returns whether `index` can be evaluated.
+    fn visit(&self, index: usize, states: &mut [State], evaluation: &mut Evaluation) -> bool {
+        states[index] = State::Visiting;
+        let mut ok = true;
+        for &dependency in self.dependencies(index) {
+            ok &= match states[dependency] {
+                State::Visiting => false,
+                State::New => self.visit(dependency, states, evaluation),
+                State::Done => !evaluation.cyclic.contains(&dependency),
+            };
+        }
+        states[index] = State::Done;
+        if ok {
+            evaluation.order.push(index);
+        } else {
+            evaluation.cyclic.push(index);
+        }
+        ok
+    }
+
+    /// The shortest chain of dependencies leading from `index` back to
+    /// itself, starting and ending with `index`, if there is one.
+    pub fn cycle_through(&self, index: usize) -> Option<Vec<usize>> {
+        let mut previous: Vec<Option<usize>> = vec![None; self.edges.len()];
+        let mut queue = std::collections::VecDeque::from([index]);
+        while let Some(current) = queue.pop_front() {
+            for &next in self.dependencies(current) {
+                if next == index {
+                    let mut path = vec![current];
+                    let mut step = current;
+                    while let Some(before) = previous[step] {
+                        path.push(before);
+                        step = before;
+                    }
+                    path.reverse();
+                    path.push(index);
+                    return Some(path);
+                }
+                if previous[next].is_none() && next != index {
+                    previous[next] = Some(current);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(source: &str) -> Expr {
+        syn::parse_str(source).unwrap()
+    }
+
+    fn value(source: &str, lookup: &dyn Fn(&str) -> Option<VariableValue>) -> Option<String> {
+        evaluate(&expr(source), lookup).and_then(|v| crate::format_literal(&v, ""))
+    }
+
+    fn nothing(_: &str) -> Option<VariableValue> {
+        None
+    }
+
+    #[test]
+    fn references_are_listed_once_in_order() {
+        assert_eq!(
+            references(&expr("b * (a + b) - f(c) + d.len() + std::f64::consts::PI")),
+            vec!["b", "a", "f", "c", "d"]
+        );
+        assert_eq!(references(&expr("vec![a, b].len() + n")), vec!["n"]);
+        assert!(references(&expr("60 * 60 * 24")).is_empty());
+    }
+
+    #[test]
+    fn names_resolve_like_shadowing() {
+        let names = ["x", "y", "x", "z"];
+        assert_eq!(resolve(&names, 3, "x"), Some(2));
+        assert_eq!(resolve(&names, 2, "x"), Some(0));
+        // Nothing earlier: the first later one.
+        assert_eq!(resolve(&names, 0, "x"), Some(2));
+        assert_eq!(resolve(&names, 0, "z"), Some(3));
+        assert_eq!(resolve(&names, 1, "w"), None);
+    }
+
+    #[test]
+    fn constant_arithmetic_needs_only_numbers() {
+        assert!(is_constant_arithmetic(&expr("60 * 60 * 24")));
+        assert!(is_constant_arithmetic(&expr("-(1.5 + 2.0) / 4.0")));
+        assert!(!is_constant_arithmetic(&expr("60 * minutes")));
+        assert!(!is_constant_arithmetic(&expr("\"a\"")));
+        assert!(!is_constant_arithmetic(&expr("1 + f(2)")));
+    }
+
+    #[test]
+    fn evaluates_chained_definitions() {
+        // base = 10; scale = base * 2; total = scale + base - 1
+        let lookup = |name: &str| match name {
+            "base" => Some(VariableValue::Int(10)),
+            "scale" => evaluate(&expr("base * 2"), &|n| {
+                (n == "base").then_some(VariableValue::Int(10))
+            }),
+            _ => None,
+        };
+        assert_eq!(value("scale + base - 1", &lookup).as_deref(), Some("29"));
+        assert_eq!(value("scale as f64 / 4.0", &lookup).as_deref(), Some("5.0"));
+        assert_eq!(
+            value("scale > base && !false", &lookup).as_deref(),
+            Some("true")
+        );
+        assert_eq!(value("missing + 1", &lookup), None);
+    }
+
+    #[test]
+    fn evaluation_follows_rust_rules() {
+        assert_eq!(value("7 / 2", &nothing).as_deref(), Some("3"));
+        assert_eq!(value("7 % -3", &nothing).as_deref(), Some("1"));
+        assert_eq!(value("1 << 4 | 1", &nothing).as_deref(), Some("17"));
+        assert_eq!(value("2.5 as i32", &nothing).as_deref(), Some("2"));
+        assert_eq!(value("true as u8", &nothing).as_deref(), Some("1"));
+        assert_eq!(value("\"a\" < \"b\"", &nothing).as_deref(), Some("true"));
+        // Mixed types, overflow and division by zero have no value.
+        assert_eq!(value("1 + 1.0", &nothing), None);
+        assert_eq!(value("1 / 0", &nothing), None);
+        assert_eq!(value("9223372036854775807 + 1", &nothing), None);
+        assert_eq!(value("1 << 64", &nothing), None);
+        assert_eq!(value("1 as String", &nothing), None);
+    }
+
+    #[test]
+    fn chains_are_ordered_after_their_dependencies() {
+        // 0 <- 1 <- 2, and 3 uses both 0 and 2.
+        let graph = Graph::new(vec![vec![], vec![0], vec![1], vec![2, 0]]);
+        let evaluation = graph.evaluation();
+        assert!(evaluation.cyclic.is_empty());
+        assert_eq!(evaluation.order, vec![0, 1, 2, 3]);
+
+        // Declared in reverse: dependencies still come first.
+        let graph = Graph::new(vec![vec![1], vec![2], vec![]]);
+        assert_eq!(graph.evaluation().order, vec![2, 1, 0]);
+        assert_eq!(graph.cycle_through(0), None);
+    }
+
+    #[test]
+    fn cycles_and_their_dependents_have_no_value() {
+        // 0 -> 1 -> 2 -> 0 is a cycle, 3 depends on it, 4 stands alone.
+        let graph = Graph::new(vec![vec![1], vec![2], vec![0], vec![1], vec![]]);
+        let evaluation = graph.evaluation();
+        assert_eq!(evaluation.cyclic, vec![0, 1, 2, 3]);
+        assert_eq!(evaluation.order, vec![4]);
+        assert_eq!(graph.cycle_through(0), Some(vec![0, 1, 2, 0]));
+        assert_eq!(graph.cycle_through(2), Some(vec![2, 0, 1, 2]));
+        assert_eq!(graph.cycle_through(3), None);
+    }
+
+    #[test]
+    fn self_references_are_cycles() {
+        let graph = Graph::new(vec![vec![0], vec![]]);
+        let evaluation = graph.evaluation();
+        assert_eq!(evaluation.cyclic, vec![0]);
+        assert_eq!(evaluation.order, vec![1]);
+        assert_eq!(graph.cycle_through(0), Some(vec![0, 0]));
+    }
+
+    #[test]
+    fn the_shortest_cycle_is_reported() {
+        // 0 -> 1 -> 0 and 0 -> 2 -> 3 -> 0.
+        let graph = Graph::new(vec![vec![2, 1], vec![0], vec![3], vec![0]]);
+        assert_eq!(graph.cycle_through(0), Some(vec![0, 1, 0]));
+        assert_eq!(graph.dependencies(0), &[2, 1]);
+        assert!(graph.dependencies(9).is_empty());
+    }
+}
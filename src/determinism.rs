@@ -0,0 +1,469 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{Expr, Token};
+
+use crate::runner::{BuildConfig, Compiler, Executor, Progress, RunInputs, RunResult, Runner};
+
+/// Methods that walk a collection in its iteration order.
+const ITERATING_METHODS: &[&str] = &[
+    "iter",
+    "iter_mut",
+    "into_iter",
+    "keys",
+    "values",
+    "values_mut",
+    "into_keys",
+    "into_values",
+    "drain",
+];
+
+/// Macros whose arguments end up in the output.
+const PRINTING_MACROS: &[&str] = &[
+    "print", "println", "eprint", "eprintln", "format", "write", "writeln",
+];
+
+/// Code that likely makes a program's output differ between identical runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cause {
+    /// Byte offset in the source, for jumping to it.
+    pub offset: usize,
+    /// 1-based.
+    pub line: usize,
+    pub what: String,
+}
+
+/// Looks for hash collection iteration, unseeded randomness and wall clock
+/// reads. Only a heuristic: hash collections are recognized by how their
+/// `let` binding is written, not by type inference.
+pub fn likely_causes(ast: &syn::File) -> Vec<Cause> {
+    let mut finder = CauseFinder {
+        hashed: HashSet::new(),
+        causes: Vec::new(),
+    };
+    finder.visit_file(ast);
+    finder.causes.sort_by_key(|cause| cause.offset);
+    finder.causes
+}
+
+struct CauseFinder {
+    /// Names bound to a `HashMap` or `HashSet`.
+    hashed: HashSet<String>,
+    causes: Vec<Cause>,
+}
+
+impl CauseFinder {
+    fn push(&mut self, span: proc_macro2::Span, what: String) {
+        self.causes.push(Cause {
+            offset: span.byte_range().start,
+            line: span.start().line,
+            what,
+        });
+    }
+
+    /// The hash collection `expr` names, looking through `&` and `&mut`.
+    fn hashed_name(&self, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Reference(reference) => self.hashed_name(&reference.expr),
+            Expr::Paren(paren) => self.hashed_name(&paren.expr),
+            Expr::Path(path) => {
+                let name = path.path.get_ident()?.to_string();
+                self.hashed.contains(&name).then_some(name)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn mentions_hash_collection(tokens: impl ToTokens) -> bool {
+    fn any_hash_ident(stream: proc_macro2::TokenStream) -> bool {
+        stream.into_iter().any(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) => ident == "HashMap" || ident == "HashSet",
+            proc_macro2::TokenTree::Group(group) => any_hash_ident(group.stream()),
+            _ => false,
+        })
+    }
+    any_hash_ident(tokens.into_token_stream())
+}
+
+impl<'ast> Visit<'ast> for CauseFinder {
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        // The initializer still sees what the name meant before.
+        visit::visit_local(self, local);
+        let (name, ty) = match &local.pat {
+            syn::Pat::Ident(ident) => (Some(&ident.ident), None),
+            syn::Pat::Type(typed) => match &*typed.pat {
+                syn::Pat::Ident(ident) => (Some(&ident.ident), Some(&typed.ty)),
+                _ => (None, None),
+            },
+            _ => (None, None),
+        };
+        if let Some(name) = name {
+            let hashed = ty.is_some_and(mentions_hash_collection)
+                || local
+                    .init
+                    .as_ref()
+                    .is_some_and(|init| mentions_hash_collection(&init.expr));
+            if hashed {
+                self.hashed.insert(name.to_string());
+            } else {
+                // Shadowed by something else.
+                self.hashed.remove(&name.to_string());
+            }
+        }
+    }
+
+    fn visit_expr_for_loop(&mut self, for_loop: &'ast syn::ExprForLoop) {
+        if let Some(name) = self.hashed_name(&for_loop.expr) {
+            self.push(
+                for_loop.expr.span(),
+                format!("Loops over `{}`, whose order changes between runs", name),
+            );
+        }
+        visit::visit_expr_for_loop(self, for_loop);
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        let method = call.method.to_string();
+        if ITERATING_METHODS.contains(&method.as_str()) {
+            if let Some(name) = self.hashed_name(&call.receiver) {
+                self.push(
+                    call.method.span(),
+                    format!(
+                        "`{}.{}()` visits entries in an order that changes between runs",
+                        name, method
+                    ),
+                );
+            }
+        }
+        visit::visit_expr_method_call(self, call);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        if let Expr::Path(path) = &*call.func {
+            let segments: Vec<String> = path
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect();
+            let what = match segments
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .as_slice()
+            {
+                [.., "thread_rng"] => Some("`thread_rng()` is seeded differently every run"),
+                [.., "rand", "random"] => Some("`rand::random()` is seeded differently every run"),
+                [.., "SystemTime", "now"] => Some("`SystemTime::now()` reads the wall clock"),
+                _ => None,
+            };
+            if let Some(what) = what {
+                self.push(path.span(), what.to_string());
+            }
+        }
+        visit::visit_expr_call(self, call);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let is_printing = mac
+            .path
+            .get_ident()
+            .is_some_and(|ident| PRINTING_MACROS.contains(&ident.to_string().as_str()));
+        if !is_printing {
+            return;
+        }
+        let Ok(args) = mac.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated) else {
+            return;
+        };
+        for arg in &args {
+            if let Some(name) = self.hashed_name(arg) {
+                self.push(
+                    arg.span(),
+                    format!("Prints `{}`, whose order changes between runs", name),
+                );
+            }
+            self.visit_expr(arg);
+        }
+    }
+}
+
+/// Outcome of running the same binary twice.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    Deterministic,
+    /// The outputs first differ on `line` (1-based), or only the exit codes
+    /// differ when it's past the end of both.
+    Differs {
+        line: usize,
+        first: RunResult,
+        second: RunResult,
+    },
+    /// A run didn't get as far as running the program.
+    Failed(String),
+}
+
+/// Compares two runs of the same binary with the same inputs.
+pub fn compare(first: RunResult, second: RunResult) -> Verdict {
+    for result in [&first, &second] {
+        if result.build_failed {
+            return Verdict::Failed(result.output.clone());
+        }
+    }
+    if first.output == second.output && first.exit_code == second.exit_code {
+        return Verdict::Deterministic;
+    }
+    let mut first_lines = first.output.lines();
+    let mut second_lines = second.output.lines();
+    let mut line = 1;
+    while let (Some(a), Some(b)) = (first_lines.next(), second_lines.next()) {
+        if a != b {
+            break;
+        }
+        line += 1;
+    }
+    Verdict::Differs {
+        line,
+        first,
+        second,
+    }
+}
+
+/// Runs `source` twice with identical inputs; the second run reuses the
+/// first one's build. Both stream into `progress`, and a stop request
+/// between them skips the second.
+pub fn probe<C: Compiler, E: Executor>(
+    runner: &mut Runner<C, E>,
+    source: &str,
+    config: &BuildConfig,
+    inputs: &RunInputs,
+    file: Option<&Path>,
+    progress: &Arc<Mutex<Progress>>,
+) -> Verdict {
+    let first = runner.run(source, config, inputs, file, progress);
+    if first.build_failed {
+        return Verdict::Failed(first.output);
+    }
+    {
+        let mut progress = progress.lock().unwrap();
+        if progress.stop_requested {
+            return Verdict::Failed("Stopped before the second run".to_string());
+        }
+        progress.running = false;
+        progress.output.clear();
+    }
+    let second = runner.run(source, config, inputs, file, progress);
+    compare(first, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::runner::Launch;
+
+    #[derive(Default)]
+    struct FakeCompiler;
+
+    impl Compiler for FakeCompiler {
+        fn toolchain(&self) -> String {
+            "fake".to_string()
+        }
+
+        fn compile(&self, source: &Path, output: &Path, _flags: &[String]) -> Result<(), String> {
+            if std::fs::read_to_string(source).unwrap().contains("broken") {
+                return Err("Compilation error:\nbroken".to_string());
+            }
+            std::fs::write(output, "binary").map_err(|e| e.to_string())
+        }
+    }
+
+    /// Hands out canned outputs in order, optionally asking to stop after
+    /// the first.
+    struct CannedExecutor {
+        outputs: Mutex<VecDeque<(String, Option<i32>)>>,
+        stop_after_first: bool,
+    }
+
+    impl Executor for CannedExecutor {
+        fn execute(
+            &self,
+            _launch: &Launch,
+            _file: Option<&Path>,
+            progress: &Arc<Mutex<Progress>>,
+        ) -> (String, Option<i32>) {
+            if self.stop_after_first {
+                progress.lock().unwrap().stop_requested = true;
+            }
+            self.outputs.lock().unwrap().pop_front().unwrap()
+        }
+    }
+
+    fn runner(name: &str, outputs: &[(&str, Option<i32>)]) -> Runner<FakeCompiler, CannedExecutor> {
+        runner_stopping(name, outputs, false)
+    }
+
+    fn runner_stopping(
+        name: &str,
+        outputs: &[(&str, Option<i32>)],
+        stop_after_first: bool,
+    ) -> Runner<FakeCompiler, CannedExecutor> {
+        let dir = std::env::temp_dir().join(format!(
+            "crowbar-test-determinism-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let executor = CannedExecutor {
+            outputs: Mutex::new(
+                outputs
+                    .iter()
+                    .map(|(output, code)| (output.to_string(), *code))
+                    .collect(),
+            ),
+            stop_after_first,
+        };
+        Runner::new(FakeCompiler, executor, dir)
+    }
+
+    fn probe_with(runner: &mut Runner<FakeCompiler, CannedExecutor>, source: &str) -> Verdict {
+        probe(
+            runner,
+            source,
+            &BuildConfig::default(),
+            &RunInputs::default(),
+            None,
+            &Arc::default(),
+        )
+    }
+
+    #[test]
+    fn identical_outputs_are_deterministic() {
+        let mut runner = runner("same", &[("a\nb\n", Some(0)), ("a\nb\n", Some(0))]);
+        assert_eq!(
+            probe_with(&mut runner, "fn main() {}"),
+            Verdict::Deterministic
+        );
+    }
+
+    #[test]
+    fn differing_outputs_name_the_first_differing_line() {
+        let mut runner = runner("differs", &[("a\nb\nc\n", Some(0)), ("a\nx\nc\n", Some(0))]);
+        let Verdict::Differs {
+            line,
+            first,
+            second,
+        } = probe_with(&mut runner, "fn main() {}")
+        else {
+            panic!("expected the outputs to differ");
+        };
+        assert_eq!(line, 2);
+        assert_eq!(first.output, "a\nb\nc\n");
+        assert_eq!(second.output, "a\nx\nc\n");
+        // The second run reuses the first one's build.
+        assert!(first.header.starts_with("compiled"));
+        assert_eq!(second.header, "reused cached build");
+    }
+
+    #[test]
+    fn extra_output_differs_past_the_shorter_one() {
+        let mut runner = runner("longer", &[("a\n", Some(0)), ("a\nb\n", Some(0))]);
+        assert!(matches!(
+            probe_with(&mut runner, "fn main() {}"),
+            Verdict::Differs { line: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn differing_exit_codes_differ() {
+        let mut runner = runner("exit", &[("a\n", Some(0)), ("a\n", Some(3))]);
+        assert!(matches!(
+            probe_with(&mut runner, "fn main() {}"),
+            Verdict::Differs { line: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn build_failure_runs_nothing() {
+        let mut runner = runner("broken", &[]);
+        assert_eq!(
+            probe_with(&mut runner, "broken"),
+            Verdict::Failed("Compilation error:\nbroken".to_string())
+        );
+    }
+
+    #[test]
+    fn stop_during_the_first_run_skips_the_second() {
+        // Only one output is canned, so a second run would panic.
+        let mut runner = runner_stopping("stop", &[("a\n", None)], true);
+        let progress = Arc::default();
+        let verdict = probe(
+            &mut runner,
+            "fn main() {}",
+            &BuildConfig::default(),
+            &RunInputs::default(),
+            None,
+            &progress,
+        );
+        assert_eq!(
+            verdict,
+            Verdict::Failed("Stopped before the second run".to_string())
+        );
+        assert!(progress.lock().unwrap().stop_requested);
+    }
+
+    fn causes(code: &str) -> Vec<(usize, String)> {
+        likely_causes(&syn::parse_file(code).unwrap())
+            .into_iter()
+            .map(|cause| (cause.line, cause.what))
+            .collect()
+    }
+
+    #[test]
+    fn finds_hash_iteration_randomness_and_clock_reads() {
+        let code = "use std::collections::HashMap;\n\
+                    fn main() {\n\
+                    let mut counts = HashMap::new();\n\
+                    counts.insert(1, 2);\n\
+                    for entry in &counts {}\n\
+                    let keys: Vec<_> = counts.keys().collect();\n\
+                    println!(\"{:?}\", counts);\n\
+                    let seed = rand::random::<u8>();\n\
+                    let now = std::time::SystemTime::now();\n\
+                    }";
+        let found = causes(code);
+        let lines: Vec<usize> = found.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![5, 6, 7, 8, 9]);
+        assert!(found[0].1.contains("Loops over `counts`"));
+        assert!(found[1].1.contains("`counts.keys()`"));
+        assert!(found[2].1.contains("Prints `counts`"));
+        assert!(found[3].1.contains("rand::random()"));
+        assert!(found[4].1.contains("SystemTime::now()"));
+    }
+
+    #[test]
+    fn shadowing_with_a_vec_clears_the_hash_collection() {
+        let code = "fn main() {\n\
+                    let items = std::collections::HashSet::<i32>::new();\n\
+                    let items: Vec<i32> = items.into_iter().collect();\n\
+                    for item in &items {}\n\
+                    }";
+        assert_eq!(causes(code).len(), 1);
+    }
+
+    #[test]
+    fn ordered_collections_are_fine() {
+        let code = "fn main() {\n\
+                    let map = std::collections::BTreeMap::<i32, i32>::new();\n\
+                    for entry in &map {}\n\
+                    println!(\"{:?}\", map);\n\
+                    }";
+        assert!(causes(code).is_empty());
+    }
+}
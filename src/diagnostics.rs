@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+use crate::checker::{Diagnostic, Severity};
+use crate::lexer::line_of;
+
+/// One diagnostic as listed in the gutter popup.
+#[derive(Clone, Debug)]
+pub struct PopupEntry {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    pub children: Vec<(Severity, String)>,
+    pub actions: Vec<QuickAction>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuickAction {
+    /// Replacements suggested by rustc, applied together.
+    Apply {
+        label: String,
+        edits: Vec<(Range<usize>, String)>,
+    },
+    /// Inserts `text`, an `#[allow(lint)]` line, at byte `offset`.
+    Allow {
+        lint: String,
+        offset: usize,
+        text: String,
+    },
+}
+
+impl QuickAction {
+    pub fn label(&self) -> String {
+        match self {
+            QuickAction::Apply { label, .. } => label.clone(),
+            QuickAction::Allow { lint, .. } => format!("Allow {} here", lint),
+        }
+    }
+
+    /// `code` with the action applied.
+    pub fn apply(&self, code: &str) -> String {
+        let mut code = code.to_string();
+        match self {
+            QuickAction::Apply { edits, .. } => {
+                let mut edits = edits.clone();
+                edits.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+                for (span, replacement) in edits {
+                    code.replace_range(span, &replacement);
+                }
+            }
+            QuickAction::Allow { offset, text, .. } => code.insert_str(*offset, text),
+        }
+        code
+    }
+}
+
+/// Diagnostics whose primary span starts on the 1-based `line` of `code`.
+pub fn on_line<'a>(code: &str, diagnostics: &'a [Diagnostic], line: usize) -> Vec<&'a Diagnostic> {
+    diagnostics
+        .iter()
+        .filter(|d| code.get(d.span.clone()).is_some() && line_of(code, d.span.start) == line)
+        .collect()
+}
+
+/// Popup contents for `diagnostics` of `code`, errors first. Suggestions
+/// that use `use ` become "Add import"; lints can be allowed on the item
+/// containing them.
+pub fn popup_entries(code: &str, diagnostics: &[&Diagnostic]) -> Vec<PopupEntry> {
+    let mut entries: Vec<PopupEntry> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut actions: Vec<QuickAction> = diagnostic
+                .suggestions
+                .iter()
+                .filter(|suggestion| {
+                    suggestion
+                        .edits
+                        .iter()
+                        .all(|(span, _)| code.get(span.clone()).is_some())
+                })
+                .map(|suggestion| {
+                    let import = suggestion
+                        .edits
+                        .iter()
+                        .find_map(|(_, text)| text.trim().strip_prefix("use "));
+                    QuickAction::Apply {
+                        label: match import {
+                            Some(path) => format!("Add import {}", path.trim_end_matches(';')),
+                            None => capitalize(&suggestion.message),
+                        },
+                        edits: suggestion.edits.clone(),
+                    }
+                })
+                .collect();
+            if let Some(lint) = diagnostic.code.as_deref().filter(|code| is_lint(code)) {
+                if let Some((offset, text)) = allow_insertion(code, diagnostic.span.start, lint) {
+                    actions.push(QuickAction::Allow {
+                        lint: lint.to_string(),
+                        offset,
+                        text,
+                    });
+                }
+            }
+            PopupEntry {
+                severity: diagnostic.severity,
+                code: diagnostic.code.clone(),
+                message: diagnostic.message.clone(),
+                children: diagnostic.children.clone(),
+                actions,
+            }
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.severity != Severity::Error);
+    entries
+}
+
+/// Error codes look like `E0308`; anything else rustc reports is a lint.
+pub fn is_error_code(code: &str) -> bool {
+    code.len() == 5 && code.starts_with('E') && code[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_lint(code: &str) -> bool {
+    !is_error_code(code)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Where `#[allow(lint)]` goes so it covers byte `offset`: the line above the
+/// innermost item containing it, with the item's indentation. `None` when
+/// the code doesn't parse or `offset` is outside every item.
+pub fn allow_insertion(code: &str, offset: usize, lint: &str) -> Option<(usize, String)> {
+    let file = crate::parse_rust_code(code).ok()?;
+    let mut finder = ItemFinder {
+        offset,
+        innermost: None,
+    };
+    finder.visit_file(&file);
+    let start = finder.innermost?.start;
+    let line_start = code[..start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &code[line_start..start];
+    let indent: String = indent.chars().take_while(|c| c.is_whitespace()).collect();
+    Some((line_start, format!("{}#[allow({})]\n", indent, lint)))
+}
+
+struct ItemFinder {
+    offset: usize,
+    innermost: Option<Range<usize>>,
+}
+
+impl ItemFinder {
+    fn consider(&mut self, node: &impl Spanned) {
+        let range = node.span().byte_range();
+        // Visited outside in, so a later match is always nested deeper.
+        if range.contains(&self.offset) {
+            self.innermost = Some(range);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for ItemFinder {
+    fn visit_item(&mut self, item: &'ast syn::Item) {
+        self.consider(item);
+        visit::visit_item(self, item);
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        self.consider(item);
+        visit::visit_impl_item_fn(self, item);
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+        self.consider(item);
+        visit::visit_trait_item_fn(self, item);
+    }
+}
+
+/// `rustc --explain` texts, each fetched once on a background thread.
+#[derive(Default)]
+pub struct Explanations {
+    /// `None` while the fetch is running.
+    texts: Arc<Mutex<HashMap<String, Option<String>>>>,
+}
+
+impl Explanations {
+    /// The explanation of error `code`, starting the fetch on first use.
+    pub fn get(&self, ctx: &egui::Context, code: &str) -> Option<String> {
+        let mut texts = self.texts.lock().unwrap();
+        if let Some(text) = texts.get(code) {
+            return text.clone();
+        }
+        texts.insert(code.to_string(), None);
+
+        let shared = self.texts.clone();
+        let code = code.to_string();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let text = match Command::new("rustc").args(["--explain", &code]).output() {
+                Ok(output) if output.status.success() => {
+                    String::from_utf8_lossy(&output.stdout).to_string()
+                }
+                Ok(output) => String::from_utf8_lossy(&output.stderr).to_string(),
+                Err(e) => format!("Failed to run rustc --explain: {}", e),
+            };
+            shared.lock().unwrap().insert(code, Some(text));
+            ctx.request_repaint();
+        });
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::Suggestion;
+
+    const CODE: &str = "use std::fmt;\n\nstruct S;\n\nimpl S {\n    /// Docs.\n    fn get(&self) -> i32 {\n        let unused = 1;\n        let m = HashMap::new();\n        2\n    }\n}\n\nfn main() {\n    let x: i32 = \"a\";\n}\n";
+
+    fn at(needle: &str) -> Range<usize> {
+        let start = CODE.find(needle).unwrap();
+        start..start + needle.len()
+    }
+
+    fn diagnostic(
+        severity: Severity,
+        code: Option<&str>,
+        message: &str,
+        span: Range<usize>,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code: code.map(str::to_string),
+            message: message.to_string(),
+            rendered: String::new(),
+            span,
+            children: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    fn diagnostics() -> Vec<Diagnostic> {
+        let mut unused = diagnostic(
+            Severity::Warning,
+            Some("unused_variables"),
+            "unused variable: `unused`",
+            at("unused"),
+        );
+        unused.children = vec![(
+            Severity::Note,
+            "`#[warn(unused_variables)]` on by default".to_string(),
+        )];
+        unused.suggestions = vec![Suggestion {
+            message: "if this is intentional, prefix it with an underscore".to_string(),
+            edits: vec![(at("unused"), "_unused".to_string())],
+        }];
+        let mut missing = diagnostic(
+            Severity::Error,
+            Some("E0433"),
+            "failed to resolve: use of undeclared type `HashMap`",
+            at("HashMap"),
+        );
+        missing.suggestions = vec![
+            Suggestion {
+                message: "consider importing this struct".to_string(),
+                edits: vec![(0..0, "use std::collections::HashMap;\n".to_string())],
+            },
+            Suggestion {
+                message: "out of date".to_string(),
+                edits: vec![(CODE.len()..CODE.len() + 5, "x".to_string())],
+            },
+        ];
+        let mismatch = diagnostic(
+            Severity::Error,
+            Some("E0308"),
+            "mismatched types",
+            at("\"a\""),
+        );
+        vec![unused, missing, mismatch]
+    }
+
+    #[test]
+    fn diagnostics_are_grouped_by_line() {
+        let all = diagnostics();
+        let line = |needle: &str| line_of(CODE, CODE.find(needle).unwrap());
+        assert_eq!(on_line(CODE, &all, line("unused")).len(), 1);
+        assert_eq!(on_line(CODE, &all, line("HashMap")).len(), 1);
+        assert!(on_line(CODE, &all, 1).is_empty());
+        // Spans from an older version of the code are dropped.
+        let stale = [diagnostic(
+            Severity::Error,
+            None,
+            "stale",
+            CODE.len()..CODE.len() + 3,
+        )];
+        assert!(on_line(CODE, &stale, line_of(CODE, CODE.len())).is_empty());
+    }
+
+    #[test]
+    fn popup_lists_errors_first_with_their_actions() {
+        let all = diagnostics();
+        let entries = popup_entries(CODE, &all.iter().collect::<Vec<_>>());
+        let summary: Vec<(Severity, Option<&str>)> = entries
+            .iter()
+            .map(|entry| (entry.severity, entry.code.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Severity::Error, Some("E0433")),
+                (Severity::Error, Some("E0308")),
+                (Severity::Warning, Some("unused_variables")),
+            ]
+        );
+
+        let labels = |entry: &PopupEntry| -> Vec<String> {
+            entry.actions.iter().map(QuickAction::label).collect()
+        };
+        // The out-of-range suggestion is dropped; error codes can't be allowed.
+        assert_eq!(
+            labels(&entries[0]),
+            vec!["Add import std::collections::HashMap"]
+        );
+        assert!(labels(&entries[1]).is_empty());
+        assert_eq!(
+            labels(&entries[2]),
+            vec![
+                "If this is intentional, prefix it with an underscore",
+                "Allow unused_variables here",
+            ]
+        );
+        assert_eq!(entries[2].children.len(), 1);
+    }
+
+    #[test]
+    fn actions_apply_to_the_code() {
+        let all = diagnostics();
+        let entries = popup_entries(CODE, &all.iter().collect::<Vec<_>>());
+        let imported = entries[0].actions[0].apply(CODE);
+        assert!(imported.starts_with("use std::collections::HashMap;\nuse std::fmt;\n"));
+        let renamed = entries[2].actions[0].apply(CODE);
+        assert!(renamed.contains("let _unused = 1;"));
+        let allowed = entries[2].actions[1].apply(CODE);
+        assert!(allowed.contains(
+            "impl S {\n    #[allow(unused_variables)]\n    /// Docs.\n    fn get(&self)"
+        ));
+        assert!(crate::parse_rust_code(&allowed).is_ok());
+    }
+
+    #[test]
+    fn allow_goes_above_the_innermost_item() {
+        let code = "mod outer {\n    fn f() {\n        fn inner() { let a = 1; }\n    }\n}\n\ntrait T {\n    fn g() { let b = 2; }\n}\n";
+        let offset = |needle: &str| code.find(needle).unwrap();
+        assert_eq!(
+            allow_insertion(code, offset("a = 1"), "dead_code"),
+            Some((
+                offset("        fn inner"),
+                "        #[allow(dead_code)]\n".to_string()
+            ))
+        );
+        assert_eq!(
+            allow_insertion(code, offset("b = 2"), "unused"),
+            Some((offset("    fn g"), "    #[allow(unused)]\n".to_string()))
+        );
+        assert_eq!(
+            allow_insertion(code, offset("outer"), "x"),
+            Some((0, "#[allow(x)]\n".to_string()))
+        );
+        // Between items, and in code that doesn't parse.
+        assert_eq!(allow_insertion(code, offset("\n\ntrait") + 1, "x"), None);
+        assert_eq!(allow_insertion("fn main( {", 3, "x"), None);
+    }
+
+    #[test]
+    fn error_codes_are_told_from_lints() {
+        assert!(is_error_code("E0308"));
+        assert!(!is_error_code("E030"));
+        assert!(!is_error_code("clippy::needless_range_loop"));
+        assert!(!is_error_code("Eabcd"));
+        assert_eq!(capitalize("éclair"), "Éclair");
+        assert_eq!(capitalize(""), "");
+    }
+}
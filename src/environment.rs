@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Stored in place of the value of a redacted variable.
+pub const REDACTED: &str = "<redacted>";
+
+pub const DEFAULT_REDACTIONS: &str = "*KEY*, *TOKEN*, *SECRET*, *PASSWORD*";
+
+/// The environment a run was started with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvironmentCapture {
+    pub vars: BTreeMap<String, String>,
+    pub working_dir: PathBuf,
+    pub rustc_version: String,
+    pub cargo_version: String,
+}
+
+impl EnvironmentCapture {
+    /// Captures the current process environment, redacting the values of
+    /// variables whose names match any of `redactions`.
+    pub fn capture(redactions: &[String]) -> Self {
+        Self {
+            vars: redact(current_vars(), redactions),
+            working_dir: std::env::current_dir().unwrap_or_default(),
+            rustc_version: tool_version("rustc"),
+            cargo_version: tool_version("cargo"),
+        }
+    }
+
+    /// The variables to replay. Redacted values can't be restored from the
+    /// capture, so they're taken from `current` when available.
+    pub fn replay_vars(&self, current: &BTreeMap<String, String>) -> Vec<(String, String)> {
+        self.vars
+            .iter()
+            .filter_map(|(key, value)| {
+                if value == REDACTED {
+                    current.get(key).map(|value| (key.clone(), value.clone()))
+                } else {
+                    Some((key.clone(), value.clone()))
+                }
+            })
+            .collect()
+    }
+
+    /// Serializes as a header of `# key: value` lines followed by one
+    /// `KEY=VALUE` line per variable.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "# working_dir: {}\n# rustc: {}\n# cargo: {}\n",
+            self.working_dir.display(),
+            self.rustc_version,
+            self.cargo_version
+        );
+        for (key, value) in &self.vars {
+            text.push_str(&format!("{}={}\n", key, escape_newlines(value)));
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        let mut capture = Self {
+            vars: BTreeMap::new(),
+            working_dir: PathBuf::new(),
+            rustc_version: String::new(),
+            cargo_version: String::new(),
+        };
+        for line in text.lines() {
+            if let Some(header) = line.strip_prefix("# ") {
+                match header.split_once(": ") {
+                    Some(("working_dir", value)) => capture.working_dir = PathBuf::from(value),
+                    Some(("rustc", value)) => capture.rustc_version = value.to_string(),
+                    Some(("cargo", value)) => capture.cargo_version = value.to_string(),
+                    _ => {}
+                }
+            } else if let Some((key, value)) = line.split_once('=') {
+                capture
+                    .vars
+                    .insert(key.to_string(), unescape_newlines(value));
+            }
+        }
+        capture
+    }
+}
+
+pub fn parse_redactions(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Collects `vars`, replacing the values of names matching any pattern.
+pub fn redact(
+    vars: impl IntoIterator<Item = (String, String)>,
+    patterns: &[String],
+) -> BTreeMap<String, String> {
+    vars.into_iter()
+        .map(|(key, value)| {
+            if patterns.iter().any(|pattern| glob_match(pattern, &key)) {
+                (key, REDACTED.to_string())
+            } else {
+                (key, value)
+            }
+        })
+        .collect()
+}
+
+/// Case-insensitive match of `name` against a pattern where `*` matches any
+/// run of characters.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_uppercase();
+    let name = name.to_ascii_uppercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !name.starts_with(first) || !name[first.len()..].ends_with(last) {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Names of variables that are missing, added or changed in `current`
+/// compared to `recorded`. Redacted variables only count when missing.
+pub fn diff(
+    recorded: &BTreeMap<String, String>,
+    current: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut names: Vec<String> = recorded
+        .iter()
+        .filter(|(key, value)| match current.get(*key) {
+            Some(current) => *value != REDACTED && current != *value,
+            None => true,
+        })
+        .map(|(key, _)| key.clone())
+        .chain(
+            current
+                .keys()
+                .filter(|key| !recorded.contains_key(*key))
+                .cloned(),
+        )
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn diff_summary(differences: &[String]) -> String {
+    match differences.len() {
+        0 => "Environment matches the current one".to_string(),
+        1 => "1 variable differs from current environment".to_string(),
+        n => format!("{} variables differ from current environment", n),
+    }
+}
+
+pub fn current_vars() -> BTreeMap<String, String> {
+    std::env::vars_os()
+        .map(|(key, value)| {
+            (
+                key.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// First line of `tool --version`, or empty when it can't be run.
+pub fn tool_version(tool: &str) -> String {
+    Command::new(tool)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+fn escape_newlines(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape_newlines(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                result.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                result.push('\\');
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn globs_match_case_insensitively() {
+        assert!(glob_match("*KEY*", "AWS_SECRET_ACCESS_KEY"));
+        assert!(glob_match("*KEY*", "api_key_file"));
+        assert!(glob_match("*key*", "KEYRING"));
+        assert!(!glob_match("*KEY*", "KY_VALUE"));
+        assert!(glob_match("GITHUB_*", "GITHUB_TOKEN"));
+        assert!(!glob_match("GITHUB_*", "MY_GITHUB_TOKEN"));
+        assert!(glob_match("*_TOKEN", "NPM_TOKEN"));
+        assert!(!glob_match("*_TOKEN", "NPM_TOKEN_FILE"));
+        assert!(glob_match("A*B*C", "AxxBxxC"));
+        assert!(!glob_match("A*B*C", "AxxCxxB"));
+        assert!(glob_match("HOME", "home"));
+        assert!(!glob_match("HOME", "HOMEPATH"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn globs_dont_reuse_overlapping_ends() {
+        assert!(!glob_match("AB*BC", "ABC"));
+        assert!(!glob_match("A*A", "A"));
+        assert!(glob_match("A*A", "AA"));
+    }
+
+    #[test]
+    fn redaction_patterns_parse_from_a_comma_list() {
+        assert_eq!(
+            parse_redactions(DEFAULT_REDACTIONS),
+            vec!["*KEY*", "*TOKEN*", "*SECRET*", "*PASSWORD*"]
+        );
+        assert_eq!(parse_redactions(" a ,, b ,"), vec!["a", "b"]);
+        assert!(parse_redactions("").is_empty());
+    }
+
+    #[test]
+    fn matching_values_are_redacted() {
+        let redacted = redact(
+            vars(&[
+                ("GITHUB_TOKEN", "ghp_123"),
+                ("DB_PASSWORD", "hunter2"),
+                ("PATH", "/usr/bin"),
+                ("Api_Key", "k"),
+            ]),
+            &parse_redactions(DEFAULT_REDACTIONS),
+        );
+        assert_eq!(
+            redacted,
+            vars(&[
+                ("GITHUB_TOKEN", REDACTED),
+                ("DB_PASSWORD", REDACTED),
+                ("PATH", "/usr/bin"),
+                ("Api_Key", REDACTED),
+            ])
+        );
+    }
+
+    #[test]
+    fn diffs_list_missing_added_and_changed_names() {
+        let recorded = vars(&[("A", "1"), ("B", "2"), ("C", "3"), ("SECRET", REDACTED)]);
+        let current = vars(&[("A", "1"), ("B", "20"), ("D", "4"), ("SECRET", "x")]);
+        let differences = diff(&recorded, &current);
+        assert_eq!(differences, vec!["B", "C", "D"]);
+        assert_eq!(
+            diff_summary(&differences),
+            "3 variables differ from current environment"
+        );
+    }
+
+    #[test]
+    fn a_missing_redacted_variable_still_differs() {
+        let recorded = vars(&[("SECRET", REDACTED)]);
+        let differences = diff(&recorded, &BTreeMap::new());
+        assert_eq!(differences, vec!["SECRET"]);
+        assert_eq!(
+            diff_summary(&differences),
+            "1 variable differs from current environment"
+        );
+        assert_eq!(
+            diff_summary(&diff(&recorded, &recorded)),
+            "Environment matches the current one"
+        );
+    }
+
+    #[test]
+    fn replay_fills_redacted_values_from_the_current_environment() {
+        let capture = EnvironmentCapture {
+            vars: vars(&[("A", "1"), ("TOKEN", REDACTED), ("GONE_KEY", REDACTED)]),
+            working_dir: PathBuf::from("/work"),
+            rustc_version: String::new(),
+            cargo_version: String::new(),
+        };
+        let current = vars(&[("A", "changed"), ("TOKEN", "live")]);
+        assert_eq!(
+            capture.replay_vars(&current),
+            vec![
+                ("A".to_string(), "1".to_string()),
+                ("TOKEN".to_string(), "live".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn captures_round_trip_through_text() {
+        let capture = EnvironmentCapture {
+            vars: vars(&[
+                ("MULTI", "line one\nline two"),
+                ("WIN", "C:\\new\\dir"),
+                ("EQ", "a=b"),
+                ("EMPTY", ""),
+            ]),
+            working_dir: PathBuf::from("/work/dir"),
+            rustc_version: "rustc 1.80.0 (051478957 2024-07-21)".to_string(),
+            cargo_version: "cargo 1.80.0".to_string(),
+        };
+        let text = capture.to_text();
+        assert_eq!(text.lines().count(), 7);
+        assert_eq!(EnvironmentCapture::from_text(&text), capture);
+    }
+}
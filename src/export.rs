@@ -0,0 +1,429 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use crate::runner::{BuildConfig, Profile, RunInputs};
+
+/// Whether the run needs more than a bare `.rs` file to be reproduced.
+pub fn needs_project(
+    config: &BuildConfig,
+    inputs: &RunInputs,
+    dependencies: &[(String, String)],
+) -> bool {
+    !dependencies.is_empty()
+        || config.profile != Profile::Debug
+        || !config.flags.trim().is_empty()
+        || !inputs.args.trim().is_empty()
+        || !inputs.env.trim().is_empty()
+        || !inputs.stdin.is_empty()
+}
+
+/// Turns a file name into a valid cargo package name.
+pub fn package_name(file: Option<&Path>) -> String {
+    let stem = file
+        .and_then(|file| file.file_stem())
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("crowbar_{}", name.trim_start_matches('_'))
+            .trim_end_matches('_')
+            .to_string()
+    }
+}
+
+/// Files of a minimal cargo project that runs `source` the way Crowbar
+/// would, as `(path, contents)` pairs under a `name/` folder. `dependencies`
+/// are `name = spec` pairs for the manifest. `variables` are the panel's
+/// `(name, type, literal)` triples, written next to the manifest so the baked
+/// in values stay readable. `provenance` describes the last run and goes on
+/// top of the README.
+pub fn cargo_project(
+    name: &str,
+    source: &str,
+    config: &BuildConfig,
+    inputs: &RunInputs,
+    dependencies: &[(String, String)],
+    variables: &[(String, String, String)],
+    provenance: Option<String>,
+) -> Vec<(String, Vec<u8>)> {
+    let mut manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n",
+        name
+    );
+    for (dependency, spec) in dependencies {
+        manifest.push_str(&format!("{} = {}\n", dependency, spec));
+    }
+    let mut files = vec![
+        (format!("{}/Cargo.toml", name), manifest.into_bytes()),
+        (format!("{}/src/main.rs", name), source.as_bytes().to_vec()),
+    ];
+
+    let flags: Vec<&str> = config.flags.split_whitespace().collect();
+    if !flags.is_empty() {
+        let quoted: Vec<String> = flags.iter().map(|flag| format!("{:?}", flag)).collect();
+        files.push((
+            format!("{}/.cargo/config.toml", name),
+            format!("[build]\nrustflags = [{}]\n", quoted.join(", ")).into_bytes(),
+        ));
+    }
+    files.push((
+        format!("{}/variables.toml", name),
+        variable_file(variables).into_bytes(),
+    ));
+    if !inputs.stdin.is_empty() {
+        files.push((
+            format!("{}/stdin.txt", name),
+            inputs.stdin.as_bytes().to_vec(),
+        ));
+    }
+    files.push((
+        format!("{}/README.txt", name),
+        (provenance.unwrap_or_default() + &readme(config, inputs)).into_bytes(),
+    ));
+    files
+}
+
+/// The sidecar listing each panel value baked into `src/main.rs`, one
+/// `[variables.name]` table per variable.
+fn variable_file(variables: &[(String, String, String)]) -> String {
+    let mut text = String::from("# Panel values baked into src/main.rs.\n");
+    for (name, var_type, literal) in variables {
+        text.push_str(&format!(
+            "\n[variables.{}]\ntype = {:?}\nvalue = {:?}\n",
+            name, var_type, literal
+        ));
+    }
+    text
+}
+
+fn readme(config: &BuildConfig, inputs: &RunInputs) -> String {
+    let mut command = String::from("cargo run");
+    if config.profile == Profile::Release {
+        command.push_str(" --release");
+    }
+    if !inputs.args.trim().is_empty() {
+        command.push_str(" -- ");
+        command.push_str(inputs.args.trim());
+    }
+    if !inputs.stdin.is_empty() {
+        command.push_str(" < stdin.txt");
+    }
+
+    let mut text = format!(
+        "Exported from Crowbar.\n\nRun it with:\n\n    {}\n\nThe panel values are listed in variables.toml.\n",
+        command
+    );
+    if !config.flags.trim().is_empty() {
+        text.push_str(&format!(
+            "\nExtra rustc flags ({}) are set in .cargo/config.toml.\n",
+            config.flags.trim()
+        ));
+    }
+    let env: Vec<&str> = inputs
+        .env
+        .lines()
+        .filter(|line| line.contains('='))
+        .collect();
+    if !env.is_empty() {
+        text.push_str("\nSet these environment variables first:\n\n");
+        for line in env {
+            text.push_str(&format!("    {}\n", line));
+        }
+    }
+    text
+}
+
+/// Writes `files` to a zip archive at `path`, deflating each entry.
+pub fn write_zip(path: &Path, files: &[(String, Vec<u8>)]) -> io::Result<()> {
+    std::fs::write(path, zip_archive(files)?)
+}
+
+fn zip_archive(files: &[(String, Vec<u8>)]) -> io::Result<Vec<u8>> {
+    // 1980-01-01 00:00, the earliest date zip can store.
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+    const UTF8_NAMES: u16 = 1 << 11;
+    const DEFLATE: u16 = 8;
+
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+    for (name, contents) in files {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(contents)?;
+        let compressed = encoder.finish()?;
+        let crc = crc32fast::hash(contents);
+        let offset = archive.len() as u32;
+
+        let mut header = Vec::new();
+        header.extend(20u16.to_le_bytes()); // version needed
+        header.extend(UTF8_NAMES.to_le_bytes());
+        header.extend(DEFLATE.to_le_bytes());
+        header.extend(DOS_TIME.to_le_bytes());
+        header.extend(DOS_DATE.to_le_bytes());
+        header.extend(crc.to_le_bytes());
+        header.extend((compressed.len() as u32).to_le_bytes());
+        header.extend((contents.len() as u32).to_le_bytes());
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // extra field length
+
+        archive.extend(0x04034b50u32.to_le_bytes());
+        archive.extend(&header);
+        archive.extend(name.as_bytes());
+        archive.extend(&compressed);
+
+        directory.extend(0x02014b50u32.to_le_bytes());
+        directory.extend(20u16.to_le_bytes()); // version made by
+        directory.extend(&header);
+        directory.extend(0u16.to_le_bytes()); // comment length
+        directory.extend(0u16.to_le_bytes()); // disk number
+        directory.extend(0u16.to_le_bytes()); // internal attributes
+        directory.extend(0u32.to_le_bytes()); // external attributes
+        directory.extend(offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+    }
+
+    let directory_offset = archive.len() as u32;
+    archive.extend(&directory);
+    archive.extend(0x06054b50u32.to_le_bytes());
+    archive.extend(0u16.to_le_bytes()); // this disk
+    archive.extend(0u16.to_le_bytes()); // directory disk
+    archive.extend((files.len() as u16).to_le_bytes());
+    archive.extend((files.len() as u16).to_le_bytes());
+    archive.extend((directory.len() as u32).to_le_bytes());
+    archive.extend(directory_offset.to_le_bytes());
+    archive.extend(0u16.to_le_bytes()); // comment length
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use flate2::read::DeflateDecoder;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "crowbar-test-export-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn u16_at(bytes: &[u8], at: usize) -> usize {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    /// Extracts `archive` into `dir` by walking its central directory, checking
+    /// each entry's size and checksum on the way.
+    fn unzip(archive: &[u8], dir: &Path) -> Vec<String> {
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(archive, end), 0x06054b50);
+        let count = u16_at(archive, end + 10);
+        let mut entry = u32_at(archive, end + 16);
+        let mut names = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(archive, entry), 0x02014b50);
+            let crc = u32_at(archive, entry + 16);
+            let size = u32_at(archive, entry + 24);
+            let name_len = u16_at(archive, entry + 28);
+            let local = u32_at(archive, entry + 42);
+            let name =
+                String::from_utf8(archive[entry + 46..entry + 46 + name_len].to_vec()).unwrap();
+            entry += 46 + name_len;
+
+            assert_eq!(u32_at(archive, local), 0x04034b50);
+            let compressed_len = u32_at(archive, local + 18);
+            let data = local + 30 + u16_at(archive, local + 26) + u16_at(archive, local + 28);
+            let mut contents = Vec::new();
+            DeflateDecoder::new(&archive[data..data + compressed_len])
+                .read_to_end(&mut contents)
+                .unwrap();
+            assert_eq!(contents.len(), size, "{}", name);
+            assert_eq!(crc32fast::hash(&contents) as usize, crc, "{}", name);
+
+            let path = dir.join(&name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            names.push(name);
+        }
+        names
+    }
+
+    /// The subset of TOML the exporter writes: table headers and `key = value`
+    /// lines whose value is a string, an array or an inline table. Returns
+    /// `(table, key, value)` for each pair.
+    fn toml_pairs(text: &str) -> Vec<(String, String, String)> {
+        let mut table = String::new();
+        let mut pairs = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                table = header.strip_suffix(']').expect(line).to_string();
+                assert!(!table.is_empty() && !table.contains(' '), "{}", line);
+                continue;
+            }
+            let (key, value) = line.split_once(" = ").expect(line);
+            assert!(
+                key.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+                "{}",
+                line
+            );
+            let quoted = value.len() >= 2 && value.starts_with('"') && value.ends_with('"');
+            let bracketed = (value.starts_with('[') && value.ends_with(']'))
+                || (value.starts_with('{') && value.ends_with('}'));
+            assert!(quoted || bracketed, "{}", line);
+            pairs.push((table.clone(), key.to_string(), value.to_string()));
+        }
+        pairs
+    }
+
+    fn value<'a>(pairs: &'a [(String, String, String)], table: &str, key: &str) -> &'a str {
+        pairs
+            .iter()
+            .find(|(t, k, _)| t == table && k == key)
+            .map(|(_, _, v)| v.as_str())
+            .unwrap_or_else(|| panic!("no {}.{}", table, key))
+    }
+
+    const SOURCE: &str = "fn main() {\n    let speed: f64 = 2.5;\n    let label: &str = \"fast\";\n    println!(\"{} {}\", label, speed);\n}\n";
+
+    #[test]
+    fn exported_project_unpacks_into_a_runnable_layout() {
+        let config = BuildConfig {
+            profile: Profile::Release,
+            flags: "-C target-cpu=native".to_string(),
+        };
+        let inputs = RunInputs {
+            args: "--fast 3".to_string(),
+            stdin: "1 2 3\n".to_string(),
+            env: "SEED=4\n".to_string(),
+            ..RunInputs::default()
+        };
+        let dependencies = vec![("rand".to_string(), "\"0.8\"".to_string())];
+        let variables = vec![
+            ("speed".to_string(), "f64".to_string(), "2.5".to_string()),
+            (
+                "label".to_string(),
+                "&str".to_string(),
+                "\"fast\"".to_string(),
+            ),
+        ];
+        let name = package_name(Some(Path::new("/tmp/My Experiment.rs")));
+        assert_eq!(name, "my_experiment");
+        let files = cargo_project(
+            &name,
+            SOURCE,
+            &config,
+            &inputs,
+            &dependencies,
+            &variables,
+            None,
+        );
+
+        let dir = scratch_dir("unpack");
+        std::fs::create_dir_all(&dir).unwrap();
+        let zip = dir.join("export.zip");
+        write_zip(&zip, &files).unwrap();
+        let names = unzip(&std::fs::read(&zip).unwrap(), &dir);
+        for expected in [
+            "Cargo.toml",
+            "src/main.rs",
+            "variables.toml",
+            "README.txt",
+            ".cargo/config.toml",
+            "stdin.txt",
+        ] {
+            assert!(
+                names.contains(&format!("{}/{}", name, expected)),
+                "{}",
+                expected
+            );
+        }
+        let project = dir.join(&name);
+
+        let main = std::fs::read_to_string(project.join("src/main.rs")).unwrap();
+        assert_eq!(main, SOURCE);
+        let file = syn::parse_file(&main).unwrap();
+        assert!(file
+            .items
+            .iter()
+            .any(|item| matches!(item, syn::Item::Fn(f) if f.sig.ident == "main")));
+
+        let manifest = toml_pairs(&std::fs::read_to_string(project.join("Cargo.toml")).unwrap());
+        assert_eq!(value(&manifest, "package", "name"), "\"my_experiment\"");
+        assert_eq!(value(&manifest, "package", "version"), "\"0.1.0\"");
+        assert_eq!(value(&manifest, "package", "edition"), "\"2021\"");
+        assert_eq!(value(&manifest, "dependencies", "rand"), "\"0.8\"");
+
+        let config =
+            toml_pairs(&std::fs::read_to_string(project.join(".cargo/config.toml")).unwrap());
+        assert_eq!(
+            value(&config, "build", "rustflags"),
+            "[\"-C\", \"target-cpu=native\"]"
+        );
+
+        let sidecar = toml_pairs(&std::fs::read_to_string(project.join("variables.toml")).unwrap());
+        assert_eq!(value(&sidecar, "variables.speed", "type"), "\"f64\"");
+        assert_eq!(value(&sidecar, "variables.speed", "value"), "\"2.5\"");
+        assert_eq!(value(&sidecar, "variables.label", "type"), "\"&str\"");
+        assert_eq!(
+            value(&sidecar, "variables.label", "value"),
+            "\"\\\"fast\\\"\""
+        );
+
+        let readme = std::fs::read_to_string(project.join("README.txt")).unwrap();
+        assert!(readme.contains("cargo run --release -- --fast 3 < stdin.txt"));
+        assert!(readme.contains("SEED=4"));
+        assert_eq!(
+            std::fs::read_to_string(project.join("stdin.txt")).unwrap(),
+            "1 2 3\n"
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn plain_debug_runs_need_no_project() {
+        let config = BuildConfig::default();
+        let inputs = RunInputs::default();
+        assert!(!needs_project(&config, &inputs, &[]));
+        assert!(needs_project(
+            &config,
+            &inputs,
+            &[("rand".to_string(), "\"0.8\"".to_string())]
+        ));
+        let inputs = RunInputs {
+            args: "1".to_string(),
+            ..RunInputs::default()
+        };
+        assert!(needs_project(&config, &inputs, &[]));
+    }
+
+    #[test]
+    fn package_names_are_valid_crate_names() {
+        assert_eq!(package_name(None), "crowbar");
+        assert_eq!(
+            package_name(Some(Path::new("2d-plot.rs"))),
+            "crowbar_2d_plot"
+        );
+        assert_eq!(package_name(Some(Path::new("Demo.rs"))), "demo");
+    }
+}
@@ -0,0 +1,324 @@
+use std::ops::Range;
+
+use crate::lexer::{tokenize, Token, TokenKind};
+use crate::numeric::Radix;
+use crate::{extract_type, literal_value, Function, Variable, VariableKind};
+
+/// `let name: Type = literal;` bindings found by scanning tokens, for code
+/// that doesn't parse. The results are approximate: they are shown in the
+/// panel and can be jumped to, but never used to rewrite the source. Ones
+/// inside macro bodies are also marked `in_macro`.
+pub fn let_variables(code: &str) -> Vec<Variable> {
+    let tokens = significant_tokens(code);
+    let macros = macro_bodies(&tokens, code);
+    let mut variables = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        if token.text(code) != "let" {
+            continue;
+        }
+        let mut rest = tokens[index + 1..].iter().peekable();
+        rest.next_if(|t| t.text(code) == "mut");
+        let Some(name) = rest.next().filter(|t| t.kind == TokenKind::Ident) else {
+            continue;
+        };
+        if rest.next().map(|t| t.text(code)) != Some(":") {
+            continue;
+        }
+
+        // The type runs up to the `=`; give up at anything a type can't hold.
+        let mut ty: Option<Range<usize>> = None;
+        let mut found_eq = false;
+        for t in rest.by_ref() {
+            match t.text(code) {
+                "=" => {
+                    found_eq = true;
+                    break;
+                }
+                ";" | "{" | "}" | "let" => break,
+                _ => ty = Some(ty.map_or(t.span.clone(), |ty| ty.start..t.span.end)),
+            }
+        }
+        let (Some(ty), true) = (ty, found_eq) else {
+            continue;
+        };
+
+        let literal = match rest.next() {
+            Some(t) if t.kind == TokenKind::Literal => t.span.clone(),
+            Some(t) if t.text(code) == "-" => match rest.next() {
+                Some(next) if next.kind == TokenKind::Literal && next.span.start == t.span.end => {
+                    t.span.start..next.span.end
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if rest.next().map(|t| t.text(code)) != Some(";") {
+            continue;
+        }
+
+        variables.push(Variable {
+            name: name.text(code).to_string(),
+            var_type: type_name(&code[ty]),
+            value: literal_value(&code[literal.clone()]).1,
+            radix: Radix::of(&code[literal.clone()]),
+            kind: VariableKind::Let,
+            span: Some(literal),
+            approximate: true,
+            in_macro: macros.iter().any(|body| body.contains(&token.span.start)),
+            edited: false,
+            original_value: None,
+            original_text: None,
+            raw: None,
+            formula: None,
+            updated_at: None,
+            function: None,
+            scope: Vec::new(),
+            annotation: Default::default(),
+        });
+    }
+
+    variables
+}
+
+/// Names of `fn` items outside any braces. Unbalanced braces in the broken
+/// part of the code can hide or invent a few of them.
+pub fn functions(code: &str) -> Vec<Function> {
+    let tokens = significant_tokens(code);
+    let macros = macro_bodies(&tokens, code);
+    let mut functions = Vec::new();
+    let mut depth = 0usize;
+
+    for (index, token) in tokens.iter().enumerate() {
+        if macros.iter().any(|body| body.contains(&token.span.start)) {
+            continue;
+        }
+        match token.text(code) {
+            "{" => depth += 1,
+            "}" => depth = depth.saturating_sub(1),
+            "fn" if depth == 0 => {
+                if let Some(name) = tokens.get(index + 1).filter(|t| t.kind == TokenKind::Ident) {
+                    functions.push(Function {
+                        name: name.text(code).to_string(),
+                        span: name.span.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    functions
+}
+
+/// Byte ranges of macro bodies, delimiters included: the group after
+/// `name!` in an invocation, or after `macro_rules! name`. Their tokens only
+/// mean something once the macro expands. An unclosed body runs to the end.
+fn macro_bodies(tokens: &[Token], code: &str) -> Vec<Range<usize>> {
+    let mut bodies: Vec<Range<usize>> = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        if bodies
+            .last()
+            .is_some_and(|body| body.contains(&token.span.start))
+        {
+            continue;
+        }
+        let is_bang = |t: &&Token| t.text(code) == "!" && t.span.start == token.span.end;
+        if token.kind != TokenKind::Ident
+            || is_keyword(token.text(code))
+            || tokens.get(index + 1).filter(is_bang).is_none()
+        {
+            continue;
+        }
+        let mut open = index + 2;
+        if token.text(code) == "macro_rules"
+            && tokens.get(open).is_some_and(|t| t.kind == TokenKind::Ident)
+        {
+            open += 1;
+        }
+        let Some(start) = tokens
+            .get(open)
+            .filter(|t| matches!(t.text(code), "(" | "[" | "{"))
+        else {
+            continue;
+        };
+
+        let mut depth = 0usize;
+        let mut end = code.len();
+        for t in &tokens[open..] {
+            match t.text(code) {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                end = t.span.end;
+                break;
+            }
+        }
+        bodies.push(start.span.start..end);
+    }
+    bodies
+}
+
+/// Keywords that can precede a `!` that negates rather than invokes.
+fn is_keyword(word: &str) -> bool {
+    matches!(
+        word,
+        "if" | "while" | "match" | "return" | "in" | "else" | "break" | "yield"
+    )
+}
+
+fn significant_tokens(code: &str) -> Vec<Token> {
+    tokenize(code)
+        .into_iter()
+        .filter(|token| !token.is_trivia())
+        .collect()
+}
+
+/// `extract_type` of the type text, which may parse even though the file
+/// around it doesn't.
+fn type_name(text: &str) -> String {
+    syn::parse_str::<syn::Type>(text)
+        .map(|ty| extract_type(&ty))
+        .unwrap_or_else(|_| "Unsupported".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A half-typed match arm below two good bindings.
+    const HALF_TYPED_MATCH: &str = "\
+fn helper() -> i32 { 1 }
+
+fn main() {
+    let speed: f64 = 2.5;
+    let mut count: u32 = 10;
+    match count {
+        0 => println!(\"none\"),
+        1 =>
+    }
+    let offset: i64 = -7;
+}
+
+fn after() {}
+";
+
+    /// An unclosed function followed by more declarations.
+    const UNCLOSED: &str = "\
+fn main() {
+    let name: &str = \"crowbar\";
+    if true {
+        let flag: bool = true;
+
+fn other() {
+    let ratio: f32 = 0.5;
+}
+";
+
+    fn summary(variables: &[Variable], code: &str) -> Vec<(String, String, String)> {
+        variables
+            .iter()
+            .map(|v| {
+                (
+                    v.name.clone(),
+                    v.var_type.clone(),
+                    code[v.span.clone().unwrap()].to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn triple(name: &str, ty: &str, literal: &str) -> (String, String, String) {
+        (name.to_string(), ty.to_string(), literal.to_string())
+    }
+
+    #[test]
+    fn fixtures_really_are_broken() {
+        for code in [HALF_TYPED_MATCH, UNCLOSED] {
+            assert!(crate::parse_rust_code(code).is_err());
+        }
+    }
+
+    #[test]
+    fn bindings_survive_a_half_typed_match_arm() {
+        let variables = let_variables(HALF_TYPED_MATCH);
+        assert_eq!(
+            summary(&variables, HALF_TYPED_MATCH),
+            vec![
+                triple("speed", "f64", "2.5"),
+                triple("count", "u32", "10"),
+                triple("offset", "i64", "-7"),
+            ]
+        );
+        assert!(variables.iter().all(|v| v.approximate && !v.in_macro));
+    }
+
+    #[test]
+    fn bindings_survive_unclosed_braces() {
+        assert_eq!(
+            summary(&let_variables(UNCLOSED), UNCLOSED),
+            vec![
+                triple("name", "&str", "\"crowbar\""),
+                triple("flag", "bool", "true"),
+                triple("ratio", "f32", "0.5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_simple_typed_literal_bindings_count() {
+        let code = "fn main() {\n    let untyped = 1;\n    let call: i32 = f();\n    let sum: i32 = 1 + 2;\n    let spaced: i32 = - 3;\n    let (a, b): (i32, i32) = (1, 2);\n    let missing: i32 =\n    let ok: Vec<u8> = 4;\n    let open: i32 = 5\n";
+        assert_eq!(
+            summary(&let_variables(code), code),
+            vec![triple("ok", "Vec<u8>", "4")]
+        );
+    }
+
+    #[test]
+    fn bindings_in_macros_are_marked() {
+        let code = "macro_rules! m {\n    () => { let inside: i32 = 1; };\n}\nfn main() {\n    lazy_static! { let lazy: u8 = 2; }\n    if !done { let outside: u8 = 3; }\n    let broken: i32 = \n";
+        let variables = let_variables(code);
+        let in_macro: Vec<(&str, bool)> = variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.in_macro))
+            .collect();
+        assert_eq!(
+            in_macro,
+            vec![("inside", true), ("lazy", true), ("outside", false)]
+        );
+    }
+
+    #[test]
+    fn top_level_functions_are_found() {
+        let names =
+            |code: &str| -> Vec<String> { functions(code).into_iter().map(|f| f.name).collect() };
+        assert_eq!(names(HALF_TYPED_MATCH), vec!["helper", "main", "after"]);
+        // Everything after the unclosed brace looks nested.
+        assert_eq!(names(UNCLOSED), vec!["main"]);
+        let code = "fn a() {\n    fn nested() {}\n}\nmacro_rules! m { () => { fn generated() {} } }\nfn b( {\n";
+        assert_eq!(names(code), vec!["a", "b"]);
+        let span = functions("fn main(").remove(0).span;
+        assert_eq!(span, 3..7);
+    }
+
+    #[test]
+    fn comments_and_strings_hide_lookalikes() {
+        let code = "// let fake: i32 = 1;\nfn main() {\n    let s: &str = \"let inner: i32 = 2;\";\n    /* fn hidden() {} */\n    let real: i32 = 3;\n    oops(\n}\n";
+        assert_eq!(
+            summary(&let_variables(code), code),
+            vec![
+                triple("s", "&str", "\"let inner: i32 = 2;\""),
+                triple("real", "i32", "3"),
+            ]
+        );
+        assert_eq!(
+            functions(code)
+                .into_iter()
+                .map(|f| f.name)
+                .collect::<Vec<_>>(),
+            vec!["main"]
+        );
+    }
+}
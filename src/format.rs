@@ -0,0 +1,268 @@
+use std::io::Write;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+use crate::indent::{self, IndentStyle};
+
+/// Formats Rust source; behind a trait so rustfmt can be swapped out.
+pub trait Formatter {
+    /// Formats a complete source file, returning why it couldn't otherwise.
+    fn format(&self, source: &str) -> Result<String, String>;
+}
+
+impl Default for Box<dyn Formatter> {
+    fn default() -> Self {
+        Box::new(Rustfmt)
+    }
+}
+
+/// Formats with the `rustfmt` found on `PATH`.
+#[derive(Default)]
+pub struct Rustfmt;
+
+impl Formatter for Rustfmt {
+    fn format(&self, source: &str) -> Result<String, String> {
+        let mut child = Command::new("rustfmt")
+            .args(["--edition", "2021", "--emit", "stdout"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run rustfmt: {}", e))?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(source.as_bytes())
+            .map_err(|e| format!("Failed to run rustfmt: {}", e))?;
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to run rustfmt: {}", e))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(format!(
+                "rustfmt failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+const WRAPPER_START: &str = "fn __crowbar_statement() {\n";
+
+/// `statement` formatted on its own inside a dummy function, re-indented for
+/// a line that starts with `indent` in the buffer's `style`.
+pub fn format_statement(
+    formatter: &dyn Formatter,
+    statement: &str,
+    indent: &str,
+    style: IndentStyle,
+) -> Result<String, String> {
+    let formatted = formatter.format(&format!("{}{}\n}}\n", WRAPPER_START, statement))?;
+    let body = formatted
+        .strip_prefix(WRAPPER_START)
+        .and_then(|rest| rest.trim_end().strip_suffix('}'))
+        .ok_or("rustfmt changed the wrapper function")?;
+    // rustfmt indents the body one level of four spaces.
+    let body: Vec<&str> = body
+        .trim_end()
+        .lines()
+        .map(|line| line.strip_prefix("    ").unwrap_or(line))
+        .collect();
+    let body = indent::convert(&body.join("\n"), style, 4);
+    Ok(body.replace('\n', &format!("\n{}", indent)))
+}
+
+/// Formats each of `statements` in `code` and splices the results back,
+/// leaving every byte outside them as it was. Nothing changes unless every
+/// statement could be formatted.
+pub fn format_statements(
+    formatter: &dyn Formatter,
+    code: &str,
+    statements: &[Range<usize>],
+    style: IndentStyle,
+) -> Result<String, String> {
+    let mut statements = statements.to_vec();
+    statements.sort_by_key(|span| std::cmp::Reverse(span.start));
+    let mut result = code.to_string();
+    for span in statements {
+        let line_start = code[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let indent: String = code[line_start..]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        let formatted = format_statement(formatter, &code[span.clone()], &indent, style)?;
+        result.replace_range(span, &formatted);
+    }
+    Ok(result)
+}
+
+/// Spans of the innermost `let` statements whose initializer contains one of
+/// `offsets`.
+pub fn statements_at(file: &syn::File, offsets: &[usize]) -> Vec<Range<usize>> {
+    let mut finder = StatementFinder {
+        offsets,
+        found: Vec::new(),
+    };
+    finder.visit_file(file);
+    let found = &finder.found;
+    found
+        .iter()
+        .filter(|span| {
+            !found
+                .iter()
+                .any(|other| other != *span && span.start <= other.start && other.end <= span.end)
+        })
+        .cloned()
+        .collect()
+}
+
+struct StatementFinder<'a> {
+    offsets: &'a [usize],
+    found: Vec<Range<usize>>,
+}
+
+impl<'ast> Visit<'ast> for StatementFinder<'_> {
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        if let Some(init) = &local.init {
+            let init = init.expr.span().byte_range();
+            if self.offsets.iter().any(|offset| init.contains(offset)) {
+                self.found.push(local.span().byte_range());
+            }
+        }
+        visit::visit_local(self, local);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Stands in for rustfmt: squeezes runs of spaces, drops the space before
+    /// `;` and indents the body one level, recording every input it gets.
+    #[derive(Default)]
+    struct Tidy {
+        inputs: RefCell<Vec<String>>,
+    }
+
+    impl Formatter for Tidy {
+        fn format(&self, source: &str) -> Result<String, String> {
+            self.inputs.borrow_mut().push(source.to_string());
+            let mut lines = source.lines();
+            let mut formatted = format!("{}\n", lines.next().unwrap_or_default());
+            for line in lines {
+                let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+                if line == "}" {
+                    formatted.push_str("}\n");
+                } else if !line.is_empty() {
+                    formatted.push_str(&format!("    {}\n", line.replace(" ;", ";")));
+                }
+            }
+            Ok(formatted)
+        }
+    }
+
+    /// rustfmt missing from `PATH`.
+    struct Missing;
+
+    impl Formatter for Missing {
+        fn format(&self, _: &str) -> Result<String, String> {
+            Err("Failed to run rustfmt: not found".to_string())
+        }
+    }
+
+    /// A formatter that rewrites more than the statement it was given.
+    struct Rename;
+
+    impl Formatter for Rename {
+        fn format(&self, source: &str) -> Result<String, String> {
+            Ok(source.replace("__crowbar_statement", "renamed"))
+        }
+    }
+
+    const CODE: &str = "fn main() {\n    let a  =  1 ;   // keep\n    let b = 2 ;\n    if true {\n        let c   = 3 ;\n    }\n}\n";
+
+    fn spans(code: &str, needles: &[&str]) -> Vec<Range<usize>> {
+        needles
+            .iter()
+            .map(|needle| {
+                let start = code.find(needle).unwrap();
+                start..start + needle.len()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_the_given_statements_change() {
+        let tidy = Tidy::default();
+        let statements = spans(CODE, &["let a  =  1 ;", "let c   = 3 ;"]);
+        let formatted =
+            format_statements(&tidy, CODE, &statements, IndentStyle::Spaces(4)).unwrap();
+        assert_eq!(
+            formatted,
+            "fn main() {\n    let a = 1;   // keep\n    let b = 2 ;\n    if true {\n        let c = 3;\n    }\n}\n"
+        );
+        assert_eq!(
+            *tidy.inputs.borrow(),
+            vec![
+                format!("{}let c   = 3 ;\n}}\n", WRAPPER_START),
+                format!("{}let a  =  1 ;\n}}\n", WRAPPER_START),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_statements_leave_the_file_as_is() {
+        let tidy = Tidy::default();
+        assert_eq!(
+            format_statements(&tidy, CODE, &[], IndentStyle::Spaces(4)).unwrap(),
+            CODE
+        );
+        assert!(tidy.inputs.borrow().is_empty());
+    }
+
+    #[test]
+    fn multi_line_statements_follow_the_buffer_indent() {
+        let code = "fn main() {\n\tif true {\n\t\tlet v = vec![1,\n   2] ;\n\t}\n}\n";
+        let statements = spans(code, &["let v = vec![1,\n   2] ;"]);
+        let formatted =
+            format_statements(&Tidy::default(), code, &statements, IndentStyle::Tabs).unwrap();
+        assert_eq!(
+            formatted,
+            "fn main() {\n\tif true {\n\t\tlet v = vec![1,\n\t\t2];\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn a_missing_formatter_changes_nothing() {
+        let statements = spans(CODE, &["let a  =  1 ;", "let b = 2 ;"]);
+        assert_eq!(
+            format_statements(&Missing, CODE, &statements, IndentStyle::Spaces(4)),
+            Err("Failed to run rustfmt: not found".to_string())
+        );
+    }
+
+    #[test]
+    fn a_rewritten_wrapper_is_refused() {
+        assert!(format_statement(&Rename, "let a = 1;", "", IndentStyle::Spaces(4)).is_err());
+    }
+
+    #[test]
+    fn statements_are_found_by_initializer_offsets() {
+        let code = "fn main() {\n    let outer = { let inner = 5; inner };\n    let other = 6;\n    let name = 7;\n}\n";
+        let file = syn::parse_file(code).unwrap();
+        let at = |needle: &str| code.find(needle).unwrap();
+
+        let found = statements_at(&file, &[at("5;")]);
+        assert_eq!(found, spans(code, &["let inner = 5;"]));
+        let found = statements_at(&file, &[at("6;"), at("5;")]);
+        assert_eq!(found, spans(code, &["let inner = 5;", "let other = 6;"]));
+        // The name isn't part of the initializer.
+        assert!(statements_at(&file, &[at("name")]).is_empty());
+    }
+}
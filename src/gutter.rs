@@ -0,0 +1,208 @@
+use std::ops::Range;
+
+/// What the gutter needs of one laid-out row of the editor's galley.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RowMetrics {
+    pub top: f32,
+    pub bottom: f32,
+    /// Whether the row ends its logical line; a wrapped line's other rows
+    /// don't.
+    pub ends_with_newline: bool,
+}
+
+/// What the gutter shows next to a row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Label {
+    /// 1-based number of the logical line the row starts.
+    Number(usize),
+    /// A row a long line wrapped onto.
+    Continuation,
+}
+
+/// Maps between logical lines, the visual rows they wrap onto and their
+/// vertical positions. Lines are 1-based like rustc's; rows are indices
+/// into the galley.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineMap {
+    rows: Vec<RowMetrics>,
+    /// Index of the first row of each line.
+    first_rows: Vec<usize>,
+}
+
+impl LineMap {
+    pub fn new(rows: Vec<RowMetrics>) -> Self {
+        let mut first_rows = vec![0];
+        for (index, row) in rows.iter().enumerate() {
+            if row.ends_with_newline && index + 1 < rows.len() {
+                first_rows.push(index + 1);
+            }
+        }
+        if rows.is_empty() {
+            first_rows.clear();
+        }
+        Self { rows, first_rows }
+    }
+
+    /// The rows `line` covers, empty when there's no such line.
+    pub fn rows_of(&self, line: usize) -> Range<usize> {
+        let Some(&start) = line.checked_sub(1).and_then(|i| self.first_rows.get(i)) else {
+            return 0..0;
+        };
+        let end = self
+            .first_rows
+            .get(line)
+            .copied()
+            .unwrap_or(self.rows.len());
+        start..end
+    }
+
+    /// The line `row` belongs to.
+    pub fn line_of_row(&self, row: usize) -> usize {
+        self.first_rows
+            .partition_point(|first| *first <= row)
+            .max(1)
+    }
+
+    /// Top of the first row to bottom of the last row of `line`.
+    pub fn y_range(&self, line: usize) -> Option<Range<f32>> {
+        let rows = self.rows_of(line);
+        let first = self.rows.get(rows.start)?;
+        let last = self.rows.get(rows.end.checked_sub(1)?)?;
+        Some(first.top..last.bottom)
+    }
+
+    /// Vertical extent of the first row of `line`, where its number goes.
+    pub fn first_row_y(&self, line: usize) -> Option<Range<f32>> {
+        let rows = self.rows_of(line);
+        let row = self.rows.get(rows.start).filter(|_| !rows.is_empty())?;
+        Some(row.top..row.bottom)
+    }
+
+    /// The label of every row, with the row's vertical extent.
+    pub fn labels(&self) -> Vec<(Range<f32>, Label)> {
+        let mut line = 0;
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let label = if self.first_rows.get(line) == Some(&index) {
+                    line += 1;
+                    Label::Number(line)
+                } else {
+                    Label::Continuation
+                };
+                (row.top..row.bottom, label)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rows 10 points high, laid out like a galley: `true` where the row
+    /// ends its line.
+    fn map(newlines: &[bool]) -> LineMap {
+        LineMap::new(
+            newlines
+                .iter()
+                .enumerate()
+                .map(|(index, ends_with_newline)| RowMetrics {
+                    top: index as f32 * 10.0,
+                    bottom: index as f32 * 10.0 + 10.0,
+                    ends_with_newline: *ends_with_newline,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn empty_galley_has_no_lines() {
+        let map = map(&[]);
+        assert_eq!(map.rows_of(1), 0..0);
+        assert_eq!(map.y_range(1), None);
+        assert_eq!(map.first_row_y(1), None);
+        assert!(map.labels().is_empty());
+        assert_eq!(map.line_of_row(0), 1);
+    }
+
+    #[test]
+    fn unwrapped_lines_are_one_row_each() {
+        // "a\nb\n": the galley has an empty row after the final newline.
+        let map = map(&[true, true, false]);
+        assert_eq!(map.rows_of(1), 0..1);
+        assert_eq!(map.rows_of(3), 2..3);
+        assert_eq!(map.rows_of(4), 0..0);
+        assert_eq!(map.rows_of(0), 0..0);
+        assert_eq!(map.y_range(2), Some(10.0..20.0));
+        let labels: Vec<Label> = map.labels().into_iter().map(|(_, l)| l).collect();
+        assert_eq!(
+            labels,
+            vec![Label::Number(1), Label::Number(2), Label::Number(3)]
+        );
+    }
+
+    #[test]
+    fn wrapped_lines_span_several_rows() {
+        // Line 1 wraps onto three rows, line 2 fits, line 3 wraps onto two.
+        let map = map(&[false, false, true, true, false, false]);
+        assert_eq!(map.rows_of(1), 0..3);
+        assert_eq!(map.rows_of(2), 3..4);
+        assert_eq!(map.rows_of(3), 4..6);
+        assert_eq!(map.y_range(1), Some(0.0..30.0));
+        assert_eq!(map.y_range(3), Some(40.0..60.0));
+        assert_eq!(map.first_row_y(1), Some(0.0..10.0));
+        assert_eq!(map.first_row_y(3), Some(40.0..50.0));
+        assert_eq!(map.first_row_y(4), None);
+
+        let rows_to_lines: Vec<usize> = (0..6).map(|row| map.line_of_row(row)).collect();
+        assert_eq!(rows_to_lines, vec![1, 1, 1, 2, 3, 3]);
+
+        assert_eq!(
+            map.labels(),
+            vec![
+                (0.0..10.0, Label::Number(1)),
+                (10.0..20.0, Label::Continuation),
+                (20.0..30.0, Label::Continuation),
+                (30.0..40.0, Label::Number(2)),
+                (40.0..50.0, Label::Number(3)),
+                (50.0..60.0, Label::Continuation),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_trailing_newline_row_is_not_a_new_line_by_itself() {
+        // Only a row after a newline starts a line; the last row's own
+        // newline flag adds nothing.
+        let map = map(&[true, true]);
+        assert_eq!(map.rows_of(2), 1..2);
+        assert_eq!(map.rows_of(3), 0..0);
+        assert_eq!(map.line_of_row(5), 2);
+    }
+
+    #[test]
+    fn uneven_row_heights_are_kept() {
+        let map = LineMap::new(vec![
+            RowMetrics {
+                top: 0.0,
+                bottom: 14.0,
+                ends_with_newline: false,
+            },
+            RowMetrics {
+                top: 14.0,
+                bottom: 30.0,
+                ends_with_newline: true,
+            },
+            RowMetrics {
+                top: 30.0,
+                bottom: 44.0,
+                ends_with_newline: false,
+            },
+        ]);
+        assert_eq!(map.y_range(1), Some(0.0..30.0));
+        assert_eq!(map.first_row_y(1), Some(0.0..14.0));
+        assert_eq!(map.y_range(2), Some(30.0..44.0));
+    }
+}
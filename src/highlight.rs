@@ -0,0 +1,158 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use eframe::egui::Color32;
+use syntect::easy::ScopeRangeIterator;
+use syntect::highlighting::{Highlighter, Theme};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use crate::spellcheck::{Dictionary, SpellCache};
+
+/// Syntax colors and spelling results for one version of the buffer.
+#[derive(Default)]
+pub struct Highlight {
+    /// Colored byte ranges covering the whole text.
+    pub sections: Vec<(Range<usize>, Color32)>,
+    /// Byte ranges of misspelled words in comments and strings.
+    pub misspelled: Vec<Range<usize>>,
+}
+
+/// Re-highlights only when the text, syntax, theme or spelling settings
+/// change; the layouter runs every frame otherwise.
+#[derive(Default)]
+pub struct HighlightCache {
+    key: Option<u64>,
+    highlight: Highlight,
+    spelling: SpellCache,
+}
+
+impl HighlightCache {
+    pub fn highlight(
+        &mut self,
+        text: &str,
+        syntax: Option<&SyntaxReference>,
+        syntax_set: &SyntaxSet,
+        theme: &Theme,
+        dictionary: Option<&Dictionary>,
+    ) -> &Highlight {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        syntax.map(|s| &s.name).hash(&mut hasher);
+        theme.name.hash(&mut hasher);
+        dictionary.is_some().hash(&mut hasher);
+        let key = hasher.finish();
+
+        if self.key != Some(key) {
+            self.highlight = highlight_text(text, syntax, syntax_set, theme)
+                .unwrap_or_else(|| plain(text, theme));
+            self.highlight.misspelled = match dictionary {
+                Some(dictionary) => self.check_spelling(text, dictionary),
+                None => Vec::new(),
+            };
+            self.key = Some(key);
+        }
+        &self.highlight
+    }
+
+    pub fn misspelled(&self) -> &[Range<usize>] {
+        &self.highlight.misspelled
+    }
+
+    /// Forces spelling to be rechecked, e.g. after the dictionary changed.
+    pub fn invalidate(&mut self) {
+        self.key = None;
+        self.spelling.clear();
+    }
+
+    fn check_spelling(&mut self, text: &str, dictionary: &Dictionary) -> Vec<Range<usize>> {
+        let prose_ranges = std::mem::take(&mut self.highlight.misspelled);
+        let mut misspelled = Vec::new();
+        for range in prose_ranges {
+            let mut offset = range.start;
+            for line in text[range].split_inclusive('\n') {
+                misspelled.extend(
+                    self.spelling
+                        .check(line, dictionary)
+                        .into_iter()
+                        .map(|word| offset + word.start..offset + word.end),
+                );
+                offset += line.len();
+            }
+        }
+        misspelled
+    }
+}
+
+/// Syntax colors for `text` without caching or spell checking, e.g. for a
+/// file that isn't in the editor.
+pub fn sections(
+    text: &str,
+    syntax: Option<&SyntaxReference>,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+) -> Vec<(Range<usize>, Color32)> {
+    highlight_text(text, syntax, syntax_set, theme)
+        .unwrap_or_else(|| plain(text, theme))
+        .sections
+}
+
+/// Highlights `text` line by line. Prose ranges (comments and strings) are
+/// collected into `misspelled` for the spell checker to narrow down.
+fn highlight_text(
+    text: &str,
+    syntax: Option<&SyntaxReference>,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+) -> Option<Highlight> {
+    let syntax = syntax?;
+    let highlighter = Highlighter::new(theme);
+    let mut parse_state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let prose_scopes = [Scope::new("comment").ok()?, Scope::new("string").ok()?];
+
+    let mut highlight = Highlight::default();
+    let mut offset = 0;
+    for line in LinesWithEndings::from(text) {
+        let ops = parse_state.parse_line(line, syntax_set).ok()?;
+        for (range, op) in ScopeRangeIterator::new(&ops, line) {
+            stack.apply(op).ok()?;
+            if range.is_empty() {
+                continue;
+            }
+            let range = offset + range.start..offset + range.end;
+            let foreground = highlighter.style_for_stack(&stack.scopes).foreground;
+            highlight.sections.push((
+                range.clone(),
+                Color32::from_rgb(foreground.r, foreground.g, foreground.b),
+            ));
+            let is_prose = stack
+                .scopes
+                .iter()
+                .any(|scope| prose_scopes.iter().any(|prose| prose.is_prefix_of(*scope)));
+            if is_prose {
+                match highlight.misspelled.last_mut() {
+                    Some(last) if last.end == range.start => last.end = range.end,
+                    _ => highlight.misspelled.push(range),
+                }
+            }
+        }
+        offset += line.len();
+    }
+    Some(highlight)
+}
+
+fn plain(text: &str, theme: &Theme) -> Highlight {
+    let foreground = theme
+        .settings
+        .foreground
+        .unwrap_or(syntect::highlighting::Color::WHITE);
+    Highlight {
+        sections: vec![(
+            0..text.len(),
+            Color32::from_rgb(foreground.r, foreground.g, foreground.b),
+        )],
+        misspelled: Vec::new(),
+    }
+}
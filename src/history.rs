@@ -0,0 +1,112 @@
+use crate::environment::EnvironmentCapture;
+use crate::provenance::Provenance;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Most runs the history keeps; older ones are dropped with their output
+/// folders.
+pub const MAX_RECORDS: usize = 100;
+
+/// One finished run, as listed in the history panel.
+pub struct RunRecord {
+    /// Seconds since the Unix epoch.
+    pub started: u64,
+    pub header: String,
+    pub output: String,
+    pub environment: Option<EnvironmentCapture>,
+    /// Missing for records that weren't produced by a run.
+    pub provenance: Option<Provenance>,
+    /// Folder the program was given for its output files.
+    pub out_dir: Option<PathBuf>,
+}
+
+/// Adds `record`, dropping the oldest records beyond [`MAX_RECORDS`] and
+/// deleting the output folders they were given.
+pub fn push(records: &mut Vec<RunRecord>, record: RunRecord) {
+    records.push(record);
+    let excess = records.len().saturating_sub(MAX_RECORDS);
+    for evicted in records.drain(..excess) {
+        if let Some(dir) = evicted.out_dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats a Unix timestamp as `HH:MM:SS` (UTC).
+pub fn format_time(secs: u64) -> String {
+    let day_secs = secs % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        day_secs / 3600,
+        day_secs % 3600 / 60,
+        day_secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(started: u64, out_dir: Option<PathBuf>) -> RunRecord {
+        RunRecord {
+            started,
+            header: String::new(),
+            output: String::new(),
+            environment: None,
+            provenance: None,
+            out_dir,
+        }
+    }
+
+    #[test]
+    fn eviction_deletes_the_oldest_output_folder() {
+        let base =
+            std::env::temp_dir().join(format!("crowbar-test-history-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let dirs: Vec<PathBuf> = (0..=MAX_RECORDS)
+            .map(|i| {
+                let dir = base.join(format!("run-{}", i));
+                std::fs::create_dir_all(&dir).unwrap();
+                std::fs::write(dir.join("out.txt"), "data").unwrap();
+                dir
+            })
+            .collect();
+        let mut records = Vec::new();
+        for (i, dir) in dirs.iter().enumerate().take(MAX_RECORDS) {
+            push(&mut records, record(i as u64, Some(dir.clone())));
+        }
+        assert!(dirs.iter().all(|dir| dir.exists()));
+
+        push(
+            &mut records,
+            record(MAX_RECORDS as u64, Some(dirs[MAX_RECORDS].clone())),
+        );
+        assert_eq!(records.len(), MAX_RECORDS);
+        assert_eq!(records[0].started, 1);
+        assert!(!dirs[0].exists());
+        assert!(dirs[1..].iter().all(|dir| dir.exists()));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn eviction_without_an_output_folder_deletes_nothing() {
+        let mut records: Vec<RunRecord> =
+            (0..MAX_RECORDS as u64).map(|i| record(i, None)).collect();
+        push(&mut records, record(MAX_RECORDS as u64, None));
+        assert_eq!(records.len(), MAX_RECORDS);
+        assert_eq!(records.last().unwrap().started, MAX_RECORDS as u64);
+    }
+
+    #[test]
+    fn format_time_wraps_at_midnight() {
+        assert_eq!(format_time(0), "00:00:00");
+        assert_eq!(format_time(86_400 + 3_661), "01:01:01");
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use eframe::egui::{Rect, Vec2};
+
+/// How often the HUD refreshes while a run is in flight.
+pub const REFRESH: Duration = Duration::from_millis(250);
+/// How long the final status stays up once a run is over.
+pub const LINGER: Duration = Duration::from_secs(2);
+/// Gap between the HUD and the edges of the editor.
+const MARGIN: f32 = 8.0;
+const MAX_LINE_CHARS: usize = 48;
+
+/// Where a HUD of `size` goes in `editor`: the bottom right corner, or the
+/// first other corner where it doesn't cover `caret_line`.
+pub fn placement(editor: Rect, size: Vec2, caret_line: Option<Rect>) -> Rect {
+    let inner = editor.shrink(MARGIN);
+    let corners = [
+        inner.right_bottom() - size,
+        inner.left_bottom() - Vec2::new(0.0, size.y),
+        inner.right_top() - Vec2::new(size.x, 0.0),
+        inner.left_top(),
+    ];
+    let rects = corners.map(|corner| Rect::from_min_size(corner, size));
+    rects
+        .iter()
+        .find(|rect| caret_line.is_none_or(|caret| !rect.intersects(caret)))
+        .copied()
+        .unwrap_or(rects[0])
+}
+
+/// When to repaint next for the HUD, given how long ago the run finished
+/// (`None` while it's still going). `None` once the HUD is gone.
+pub fn next_repaint(since_finished: Option<Duration>) -> Option<Duration> {
+    match since_finished {
+        None => Some(REFRESH),
+        Some(since) => LINGER.checked_sub(since).filter(|left| !left.is_zero()),
+    }
+}
+
+/// The last non-empty line of `output`, shortened to fit the HUD.
+pub fn last_line(output: &str) -> String {
+    let line = output
+        .lines()
+        .rev()
+        .map(str::trim_end)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    if line.chars().count() <= MAX_LINE_CHARS {
+        return line.to_string();
+    }
+    let mut short: String = line.chars().take(MAX_LINE_CHARS - 1).collect();
+    short.push('…');
+    short
+}
+
+/// `4.2s` under a minute, `1:05` above.
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", elapsed.as_secs_f32())
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// One-line summary of how a run ended.
+pub fn final_status(
+    build_failed: bool,
+    stopped: bool,
+    exit_code: Option<i32>,
+    elapsed: Duration,
+) -> String {
+    let outcome = if stopped {
+        "■ Stopped".to_string()
+    } else if build_failed {
+        "✖ Build failed".to_string()
+    } else {
+        match exit_code {
+            Some(0) => "✔ Finished".to_string(),
+            Some(code) => format!("✖ Exited with {}", code),
+            None => "✖ Killed".to_string(),
+        }
+    };
+    format!("{} after {}", outcome, format_elapsed(elapsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::pos2;
+
+    fn editor() -> Rect {
+        Rect::from_min_max(pos2(0.0, 0.0), pos2(400.0, 300.0))
+    }
+
+    /// A caret line across the editor at height `y`.
+    fn caret(y: f32) -> Option<Rect> {
+        Some(Rect::from_min_max(pos2(0.0, y), pos2(400.0, y + 14.0)))
+    }
+
+    #[test]
+    fn the_hud_sits_bottom_right_inside_the_margin() {
+        let size = Vec2::new(100.0, 40.0);
+        let bottom_right = Rect::from_min_max(pos2(292.0, 252.0), pos2(392.0, 292.0));
+        assert_eq!(placement(editor(), size, None), bottom_right);
+        assert_eq!(placement(editor(), size, caret(100.0)), bottom_right);
+    }
+
+    #[test]
+    fn the_hud_moves_to_a_corner_clear_of_the_caret() {
+        let size = Vec2::new(100.0, 40.0);
+        // Both bottom corners cover a caret on the last line.
+        let top_right = Rect::from_min_max(pos2(292.0, 8.0), pos2(392.0, 48.0));
+        assert_eq!(placement(editor(), size, caret(270.0)), top_right);
+
+        // A caret line only on the right leaves the bottom left corner free.
+        let right = Rect::from_min_max(pos2(300.0, 270.0), pos2(400.0, 284.0));
+        let bottom_left = Rect::from_min_max(pos2(8.0, 252.0), pos2(108.0, 292.0));
+        assert_eq!(placement(editor(), size, Some(right)), bottom_left);
+
+        // With nowhere clear it stays bottom right.
+        let everywhere = Some(editor());
+        let bottom_right = Rect::from_min_max(pos2(292.0, 252.0), pos2(392.0, 292.0));
+        assert_eq!(placement(editor(), size, everywhere), bottom_right);
+    }
+
+    #[test]
+    fn the_hud_refreshes_while_running_then_lingers() {
+        assert_eq!(next_repaint(None), Some(REFRESH));
+        assert_eq!(next_repaint(Some(Duration::ZERO)), Some(LINGER));
+        assert_eq!(
+            next_repaint(Some(Duration::from_millis(500))),
+            Some(LINGER - Duration::from_millis(500))
+        );
+        assert_eq!(next_repaint(Some(LINGER)), None);
+        assert_eq!(next_repaint(Some(LINGER * 3)), None);
+    }
+
+    #[test]
+    fn elapsed_times_are_seconds_then_minutes() {
+        assert_eq!(format_elapsed(Duration::ZERO), "0.0s");
+        assert_eq!(format_elapsed(Duration::from_millis(4_240)), "4.2s");
+        assert_eq!(format_elapsed(Duration::from_millis(59_900)), "59.9s");
+        assert_eq!(format_elapsed(Duration::from_secs(60)), "1:00");
+        assert_eq!(format_elapsed(Duration::from_secs(65)), "1:05");
+        assert_eq!(format_elapsed(Duration::from_secs(3_725)), "62:05");
+        assert_eq!(
+            final_status(false, false, Some(0), Duration::from_secs(65)),
+            "✔ Finished after 1:05"
+        );
+    }
+}
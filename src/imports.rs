@@ -0,0 +1,270 @@
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::Item;
+
+/// An unresolved name rustc suggested imports for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportSuggestion {
+    pub name: String,
+    /// Full `use …;` lines, in rustc's order.
+    pub candidates: Vec<String>,
+}
+
+/// Collects the `use` lines rustc suggests under "help: consider importing"
+/// for unresolved names, one entry per name.
+pub fn parse_suggestions(stderr: &str) -> Vec<ImportSuggestion> {
+    let mut suggestions: Vec<ImportSuggestion> = Vec::new();
+    let mut name = None;
+    let mut in_import_help = false;
+    for line in stderr.lines() {
+        if line.starts_with("error") {
+            name = line
+                .contains("cannot find")
+                .then(|| line.split('`').nth(1).map(str::to_string))
+                .flatten();
+            in_import_help = false;
+        } else if let Some(help) = line.strip_prefix("help: ") {
+            in_import_help = help.starts_with("consider importing");
+        } else if let (true, Some(name)) = (in_import_help, &name) {
+            let Some(candidate) = suggested_use(line) else {
+                continue;
+            };
+            match suggestions.iter_mut().find(|s| s.name == *name) {
+                Some(suggestion) if suggestion.candidates.contains(&candidate) => {}
+                Some(suggestion) => suggestion.candidates.push(candidate),
+                None => suggestions.push(ImportSuggestion {
+                    name: name.clone(),
+                    candidates: vec![candidate],
+                }),
+            }
+        }
+    }
+    suggestions
+}
+
+/// `use …;` from a suggestion line such as `1 + use std::rc::Rc;`.
+fn suggested_use(line: &str) -> Option<String> {
+    let (line_number, added) = line.split_once('+')?;
+    let added = added.trim();
+    (line_number.trim().parse::<usize>().is_ok()
+        && added.starts_with("use ")
+        && added.ends_with(';'))
+    .then(|| added.to_string())
+}
+
+/// Inserts `use_line` among the top-level `use` items, keeping them sorted,
+/// or after the inner attributes when there are none. Returns `None` when
+/// the import is already there.
+pub fn add_import(code: &str, use_line: &str) -> Option<String> {
+    let use_line = use_line.trim();
+    // Compared as tokens, so spacing differences don't matter.
+    let tokens = |text: &str| {
+        syn::parse_str::<syn::ItemUse>(text)
+            .map(|item| item.to_token_stream().to_string())
+            .unwrap_or_else(|_| text.split_whitespace().collect::<Vec<_>>().join(" "))
+    };
+    let wanted = tokens(use_line);
+
+    let insert_at = match crate::parse_rust_code(code) {
+        Ok(file) => {
+            let uses: Vec<_> = file
+                .items
+                .iter()
+                .filter(|item| matches!(item, Item::Use(_)))
+                .map(|item| item.span().byte_range())
+                .collect();
+            if uses
+                .iter()
+                .any(|span| tokens(&code[span.clone()]) == wanted)
+            {
+                return None;
+            }
+            match uses.iter().find(|span| code[(*span).clone()] > *use_line) {
+                Some(next) => Insert::Before(line_start(code, next.start)),
+                None => match uses.last() {
+                    Some(last) => Insert::After(last.end),
+                    None => match file.attrs.last() {
+                        Some(attr) => Insert::Block(attr.span().byte_range().end),
+                        None => Insert::Block(0),
+                    },
+                },
+            }
+        }
+        // The buffer doesn't parse, so fall back to skipping inner attribute
+        // and doc lines.
+        Err(_) => {
+            if code.lines().any(|line| tokens(line) == wanted) {
+                return None;
+            }
+            let header = code
+                .lines()
+                .take_while(|line| line.starts_with("#![") || line.starts_with("//!"))
+                .map(|line| line.len() + 1)
+                .sum::<usize>();
+            Insert::Block(header.saturating_sub(1).min(code.len()))
+        }
+    };
+
+    let mut result = code.to_string();
+    match insert_at {
+        Insert::Before(offset) => result.insert_str(offset, &format!("{}\n", use_line)),
+        Insert::After(offset) => result.insert_str(offset, &format!("\n{}", use_line)),
+        Insert::Block(0) => result.insert_str(0, &format!("{}\n\n", use_line)),
+        Insert::Block(offset) => result.insert_str(offset, &format!("\n\n{}", use_line)),
+    }
+    Some(result)
+}
+
+enum Insert {
+    Before(usize),
+    After(usize),
+    /// Starts a new `use` block after `offset`.
+    Block(usize),
+}
+
+fn line_start(code: &str, offset: usize) -> usize {
+    code[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASHMAP: &str = "use std::collections::HashMap;";
+
+    #[test]
+    fn an_empty_file_gets_a_use_block() {
+        assert_eq!(add_import("", HASHMAP).unwrap(), format!("{}\n\n", HASHMAP));
+        assert_eq!(
+            add_import("fn main() {}\n", HASHMAP).unwrap(),
+            format!("{}\n\nfn main() {{}}\n", HASHMAP)
+        );
+    }
+
+    #[test]
+    fn imports_go_after_inner_attributes() {
+        let code = "#![allow(unused)]\n//! Crate docs.\n\nfn main() {}\n";
+        assert_eq!(
+            add_import(code, HASHMAP).unwrap(),
+            format!(
+                "#![allow(unused)]\n//! Crate docs.\n\n{}\n\nfn main() {{}}\n",
+                HASHMAP
+            )
+        );
+    }
+
+    #[test]
+    fn imports_stay_sorted_among_existing_uses() {
+        let code = "#![allow(unused)]\n\nuse std::cell::Cell;\nuse std::rc::Rc;\n\nfn main() {}\n";
+        assert_eq!(
+            add_import(code, HASHMAP).unwrap(),
+            format!(
+                "#![allow(unused)]\n\nuse std::cell::Cell;\n{}\nuse std::rc::Rc;\n\nfn main() {{}}\n",
+                HASHMAP
+            )
+        );
+        assert_eq!(
+            add_import(code, "use std::sync::Arc;").unwrap(),
+            "#![allow(unused)]\n\nuse std::cell::Cell;\nuse std::rc::Rc;\nuse std::sync::Arc;\n\nfn main() {}\n"
+        );
+        assert_eq!(
+            add_import(code, "use std::alloc::Layout;").unwrap(),
+            "#![allow(unused)]\n\nuse std::alloc::Layout;\nuse std::cell::Cell;\nuse std::rc::Rc;\n\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn duplicate_imports_are_skipped() {
+        let code = format!("{}\n\nfn main() {{}}\n", HASHMAP);
+        assert_eq!(add_import(&code, HASHMAP), None);
+        assert_eq!(
+            add_import(&code, "  use std::collections::HashMap;\n"),
+            None
+        );
+        let spaced = "use  std::collections::HashMap ;\nfn main() {}\n";
+        assert_eq!(add_import(spaced, HASHMAP), None);
+    }
+
+    #[test]
+    fn broken_code_still_gets_imports() {
+        let code = "#![allow(unused)]\nfn main( {\n";
+        assert_eq!(
+            add_import(code, HASHMAP).unwrap(),
+            format!("#![allow(unused)]\n\n{}\nfn main( {{\n", HASHMAP)
+        );
+        let with_import = "use std::collections::HashMap ;\nfn main( {\n";
+        assert_eq!(add_import(with_import, HASHMAP), None);
+    }
+
+    #[test]
+    fn nested_uses_dont_count() {
+        let code = "fn main() {\n    use std::collections::HashMap;\n}\n";
+        assert_eq!(
+            add_import(code, HASHMAP).unwrap(),
+            format!("{}\n\n{}", HASHMAP, code)
+        );
+    }
+
+    const STDERR: &str = "\
+error[E0433]: failed to resolve: use of undeclared type `HashMap`
+ --> main.rs:2:13
+  |
+2 |     let m = HashMap::new();
+  |             ^^^^^^^ use of undeclared type `HashMap`
+  |
+help: consider importing this struct
+  |
+1 + use std::collections::HashMap;
+  |
+
+error[E0412]: cannot find type `Rc` in this scope
+ --> main.rs:3:12
+  |
+help: consider importing one of these items
+  |
+1 + use std::rc::Rc;
+  |
+1 + use std::sync::Rc;
+  |
+1 + use std::rc::Rc;
+  |
+
+error[E0425]: cannot find value `Ordering` in this scope
+ --> main.rs:4:5
+  |
+help: consider importing one of these items
+  |
+1 + use std::cmp::Ordering;
+  |
+1 + use std::sync::atomic::Ordering;
+  |
+help: a local variable with a similar name exists
+  |
+4 -     Ordering
+4 +     ordering
+  |
+";
+
+    #[test]
+    fn suggestions_are_collected_per_name() {
+        assert_eq!(
+            parse_suggestions(STDERR),
+            vec![
+                ImportSuggestion {
+                    name: "Rc".to_string(),
+                    candidates: vec![
+                        "use std::rc::Rc;".to_string(),
+                        "use std::sync::Rc;".to_string(),
+                    ],
+                },
+                ImportSuggestion {
+                    name: "Ordering".to_string(),
+                    candidates: vec![
+                        "use std::cmp::Ordering;".to_string(),
+                        "use std::sync::atomic::Ordering;".to_string(),
+                    ],
+                },
+            ]
+        );
+    }
+}
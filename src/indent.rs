@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::lexer::{tokenize, TokenKind};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndentStyle {
+    Tabs,
+    /// Spaces per indentation level.
+    Spaces(usize),
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        IndentStyle::Spaces(4)
+    }
+}
+
+impl IndentStyle {
+    /// Text of one indentation level.
+    pub fn unit(self) -> String {
+        match self {
+            IndentStyle::Tabs => "\t".to_string(),
+            IndentStyle::Spaces(width) => " ".repeat(width),
+        }
+    }
+
+    /// Columns of one level; tabs count as four.
+    pub fn width(self) -> usize {
+        match self {
+            IndentStyle::Tabs => 4,
+            IndentStyle::Spaces(width) => width,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            IndentStyle::Tabs => "Tabs".to_string(),
+            IndentStyle::Spaces(width) => format!("Spaces: {}", width),
+        }
+    }
+}
+
+/// The indentation `code` mostly uses, or `None` when no line is indented.
+/// Lines starting inside multi-line strings or block comments are ignored.
+pub fn detect(code: &str) -> Option<IndentStyle> {
+    let literal_lines = literal_line_starts(code);
+    let mut tabs = 0;
+    let mut spaces = 0;
+    let mut steps: HashMap<usize, usize> = HashMap::new();
+    let mut previous = 0;
+
+    for (start, line) in lines(code) {
+        if line.trim().is_empty() || literal_lines.contains(&start) {
+            continue;
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        if indent.starts_with('\t') {
+            tabs += 1;
+            continue;
+        }
+        if indent.starts_with(' ') {
+            spaces += 1;
+        }
+        let width = indent.len();
+        if width > previous {
+            *steps.entry(width - previous).or_default() += 1;
+        }
+        previous = width;
+    }
+
+    if tabs == 0 && spaces == 0 {
+        return None;
+    }
+    if tabs > spaces {
+        return Some(IndentStyle::Tabs);
+    }
+    // The most common increase between lines; ties go to the smaller step.
+    let width = steps
+        .into_iter()
+        .filter(|(step, _)| (2..=8).contains(step))
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map_or(4, |(step, _)| step);
+    Some(IndentStyle::Spaces(width))
+}
+
+/// Re-indents every line of `code` with `to`, reading the existing
+/// indentation as levels of `level_width` columns where a tab is one level.
+/// Whitespace that doesn't make up a whole level stays as spaces. Lines
+/// starting inside strings or block comments are untouched.
+pub fn convert(code: &str, to: IndentStyle, level_width: usize) -> String {
+    let level_width = level_width.max(1);
+    let literal_lines = literal_line_starts(code);
+    let mut result = String::with_capacity(code.len());
+    for (start, line) in lines(code) {
+        let content = line.trim_start_matches([' ', '\t']);
+        if literal_lines.contains(&start) || content.trim().is_empty() {
+            result.push_str(line);
+            continue;
+        }
+        let columns: usize = line[..line.len() - content.len()]
+            .chars()
+            .map(|c| if c == '\t' { level_width } else { 1 })
+            .sum();
+        result.push_str(&to.unit().repeat(columns / level_width));
+        result.push_str(&" ".repeat(columns % level_width));
+        result.push_str(content);
+    }
+    result
+}
+
+/// Lines with their byte offsets, each keeping its line break.
+fn lines(code: &str) -> impl Iterator<Item = (usize, &str)> {
+    code.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    })
+}
+
+/// Offsets of line starts that fall inside a string or block comment.
+fn literal_line_starts(code: &str) -> Vec<usize> {
+    let spans: Vec<Range<usize>> = tokenize(code)
+        .into_iter()
+        .filter(|token| matches!(token.kind, TokenKind::Literal | TokenKind::BlockComment))
+        .map(|token| token.span)
+        .filter(|span| code[span.clone()].contains('\n'))
+        .collect();
+    lines(code)
+        .map(|(start, _)| start)
+        .filter(|start| {
+            spans
+                .iter()
+                .any(|span| span.start < *start && *start < span.end)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unindented_code_has_no_style() {
+        assert_eq!(detect(""), None);
+        assert_eq!(detect("fn main() {}\n"), None);
+        assert_eq!(detect("let a = 1;\n\n   \nlet b = 2;\n"), None);
+    }
+
+    #[test]
+    fn detects_space_widths() {
+        let two = "fn main() {\n  if x {\n    y();\n  }\n}\n";
+        assert_eq!(detect(two), Some(IndentStyle::Spaces(2)));
+        let four = "fn main() {\n    if x {\n        y();\n    }\n}\n";
+        assert_eq!(detect(four), Some(IndentStyle::Spaces(4)));
+        // A lone continuation line doesn't outvote the usual step.
+        let aligned = "fn main() {\n    let v = f(a,\n              b);\n    if x {\n        y();\n    }\n}\n";
+        assert_eq!(detect(aligned), Some(IndentStyle::Spaces(4)));
+    }
+
+    #[test]
+    fn odd_steps_fall_back_to_four() {
+        assert_eq!(detect("a\n b\n"), Some(IndentStyle::Spaces(4)));
+        assert_eq!(
+            detect("a\n                b\n"),
+            Some(IndentStyle::Spaces(4))
+        );
+    }
+
+    #[test]
+    fn mixed_files_follow_the_majority() {
+        let tabs = "fn a() {\n\tx();\n\ty();\n}\nfn b() {\n    z();\n}\n";
+        assert_eq!(detect(tabs), Some(IndentStyle::Tabs));
+        let spaces = "fn a() {\n\tx();\n}\nfn b() {\n    y();\n    z();\n}\n";
+        assert_eq!(detect(spaces), Some(IndentStyle::Spaces(4)));
+    }
+
+    #[test]
+    fn multi_line_literals_are_ignored() {
+        let code = "fn main() {\n\tlet s = \"\n  one\n  two\n  three\n\";\n\t/*\n  note\n  */\n}\n";
+        assert_eq!(detect(code), Some(IndentStyle::Tabs));
+        assert_eq!(
+            convert(code, IndentStyle::Spaces(4), 4),
+            "fn main() {\n    let s = \"\n  one\n  two\n  three\n\";\n    /*\n  note\n  */\n}\n"
+        );
+    }
+
+    #[test]
+    fn converts_spaces_to_tabs_and_back() {
+        let spaces = "fn main() {\n    if x {\n        y();\n    }\n}\n";
+        let tabs = "fn main() {\n\tif x {\n\t\ty();\n\t}\n}\n";
+        assert_eq!(convert(spaces, IndentStyle::Tabs, 4), tabs);
+        assert_eq!(convert(tabs, IndentStyle::Spaces(4), 4), spaces);
+        assert_eq!(
+            convert(tabs, IndentStyle::Spaces(2), 4),
+            "fn main() {\n  if x {\n    y();\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn partial_levels_and_mixed_lines_are_kept() {
+        // Alignment that isn't a whole level stays as spaces after the tabs.
+        assert_eq!(
+            convert("f(a,\n      b);\n", IndentStyle::Tabs, 4),
+            "f(a,\n\t  b);\n"
+        );
+        // A tab followed by spaces counts both towards the level.
+        assert_eq!(convert("\t    x\n", IndentStyle::Tabs, 4), "\t\tx\n");
+        assert_eq!(convert("\t  x\n", IndentStyle::Spaces(2), 2), "    x\n");
+    }
+
+    #[test]
+    fn blank_lines_and_missing_final_newline_survive() {
+        let code = "fn main() {\n    \n\n    x();\n}";
+        assert_eq!(
+            convert(code, IndentStyle::Tabs, 4),
+            "fn main() {\n    \n\n\tx();\n}"
+        );
+        assert_eq!(convert("", IndentStyle::Tabs, 4), "");
+        // A zero level width is treated as one column.
+        assert_eq!(convert("  x\n", IndentStyle::Tabs, 0), "\t\tx\n");
+    }
+
+    #[test]
+    fn style_helpers() {
+        assert_eq!(IndentStyle::default(), IndentStyle::Spaces(4));
+        assert_eq!(IndentStyle::Tabs.unit(), "\t");
+        assert_eq!(IndentStyle::Spaces(2).unit(), "  ");
+        assert_eq!(IndentStyle::Tabs.width(), 4);
+        assert_eq!(IndentStyle::Spaces(3).width(), 3);
+        assert_eq!(IndentStyle::Tabs.label(), "Tabs");
+        assert_eq!(IndentStyle::Spaces(2).label(), "Spaces: 2");
+    }
+}
@@ -0,0 +1,219 @@
+use std::ops::Range;
+
+/// Coarse token classes produced by [`tokenize`].
+///
+/// syn discards comments, so anything that needs to look at them (marker
+/// comments, annotations) goes through this lightweight lexer instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+    Ident,
+    Lifetime,
+    Literal,
+    Punct,
+}
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+impl Token {
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.span.clone()]
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
+    }
+}
+
+/// Splits `src` into tokens. Never fails: unterminated strings and comments
+/// simply run to the end of the input.
+pub fn tokenize(src: &str) -> Vec<Token> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let c = src[pos..].chars().next().unwrap();
+
+        let kind = if c.is_whitespace() {
+            pos += c.len_utf8();
+            while let Some(c) = src[pos..].chars().next() {
+                if !c.is_whitespace() {
+                    break;
+                }
+                pos += c.len_utf8();
+            }
+            TokenKind::Whitespace
+        } else if src[pos..].starts_with("//") {
+            pos = src[pos..].find('\n').map_or(bytes.len(), |i| pos + i);
+            TokenKind::LineComment
+        } else if src[pos..].starts_with("/*") {
+            pos = block_comment_end(src, pos);
+            TokenKind::BlockComment
+        } else if let Some(end) = string_literal_end(src, pos) {
+            pos = end;
+            TokenKind::Literal
+        } else if c == '\'' {
+            let (end, kind) = quote_end(src, pos);
+            pos = end;
+            kind
+        } else if c.is_ascii_digit() {
+            pos = number_end(src, pos);
+            TokenKind::Literal
+        } else if c == '_' || c.is_alphabetic() {
+            pos = ident_end(src, pos);
+            match &src[start..pos] {
+                "true" | "false" => TokenKind::Literal,
+                _ => TokenKind::Ident,
+            }
+        } else {
+            pos += c.len_utf8();
+            TokenKind::Punct
+        };
+
+        tokens.push(Token {
+            kind,
+            span: start..pos,
+        });
+    }
+
+    tokens
+}
+
+fn ident_end(src: &str, mut pos: usize) -> usize {
+    while let Some(c) = src[pos..].chars().next() {
+        if c != '_' && !c.is_alphanumeric() {
+            break;
+        }
+        pos += c.len_utf8();
+    }
+    pos
+}
+
+fn block_comment_end(src: &str, mut pos: usize) -> usize {
+    let bytes = src.as_bytes();
+    let mut depth = 0;
+    while pos < bytes.len() {
+        if src[pos..].starts_with("/*") {
+            depth += 1;
+            pos += 2;
+        } else if src[pos..].starts_with("*/") {
+            depth -= 1;
+            pos += 2;
+            if depth == 0 {
+                return pos;
+            }
+        } else {
+            pos += 1;
+        }
+    }
+    bytes.len()
+}
+
+/// Recognizes `"..."`, `r#"..."#`, `b"..."`, `br"..."` and `c"..."` literals
+/// starting at `pos` and returns the offset just past the closing quote.
+fn string_literal_end(src: &str, pos: usize) -> Option<usize> {
+    let rest = &src[pos..];
+    let prefix_len = ["br", "cr", "b", "c", "r", ""]
+        .iter()
+        .find(|prefix| {
+            rest.starts_with(*prefix)
+                && matches!(rest[prefix.len()..].chars().next(), Some('"' | '#'))
+        })?
+        .len();
+    let raw = rest[..prefix_len].ends_with('r');
+    let after_prefix = &rest[prefix_len..];
+
+    if raw {
+        let hashes = after_prefix.len() - after_prefix.trim_start_matches('#').len();
+        if !after_prefix[hashes..].starts_with('"') {
+            return None;
+        }
+        let body_start = pos + prefix_len + hashes + 1;
+        let closing = format!("\"{}", "#".repeat(hashes));
+        return Some(
+            src[body_start..]
+                .find(&closing)
+                .map_or(src.len(), |i| body_start + i + closing.len()),
+        );
+    }
+
+    if !after_prefix.starts_with('"') {
+        return None;
+    }
+    let bytes = src.as_bytes();
+    let mut i = pos + prefix_len + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    Some(bytes.len())
+}
+
+/// Distinguishes char literals (`'a'`, `'\n'`) from lifetimes (`'a`).
+fn quote_end(src: &str, pos: usize) -> (usize, TokenKind) {
+    let rest = &src[pos + 1..];
+    if rest.starts_with('\\') {
+        let end = rest[2.min(rest.len())..]
+            .find('\'')
+            .map_or(src.len(), |i| pos + 1 + 2 + i + 1);
+        return (end, TokenKind::Literal);
+    }
+    let mut chars = rest.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), Some('\'')) => (pos + 1 + c.len_utf8() + 1, TokenKind::Literal),
+        (Some(c), _) if c == '_' || c.is_alphabetic() => {
+            (ident_end(src, pos + 1), TokenKind::Lifetime)
+        }
+        _ => (pos + 1, TokenKind::Punct),
+    }
+}
+
+fn number_end(src: &str, start: usize) -> usize {
+    let bytes = src.as_bytes();
+    let mut pos = start;
+    let is_radix_prefixed = src[start..].starts_with("0x")
+        || src[start..].starts_with("0o")
+        || src[start..].starts_with("0b");
+    let mut seen_dot = false;
+    while pos < bytes.len() {
+        let b = bytes[pos];
+        let is_exponent_sign = (b == b'+' || b == b'-')
+            && !is_radix_prefixed
+            && matches!(bytes[pos - 1], b'e' | b'E')
+            && bytes[start..pos - 1]
+                .iter()
+                .all(|b| b.is_ascii_digit() || matches!(b, b'_' | b'.'));
+        if b.is_ascii_alphanumeric() || b == b'_' || is_exponent_sign {
+            pos += 1;
+        } else if b == b'.'
+            && !seen_dot
+            && !is_radix_prefixed
+            && bytes.get(pos + 1).is_some_and(|b| b.is_ascii_digit())
+        {
+            seen_dot = true;
+            pos += 1;
+        } else {
+            break;
+        }
+    }
+    pos
+}
+
+/// 1-based line number of the byte offset `pos` in `src`.
+pub fn line_of(src: &str, pos: usize) -> usize {
+    src[..pos].matches('\n').count() + 1
+}
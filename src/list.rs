@@ -0,0 +1,166 @@
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::{Expr, Token};
+
+use crate::{
+    default_value, format_literal, literal_like, number_value, numeric_literal, typed_literal,
+    VariableValue,
+};
+
+/// How a list initializer is written, kept when writing it back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Form {
+    /// `vec![a, b]`
+    Vec,
+    /// `[a, b]`
+    Array,
+    /// `[a; count]`, or `vec![a; count]` with `vec`, with the count as
+    /// written. Its one element is edited.
+    Repeat { vec: bool, count: String },
+}
+
+impl Form {
+    /// The form of the list literal `text`.
+    pub fn of(text: &str) -> Self {
+        match syn::parse_str::<Expr>(text) {
+            Ok(Expr::Repeat(repeat)) => Form::Repeat {
+                vec: false,
+                count: repeat.len.to_token_stream().to_string(),
+            },
+            Ok(Expr::Macro(mac)) => match vec_repeat(&mac.mac) {
+                Some(repeat) => Form::Repeat {
+                    vec: true,
+                    count: repeat.len.to_token_stream().to_string(),
+                },
+                None => Form::Vec,
+            },
+            Ok(Expr::Array(_)) => Form::Array,
+            _ => Form::Vec,
+        }
+    }
+}
+
+/// Element type of a `Vec<T>` or `[T; N]` type as [`crate::extract_type`]
+/// writes it.
+pub fn element_type(var_type: &str) -> Option<&str> {
+    if let Some(array) = var_type.strip_prefix('[') {
+        let (element, _) = array.strip_suffix(']')?.rsplit_once(';')?;
+        return Some(element.trim());
+    }
+    var_type.strip_prefix("Vec<")?.strip_suffix('>')
+}
+
+/// Whether the type fixes the number of elements, as an array's does.
+pub fn is_fixed(var_type: &str) -> bool {
+    var_type.starts_with('[')
+}
+
+/// The elements of a `vec![…]` or `[…]` initializer, or the one repeated
+/// element of the `[x; n]` forms; `None` for anything else.
+fn elements(expr: &Expr) -> Option<Vec<Expr>> {
+    match expr {
+        Expr::Array(array) => Some(array.elems.iter().cloned().collect()),
+        Expr::Repeat(repeat) => Some(vec![(*repeat.expr).clone()]),
+        Expr::Macro(mac) if mac.mac.path.is_ident("vec") => {
+            if let Some(repeat) = vec_repeat(&mac.mac) {
+                return Some(vec![*repeat.expr]);
+            }
+            mac.mac
+                .parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+                .ok()
+                .map(|elems| elems.into_iter().collect())
+        }
+        _ => None,
+    }
+}
+
+/// The body of `vec![x; n]` as the `[x; n]` it stands for.
+fn vec_repeat(mac: &syn::Macro) -> Option<syn::ExprRepeat> {
+    let tokens = &mac.tokens;
+    syn::parse2(quote::quote!([#tokens])).ok()
+}
+
+/// The type of an untyped list literal with elements of `element`:
+/// `Vec<T>` for `vec!`, `[T; N]` for arrays.
+pub fn list_type(expr: &Expr, element: &str, len: usize) -> String {
+    match expr {
+        Expr::Macro(_) => format!("Vec<{}>", element),
+        Expr::Repeat(repeat) => format!("[{}; {}]", element, repeat.len.to_token_stream()),
+        _ => format!("[{}; {}]", element, len),
+    }
+}
+
+/// Element type and values of a list of literals all of one kind. Elements
+/// take `element_type` when the declaration gives one, otherwise the type
+/// of the first element's literal. Lists with anything but literals, or
+/// literals of different kinds, aren't editable.
+pub fn parse(expr: &Expr, element_type: Option<&str>) -> Option<(String, Vec<VariableValue>)> {
+    let elements = elements(expr)?;
+    let mut ty = element_type.map(str::to_string);
+    let mut values = Vec::with_capacity(elements.len());
+    for element in &elements {
+        let value = match (&ty, numeric_literal(element)) {
+            (Some(ty), Some((lit, negative))) => number_value(lit, negative, ty),
+            _ => {
+                let (literal_ty, value) = typed_literal(element)?;
+                ty.get_or_insert(literal_ty);
+                value
+            }
+        };
+        values.push(value);
+    }
+    let ty = ty?;
+    let kind = std::mem::discriminant(&default_value(&ty));
+    let editable = !matches!(default_value(&ty), VariableValue::Unknown)
+        && values
+            .iter()
+            .all(|value| std::mem::discriminant(value) == kind);
+    editable.then_some((ty, values))
+}
+
+/// `items` written over the list literal `source` in the same form, each
+/// element written like the one it replaces, or like the last one when
+/// there are more of them now.
+pub fn rewrite(items: &[VariableValue], element_type: &str, source: &str) -> Option<String> {
+    let like = syn::parse_str::<Expr>(source)
+        .ok()
+        .as_ref()
+        .and_then(elements)
+        .unwrap_or_default();
+    literal(items, element_type, &Form::of(source), &like)
+}
+
+/// `items` written as a list of `element_type` in `form`, each like the
+/// element of `like` in its place, or the last one past its end.
+pub fn literal(
+    items: &[VariableValue],
+    element_type: &str,
+    form: &Form,
+    like: &[Expr],
+) -> Option<String> {
+    let elements = items
+        .iter()
+        .enumerate()
+        .map(
+            |(index, item)| match (like.get(index).or(like.last()), item) {
+                (Some(original), item) => literal_like(item, element_type, original),
+                (None, VariableValue::Str(val)) if element_type == "String" => {
+                    Some(format!("\"{}\".to_string()", val))
+                }
+                (None, item) => format_literal(item, ""),
+            },
+        )
+        .collect::<Option<Vec<_>>>()?;
+    Some(match form {
+        Form::Vec => format!("vec![{}]", elements.join(", ")),
+        Form::Array => format!("[{}]", elements.join(", ")),
+        Form::Repeat { vec, count } => {
+            let element = elements.first()?;
+            if *vec {
+                format!("vec![{}; {}]", element, count)
+            } else {
+                format!("[{}; {}]", element, count)
+            }
+        }
+    })
+}
@@ -0,0 +1,208 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often a waiting instance looks at the lock again.
+const POLL: Duration = Duration::from_millis(100);
+
+/// An exclusive claim on a path other Crowbar instances also write to, held
+/// as a `<path>.lock` file containing the owner's pid. Released when dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub fn lock_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    target.with_file_name(name)
+}
+
+/// Pid recorded in the lock file at `path`, `None` if it's missing or was
+/// left half-written.
+fn owner(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Takes the lock on `target` if nobody holds it. A lock whose owner isn't
+/// `alive` any more is stale and taken over. `Err(Some(pid))` names the
+/// holder; `Err(None)` means it's unknown or the lock file can't be created.
+pub fn try_lock(target: &Path, alive: &dyn Fn(u32) -> bool) -> Result<FileLock, Option<u32>> {
+    let path = lock_path(target);
+    // Twice at most: once more after clearing a stale lock.
+    for _ in 0..2 {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                let lock = FileLock { path };
+                file.write_all(std::process::id().to_string().as_bytes())
+                    .map_err(|_| None)?;
+                return Ok(lock);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => match owner(&path) {
+                Some(pid) if alive(pid) => return Err(Some(pid)),
+                // Without a pid it's normally being written by its creator.
+                None if is_recent(&path) => return Err(None),
+                _ => clear_stale(&path, alive),
+            },
+            Err(_) => return Err(None),
+        }
+    }
+    Err(owner(&path))
+}
+
+/// Whether the file at `path` was modified in the last few seconds.
+fn is_recent(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < Duration::from_secs(5))
+}
+
+/// Removes the lock at `path` unless its owner turns out to be alive.
+/// Renamed aside first so that two instances clearing the same stale lock
+/// can't remove a fresh one the other just created.
+fn clear_stale(path: &Path, alive: &dyn Fn(u32) -> bool) {
+    let aside = path.with_extension(format!("lock.stale-{}", std::process::id()));
+    if std::fs::rename(path, &aside).is_err() {
+        return;
+    }
+    // Someone else replaced the stale lock in the meantime; put theirs back.
+    if owner(&aside).is_some_and(alive) {
+        let _ = std::fs::hard_link(&aside, path);
+    }
+    let _ = std::fs::remove_file(&aside);
+}
+
+/// Waits up to `timeout` for the lock on `target`, calling `waiting` with the
+/// holder's pid each time it's found taken. Gives up early once `waiting`
+/// returns false.
+pub fn lock(
+    target: &Path,
+    timeout: Duration,
+    alive: &dyn Fn(u32) -> bool,
+    mut waiting: impl FnMut(Option<u32>) -> bool,
+) -> Result<FileLock, String> {
+    let started = Instant::now();
+    loop {
+        match try_lock(target, alive) {
+            Ok(lock) => return Ok(lock),
+            Err(holder) if started.elapsed() < timeout && waiting(holder) => {
+                std::thread::sleep(POLL);
+            }
+            Err(Some(pid)) => {
+                return Err(format!(
+                    "Another Crowbar instance (pid {}) is still using {}",
+                    pid,
+                    target.display()
+                ))
+            }
+            Err(None) => {
+                return Err(format!(
+                    "Couldn't lock {} (see {})",
+                    target.display(),
+                    lock_path(target).display()
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// A fresh directory for one test's lock files.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("crowbar-test-lock-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn two_threads_never_hold_the_lock_at_once() {
+        let target = scratch_dir("contention").join("temp_executable");
+        let inside = Arc::new(AtomicBool::new(false));
+        let waited = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let (target, inside, waited) = (target.clone(), inside.clone(), waited.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        let _lock = lock(&target, Duration::from_secs(10), &|_| true, |_| {
+                            waited.fetch_add(1, Ordering::SeqCst);
+                            true
+                        })
+                        .unwrap();
+                        assert!(!inside.swap(true, Ordering::SeqCst), "lock held twice");
+                        std::thread::sleep(Duration::from_millis(20));
+                        inside.store(false, Ordering::SeqCst);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(waited.load(Ordering::SeqCst) > 0);
+        assert!(!lock_path(&target).exists());
+    }
+
+    #[test]
+    fn held_lock_names_its_owner() {
+        let target = scratch_dir("held").join("temp_executable");
+        let _lock = try_lock(&target, &|_| true).unwrap();
+        assert_eq!(
+            try_lock(&target, &|_| true).unwrap_err(),
+            Some(std::process::id())
+        );
+    }
+
+    #[test]
+    fn stale_lock_of_a_dead_pid_is_taken_over() {
+        let target = scratch_dir("stale").join("temp_executable");
+        std::fs::write(lock_path(&target), "4000000").unwrap();
+        let lock = try_lock(&target, &|pid| pid != 4_000_000).unwrap();
+        assert_eq!(owner(&lock_path(&target)), Some(std::process::id()));
+        drop(lock);
+        assert!(!lock_path(&target).exists());
+    }
+
+    #[test]
+    fn stale_lock_is_kept_while_its_owner_lives() {
+        let target = scratch_dir("alive").join("temp_executable");
+        std::fs::write(lock_path(&target), "4000000").unwrap();
+        assert_eq!(try_lock(&target, &|_| true).unwrap_err(), Some(4_000_000));
+        assert_eq!(owner(&lock_path(&target)), Some(4_000_000));
+    }
+
+    #[test]
+    fn half_written_lock_is_left_to_its_creator() {
+        let target = scratch_dir("empty").join("temp_executable");
+        std::fs::write(lock_path(&target), "").unwrap();
+        assert_eq!(try_lock(&target, &|_| false).unwrap_err(), None);
+    }
+
+    #[test]
+    fn waiting_gives_up_when_asked() {
+        let target = scratch_dir("give-up").join("temp_executable");
+        std::fs::write(lock_path(&target), "4000000").unwrap();
+        let error = lock(&target, Duration::from_secs(10), &|_| true, |_| false).unwrap_err();
+        assert!(error.contains("4000000"), "{}", error);
+    }
+}
@@ -1,29 +1,140 @@
+mod analysis;
+mod annotation;
+mod bugreport;
+mod capabilities;
+mod checker;
+mod compare;
+mod debug_log;
+mod dependencies;
+mod determinism;
+mod diagnostics;
+mod environment;
+mod export;
+mod fallback;
+mod format;
+mod gutter;
+mod highlight;
+mod history;
+mod hud;
+mod imports;
+mod indent;
+mod lexer;
+mod list;
+mod lock;
+mod markers;
+mod nudge;
+mod numeric;
+mod optional;
+mod outline;
+mod outputs;
+mod panel;
+mod paths;
+mod processes;
+mod project;
+mod provenance;
+mod randomize;
+mod runner;
+mod script;
+mod snippets;
+mod spellcheck;
+mod storage;
+mod structure;
+mod sweep;
+mod symbols;
+mod syntax;
+mod theme;
+mod tour;
+mod tunable;
+mod tuple;
+mod types;
+
+use capabilities::{Capabilities, Capability};
 use eframe::egui;
 use egui_file::FileDialog;
+use runner::{BuildConfig, Profile, RunInputs, Runner, Rustc};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use std::path::PathBuf;
-use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use storage::Filesystem;
+use syn::spanned::Spanned;
 use syn::{parse_file, visit::Visit, File as SynFile, Pat, PatType, Type};
-use syntect::easy::HighlightLines;
 use syntect::highlighting::{Theme, ThemeSet};
 use syntect::parsing::SyntaxSet;
+use theme::{ColorOverrides, EditorColors};
+
+/// Opened by the onboarding tour.
+const EXAMPLE: &str = include_str!("../sample.rs");
+/// How long a recomputed value stays highlighted.
+const FLASH_SECS: f32 = 1.0;
+/// How long the code has to stay unchanged before it's re-parsed.
+const REPARSE_DELAY: Duration = Duration::from_millis(300);
+/// How long panel edits have to settle before live edits write them.
+const LIVE_EDIT_DELAY: Duration = Duration::from_millis(300);
 
 fn main() -> Result<(), eframe::Error> {
+    debug_log::install_crash_hook();
     let options = eframe::NativeOptions::default();
     eframe::run_native(
         "Crowbar",
         options,
-        Box::new(|_cc| Ok(Box::new(MyApp::new()))),
+        Box::new(|_cc| {
+            Ok(Box::new(MyApp::new(Capabilities::from_args(
+                std::env::args().skip(1),
+            ))))
+        }),
     )
 }
 
+#[derive(Clone, PartialEq)]
 enum VariableValue {
     Int(i64),
+    /// An `i128`, which can hold more than the drag widget's `i64`.
+    Wide(i128),
+    /// A `u64`, `usize` or `u128`, which can be past `i64::MAX`.
+    UInt(u128),
     Float(f64),
     Bool(bool),
     Str(String),
+    Char(char),
+    /// Contents of a byte string literal.
+    Bytes(Vec<u8>),
+    /// Elements of a `vec![…]` or array of literals, all of the same kind.
+    List(Vec<VariableValue>),
+    /// Elements of a tuple of literals, each of its own type.
+    Tuple(Vec<VariableValue>),
+    /// An `Option` of a one-line value. The value is kept while it's `None`,
+    /// so that switching back to `Some` brings it back.
+    Optional {
+        some: bool,
+        value: Box<VariableValue>,
+    },
+    /// A unit variant of an enum defined in the file, with the enum's other
+    /// unit variants to choose from.
+    Enum {
+        name: String,
+        variant: String,
+        variants: Vec<String>,
+    },
+    /// A literal of a struct defined in the file, with a variable per field
+    /// in the order they're written.
+    Struct {
+        name: String,
+        fields: Vec<Variable>,
+    },
     Unknown,
 }
 
+/// Struct fields compare by name and value, which is all a literal is.
+impl PartialEq for Variable {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value
+    }
+}
+
 #[derive(Default)]
 struct MyApp {
     code: String,
@@ -31,407 +142,8705 @@ struct MyApp {
     open_file_dialog: Option<FileDialog>,
     variables: Vec<Variable>,
     syntax_set: SyntaxSet,
+    syntax_folder: String,
+    syntax_load_error: Option<String>,
+    theme_set: ThemeSet,
+    theme_name: String,
     theme: Theme,
+    color_overrides: ColorOverrides,
+    show_settings: bool,
     output: String,
+    runner: Runner<Rustc>,
+    /// The run in flight, which has the runner until it's done.
+    active_run: Option<ActiveRun>,
+    /// When the last run ended and how, shown by the HUD for a moment.
+    finished_run: Option<(Instant, String)>,
+    /// Set by the HUD to open the output section on the next frame.
+    reveal_output: bool,
+    build_config: BuildConfig,
+    run_inputs: RunInputs,
+    build_failed: bool,
+    checker: checker::Checker,
+    /// Hash of the buffer the checker was last asked about.
+    checked_source: Option<u64>,
+    /// Line whose gutter marker was clicked, listed in the diagnostics window.
+    diagnostic_line: Option<usize>,
+    /// Error code whose explanation the diagnostics window shows.
+    explain_code: Option<String>,
+    explanations: diagnostics::Explanations,
+    /// Hash of the buffer `variables` and `functions` were parsed from.
+    parsed_source: Option<u64>,
+    /// Buffer `variables` were last parsed from, which their spans are into.
+    parsed_code: String,
+    /// Hash of the buffer when it was last seen to change, and when, for
+    /// waiting until typing pauses before re-parsing.
+    source_changed: Option<(u64, Instant)>,
+    /// The variables of the last parse before the code stopped parsing,
+    /// with the values typed into them, which the approximate list can't
+    /// all hold. They're matched again once it parses.
+    last_parsed: Vec<Variable>,
+    functions: Vec<Function>,
+    /// Which variables each computed variable's initializer references.
+    dependency_graph: dependencies::Graph,
+    /// Computed variables on or behind a circular definition.
+    cyclic_variables: Vec<usize>,
+    /// Row the panel scrolls to on the next frame.
+    reveal_variable: Option<usize>,
+    /// Edited variables the last write-back couldn't find in the code.
+    unwritten: Vec<String>,
+    /// Line the editor scrolls to on the next frame.
+    reveal_line: Option<usize>,
+    /// Line last jumped to, highlighted for a moment, and when.
+    flash_line: Option<(usize, Instant)>,
+    /// The "Add tunable…" form while it's open.
+    tunable_form: Option<TunableForm>,
+    /// Indentation the buffer uses, kept from the last file that had any.
+    detected_indent: indent::IndentStyle,
+    indent_override: Option<indent::IndentStyle>,
+    /// Shebang and cargo block of a rust-script file.
+    script: Option<script::Script>,
+    /// Where the code doesn't parse and why, while it doesn't: `let`
+    /// bindings and functions are lexer guesses meanwhile.
+    parse_error: Option<(usize, String)>,
+    /// Top-level items as of the last time the code parsed.
+    outline: Vec<outline::Entry>,
+    /// What symbol search can find, as of the last time the code parsed.
+    symbols: Vec<symbols::Symbol>,
+    /// The Ctrl+Shift+O symbol search while it's open.
+    symbol_search: Option<SymbolSearch>,
+    import_suggestions: Vec<imports::ImportSuggestion>,
+    history: Vec<history::RunRecord>,
+    show_sensitivity: bool,
+    /// Result of the last "Check determinism" probe.
+    determinism: Option<DeterminismReport>,
+    show_determinism: bool,
+    /// The sweep window's settings, and the sweep in flight or last run.
+    sweep: Option<SweepWindow>,
+    show_sweep: bool,
+    output_location: outputs::Location,
+    /// Run rustfmt over statements the panel rewrote.
+    format_rewrites: bool,
+    formatter: Box<dyn format::Formatter>,
+    /// Text the analyzed number follows in the output; empty for the last
+    /// number printed.
+    sensitivity_label: String,
+    record_environment: bool,
+    redaction_patterns: String,
+    env_export_dialog: Option<(FileDialog, usize)>,
+    env_import_dialog: Option<FileDialog>,
+    /// Save dialog for "Export as…" and whether it exports a cargo project.
+    export_dialog: Option<(FileDialog, bool)>,
+    export_message: Option<String>,
+    output_save_dialog: Option<FileDialog>,
+    output_compare_dialog: Option<FileDialog>,
+    compare_dialog: Option<FileDialog>,
+    scan_dialog: Option<FileDialog>,
+    project_scan: project::ProjectScan,
+    /// Folder of the last "Scan folder…", which keeps its window open.
+    scan_root: Option<PathBuf>,
+    /// Ignored paths under `scan_root` the user asked to scan anyway.
+    scan_include: Vec<PathBuf>,
+    /// The "Create bug report bundle" window while it's open.
+    bug_report: Option<BugReport>,
+    /// The buffer against another file, from "Compare with…".
+    comparison: Option<compare::Comparison>,
+    /// Outputs of both sides of `comparison`.
+    output_comparison: Option<compare::Comparison>,
+    history_error: Option<String>,
+    show_processes: bool,
+    leftover_processes: Vec<processes::ProcessEntry>,
+    process_error: Option<String>,
+    marker_warnings: Vec<String>,
+    panel: panel::PanelState,
+    highlight_cache: highlight::HighlightCache,
+    spell_check: bool,
+    dictionary: spellcheck::Dictionary,
+    dictionary_error: Option<String>,
+    /// Misspelled word the editor's context menu was opened on.
+    spell_target: Option<Range<usize>>,
+    /// Offset of the literal the editor's context menu was opened on.
+    extract_target: Option<usize>,
+    /// Variable a value is being pasted into, the text so far, and why it
+    /// didn't take.
+    paste_draft: Option<(usize, String, Option<String>)>,
+    /// Write panel edits into the code as they're made, not only on Run.
+    live_edits: bool,
+    /// When the last panel edit not yet written live was made.
+    live_edit_at: Option<Instant>,
+    /// Seed "Randomize all" starts from, so a draw can be repeated.
+    random_seed: u64,
+    /// Generator the per-variable dice draw from.
+    rng: randomize::Rng,
+    snippets: Vec<snippets::Snippet>,
+    snippet_error: Option<String>,
+    /// Placeholders of the snippet being filled in, and the buffer they
+    /// were last updated for.
+    snippet_session: Option<(snippets::Session, String)>,
+    capabilities: Capabilities,
+    safe_mode_message: Option<String>,
+    tour: Option<tour::Tour>,
+    /// `(tool, version)` pairs for the tour; the version is empty when the
+    /// tool couldn't be run.
+    toolchain: Vec<(&'static str, String)>,
+    storage: storage::StorageManager,
+    storage_caps: storage::Caps,
+    /// Set by runs and cap changes until the caps are enforced again.
+    storage_stale: bool,
+}
+
+/// A run, or the runs of a check, on a background thread. Its outcome is
+/// taken in once the thread hands the runner back.
+struct ActiveRun {
+    progress: Arc<Mutex<runner::Progress>>,
+    result: Receiver<(Runner<Rustc>, Finished)>,
+    timer: Instant,
+    /// Shown over the progress for anything but a plain run.
+    label: Option<&'static str>,
+    /// A plain run's output folder.
+    out_dir: Option<PathBuf>,
+}
+
+/// What a background thread hands back with the runner.
+enum Finished {
+    /// A plain run, recorded in the history.
+    Run {
+        result: runner::RunResult,
+        provenance: provenance::Provenance,
+        environment: Option<environment::EnvironmentCapture>,
+    },
+    Determinism(DeterminismReport),
+    /// What each side of the file comparison printed; `None` if stopped.
+    Both(Option<compare::Comparison>),
+}
+
+/// Column widths and row height shared by a group's rows.
+#[derive(Clone, Copy)]
+struct RowLayout {
+    name_width: f32,
+    badge_width: f32,
+    value_width: f32,
+    row_height: f32,
+}
+
+/// What the sweep window steps a variable through, and how far it got.
+struct SweepWindow {
+    key: panel::VariableKey,
+    start: String,
+    end: String,
+    step: String,
+    /// Values to use instead of the range, separated by commas.
+    list: String,
+    error: Option<String>,
+    /// How many values the current or last sweep has.
+    total: usize,
+    progress: Arc<Mutex<sweep::Progress>>,
+    /// Hands the runner back once the sweep in flight is done.
+    result: Option<Receiver<Runner<Rustc>>>,
+}
+
+/// A new `let` binding being filled in on the panel.
+#[derive(Default)]
+struct TunableForm {
+    name: String,
+    /// Index into [`tunable::TYPES`].
+    ty: usize,
+    value: String,
+    /// Cursor when the form was opened, as a byte offset.
+    cursor: Option<usize>,
+    at_cursor: bool,
+    error: Option<String>,
+}
+
+/// What's typed into symbol search, and which of the matches is picked.
+#[derive(Default)]
+struct SymbolSearch {
+    query: String,
+    selected: usize,
+}
+
+/// Whether running the buffer twice gave the same output, and what in it
+/// might make it differ.
+struct DeterminismReport {
+    /// Hash of the buffer that was probed; the report is stale otherwise.
+    source_hash: String,
+    verdict: determinism::Verdict,
+    causes: Vec<determinism::Cause>,
+}
+
+/// A bug report being put together; `sources` are gathered once when the
+/// window opens so the preview matches what gets written.
+struct BugReport {
+    sources: bugreport::Sources,
+    redactor: bugreport::Redactor,
+    /// Whether the buffer and the last run's provenance go in too.
+    include_code: bool,
+    dialog: Option<FileDialog>,
+    message: Option<String>,
 }
 
 impl MyApp {
-    fn new() -> Self {
+    /// Every persisted-state read and subsystem launch at startup is gated on
+    /// `capabilities`, see `enable_capability` for the deferred versions.
+    fn new(capabilities: Capabilities) -> Self {
         let syntax_set = SyntaxSet::load_defaults_newlines();
-        let theme = ThemeSet::load_defaults().themes["base16-ocean.dark"].clone();
+        let theme_set = ThemeSet::load_defaults();
+        let theme_name = "base16-ocean.dark".to_string();
+        let theme = theme_set.themes[&theme_name].clone();
+        let leftover_processes = if capabilities.allows(Capability::ProcessRecovery) {
+            processes::leftovers()
+        } else {
+            Vec::new()
+        };
+        let tour = (capabilities.allows(Capability::StoredSettings)
+            && !onboarding_flag_path().exists())
+        .then(onboarding_tour);
+        let (snippets, snippet_error) = if capabilities.allows(Capability::StoredSettings) {
+            match snippets::load() {
+                Ok(snippets) => (snippets, None),
+                Err(e) => (Vec::new(), Some(e)),
+            }
+        } else {
+            (Vec::new(), None)
+        };
+        let dictionary = if capabilities.allows(Capability::UserDictionary) {
+            spellcheck::Dictionary::with_user_dictionary()
+        } else {
+            spellcheck::Dictionary::default()
+        };
 
         Self {
+            show_processes: !leftover_processes.is_empty(),
+            leftover_processes,
+            dictionary,
+            snippets,
+            snippet_error,
+            toolchain: if tour.is_some() {
+                detect_toolchain()
+            } else {
+                Vec::new()
+            },
+            tour,
+            capabilities,
+            redaction_patterns: environment::DEFAULT_REDACTIONS.to_string(),
             syntax_set,
+            theme_set,
+            theme_name,
             theme,
             ..Default::default()
         }
     }
 
-    fn parse_variables(&mut self) {
-        if let Ok(ast) = parse_rust_code(&self.code) {
-            let mut visitor = VariableVisitor::new();
-            visitor.visit_file(&ast);
-            self.variables = visitor.variables;
+    fn enable_capability(&mut self, capability: Capability) {
+        debug_log::record(format!("enabled {}", capability.label()));
+        self.capabilities.enable(capability);
+        match capability {
+            Capability::UserDictionary => {
+                self.dictionary = spellcheck::Dictionary::with_user_dictionary();
+                self.highlight_cache.invalidate();
+            }
+            Capability::ProcessRecovery => {
+                self.leftover_processes = processes::leftovers();
+                self.show_processes |= !self.leftover_processes.is_empty();
+            }
+            Capability::StoredSettings => match snippets::load() {
+                Ok(snippets) => self.snippets = snippets,
+                Err(e) => self.snippet_error = Some(e),
+            },
+            Capability::ExternalProcesses => {}
         }
     }
 
-    fn update_code_with_variables(&mut self) {
-        for variable in &self.variables {
-            let search_patterns = vec![
-                format!("let {}: {} = ", variable.name, variable.var_type),
-                format!("let mut {}: {} = ", variable.name, variable.var_type),
-            ];
-
-            let mut code_replaced = String::new();
-            let mut last_pos = 0;
-
-            for search_str in search_patterns {
-                while let Some(pos) = self.code[last_pos..].find(&search_str) {
-                    let actual_pos = last_pos + pos;
-                    let end_pos = self.code[actual_pos..].find(';').unwrap() + actual_pos + 1;
-                    let new_value_str = match &variable.value {
-                        VariableValue::Int(val) => format!("{};", val),
-                        VariableValue::Float(val) => format!("{};", val),
-                        VariableValue::Bool(val) => format!("{};", val),
-                        VariableValue::Str(val) => {
-                            if variable.var_type == "String" {
-                                format!("\"{}\".to_string();", val)
-                            } else {
-                                format!("\"{}\";", val)
-                            }
-                        }
-                        VariableValue::Unknown => continue,
-                    };
-                    code_replaced.push_str(&self.code[last_pos..actual_pos + search_str.len()]);
-                    code_replaced.push_str(&new_value_str);
-                    last_pos = end_pos;
+    fn show_safe_mode_banner(&mut self, ctx: &egui::Context) {
+        if self.capabilities.vetoed().is_empty() {
+            return;
+        }
+        egui::TopBottomPanel::top("safe_mode_banner").show(ctx, |ui| {
+            let labels: Vec<&str> = self
+                .capabilities
+                .vetoed()
+                .iter()
+                .map(|c| c.label())
+                .collect();
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                format!("Safe mode: disabled {}", labels.join(", ")),
+            );
+            ui.horizontal(|ui| {
+                for capability in self.capabilities.vetoed().to_vec() {
+                    if ui
+                        .button(format!("Enable {}", capability.label()))
+                        .clicked()
+                    {
+                        self.enable_capability(capability);
+                    }
+                }
+                if ui
+                    .button("Reset stored state")
+                    .on_hover_text("Moves the config folder to a backup first")
+                    .clicked()
+                {
+                    self.safe_mode_message = Some(match paths::reset_config_dir() {
+                        Ok(backup) => format!("Stored state moved to {}", backup.display()),
+                        Err(e) => format!("Failed to reset stored state: {}", e),
+                    });
                 }
+            });
+            if let Some(message) = &self.safe_mode_message {
+                ui.label(message);
             }
+        });
+    }
 
-            code_replaced.push_str(&self.code[last_pos..]);
-            self.code = code_replaced;
+    /// Asks the background checker about the buffer whenever it changed.
+    fn request_check(&mut self, ctx: &egui::Context) {
+        if self.code.trim().is_empty()
+            || !self.capabilities.allows(Capability::ExternalProcesses)
+            || self.is_running()
+        {
+            return;
+        }
+        let mut hasher = DefaultHasher::new();
+        self.code.hash(&mut hasher);
+        self.build_config.flags.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.checked_source != Some(hash) {
+            self.checked_source = Some(hash);
+            self.checker.request(
+                ctx,
+                &script::compile_source(&self.code),
+                &self.build_config.flags,
+            );
         }
     }
 
-    fn run_code(&mut self) {
-        let temp_file_path = "temp_code.rs";
-        if let Err(e) = std::fs::write(temp_file_path, &self.code) {
-            self.output = format!("Failed to write code to file: {}", e);
-            return;
+    fn notify_tour(&mut self, event: tour::Event) {
+        if let Some(tour) = &mut self.tour {
+            tour.notify(event);
         }
+    }
 
-        let output = Command::new("rustc")
-            .arg(temp_file_path)
-            .arg("-o")
-            .arg("temp_executable")
-            .output();
+    fn tour_anchor(&mut self, name: &str, rect: egui::Rect) {
+        if let Some(tour) = &mut self.tour {
+            tour.anchor(tour::anchor_id(name), rect);
+        }
+    }
 
-        match output {
-            Ok(output) => {
-                if !output.stderr.is_empty() {
-                    self.output = format!(
-                        "Compilation error:\n{}",
-                        String::from_utf8_lossy(&output.stderr)
-                    );
-                } else {
-                    match Command::new("./temp_executable").output() {
-                        Ok(run_output) => {
-                            self.output = String::from_utf8_lossy(&run_output.stdout).to_string();
-                        }
-                        Err(e) => {
-                            self.output = format!("Failed to run the code: {}", e);
-                        }
+    fn show_tour(&mut self, ctx: &egui::Context) {
+        let Some(mut tour) = self.tour.take() else {
+            return;
+        };
+        let mut open_example = false;
+        let mut recheck = false;
+        tour.show(ctx, |ui, step| match step {
+            0 => {
+                for (tool, version) in &self.toolchain {
+                    if version.is_empty() {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("✖ {} not found on PATH", tool),
+                        );
+                    } else {
+                        ui.label(format!("✔ {}", version));
                     }
                 }
+                recheck = ui.button("Check again").clicked();
             }
-            Err(e) => {
-                self.output = format!("Failed to compile the code: {}", e);
+            1 => open_example = ui.button("Open example").clicked(),
+            _ => {}
+        });
+        if recheck {
+            self.toolchain = detect_toolchain();
+        }
+        if open_example {
+            self.code = EXAMPLE.to_string();
+            self.opened_file = None;
+            self.variables.clear();
+            self.last_parsed.clear();
+            self.parse_variables();
+            tour.notify(tour::Event::FileLoaded);
+        }
+
+        if tour.status() == tour::Status::Active {
+            self.tour = Some(tour);
+        } else if self.capabilities.allows(Capability::StoredSettings) {
+            let path = onboarding_flag_path();
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
             }
+            let _ = std::fs::write(path, "");
         }
     }
-}
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Top panel for the header and buttons
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            ui.heading("Crowbar");
-            ui.separator();
-            ui.horizontal(|ui| {
-                if ui.button("Load File").clicked() {
-                    let mut dialog = FileDialog::open_file(self.opened_file.clone());
-                    dialog.open();
-                    self.open_file_dialog = Some(dialog);
-                }
-                if ui.button("Run Code").clicked() {
-                    self.update_code_with_variables();
-                    self.run_code();
-                }
-            });
-            ui.add_space(10.0);
-        });
+    fn reload_syntaxes(&mut self) {
+        let folder = self.syntax_folder.trim();
+        let folder = (!folder.is_empty()).then(|| PathBuf::from(folder));
+        let (syntax_set, error) = syntax::load_syntax_set(folder.as_deref());
+        self.syntax_set = syntax_set;
+        self.syntax_load_error = error;
+    }
 
-        // Handle file dialog outside the main panels to ensure it works
-        if let Some(dialog) = &mut self.open_file_dialog {
-            if dialog.show(ctx).selected() {
-                if let Some(file) = dialog.path() {
-                    self.opened_file = Some(file.to_path_buf());
-                    if let Ok(content) = std::fs::read_to_string(&file) {
-                        self.code = content;
-                        self.parse_variables();
-                    }
-                }
-            }
-        }
+    fn editor_colors(&self) -> EditorColors {
+        EditorColors::from_theme(&self.theme).with_overrides(&self.color_overrides)
+    }
 
-        // Central panel to hold both code editor/output and variables inspector
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                // Left side for the code editor and output, taking up remaining space
-                ui.allocate_ui_with_layout(
-                    egui::Vec2::new(ui.available_width() - 200.0, ui.available_height()),
-                    egui::Layout::top_down(egui::Align::Min),
-                    |ui| {
-                        if let Some(path) = &self.opened_file {
-                            ui.label(format!("Current File: {:?}", path.display()));
+    fn show_settings_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::ComboBox::from_label("Theme")
+                    .selected_text(&self.theme_name)
+                    .show_ui(ui, |ui| {
+                        for name in self.theme_set.themes.keys() {
+                            if ui
+                                .selectable_label(*name == self.theme_name, name)
+                                .clicked()
+                            {
+                                self.theme_name = name.clone();
+                                self.theme = self.theme_set.themes[name].clone();
+                            }
                         }
+                    });
 
-                        let line_count = self.code.lines().count();
-                        let line_numbers = (1..=line_count)
-                            .map(|i| format!("{}\n", i))
-                            .collect::<String>();
-
-                        // Scroll area for the code editor and line numbers
-                        egui::ScrollArea::vertical()
-                            .id_source("code_scroll_area")
-                            .show(ui, |ui| {
-                                ui.horizontal(|ui| {
-                                    let mut line_number_layouter =
-                                        |ui: &egui::Ui, string: &str, wrap_width: f32| {
-                                            let mut job = egui::text::LayoutJob::default();
-                                            job.append(
-                                                string,
-                                                0.0,
-                                                egui::TextFormat {
-                                                    font_id: egui::TextStyle::Monospace
-                                                        .resolve(ui.style()),
-                                                    color: egui::Color32::GRAY,
-                                                    line_height: Some(16.0),
-                                                    ..Default::default()
-                                                },
-                                            );
-                                            job.wrap.max_width = wrap_width;
-                                            ui.fonts(|f| f.layout_job(job))
-                                        };
+                ui.separator();
+                ui.label("Color overrides");
+                let derived = EditorColors::from_theme(&self.theme);
+                let overrides = &mut self.color_overrides;
+                egui::Grid::new("color_overrides").show(ui, |ui| {
+                    for (label, slot, default) in [
+                        ("Line numbers", &mut overrides.gutter, derived.gutter),
+                        (
+                            "Current line",
+                            &mut overrides.current_line,
+                            derived.current_line,
+                        ),
+                        ("Selection", &mut overrides.selection, derived.selection),
+                        ("Errors", &mut overrides.error, derived.error),
+                        ("Annotations", &mut overrides.annotation, derived.annotation),
+                    ] {
+                        let mut enabled = slot.is_some();
+                        if ui.checkbox(&mut enabled, label).changed() {
+                            *slot = enabled.then_some(default);
+                        }
+                        let mut color = slot.unwrap_or(default);
+                        ui.add_enabled_ui(enabled, |ui| {
+                            if ui.color_edit_button_srgba(&mut color).changed() {
+                                *slot = Some(color);
+                            }
+                        });
+                        ui.label(format!(
+                            "{:.1}:1",
+                            theme::contrast_ratio(color, derived.background)
+                        ));
+                        ui.end_row();
+                    }
+                });
 
-                                    ui.add(
-                                        egui::TextEdit::multiline(&mut line_numbers.clone())
-                                            .font(egui::TextStyle::Monospace)
-                                            .code_editor()
-                                            .lock_focus(true)
-                                            .interactive(false)
-                                            .desired_width(30.0)
-                                            .layouter(&mut line_number_layouter),
-                                    );
-
-                                    let mut layouter =
-                                        |ui: &egui::Ui, string: &str, wrap_width: f32| {
-                                            let mut h = HighlightLines::new(
-                                                self.syntax_set
-                                                    .find_syntax_by_extension("rs")
-                                                    .unwrap(),
-                                                &self.theme,
-                                            );
-                                            let ranges: Vec<(syntect::highlighting::Style, &str)> =
-                                                h.highlight_line(string, &self.syntax_set).unwrap();
-                                            let mut job = egui::text::LayoutJob::default();
-                                            for (style, text) in ranges {
-                                                let color = egui::Color32::from_rgb(
-                                                    style.foreground.r,
-                                                    style.foreground.g,
-                                                    style.foreground.b,
-                                                );
-                                                job.append(
-                                                    text,
-                                                    0.0,
-                                                    egui::TextFormat {
-                                                        color,
-                                                        ..Default::default()
-                                                    },
-                                                );
-                                            }
-                                            job.wrap.max_width = wrap_width;
-                                            ui.fonts(|f| f.layout_job(job))
-                                        };
+                ui.separator();
+                if ui
+                    .checkbox(&mut self.spell_check, "Spell check comments and strings")
+                    .changed()
+                {
+                    self.highlight_cache.invalidate();
+                }
+                if let Some(error) = &self.dictionary_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
 
-                                    ui.add(
-                                        egui::TextEdit::multiline(&mut self.code)
-                                            .font(egui::TextStyle::Monospace)
-                                            .code_editor()
-                                            .lock_focus(true)
-                                            .desired_width(f32::INFINITY)
-                                            .layouter(&mut layouter),
-                                    );
-                                });
-                            });
+                ui.checkbox(
+                    &mut self.format_rewrites,
+                    "Format statements changed from the panel with rustfmt",
+                )
+                .on_hover_text("Only the rewritten statements; the rest of the file is untouched");
 
-                        ui.add_space(10.0);
-                        ui.separator();
+                ui.separator();
+                self.show_snippet_settings(ui);
 
-                        // Output section
-                        egui::ScrollArea::vertical()
-                            .id_source("output_scroll_area")
-                            .show(ui, |ui| {
-                                ui.collapsing("Output", |ui| {
-                                    ui.with_layout(
-                                        egui::Layout::top_down(egui::Align::Min)
-                                            .with_main_wrap(false),
-                                        |ui| {
-                                            ui.label(&self.output);
-                                        },
-                                    );
-                                });
-                            });
-                    },
-                );
+                ui.separator();
+                ui.label("Redacted environment variables")
+                    .on_hover_text("Comma separated name patterns, `*` matches anything");
+                ui.text_edit_singleline(&mut self.redaction_patterns);
 
-                // Right side for the variables inspector with fixed width of 400
-                ui.allocate_ui_with_layout(
-                    egui::Vec2::new(400.0, ui.available_height()),
-                    egui::Layout::top_down(egui::Align::Min),
+                ui.separator();
+                ui.label("Extra syntax definitions folder");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.syntax_folder);
+                    if ui.button("Reload").clicked() {
+                        self.reload_syntaxes();
+                    }
+                });
+                if let Some(error) = &self.syntax_load_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                ui.collapsing(
+                    format!("Loaded syntaxes ({})", self.syntax_set.syntaxes().len()),
                     |ui| {
                         egui::ScrollArea::vertical()
-                            .id_source("variables_scroll_area")
+                            .max_height(200.0)
                             .show(ui, |ui| {
-                                if self.variables.is_empty() {
-                                    ui.label("No variables found.");
-                                } else {
-                                    for variable in &mut self.variables {
-                                        ui.label(format!(
-                                            "Variable: {} of type {}",
-                                            variable.name, variable.var_type
-                                        ));
-                                        match &mut variable.value {
-                                            VariableValue::Int(val) => {
-                                                ui.add(
-                                                    egui::DragValue::new(val)
-                                                        .speed(1)
-                                                        .range(i64::MIN..=i64::MAX),
-                                                );
-                                            }
-                                            VariableValue::Float(val) => {
-                                                ui.add(
-                                                    egui::DragValue::new(val)
-                                                        .speed(0.1)
-                                                        .range(f64::MIN..=f64::MAX),
-                                                );
-                                            }
-                                            VariableValue::Bool(val) => {
-                                                ui.checkbox(val, "Value");
-                                            }
-                                            VariableValue::Str(val) => {
-                                                ui.text_edit_singleline(val);
-                                            }
-                                            VariableValue::Unknown => {
-                                                ui.label("Unsupported type for input");
-                                            }
-                                        }
-                                    }
+                                for syntax in self.syntax_set.syntaxes() {
+                                    ui.label(format!(
+                                        "{} ({})",
+                                        syntax.name,
+                                        syntax.file_extensions.join(", ")
+                                    ));
                                 }
                             });
                     },
                 );
+
+                ui.separator();
+                self.show_storage_settings(ui);
             });
+        self.show_settings = open;
+    }
+
+    /// Editable list of the user's snippets, saved on every change.
+    fn show_snippet_settings(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        ui.collapsing(format!("Snippets ({})", self.snippets.len()), |ui| {
+            ui.weak(
+                "Type a trigger and press Tab to expand it. In the body, $1 or \
+                 ${1:default} are placeholders Tab moves through, $0 is where the \
+                 cursor ends up.",
+            );
+            let mut removed = None;
+            for (index, snippet) in self.snippets.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Name");
+                        changed |= ui
+                            .add(egui::TextEdit::singleline(&mut snippet.name).desired_width(120.0))
+                            .changed();
+                        ui.label("Trigger");
+                        changed |= ui
+                            .add(
+                                egui::TextEdit::singleline(&mut snippet.trigger)
+                                    .desired_width(60.0),
+                            )
+                            .changed();
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::multiline(&mut snippet.body)
+                                .code_editor()
+                                .desired_rows(3),
+                        )
+                        .changed();
+                });
+                ui.separator();
+            }
+            if let Some(index) = removed {
+                self.snippets.remove(index);
+                changed = true;
+            }
+            if ui.button("Add snippet").clicked() {
+                self.snippets.push(snippets::Snippet::default());
+                changed = true;
+            }
         });
+        if changed && self.capabilities.allows(Capability::StoredSettings) {
+            self.snippet_error = snippets::save(&self.snippets)
+                .err()
+                .map(|e| format!("Failed to save snippets: {}", e));
+        }
+        if let Some(error) = &self.snippet_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
     }
-}
 
-fn parse_rust_code(code: &str) -> Result<SynFile, syn::Error> {
-    parse_file(code)
-}
+    /// Keeps the placeholders of the snippet being filled in on their text
+    /// after edits, ending the session when an edit cuts across one.
+    fn sync_snippet_session(&mut self) {
+        let Some((session, seen)) = &mut self.snippet_session else {
+            return;
+        };
+        if let Some((range, inserted)) = snippets::diff(seen, &self.code) {
+            if !session.edit(range, inserted) {
+                self.snippet_session = None;
+                return;
+            }
+            *seen = self.code.clone();
+        }
+    }
 
-struct Variable {
-    name: String,
-    var_type: String,
-    value: VariableValue,
-}
+    /// Handles Tab in the editor: selects the next placeholder of the snippet
+    /// being filled in, or expands the snippet whose trigger is right before
+    /// the cursor. Returns false when Tab should indent as usual.
+    fn snippet_tab(&mut self, ctx: &egui::Context) -> bool {
+        self.sync_snippet_session();
+        if let Some((session, _)) = &mut self.snippet_session {
+            if let Some(stop) = session.advance() {
+                set_editor_selection(ctx, &self.code, stop);
+                return true;
+            }
+            self.snippet_session = None;
+        }
 
-struct VariableVisitor {
-    variables: Vec<Variable>,
-}
+        let Some(cursor) = egui::TextEdit::load_state(ctx, code_editor_id())
+            .and_then(|state| state.cursor.char_range())
+            .filter(|range| range.primary == range.secondary)
+            .map(|range| byte_offset(&self.code, range.primary.index))
+        else {
+            return false;
+        };
+        let Some(snippet) = snippets::matching(&self.snippets, &self.code[..cursor]) else {
+            return false;
+        };
+        // Later lines of the body line up with the line it's expanded on.
+        let line_start = self.code[..cursor].rfind('\n').map_or(0, |i| i + 1);
+        let indent: String = self.code[line_start..]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        let body = indent::convert(&snippet.body, self.indent_style(), 4)
+            .replace('\n', &format!("\n{}", indent));
+        let expansion = snippets::expand(&body);
+        let start = cursor - snippet.trigger.len();
 
-impl VariableVisitor {
-    fn new() -> Self {
-        Self {
-            variables: Vec::new(),
+        push_undo_point(ctx, &self.code);
+        self.code.replace_range(start..cursor, &expansion.text);
+        let session = snippets::Session::new(start, &expansion);
+        set_editor_selection(ctx, &self.code, session.current());
+        self.snippet_session = (expansion.stops.len() > 1).then(|| (session, self.code.clone()));
+        true
+    }
+
+    /// Disk usage per category with its cap and a "Clean now" button. Sizes
+    /// come from the storage manager's last background scan.
+    fn show_storage_settings(&mut self, ui: &mut egui::Ui) {
+        const MB: u64 = 1024 * 1024;
+        let sizes = self.storage.sizes();
+        if sizes.is_none() && !self.storage.is_busy() {
+            self.storage
+                .enforce(ui.ctx(), &self.storage_caps, self.latest_out_dir());
+        }
+        let busy = self.storage.is_busy();
+        let caps_before = self.storage_caps.clone();
+        let mut clean = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Storage");
+            if busy {
+                ui.spinner();
+            }
+        });
+        let cap_editor = |ui: &mut egui::Ui, cap: &mut Option<u64>| {
+            let mut enabled = cap.is_some();
+            ui.checkbox(&mut enabled, "Cap");
+            let mut megabytes = cap.map_or(256, |cap| cap / MB);
+            ui.add_enabled(
+                enabled,
+                egui::DragValue::new(&mut megabytes)
+                    .range(1..=u64::MAX)
+                    .suffix(" MB"),
+            );
+            *cap = enabled.then_some(megabytes * MB);
+        };
+        egui::Grid::new("storage_grid").show(ui, |ui| {
+            for (category, cap) in &mut self.storage_caps.categories {
+                ui.label(category.label());
+                let size = sizes
+                    .iter()
+                    .flatten()
+                    .find(|(c, _)| c == category)
+                    .map_or("…".to_string(), |(_, size)| storage::format_size(*size));
+                ui.label(size);
+                cap_editor(ui, cap);
+                if ui
+                    .add_enabled(!busy, egui::Button::new("Clean now"))
+                    .clicked()
+                {
+                    clean = Some(*category);
+                }
+                ui.end_row();
+            }
+            ui.label("Total");
+            ui.label(sizes.as_ref().map_or("…".to_string(), |sizes| {
+                storage::format_size(sizes.iter().map(|(_, size)| size).sum())
+            }));
+            cap_editor(ui, &mut self.storage_caps.total);
+            ui.end_row();
+        });
+        ui.weak("The current build and this window's check files are never removed.");
+        if let Some(error) = self.storage.error() {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        if let Some(category) = clean {
+            self.storage
+                .clean(ui.ctx(), category, self.latest_out_dir());
+        } else if self.storage_caps != caps_before {
+            self.storage_stale = true;
         }
     }
-}
 
-impl<'ast> Visit<'ast> for VariableVisitor {
-    fn visit_local(&mut self, local: &'ast syn::Local) {
-        if let Pat::Type(PatType { pat, ty, .. }) = &local.pat {
-            if let Pat::Ident(ident) = &**pat {
-                let var_name = ident.ident.to_string();
-                let var_type = extract_type(&**ty);
-
-                let value = match var_type.as_str() {
-                    "i32" | "i64" => VariableValue::Int(0),
-                    "f32" | "f64" => VariableValue::Float(0.0),
-                    "bool" => VariableValue::Bool(false),
-                    "&str" | "String" => VariableValue::Str(String::new()),
-                    _ => VariableValue::Unknown,
-                };
+    /// Variables as a grid of name, type badge, value editor and a per-row
+    /// menu. Only value editors take keyboard focus, so Tab moves between them.
+    fn show_variables_panel(&mut self, ui: &mut egui::Ui) {
+        self.show_tunable_form(ui);
+        if self.variables.is_empty() {
+            ui.label("No variables found.");
+            return;
+        }
 
-                self.variables.push(Variable {
-                    name: var_name,
-                    var_type,
-                    value,
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.panel.filter)
+                    .hint_text("Filter by name")
+                    .desired_width(160.0),
+            );
+            egui::ComboBox::from_id_source("variable_type_filter")
+                .selected_text(
+                    self.panel
+                        .type_filter
+                        .map_or("All types", |kind| kind.label()),
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.panel.type_filter, None, "All types");
+                    for kind in panel::TypeFilter::ALL {
+                        ui.selectable_value(&mut self.panel.type_filter, Some(kind), kind.label());
+                    }
                 });
+        });
 
-                if let Some(local_init) = &local.init {
-                    if let Some(variable) = self.variables.last_mut() {
-                        match &*local_init.expr {
-                            syn::Expr::Lit(syn::ExprLit {
-                                lit: syn::Lit::Str(lit_str),
-                                ..
-                            }) => {
-                                variable.value = VariableValue::Str(lit_str.value());
-                            }
-                            syn::Expr::Call(syn::ExprCall { func, args, .. }) => {
-                                if let syn::Expr::Path(ref expr_path) = **func {
-                                    if expr_path.path.is_ident("String::from") {
-                                        if let Some(syn::Expr::Lit(syn::ExprLit {
-                                            lit: syn::Lit::Str(lit_str),
-                                            ..
-                                        })) = args.first()
-                                        {
-                                            variable.value = VariableValue::Str(lit_str.value());
-                                        }
-                                    }
-                                }
-                            }
-                            syn::Expr::Lit(syn::ExprLit {
-                                lit: syn::Lit::Int(lit_int),
-                                ..
-                            }) => {
-                                variable.value =
-                                    VariableValue::Int(lit_int.base10_parse().unwrap_or(0));
-                            }
-                            syn::Expr::Lit(syn::ExprLit {
-                                lit: syn::Lit::Float(lit_float),
-                                ..
-                            }) => {
-                                variable.value =
-                                    VariableValue::Float(lit_float.base10_parse().unwrap_or(0.0));
-                            }
-                            syn::Expr::Lit(syn::ExprLit {
-                                lit: syn::Lit::Bool(lit_bool),
-                                ..
-                            }) => {
-                                variable.value = VariableValue::Bool(lit_bool.value);
-                            }
-                            _ => {}
+        let hidden = panel::hidden_count(&self.variables, &self.panel);
+        let touched = self.variables.iter().any(|variable| {
+            variable.edited
+                || variable.original_text.as_ref().is_some_and(|text| {
+                    variable
+                        .span
+                        .as_ref()
+                        .and_then(|span| self.code.get(span.clone()))
+                        != Some(text.as_str())
+                })
+        });
+        let unsupported = panel::unsupported(&self.variables);
+        let (unsupported_count, unsupported) = (unsupported.len(), unsupported.join(", "));
+        ui.horizontal(|ui| {
+            if hidden > 0 {
+                ui.checkbox(
+                    &mut self.panel.show_hidden,
+                    format!("Show {} hidden", hidden),
+                );
+            }
+            if unsupported_count > 0 {
+                ui.checkbox(&mut self.panel.show_unsupported, "Show unsupported");
+                if !self.panel.show_unsupported {
+                    ui.weak(format!("{} hidden", unsupported_count))
+                        .on_hover_text(unsupported);
+                }
+            }
+            let reset = ui
+                .add_enabled(touched, egui::Button::new("Reset all"))
+                .on_hover_text("Put back every value as written when the file was opened");
+            let confirm_id = ui.make_persistent_id("reset_all_confirm");
+            if reset.clicked() {
+                ui.memory_mut(|m| m.toggle_popup(confirm_id));
+            }
+            egui::popup::popup_below_widget(
+                ui,
+                confirm_id,
+                &reset,
+                egui::PopupCloseBehavior::CloseOnClickOutside,
+                |ui| {
+                    ui.set_min_width(200.0);
+                    ui.label("Discard all tuning and restore the file's values?");
+                    ui.horizontal(|ui| {
+                        if ui.button("Reset all").clicked() {
+                            self.reset_all_variables(ui.ctx());
+                            ui.memory_mut(|m| m.close_popup());
                         }
-                    }
+                        if ui.button("Cancel").clicked() {
+                            ui.memory_mut(|m| m.close_popup());
+                        }
+                    });
+                },
+            );
+        });
+        let randomizable = (0..self.variables.len()).any(|index| self.is_editable_number(index));
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.live_edits, "Apply edits live")
+                .on_hover_text("Write values into the code as they're edited, not only on Run");
+            if ui
+                .button("Copy all")
+                .on_hover_text("Every value as `name = value`, a line each")
+                .clicked()
+            {
+                let lines: Vec<String> = self
+                    .variables
+                    .iter()
+                    .filter_map(|variable| {
+                        let value = format_literal(&variable.value, "")?;
+                        Some(format!("{} = {}", variable.name, value))
+                    })
+                    .collect();
+                ui.output_mut(|o| o.copied_text = lines.join("\n"));
+            }
+            if ui
+                .add_enabled(randomizable, egui::Button::new("🎲 Randomize all"))
+                .on_hover_text("Random values for every unlocked number, drawn from the seed")
+                .clicked()
+            {
+                self.randomize_all();
+                if self.live_edits {
+                    self.live_edit_at = Some(Instant::now());
                 }
             }
+            ui.add(egui::DragValue::new(&mut self.random_seed).prefix("seed "));
+        });
+
+        let rows = panel::rows(&self.variables, &self.code, &self.panel);
+        if rows.is_empty() && (!self.panel.filter.is_empty() || self.panel.type_filter.is_some()) {
+            ui.weak("No variables match the filter.");
         }
 
-        syn::visit::visit_local(self, local);
-    }
-}
+        let mut action = None;
+        for group in panel::groups(rows) {
+            // A variable being revealed can't stay in a collapsed group.
+            let revealing = self
+                .reveal_variable
+                .is_some_and(|index| group.rows.iter().any(|row| row.index == index));
+            egui::CollapsingHeader::new(&group.title)
+                .id_source(("variable_group", &group.title))
+                .default_open(true)
+                .open(revealing.then_some(true))
+                .show(ui, |ui| {
+                    let width = ui.available_width();
+                    let name_width = (width * 0.4).max(80.0);
+                    let badge_width = 56.0;
+                    let layout = RowLayout {
+                        name_width,
+                        badge_width,
+                        value_width: (width - name_width - badge_width - 48.0).max(60.0),
+                        row_height: ui.spacing().interact_size.y,
+                    };
+                    egui::Grid::new(("variables_grid", &group.title))
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for row in &group.rows {
+                                if let Some(row_action) = self.show_variable_row(ui, row, layout) {
+                                    action = Some(row_action);
+                                }
+                            }
+                        });
+                });
+        }
 
-fn extract_type(ty: &Type) -> String {
-    match ty {
-        Type::Path(ref typepath) => {
-            if let Some(ident) = typepath.path.get_ident() {
-                ident.to_string()
-            } else {
-                "Unknown".to_string()
+        let changes_code = matches!(
+            action,
+            Some(
+                PanelAction::Edited
+                    | PanelAction::Paste(..)
+                    | PanelAction::Randomize(_)
+                    | PanelAction::Reset(_)
+                    | PanelAction::Radix(..)
+                    | PanelAction::Decimals(..)
+            )
+        );
+        if changes_code && self.live_edits {
+            self.live_edit_at = Some(Instant::now());
+        }
+        match action {
+            Some(PanelAction::Pin(index)) => {
+                self.panel.toggle_pin(&self.variables, &self.code, index);
+            }
+            Some(PanelAction::Lock(index)) => {
+                self.panel.toggle_lock(&self.variables, &self.code, index);
+            }
+            Some(PanelAction::Hide(key)) => panel::PanelState::toggle(&mut self.panel.hidden, key),
+            Some(PanelAction::Radix(key, radix)) => {
+                self.panel.radix.insert(key, radix);
+            }
+            Some(PanelAction::Decimals(key, Some(decimals))) => {
+                self.panel.decimals.insert(key, decimals);
+            }
+            Some(PanelAction::Decimals(key, None)) => {
+                self.panel.decimals.remove(&key);
+            }
+            Some(PanelAction::DragSpeed(index, speed)) => {
+                panel::set_setting(
+                    &mut self.panel.speed,
+                    &self.variables,
+                    &self.code,
+                    index,
+                    speed,
+                );
+            }
+            Some(PanelAction::Step(index, step)) => {
+                panel::set_setting(
+                    &mut self.panel.step,
+                    &self.variables,
+                    &self.code,
+                    index,
+                    step,
+                );
+            }
+            Some(PanelAction::Goto(index)) => {
+                if let Some(span) = self.variables[index].span.clone() {
+                    self.jump_to(ui.ctx(), span.start);
+                }
+            }
+            Some(PanelAction::Reveal(index)) => {
+                if self
+                    .panel
+                    .hidden
+                    .contains(&panel::VariableKey::of(&self.variables[index]))
+                {
+                    self.panel.show_hidden = true;
+                }
+                // A revealed variable can't stay filtered out either.
+                let rows = panel::rows(&self.variables, &self.code, &self.panel);
+                if !rows.iter().any(|row| row.index == index) {
+                    self.panel.filter.clear();
+                    self.panel.type_filter = None;
+                    self.panel.show_unsupported = true;
+                }
+                self.reveal_variable = Some(index);
+            }
+            Some(PanelAction::Paste(index, value)) => {
+                self.variables[index].value = value;
+                self.variables[index].edited = true;
+                self.recompute_variables(false);
+                self.notify_tour(tour::Event::ValueEdited);
+            }
+            Some(PanelAction::Randomize(index)) => {
+                self.randomize_variable(index);
+                self.recompute_variables(false);
+            }
+            Some(PanelAction::Reset(index)) => {
+                self.reset_variable(index);
+                self.recompute_variables(false);
+            }
+            Some(PanelAction::Sweep(index)) => self.open_sweep(index),
+            Some(PanelAction::Edited) => {
+                self.recompute_variables(false);
+                self.notify_tour(tour::Event::ValueEdited);
             }
+            None => {}
         }
-        Type::Reference(_) => "&str".to_string(),
-        _ => "Unsupported".to_string(),
+    }
+
+    /// One row of the variables grid, plus a row of dependency chips for
+    /// computed values.
+    fn show_variable_row(
+        &mut self,
+        ui: &mut egui::Ui,
+        row: &panel::VariableRow,
+        layout: RowLayout,
+    ) -> Option<PanelAction> {
+        let RowLayout {
+            name_width,
+            badge_width,
+            value_width,
+            row_height,
+        } = layout;
+        let colors = self.editor_colors();
+        let mut action = None;
+        let computed = self.is_computed(row.index);
+        let name_cell = ui.allocate_ui_with_layout(
+            egui::vec2(name_width, row_height),
+            egui::Layout::left_to_right(egui::Align::Center),
+            |ui| {
+                ui.set_width(name_width);
+                if icon_toggle(ui, "📌", row.pinned, "Pin to top").clicked() {
+                    action = Some(PanelAction::Pin(row.index));
+                }
+                let lock = if row.locked {
+                    "Locked: can't be edited or written back. Click to unlock"
+                } else {
+                    "Lock value"
+                };
+                if icon_toggle(ui, "🔒", row.locked, lock).clicked() {
+                    action = Some(PanelAction::Lock(row.index));
+                }
+                if icon_toggle(ui, "➡", false, "Go to definition").clicked() {
+                    action = Some(PanelAction::Goto(row.index));
+                }
+                let line = self.variables[row.index]
+                    .span
+                    .as_ref()
+                    .and_then(|span| self.code.get(..span.start))
+                    .map(|before| before.matches('\n').count() + 1);
+                // Shadowed bindings are told apart by where they are.
+                let text = match line {
+                    Some(line) if row.shadowed => format!("{} (line {})", row.key.name, line),
+                    _ => row.key.name.clone(),
+                };
+                let name = if row.hidden {
+                    egui::RichText::new(text).weak()
+                } else {
+                    egui::RichText::new(text)
+                };
+                // Bindings in a closure, loop or other block say which.
+                let variable = &self.variables[row.index];
+                if !variable.scope.is_empty() {
+                    ui.weak("↳");
+                }
+                let errors = &variable.annotation.errors;
+                if !errors.is_empty() {
+                    ui.colored_label(colors.error, "⚠")
+                        .on_hover_text(errors.join("\n"));
+                }
+                let label = ui.add(
+                    egui::Label::new(name)
+                        .truncate()
+                        .sense(egui::Sense::click()),
+                );
+                if label.clicked() {
+                    action = Some(PanelAction::Goto(row.index));
+                }
+                if let Some(line) = line {
+                    let path: Vec<&str> = variable
+                        .function
+                        .iter()
+                        .map(String::as_str)
+                        .chain(variable.scope.iter().copied())
+                        .collect();
+                    label.on_hover_text(if path.is_empty() {
+                        format!("Declared on line {}, click to go there", line)
+                    } else {
+                        format!(
+                            "Declared on line {} in {}, click to go there",
+                            line,
+                            path.join(" › ")
+                        )
+                    });
+                }
+            },
+        );
+        if self.reveal_variable == Some(row.index) {
+            self.reveal_variable = None;
+            ui.scroll_to_rect(name_cell.response.rect, Some(egui::Align::Center));
+        }
+
+        ui.allocate_ui_with_layout(
+            egui::vec2(badge_width, row_height),
+            egui::Layout::left_to_right(egui::Align::Center),
+            |ui| {
+                ui.set_width(badge_width);
+                let badge = egui::RichText::new(&row.type_label)
+                    .monospace()
+                    .color(row.badge.color());
+                ui.add(egui::Label::new(badge).truncate())
+                    .on_hover_text(match row.key.kind {
+                        VariableKind::Let if row.in_macro => {
+                            "let binding inside a macro body: shown for \
+                             reference, it can't be rewritten safely"
+                        }
+                        VariableKind::Let if row.approximate => "let binding (approximate)",
+                        VariableKind::Let => "let binding",
+                        VariableKind::Const => "const item",
+                        VariableKind::Static { mutable: false } => "static item: global state",
+                        VariableKind::Static { mutable: true } => {
+                            "static mut item: global, mutable state"
+                        }
+                        VariableKind::Marker => "marker comment",
+                    });
+            },
+        );
+
+        let editable = !row.locked && !row.approximate && !computed;
+        let radix = self.radix_of(row.index);
+        let var_type = self.variables[row.index].var_type.clone();
+        let source = self.variables[row.index]
+            .span
+            .as_ref()
+            .and_then(|span| self.code.get(span.clone()))
+            .map(str::to_string);
+        let declaration = source.as_ref().map(|text| {
+            format!(
+                "{}: {} = {}",
+                self.variables[row.index].name, var_type, text
+            )
+        });
+        // Arrays, and lists written `[x; n]`, keep their length.
+        let fixed_length = list::is_fixed(&var_type)
+            || (matches!(self.variables[row.index].value, VariableValue::List(_))
+                && source
+                    .as_deref()
+                    .is_some_and(|text| matches!(list::Form::of(text), list::Form::Repeat { .. })));
+        // Annotated with a range, numbers get a slider, with room beside
+        // it for the number itself.
+        // Steps and drag speeds come from the row's settings, else from
+        // the annotation, and speeds else suit the original value's size.
+        let annotated = self.variables[row.index].annotation.clone();
+        let mut annotation = annotated.clone();
+        let speed = panel::setting(&self.panel.speed, &self.variables, &self.code, row.index);
+        let step = panel::setting(&self.panel.step, &self.variables, &self.code, row.index);
+        annotation.speed = speed.or(annotation.speed);
+        annotation.step = step.or(annotation.step);
+        let original = {
+            let variable = &self.variables[row.index];
+            match variable.original_value.as_ref().unwrap_or(&variable.value) {
+                VariableValue::Int(val) => *val as f64,
+                VariableValue::UInt(val) => *val as f64,
+                VariableValue::Float(val) => *val,
+                _ => 0.0,
+            }
+        };
+        let integer = row.badge == panel::Badge::Int;
+        let auto_speed = numeric::drag_speed(original, integer);
+        let range = annotation.range;
+        let decimals = self.panel.decimals.get(&row.key).copied();
+        let step = annotation.step.unwrap_or(0.0);
+        let slider_width = (value_width - 64.0).max(40.0);
+        // Three or four channels get a color picker when the name or the
+        // annotation says they're a color.
+        let color = {
+            let variable = &self.variables[row.index];
+            let name = variable.name.to_lowercase();
+            (variable.annotation.color
+                || ["color", "colour", "rgb"]
+                    .iter()
+                    .any(|word| name.contains(word)))
+                && is_color(&variable.value, &var_type)
+        };
+        // What's typed over an initializer the panel can't edit.
+        let mut raw = self.variables[row.index]
+            .raw
+            .clone()
+            .or_else(|| source.clone())
+            .unwrap_or_default();
+        let edited = ui.add_enabled_ui(editable, |ui| {
+            match &mut self.variables[row.index].value {
+                VariableValue::List(items) | VariableValue::Tuple(items) if color => {
+                    edit_color(ui, items)
+                }
+                VariableValue::Int(val)
+                    if range.is_some_and(|(min, max)| min.ceil() <= max.floor()) =>
+                {
+                    let (min, max) = range.unwrap_or_default();
+                    ui.spacing_mut().slider_width = slider_width;
+                    ui.add(
+                        egui::Slider::new(val, min.ceil() as i64..=max.floor() as i64)
+                            .step_by(step.round())
+                            .drag_value_speed(annotation.speed_or(auto_speed))
+                            .custom_formatter(|n, _| radix.format(n as i64)),
+                    )
+                }
+                VariableValue::UInt(val)
+                    if *val <= u128::from(u64::MAX)
+                        && range.is_some_and(|(min, max)| min.ceil().max(0.0) <= max.floor()) =>
+                {
+                    let (min, max) = range.unwrap_or_default();
+                    let mut value = *val as u64;
+                    ui.spacing_mut().slider_width = slider_width;
+                    let response = ui.add(
+                        egui::Slider::new(
+                            &mut value,
+                            min.ceil().max(0.0) as u64..=max.floor() as u64,
+                        )
+                        .step_by(step.round())
+                        .drag_value_speed(annotation.speed_or(auto_speed))
+                        .custom_formatter(|n, _| radix.format_unsigned(n as u128)),
+                    );
+                    *val = value.into();
+                    response
+                }
+                VariableValue::Float(val) if range.is_some() => {
+                    let (min, max) = range.unwrap_or_default();
+                    ui.spacing_mut().slider_width = slider_width;
+                    let mut slider = egui::Slider::new(val, min..=max)
+                        .step_by(step)
+                        .drag_value_speed(annotation.speed_or(auto_speed));
+                    if let Some(decimals) = decimals {
+                        slider = slider.fixed_decimals(decimals);
+                    }
+                    ui.add(slider)
+                }
+                VariableValue::Int(val) => {
+                    let (min, max) = numeric::int_range(&var_type).unwrap_or((i64::MIN, i64::MAX));
+                    let response = ui.add_sized(
+                        [value_width, row_height],
+                        egui::DragValue::new(val)
+                            .speed(annotation.speed_or(auto_speed))
+                            .range(min..=max)
+                            .custom_formatter(|n, _| radix.format(n as i64))
+                            .custom_parser(|text| match numeric::parse_entry(text, &var_type) {
+                                Ok(VariableValue::Int(value)) => Some(value as f64),
+                                _ => None,
+                            }),
+                    );
+                    show_entry_preview(ui, &response, &var_type, colors.error);
+                    if response.changed() {
+                        *val = (annotation.snap(*val as f64) as i64).clamp(min, max);
+                    }
+                    response
+                }
+                VariableValue::UInt(val) if *val <= numeric::MAX_EXACT => {
+                    let max = numeric::uint_max(&var_type).unwrap_or(u128::MAX);
+                    // Dragging goes through an `f64`, so what's typed is
+                    // taken as parsed rather than rounded to one.
+                    let typed = std::cell::Cell::new(None);
+                    let mut value = *val as f64;
+                    let response = ui.add_sized(
+                        [value_width, row_height],
+                        egui::DragValue::new(&mut value)
+                            .speed(annotation.speed_or(auto_speed))
+                            .range(0.0..=max as f64)
+                            .custom_formatter(|n, _| radix.format_unsigned(n as u128))
+                            .custom_parser(|text| match numeric::parse_entry(text, &var_type) {
+                                Ok(VariableValue::UInt(value)) => {
+                                    typed.set(Some(value));
+                                    Some(value as f64)
+                                }
+                                _ => None,
+                            }),
+                    );
+                    show_entry_preview(ui, &response, &var_type, colors.error);
+                    if response.changed() {
+                        *val = typed
+                            .get()
+                            .unwrap_or_else(|| annotation.snap(value).round().max(0.0) as u128)
+                            .min(max);
+                    }
+                    response
+                }
+                // Past what an `f64` holds exactly, so typed only.
+                VariableValue::UInt(val) => parsed_field(
+                    ui,
+                    ui.make_persistent_id(("uint_entry", row.index)),
+                    [value_width, row_height],
+                    val,
+                    |val| radix.format_unsigned(*val),
+                    |text| numeric::parse_uint(text, &var_type),
+                    colors.error,
+                ),
+                VariableValue::Wide(val) => parsed_field(
+                    ui,
+                    ui.make_persistent_id(("wide_entry", row.index)),
+                    [value_width, row_height],
+                    val,
+                    |val| radix.format(*val),
+                    |text| numeric::parse_wide(text, &var_type),
+                    colors.error,
+                ),
+                VariableValue::Float(val) => {
+                    let response = ui.add_sized(
+                        [value_width, row_height],
+                        egui::DragValue::new(val)
+                            .speed(annotation.speed_or(auto_speed))
+                            .range(numeric::float_range(&var_type))
+                            .min_decimals(decimals.unwrap_or(0))
+                            .max_decimals_opt(decimals)
+                            .custom_parser(|text| match numeric::parse_entry(text, &var_type) {
+                                Ok(VariableValue::Float(value)) => Some(value),
+                                _ => None,
+                            }),
+                    );
+                    show_entry_preview(ui, &response, &var_type, colors.error);
+                    if response.changed() {
+                        *val = annotation.snap(*val);
+                        if let Some(decimals) = decimals {
+                            *val = format!("{:.*}", decimals, val).parse().unwrap_or(*val);
+                        }
+                    }
+                    response
+                }
+                VariableValue::Bool(val) => ui.checkbox(val, ""),
+                // Long or multi-line strings get rows to match, up to a
+                // cap past which they scroll. Both keep one id so typing
+                // past the limit doesn't lose focus.
+                VariableValue::Str(val) if val.contains('\n') || val.chars().count() > 60 => {
+                    egui::ScrollArea::vertical()
+                        .id_source(("string_scroll", row.index))
+                        .max_height(row_height * 8.0)
+                        .show(ui, |ui| {
+                            ui.add(
+                                egui::TextEdit::multiline(val)
+                                    .id(ui.make_persistent_id(("string", row.index)))
+                                    .desired_width(value_width)
+                                    .desired_rows(2),
+                            )
+                        })
+                        .inner
+                }
+                VariableValue::Str(val) => ui.add(
+                    egui::TextEdit::singleline(val)
+                        .id(ui.make_persistent_id(("string", row.index)))
+                        .desired_width(value_width),
+                ),
+                VariableValue::Char(val) => parsed_field(
+                    ui,
+                    ui.make_persistent_id(("char_entry", row.index)),
+                    [value_width, row_height],
+                    val,
+                    |val| char_entry_text(*val),
+                    parse_char_entry,
+                    colors.error,
+                ),
+                VariableValue::Bytes(val) => parsed_field(
+                    ui,
+                    ui.make_persistent_id(("bytes_entry", row.index)),
+                    [value_width, row_height],
+                    val,
+                    |val| val.escape_ascii().to_string(),
+                    parse_bytes_entry,
+                    colors.error,
+                ),
+                VariableValue::List(items) => edit_list(
+                    ui,
+                    items,
+                    list::element_type(&var_type).unwrap_or_default(),
+                    fixed_length,
+                    value_width,
+                ),
+                VariableValue::Tuple(items) => edit_tuple(
+                    ui,
+                    items,
+                    &tuple::element_types(&var_type).unwrap_or_default(),
+                    value_width,
+                ),
+                VariableValue::Optional { some, value } => edit_optional(
+                    ui,
+                    some,
+                    value,
+                    optional::inner_type(&var_type).unwrap_or_default(),
+                    value_width,
+                ),
+                VariableValue::Enum {
+                    variant, variants, ..
+                } => edit_variant(
+                    ui,
+                    ui.make_persistent_id(("variant", row.index)),
+                    variant,
+                    variants,
+                    value_width,
+                ),
+                VariableValue::Struct { fields, .. } => edit_struct(
+                    ui,
+                    ui.make_persistent_id(("struct", row.index)),
+                    fields,
+                    value_width,
+                ),
+                // Anything else is shown as written, and can be written over.
+                VariableValue::Unknown => match (&source, &declaration) {
+                    (Some(_), Some(declaration)) if editable => {
+                        ui.horizontal(|ui| {
+                            if ui
+                                .small_button("📋")
+                                .on_hover_text("Copy the initializer")
+                                .clicked()
+                            {
+                                ui.output_mut(|output| output.copied_text = raw.clone());
+                            }
+                            parsed_field(
+                                ui,
+                                ui.make_persistent_id(("raw_entry", row.index)),
+                                [value_width - 32.0, row_height],
+                                &mut raw,
+                                |text| text.split_whitespace().collect::<Vec<_>>().join(" "),
+                                |text| parse_raw_initializer(text, &var_type),
+                                colors.error,
+                            )
+                            .on_hover_text(egui::RichText::new(declaration).monospace())
+                        })
+                        .inner
+                    }
+                    (Some(text), Some(declaration)) => {
+                        show_initializer(ui, text, declaration, value_width)
+                    }
+                    _ => ui.label("Unsupported type for input"),
+                },
+            }
+            .changed()
+        });
+        if edited.inner {
+            if matches!(self.variables[row.index].value, VariableValue::Unknown) {
+                self.variables[row.index].raw = Some(raw);
+            }
+            self.variables[row.index].edited = true;
+            action = Some(PanelAction::Edited);
+        }
+        let dependencies = self.dependency_graph.dependencies(row.index);
+        let cycle = self
+            .cyclic_variables
+            .contains(&row.index)
+            .then(|| self.dependency_graph.cycle_through(row.index))
+            .flatten();
+        if computed {
+            let names: Vec<&str> = dependencies
+                .iter()
+                .map(|i| self.variables[*i].name.as_str())
+                .collect();
+            let hover = if let Some(cycle) = &cycle {
+                let chain: Vec<&str> = cycle
+                    .iter()
+                    .map(|i| self.variables[*i].name.as_str())
+                    .collect();
+                format!("Circular definition: {}", chain.join(" → "))
+            } else if self.cyclic_variables.contains(&row.index) {
+                "Depends on a circular definition".to_string()
+            } else {
+                format!("Computed from {}", names.join(", "))
+            };
+            edited.response.clone().on_hover_text(hover);
+        }
+        if let Some(updated_at) = self.variables[row.index].updated_at {
+            let age = updated_at.elapsed().as_secs_f32();
+            if age < FLASH_SECS {
+                ui.painter().rect_filled(
+                    edited.response.rect,
+                    2.0,
+                    colors
+                        .annotation
+                        .gamma_multiply(0.4 * (1.0 - age / FLASH_SECS)),
+                );
+                ui.ctx().request_repaint();
+            }
+        }
+
+        let variable = &self.variables[row.index];
+        // Initializers the panel can't edit compare as text.
+        let touched = match &variable.value {
+            VariableValue::Unknown => {
+                variable.original_text.is_some()
+                    && variable.raw.as_ref().or(source.as_ref()) != variable.original_text.as_ref()
+            }
+            value => variable
+                .original_value
+                .as_ref()
+                .is_some_and(|original| original != value),
+        };
+        // What the file said, as it said it, while the value differs.
+        let was = touched
+            .then(|| {
+                variable
+                    .original_text
+                    .clone()
+                    .or_else(|| format_literal(variable.original_value.as_ref()?, ""))
+            })
+            .flatten();
+        let numeric = matches!(
+            variable.value,
+            VariableValue::Int(_) | VariableValue::UInt(_) | VariableValue::Float(_)
+        );
+        let randomizable = self.is_editable_number(row.index);
+        let last_cell = ui.horizontal(|ui| {
+            if numeric {
+                let dice = ui
+                    .add_enabled(randomizable, egui::Button::new("🎲").small())
+                    .on_hover_text("Random value within its range, or ten times the original");
+                if dice.clicked() {
+                    action = Some(PanelAction::Randomize(row.index));
+                }
+            }
+            let reset = ui
+                .add_enabled(touched && editable, egui::Button::new("↺").small())
+                .on_hover_text("Reset to the value when the file was opened")
+                .on_disabled_hover_text("Unchanged since the file was opened");
+            if reset.clicked() {
+                action = Some(PanelAction::Reset(row.index));
+            }
+            if let Some(was) = &was {
+                let short = match was.lines().next() {
+                    Some(line) if line.chars().count() <= 12 && !was.contains('\n') => {
+                        line.to_string()
+                    }
+                    Some(line) => format!("{}…", line.chars().take(11).collect::<String>()),
+                    None => String::new(),
+                };
+                let label = ui
+                    .add(
+                        egui::Label::new(
+                            egui::RichText::new(format!("was {}", short))
+                                .monospace()
+                                .weak(),
+                        )
+                        .sense(egui::Sense::click()),
+                    )
+                    .on_hover_text(if editable {
+                        format!("Was {} when the file was opened, click to reset", was)
+                    } else {
+                        format!("Was {} when the file was opened", was)
+                    });
+                if label.clicked() && editable {
+                    action = Some(PanelAction::Reset(row.index));
+                }
+            }
+            icon_toggle(ui, "☰", false, "More actions")
+        });
+        // Changed rows are tinted, from the name to the menu.
+        if touched {
+            ui.painter().rect_filled(
+                name_cell.response.rect.union(last_cell.response.rect),
+                2.0,
+                colors.annotation.gamma_multiply(0.08),
+            );
+        }
+        let menu = last_cell.inner;
+        let menu_id = ui.make_persistent_id(("variable_menu", row.index));
+        let settings_id = ui.make_persistent_id(("drag_settings", row.index));
+        let mut open_settings = false;
+        let paste_id = ui.make_persistent_id(("paste", row.index));
+        let mut open_paste = false;
+        if menu.clicked() {
+            ui.memory_mut(|m| m.toggle_popup(menu_id));
+        }
+        egui::popup::popup_below_widget(
+            ui,
+            menu_id,
+            &menu,
+            egui::PopupCloseBehavior::CloseOnClick,
+            |ui| {
+                ui.set_min_width(120.0);
+                let hide = if row.hidden { "Show" } else { "Hide" };
+                if ui.button(hide).clicked() {
+                    action = Some(PanelAction::Hide(row.key.clone()));
+                }
+                ui.separator();
+                let copied = clipboard_text(&self.variables[row.index].value);
+                if ui
+                    .add_enabled(copied.is_some(), egui::Button::new("Copy value"))
+                    .clicked()
+                {
+                    ui.output_mut(|o| o.copied_text = copied.unwrap_or_default());
+                }
+                if ui
+                    .add_enabled(editable, egui::Button::new("Paste value…"))
+                    .clicked()
+                {
+                    open_paste = true;
+                }
+                if row.badge == panel::Badge::Int {
+                    ui.separator();
+                    for choice in numeric::Radix::ALL {
+                        if ui.radio(radix == choice, choice.label()).clicked() {
+                            action = Some(PanelAction::Radix(row.key.clone(), choice));
+                        }
+                    }
+                }
+                if row.badge == panel::Badge::Float {
+                    ui.separator();
+                    let current = self.panel.decimals.get(&row.key).copied();
+                    for choice in [None, Some(1), Some(2), Some(3), Some(4), Some(6)] {
+                        let label = match choice {
+                            None => "Decimals as written".to_string(),
+                            Some(1) => "1 decimal".to_string(),
+                            Some(n) => format!("{} decimals", n),
+                        };
+                        if ui.radio(current == choice, label).clicked() {
+                            action = Some(PanelAction::Decimals(row.key.clone(), choice));
+                        }
+                    }
+                }
+                if numeric {
+                    ui.separator();
+                    if ui.button("Drag speed and step…").clicked() {
+                        open_settings = true;
+                    }
+                    if ui
+                        .add_enabled(randomizable, egui::Button::new("Sweep…"))
+                        .on_hover_text("Run the program once for each of a range of values")
+                        .clicked()
+                    {
+                        action = Some(PanelAction::Sweep(row.index));
+                    }
+                }
+            },
+        );
+        if open_settings {
+            ui.memory_mut(|m| m.open_popup(settings_id));
+        }
+        if open_paste {
+            self.paste_draft = Some((row.index, String::new(), None));
+            ui.memory_mut(|m| m.open_popup(paste_id));
+        }
+        egui::popup::popup_below_widget(
+            ui,
+            paste_id,
+            &menu,
+            egui::PopupCloseBehavior::CloseOnClickOutside,
+            |ui| {
+                let Some((_, text, error)) = self
+                    .paste_draft
+                    .as_mut()
+                    .filter(|(index, _, _)| *index == row.index)
+                else {
+                    return;
+                };
+                ui.label(format!("Paste a value for {}", row.key.name));
+                let field = ui.add(
+                    egui::TextEdit::multiline(text)
+                        .hint_text("Ctrl+V")
+                        .desired_rows(2)
+                        .desired_width(200.0),
+                );
+                if open_paste {
+                    field.request_focus();
+                }
+                if let Some(error) = error.as_ref() {
+                    ui.colored_label(colors.error, error);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Apply").clicked() {
+                        match parse_pasted(text, &self.variables[row.index]) {
+                            Ok(value) => {
+                                action = Some(PanelAction::Paste(row.index, value));
+                                ui.memory_mut(|m| m.close_popup());
+                            }
+                            Err(message) => *error = Some(message),
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        ui.memory_mut(|m| m.close_popup());
+                    }
+                });
+            },
+        );
+        egui::popup::popup_below_widget(
+            ui,
+            settings_id,
+            &menu,
+            egui::PopupCloseBehavior::CloseOnClickOutside,
+            |ui| {
+                ui.set_min_width(180.0);
+                let speed =
+                    panel::setting(&self.panel.speed, &self.variables, &self.code, row.index);
+                let mut custom = speed.is_some();
+                let mut value = speed.unwrap_or(annotated.speed_or(auto_speed));
+                ui.horizontal(|ui| {
+                    let toggled = ui
+                        .checkbox(&mut custom, "Drag speed")
+                        .on_hover_text(
+                            "Otherwise as annotated, or a hundredth of the original value",
+                        )
+                        .changed();
+                    let drag = value * 0.05;
+                    let changed = ui
+                        .add_enabled(
+                            custom,
+                            egui::DragValue::new(&mut value)
+                                .speed(drag)
+                                .range(f64::MIN_POSITIVE..=f64::MAX),
+                        )
+                        .changed();
+                    if toggled || changed {
+                        action = Some(PanelAction::DragSpeed(row.index, custom.then_some(value)));
+                    }
+                });
+                let step = panel::setting(&self.panel.step, &self.variables, &self.code, row.index);
+                let mut custom = step.is_some();
+                let mut value = step
+                    .or(annotated.step)
+                    .unwrap_or(if integer { 1.0 } else { 0.1 });
+                ui.horizontal(|ui| {
+                    let toggled = ui
+                        .checkbox(&mut custom, "Step")
+                        .on_hover_text("Keep values to multiples of this")
+                        .changed();
+                    let drag = value * 0.05;
+                    let changed = ui
+                        .add_enabled(
+                            custom,
+                            egui::DragValue::new(&mut value)
+                                .speed(drag)
+                                .range(f64::MIN_POSITIVE..=f64::MAX),
+                        )
+                        .changed();
+                    if toggled || changed {
+                        action = Some(PanelAction::Step(row.index, custom.then_some(value)));
+                    }
+                });
+            },
+        );
+        ui.end_row();
+
+        // The expression a derived value was worked out from, which an edit
+        // replaces with a literal.
+        let variable = &self.variables[row.index];
+        let derived = variable
+            .formula
+            .as_ref()
+            .is_some_and(dependencies::is_constant_arithmetic);
+        if derived && !computed && !variable.edited {
+            if let Some(source) = &source {
+                ui.label("");
+                ui.weak("from");
+                ui.label(egui::RichText::new(source).monospace().weak())
+                    .on_hover_text("Derived: editing the value replaces this with a literal");
+                ui.end_row();
+            }
+        }
+
+        // Chips for the variables a computed value references
+        if computed {
+            ui.label("");
+            ui.weak("uses");
+            ui.horizontal_wrapped(|ui| {
+                for &dependency in self.dependency_graph.dependencies(row.index) {
+                    let chip = egui::Button::new(
+                        egui::RichText::new(&self.variables[dependency].name).small(),
+                    )
+                    .rounding(8.0);
+                    let on_cycle = cycle
+                        .as_ref()
+                        .is_some_and(|cycle| cycle.contains(&dependency));
+                    let chip = if on_cycle {
+                        chip.stroke(egui::Stroke::new(1.0, colors.error))
+                    } else {
+                        chip
+                    };
+                    if ui.add(chip).on_hover_text("Show this variable").clicked() {
+                        action = Some(PanelAction::Reveal(dependency));
+                    }
+                }
+            });
+            ui.end_row();
+        }
+        action
+    }
+
+    /// "Add tunable…" and the form it opens, which inserts a new binding.
+    fn show_tunable_form(&mut self, ui: &mut egui::Ui) {
+        let Some(form) = &mut self.tunable_form else {
+            if ui.button("Add tunable…").clicked() {
+                let cursor = egui::TextEdit::load_state(ui.ctx(), code_editor_id())
+                    .and_then(|state| state.cursor.char_range())
+                    .map(|range| byte_offset(&self.code, range.primary.index));
+                self.tunable_form = Some(TunableForm {
+                    cursor,
+                    ..Default::default()
+                });
+            }
+            return;
+        };
+        let mut add = false;
+        let mut cancel = false;
+        ui.group(|ui| {
+            egui::Grid::new("tunable_form")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut form.name);
+                    ui.end_row();
+                    ui.label("Type");
+                    egui::ComboBox::from_id_source("tunable_type")
+                        .selected_text(tunable::TYPES[form.ty])
+                        .show_ui(ui, |ui| {
+                            for (index, ty) in tunable::TYPES.iter().enumerate() {
+                                ui.selectable_value(&mut form.ty, index, *ty);
+                            }
+                        });
+                    ui.end_row();
+                    ui.label("Value");
+                    ui.text_edit_singleline(&mut form.value);
+                    ui.end_row();
+                });
+            ui.add_enabled_ui(form.cursor.is_some(), |ui| {
+                ui.checkbox(
+                    &mut form.at_cursor,
+                    "At the cursor instead of the top of main",
+                );
+            });
+            if let Some(error) = &form.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            ui.horizontal(|ui| {
+                add = ui.button("Add").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+        if cancel {
+            self.tunable_form = None;
+        } else if add {
+            match self.add_tunable(ui.ctx()) {
+                Ok(()) => self.tunable_form = None,
+                Err(e) => {
+                    if let Some(form) = &mut self.tunable_form {
+                        form.error = Some(e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_tunable(&mut self, ctx: &egui::Context) -> Result<(), String> {
+        let Some(form) = &self.tunable_form else {
+            return Ok(());
+        };
+        let ty = tunable::TYPES[form.ty];
+        let literal = tunable::value_literal(ty, &form.value)?;
+        let location = match form.cursor {
+            Some(cursor) if form.at_cursor => tunable::Location::Cursor(cursor),
+            _ => tunable::Location::MainTop,
+        };
+        let name = form.name.trim().to_string();
+        let insertion = tunable::insert(
+            &self.code,
+            &name,
+            ty,
+            &literal,
+            location,
+            &self.indent_style().unit(),
+        )?;
+        if insertion.name != name {
+            self.export_message = Some(format!(
+                "`{}` was taken, added `{}` instead",
+                name, insertion.name
+            ));
+        }
+        push_undo_point(ctx, &self.code);
+        self.code = insertion.code;
+        self.parse_variables();
+        self.jump_to(ctx, insertion.cursor);
+        Ok(())
+    }
+
+    /// Puts the cursor at `offset` and scrolls its line into view.
+    fn jump_to(&mut self, ctx: &egui::Context, offset: usize) {
+        move_editor_cursor(ctx, &self.code, offset);
+        let line = lexer::line_of(&self.code, offset);
+        self.reveal_line = Some(line);
+        self.flash_line = Some((line, Instant::now()));
+    }
+
+    /// Top-level items and the methods of `impl` blocks, each jumping to
+    /// its definition. While the code doesn't parse, the last outline it
+    /// had is shown.
+    fn show_outline(&mut self, ui: &mut egui::Ui) {
+        if self.outline.is_empty() {
+            return;
+        }
+        let title = if self.parse_error.is_some() {
+            "Outline (as last parsed)"
+        } else {
+            "Outline"
+        };
+        let mut target = None;
+        let link = |ui: &mut egui::Ui, entry: &outline::Entry, target: &mut Option<usize>| {
+            ui.horizontal(|ui| {
+                if ui.link(&entry.label).clicked() {
+                    *target = Some(entry.offset);
+                }
+                ui.weak(entry.line.to_string());
+            });
+        };
+        egui::CollapsingHeader::new(title)
+            .id_source("outline")
+            .default_open(true)
+            .show(ui, |ui| {
+                for (index, entry) in self.outline.iter().enumerate() {
+                    if entry.children.is_empty() {
+                        link(ui, entry, &mut target);
+                        continue;
+                    }
+                    egui::CollapsingHeader::new(&entry.label)
+                        .id_source(("outline", index))
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            for child in &entry.children {
+                                link(ui, child, &mut target);
+                            }
+                        })
+                        .header_response
+                        .on_hover_text(format!("Line {}", entry.line));
+                }
+            });
+        if let Some(offset) = target {
+            self.jump_to(ui.ctx(), offset.min(self.code.len()));
+        }
+    }
+
+    /// The Ctrl+Shift+O popup that finds a function, type or variable by
+    /// part of its name and jumps to it, used from the keyboard: arrows
+    /// pick a match, Enter goes to it and Escape closes.
+    fn show_symbol_search(&mut self, ctx: &egui::Context) {
+        let shortcut = egui::KeyboardShortcut::new(
+            egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+            egui::Key::O,
+        );
+        if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+            self.symbol_search = Some(SymbolSearch::default());
+        }
+        let Some(search) = &mut self.symbol_search else {
+            return;
+        };
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.key_pressed(egui::Key::Enter),
+                i.key_pressed(egui::Key::Escape),
+            )
+        });
+        let found = symbols::search(&self.symbols, &search.query);
+        if up {
+            search.selected = search.selected.saturating_sub(1);
+        }
+        if down {
+            search.selected += 1;
+        }
+        search.selected = search.selected.min(found.len().saturating_sub(1));
+        let mut chosen = enter.then(|| found.get(search.selected)).flatten().copied();
+        egui::Window::new("Go to symbol")
+            .id(egui::Id::new("symbol_search"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+            .show(ctx, |ui| {
+                let query = ui.add(
+                    egui::TextEdit::singleline(&mut search.query)
+                        .hint_text("Function, type or variable name")
+                        .desired_width(320.0),
+                );
+                query.request_focus();
+                if query.changed() {
+                    search.selected = 0;
+                }
+                if found.is_empty() {
+                    ui.weak("Nothing matches");
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (index, symbol) in found.iter().enumerate().take(100) {
+                            let label = format!("{} {}", symbol.kind, symbol.name);
+                            let row = ui.horizontal(|ui| {
+                                let row = ui.selectable_label(index == search.selected, label);
+                                ui.weak(symbol.line.to_string());
+                                row
+                            });
+                            if index == search.selected && (up || down) {
+                                row.inner.scroll_to_me(None);
+                            }
+                            if row.inner.clicked() {
+                                chosen = Some(symbol);
+                            }
+                        }
+                    });
+            });
+        if let Some(symbol) = chosen {
+            let offset = symbol.offset.min(self.code.len());
+            self.symbol_search = None;
+            self.jump_to(ctx, offset);
+        } else if escape {
+            self.symbol_search = None;
+        }
+    }
+
+    /// Restores a variable's value to what it was when the file was opened.
+    /// If the source still says so, the variable goes back to following
+    /// it; otherwise the next run writes the original back.
+    fn reset_variable(&mut self, index: usize) {
+        let target = &self.variables[index];
+        let occurrence = self.variables[..index]
+            .iter()
+            .filter(|v| {
+                v.kind == target.kind && v.name == target.name && v.function == target.function
+            })
+            .count();
+        let parsed = match target.kind {
+            VariableKind::Let | VariableKind::Const | VariableKind::Static { .. } => {
+                parse_let_variables(&self.code).unwrap_or_default()
+            }
+            VariableKind::Marker => parse_marker_variables(&self.code).0,
+        };
+        let source = parsed
+            .into_iter()
+            .filter(|v| {
+                v.kind == target.kind && v.name == target.name && v.function == target.function
+            })
+            .nth(occurrence);
+        let written = self.variables[index]
+            .span
+            .clone()
+            .and_then(|span| self.code.get(span))
+            .map(str::to_string);
+        let variable = &mut self.variables[index];
+        // Initializers typed over go back to the text the file had.
+        if matches!(variable.value, VariableValue::Unknown) {
+            variable.raw = variable
+                .original_text
+                .clone()
+                .filter(|text| written.as_ref() != Some(text));
+            variable.edited = variable.raw.is_some();
+            return;
+        }
+        match (source, variable.original_value.clone()) {
+            (Some(source), original)
+                if original
+                    .as_ref()
+                    .is_none_or(|original| *original == source.value) =>
+            {
+                variable.value = source.value;
+                variable.edited = false;
+            }
+            (_, Some(original)) => {
+                variable.value = original;
+                variable.edited = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes every initializer back the way it was when the file was
+    /// opened, undoably, and re-parses so the panel's values follow.
+    fn reset_all_variables(&mut self, ctx: &egui::Context) {
+        // Spans have to match the code as it is now.
+        self.parse_variables();
+        let mut replacements: Vec<(Range<usize>, String)> = self
+            .variables
+            .iter()
+            .filter(|variable| !variable.approximate)
+            .filter_map(|variable| Some((variable.span.clone()?, variable.original_text.clone()?)))
+            .filter(|(span, text)| self.code.get(span.clone()) != Some(text.as_str()))
+            .collect();
+        replacements.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+        if !replacements.is_empty() {
+            push_undo_point(ctx, &self.code);
+        }
+        // From the end backwards, so earlier spans stay put, skipping a
+        // marker's literal inside a declaration already put back.
+        let mut end = self.code.len();
+        for (span, text) in replacements {
+            if span.end > end {
+                continue;
+            }
+            end = span.start;
+            self.code.replace_range(span, &text);
+        }
+        for variable in &mut self.variables {
+            variable.edited = false;
+        }
+        self.parse_variables();
+    }
+
+    /// Context menu item moving the literal right-clicked in the editor
+    /// into a variable of its own, so the panel lists it.
+    fn show_extract_menu(&mut self, ui: &mut egui::Ui) {
+        let Some(offset) = self.extract_target else {
+            ui.close_menu();
+            return;
+        };
+        if ui.button("Extract literal to variable").clicked() {
+            match tunable::extract(&self.code, offset) {
+                Ok(insertion) => {
+                    push_undo_point(ui.ctx(), &self.code);
+                    self.code = insertion.code;
+                    self.parse_variables();
+                    self.jump_to(ui.ctx(), insertion.cursor);
+                }
+                Err(e) => self.export_message = Some(e),
+            }
+            self.extract_target = None;
+            ui.close_menu();
+        }
+    }
+
+    fn show_spelling_menu(&mut self, ui: &mut egui::Ui) {
+        let Some(range) = self.spell_target.clone() else {
+            return;
+        };
+        let Some(word) = self.code.get(range.clone()).map(str::to_string) else {
+            ui.close_menu();
+            return;
+        };
+
+        let suggestions = self.dictionary.suggestions(&word, 5);
+        if suggestions.is_empty() {
+            ui.label("No suggestions");
+        }
+        for suggestion in suggestions {
+            let suggestion = match_case(&suggestion, &word);
+            if ui.button(&suggestion).clicked() {
+                push_undo_point(ui.ctx(), &self.code);
+                self.code.replace_range(range.clone(), &suggestion);
+                self.spell_target = None;
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        if ui.button("Add to dictionary").clicked() {
+            self.dictionary_error = self
+                .dictionary
+                .add(&word)
+                .err()
+                .map(|e| format!("Failed to save the dictionary: {}", e));
+            self.highlight_cache.invalidate();
+            self.spell_target = None;
+            ui.close_menu();
+        }
+    }
+
+    /// Rebuilds the variables and functions from the buffer. While it doesn't
+    /// parse, `let` bindings and functions come from [`fallback`] and are
+    /// marked approximate until the next successful parse.
+    fn parse_variables(&mut self) {
+        let mut previous = std::mem::take(&mut self.variables);
+        let previous_code = std::mem::replace(&mut self.parsed_code, self.code.clone());
+        let was_broken = self.parse_error.is_some();
+        let scopes = match parse_rust_code(&self.code) {
+            Ok(ast) => {
+                self.variables = let_variables(&ast);
+                self.functions = functions(&ast);
+                self.outline = outline::entries(&ast, &self.code);
+                self.symbols = symbols::index(&ast, &self.code);
+                self.parse_error = None;
+                function_scopes(&ast)
+            }
+            Err(error) => {
+                self.variables = fallback::let_variables(&self.code);
+                self.functions = fallback::functions(&self.code);
+                // Tokenizing failures come with a message about token
+                // streams; what they mean is more use.
+                let message = match error.to_string() {
+                    message if message.contains("token stream") => {
+                        "unbalanced brackets or an unterminated literal".to_string()
+                    }
+                    message => message,
+                };
+                self.parse_error = Some((error.span().byte_range().start, message));
+                // Each function runs until the next one starts.
+                let starts: Vec<usize> = self.functions.iter().map(|f| f.span.start).collect();
+                self.functions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| {
+                        let end = starts.get(i + 1).copied().unwrap_or(self.code.len());
+                        (f.name.clone(), f.span.start..end)
+                    })
+                    .collect()
+            }
+        };
+
+        match (was_broken, self.parse_error.is_some()) {
+            (false, true) => self.last_parsed = previous.clone(),
+            (true, false) if !self.last_parsed.is_empty() => {
+                previous = std::mem::take(&mut self.last_parsed);
+            }
+            _ => {}
+        }
+
+        self.script = script::detect(&self.code);
+        if let Some(style) = indent::detect(&self.code) {
+            self.detected_indent = style;
+        }
+
+        let (markers, warnings) = parse_marker_variables(&self.code);
+        self.variables.extend(markers);
+        self.marker_warnings = warnings;
+        for variable in &mut self.variables {
+            if variable.kind == VariableKind::Marker || variable.approximate {
+                variable.function = variable
+                    .span
+                    .as_ref()
+                    .and_then(|span| enclosing_function(&scopes, span.start));
+            }
+        }
+        let annotations = annotation::by_line(&self.code);
+        for variable in &mut self.variables {
+            let Some(span) = &variable.span else {
+                continue;
+            };
+            let line = lexer::line_of(&self.code, span.end);
+            variable.annotation = annotations.get(&line).cloned().unwrap_or_default();
+            // Values start out inside their range.
+            match (&mut variable.value, variable.annotation.range) {
+                (VariableValue::Int(val), Some((min, max))) if min.ceil() <= max.floor() => {
+                    *val = (*val).clamp(min.ceil() as i64, max.floor() as i64);
+                }
+                (VariableValue::UInt(val), Some((min, max)))
+                    if min.ceil().max(0.0) <= max.floor() =>
+                {
+                    *val = (*val).clamp(min.ceil().max(0.0) as u128, max.floor() as u128);
+                }
+                (VariableValue::Float(val), Some((min, max))) => *val = val.clamp(min, max),
+                _ => {}
+            }
+        }
+
+        for variable in &mut self.variables {
+            variable.original_value = Some(variable.value.clone());
+            variable.original_text = variable
+                .span
+                .as_ref()
+                .and_then(|span| self.code.get(span.clone()))
+                .map(str::to_string);
+        }
+
+        // Values typed into the panel outlive edits to the surrounding code,
+        // and original values outlive writing them back, as long as the
+        // type stays the same. Each goes to the same-named variable declared
+        // nearest to where the edit moved its old declaration, so a binding
+        // added above an edited one doesn't take its value.
+        let mut taken = vec![false; self.variables.len()];
+        for old in &previous {
+            let line = old.span.as_ref().map_or(0, |span| {
+                lexer::line_of(
+                    &self.code,
+                    moved_offset(&previous_code, &self.code, span.start),
+                )
+            });
+            let Some(index) = self
+                .variables
+                .iter()
+                .enumerate()
+                .filter(|&(index, v)| {
+                    !taken[index]
+                        && v.kind == old.kind
+                        && v.name == old.name
+                        && v.function == old.function
+                        && v.var_type == old.var_type
+                })
+                .min_by_key(|(_, v)| {
+                    v.span
+                        .as_ref()
+                        .map_or(0, |span| lexer::line_of(&self.code, span.start))
+                        .abs_diff(line)
+                })
+                .map(|(index, _)| index)
+            else {
+                continue;
+            };
+            taken[index] = true;
+            let variable = &mut self.variables[index];
+            if old.original_value.is_some() {
+                variable.original_value = old.original_value.clone();
+                variable.original_text = old.original_text.clone();
+            }
+            if old.edited {
+                variable.value = old.value.clone();
+                variable.raw = old.raw.clone();
+                variable.edited = true;
+            }
+        }
+        self.panel.follow_pins(&self.variables, &self.code);
+        self.recompute_variables(true);
+    }
+
+    /// Whether the variable is a number that can be edited, which the dice
+    /// can give random values and a sweep can step through.
+    fn is_editable_number(&self, index: usize) -> bool {
+        let variable = &self.variables[index];
+        matches!(
+            variable.value,
+            VariableValue::Int(_) | VariableValue::UInt(_) | VariableValue::Float(_)
+        ) && !variable.approximate
+            && !self.is_computed(index)
+            && !self.is_locked(index)
+    }
+
+    /// Gives a number a random value, drawn from its annotated range or
+    /// else up to ten times its original value either way, snapped to its
+    /// step and kept within its type.
+    fn randomize_variable(&mut self, index: usize) {
+        let decimals = self
+            .panel
+            .decimals
+            .get(&panel::VariableKey::of(&self.variables[index]))
+            .copied();
+        let variable = &mut self.variables[index];
+        let original = match variable.original_value.as_ref().unwrap_or(&variable.value) {
+            VariableValue::Int(val) => *val as f64,
+            VariableValue::UInt(val) => *val as f64,
+            VariableValue::Float(val) => *val,
+            _ => return,
+        };
+        let annotation = &variable.annotation;
+        match &mut variable.value {
+            VariableValue::Int(val) => {
+                let (lowest, highest) =
+                    numeric::int_range(&variable.var_type).unwrap_or((i64::MIN, i64::MAX));
+                let (min, max) =
+                    randomize::bounds(original, annotation.range, (lowest as f64, highest as f64));
+                let (min, max) = (min.ceil() as i64, max.floor() as i64);
+                if min > max {
+                    return;
+                }
+                let drawn = annotation.snap(self.rng.int(min, max) as f64) as i64;
+                *val = drawn.clamp(min, max);
+            }
+            VariableValue::UInt(val) => {
+                // Drawn as an `i64`, which ten times any but the largest
+                // values stays within.
+                let highest = numeric::uint_max(&variable.var_type).unwrap_or(u128::MAX);
+                let (min, max) = randomize::bounds(
+                    original,
+                    annotation.range,
+                    (0.0, (highest as f64).min(i64::MAX as f64)),
+                );
+                let (min, max) = (min.ceil() as i64, max.floor() as i64);
+                if min > max {
+                    return;
+                }
+                let drawn = annotation.snap(self.rng.int(min, max) as f64) as i64;
+                *val = drawn.clamp(min, max) as u128;
+            }
+            VariableValue::Float(val) => {
+                let limits = match variable.var_type.as_str() {
+                    "f32" => (f32::MIN as f64, f32::MAX as f64),
+                    _ => (f64::MIN, f64::MAX),
+                };
+                let (min, max) = randomize::bounds(original, annotation.range, limits);
+                if min > max {
+                    return;
+                }
+                let drawn = annotation.snap(self.rng.float(min, max)).clamp(min, max);
+                // Written back with as many decimals as the row shows, or a
+                // few rather than all of them.
+                let decimals = decimals.unwrap_or(4);
+                *val = format!("{:.*}", decimals, drawn).parse().unwrap_or(drawn);
+            }
+            _ => return,
+        }
+        variable.edited = true;
+    }
+
+    /// Re-seeds the generator and randomizes every number that can be
+    /// edited, in source order, so the same seed gives the same values.
+    fn randomize_all(&mut self) {
+        self.rng = randomize::Rng::new(self.random_seed);
+        for index in 0..self.variables.len() {
+            if self.is_editable_number(index) {
+                self.randomize_variable(index);
+            }
+        }
+        self.recompute_variables(false);
+    }
+
+    /// Whether the variable's lock is on, keeping its value out of edits
+    /// and write-backs.
+    fn is_locked(&self, index: usize) -> bool {
+        self.panel.is_locked(&self.variables, &self.code, index)
+    }
+
+    /// Radix the panel writes the variable's integer value in.
+    fn radix_of(&self, index: usize) -> numeric::Radix {
+        let variable = &self.variables[index];
+        self.panel
+            .radix
+            .get(&panel::VariableKey::of(variable))
+            .copied()
+            .unwrap_or(variable.radix)
+    }
+
+    /// The variable's float `value` written over the literal `like`, to the
+    /// decimals picked for it if there are any. `None` when it isn't finite.
+    fn float_text(&self, index: usize, value: f64, like: &str) -> Option<String> {
+        let variable = &self.variables[index];
+        match self.panel.decimals.get(&panel::VariableKey::of(variable)) {
+            Some(&decimals) => numeric::fixed_like(value, &variable.var_type, decimals, like),
+            None => numeric::float_like(value, &variable.var_type, like),
+        }
+    }
+
+    /// Whether the variable's value follows from other variables rather than
+    /// being edited in the panel.
+    fn is_computed(&self, index: usize) -> bool {
+        !self.dependency_graph.dependencies(index).is_empty()
+    }
+
+    /// Works out which variables computed ones reference and re-evaluates
+    /// them in dependency order. Values that change are highlighted unless
+    /// this is the first evaluation after a parse.
+    fn recompute_variables(&mut self, initial: bool) {
+        // Only `let` bindings and items can be referenced by name.
+        let names: Vec<String> = self
+            .variables
+            .iter()
+            .map(|v| match v.kind {
+                VariableKind::Let | VariableKind::Const | VariableKind::Static { .. } => {
+                    v.name.clone()
+                }
+                VariableKind::Marker => String::new(),
+            })
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let edges = self
+            .variables
+            .iter()
+            .enumerate()
+            .map(|(index, variable)| match &variable.formula {
+                Some(formula) if variable.kind != VariableKind::Marker && !variable.approximate => {
+                    let mut references: Vec<usize> = dependencies::references(formula)
+                        .iter()
+                        .filter_map(|name| dependencies::resolve(&names, index, name))
+                        .collect();
+                    references.dedup();
+                    references
+                }
+                _ => Vec::new(),
+            })
+            .collect();
+        self.dependency_graph = dependencies::Graph::new(edges);
+
+        let evaluation = self.dependency_graph.evaluation();
+        let now = Instant::now();
+        let update = |variable: &mut Variable, value: VariableValue| {
+            if !initial && format_literal(&variable.value, "") != format_literal(&value, "") {
+                variable.updated_at = Some(now);
+            }
+            variable.value = value;
+        };
+        for &index in &evaluation.order {
+            let Some(formula) = self.variables[index]
+                .formula
+                .as_ref()
+                .filter(|_| self.is_computed(index))
+            else {
+                continue;
+            };
+            let lookup = |name: &str| {
+                dependencies::resolve(&names, index, name)
+                    .map(|i| numeric::for_arithmetic(self.variables[i].value.clone()))
+            };
+            let value = dependencies::evaluate(formula, &lookup)
+                .map_or(VariableValue::Unknown, |value| {
+                    numeric::of_type(value, &self.variables[index].var_type)
+                });
+            update(&mut self.variables[index], value);
+        }
+        for &index in &evaluation.cyclic {
+            update(&mut self.variables[index], VariableValue::Unknown);
+        }
+        self.cyclic_variables = evaluation.cyclic;
+    }
+
+    /// What every feature that writes code indents with: the status bar
+    /// override, or else the style detected in the buffer.
+    fn indent_style(&self) -> indent::IndentStyle {
+        self.indent_override.unwrap_or(self.detected_indent)
+    }
+
+    /// Status bar menu showing the indentation and letting it be overridden
+    /// or applied to the whole buffer.
+    fn show_indent_menu(&mut self, ui: &mut egui::Ui) {
+        let style = self.indent_style();
+        ui.menu_button(style.label(), |ui| {
+            ui.radio_value(
+                &mut self.indent_override,
+                None,
+                format!("Detect ({})", self.detected_indent.label()),
+            );
+            for choice in [
+                indent::IndentStyle::Tabs,
+                indent::IndentStyle::Spaces(2),
+                indent::IndentStyle::Spaces(4),
+                indent::IndentStyle::Spaces(8),
+            ] {
+                ui.radio_value(&mut self.indent_override, Some(choice), choice.label());
+            }
+            ui.separator();
+            let spaces = indent::IndentStyle::Spaces(style.width());
+            for (label, target) in [
+                ("Convert indentation to spaces", spaces),
+                ("Convert indentation to tabs", indent::IndentStyle::Tabs),
+            ] {
+                if ui.button(label).clicked() {
+                    push_undo_point(ui.ctx(), &self.code);
+                    self.code = indent::convert(&self.code, target, style.width());
+                    self.indent_override = None;
+                    self.detected_indent = target;
+                    ui.close_menu();
+                }
+            }
+        });
+    }
+
+    /// Writes panel edits into the code once they've settled for
+    /// [`LIVE_EDIT_DELAY`], so dragging doesn't rewrite it every frame. The
+    /// editor's cursor stays on the same text.
+    fn apply_live_edits(&mut self, ctx: &egui::Context) {
+        let Some(edited_at) = self.live_edit_at else {
+            return;
+        };
+        let wait = LIVE_EDIT_DELAY.saturating_sub(edited_at.elapsed());
+        if !wait.is_zero() {
+            ctx.request_repaint_after(wait);
+            return;
+        }
+        self.live_edit_at = None;
+        let before = self.code.clone();
+        self.update_code_with_variables();
+        if self.code != before {
+            push_undo_point(ctx, &before);
+            keep_editor_cursor(ctx, &before, &self.code);
+        }
+    }
+
+    /// Re-parses once the buffer has changed since the last parse and then
+    /// stayed the same for [`REPARSE_DELAY`].
+    fn reparse_if_changed(&mut self, ctx: &egui::Context) {
+        let mut hasher = DefaultHasher::new();
+        self.code.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.parsed_source == Some(hash) {
+            self.source_changed = None;
+            return;
+        }
+        let changed_at = match self.source_changed {
+            Some((seen, at)) if seen == hash => at,
+            _ => {
+                self.source_changed = Some((hash, Instant::now()));
+                ctx.request_repaint_after(REPARSE_DELAY);
+                return;
+            }
+        };
+        let wait = REPARSE_DELAY.saturating_sub(changed_at.elapsed());
+        if !wait.is_zero() {
+            ctx.request_repaint_after(wait);
+            return;
+        }
+        self.parsed_source = Some(hash);
+        self.source_changed = None;
+        self.parse_variables();
+    }
+
+    /// Steps the numeric literal at byte `offset` in place, keeping the panel
+    /// entry that owns the literal (if any) in sync.
+    fn nudge_literal(&mut self, ctx: &egui::Context, offset: usize, steps: i32, coarse: bool) {
+        let owner_at = |code: &str, offset: usize| {
+            let mut variables = parse_let_variables(code).unwrap_or_default();
+            variables.extend(parse_marker_variables(code).0);
+            let index = variables
+                .iter()
+                .position(|v| v.span.as_ref().is_some_and(|span| span.contains(&offset)))?;
+            let owner = &variables[index];
+            let occurrence = variables[..index]
+                .iter()
+                .filter(|v| v.kind == owner.kind && v.name == owner.name)
+                .count();
+            Some((
+                owner.kind,
+                owner.name.clone(),
+                occurrence,
+                variables.swap_remove(index),
+            ))
+        };
+
+        let type_hint = owner_at(&self.code, offset).map(|(.., owner)| owner.var_type);
+        let Some(nudge) =
+            nudge::nudge_literal(&self.code, offset, steps, coarse, type_hint.as_deref())
+        else {
+            return;
+        };
+
+        push_undo_point(ctx, &self.code);
+        self.code.replace_range(nudge.span.clone(), &nudge.text);
+
+        if let Some((kind, name, occurrence, owner)) = owner_at(&self.code, nudge.span.start) {
+            if let Some(variable) = self
+                .variables
+                .iter_mut()
+                .filter(|v| v.kind == kind && v.name == name)
+                .nth(occurrence)
+            {
+                variable.value = owner.value;
+            }
+        }
+    }
+
+    /// Rewrites the literals following marker comments. Markers are rescanned
+    /// so edits made in the editor since the last parse don't shift the spans.
+    /// Returns the names of edited markers that are no longer there.
+    fn update_code_with_markers(&mut self) -> Vec<String> {
+        let (markers, _) = markers::find_markers(&self.code);
+        let mut edits = Vec::new();
+        let mut unwritten = Vec::new();
+
+        for (index, variable) in self.variables.iter().enumerate() {
+            // Only edited markers are written, so the others keep their text.
+            if variable.kind != VariableKind::Marker || !variable.edited || self.is_locked(index) {
+                continue;
+            }
+            let occurrence = self.variables[..index]
+                .iter()
+                .filter(|v| v.kind == VariableKind::Marker && v.name == variable.name)
+                .count();
+            let Some(marker) = markers
+                .iter()
+                .filter(|m| m.name == variable.name)
+                .nth(occurrence)
+            else {
+                if variable.edited {
+                    unwritten.push(variable.name.clone());
+                }
+                continue;
+            };
+
+            let current = self.code[marker.span.clone()].trim_start_matches('-');
+            let suffix = match syn::parse_str::<syn::Lit>(current) {
+                Ok(lit) => written_suffix(&lit),
+                Err(_) => String::new(),
+            };
+            let literal = match &variable.value {
+                VariableValue::Int(val) => Some(format!(
+                    "{}{}",
+                    numeric::format_like(
+                        self.radix_of(index),
+                        numeric::clamp_int(&variable.var_type, *val),
+                        current
+                    ),
+                    suffix
+                )),
+                VariableValue::Wide(val) => Some(format!(
+                    "{}{}",
+                    numeric::format_like(self.radix_of(index), *val, current),
+                    suffix
+                )),
+                VariableValue::UInt(val) => Some(format!(
+                    "{}{}",
+                    numeric::format_unsigned_like(self.radix_of(index), *val, current),
+                    suffix
+                )),
+                VariableValue::Float(val) => self
+                    .float_text(index, *val, current)
+                    .map(|text| format!("{}{}", text, suffix)),
+                VariableValue::Str(val) => {
+                    let source = syn::parse_str::<syn::Expr>(current).ok();
+                    Some(str_literal(val, source.as_ref().and_then(string_literal)))
+                }
+                value => format_literal(value, &suffix),
+            };
+            match literal {
+                Some(literal) => edits.push((marker.span.clone(), literal)),
+                None if variable.edited => unwritten.push(variable.name.clone()),
+                None => {}
+            }
+        }
+
+        edits.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+        for (span, literal) in edits {
+            self.code.replace_range(span, &literal);
+        }
+        unwritten
+    }
+
+    /// Writes panel values back into the source. Declarations are re-parsed
+    /// for the same reason markers are rescanned, and matched to variables by
+    /// name, type and occurrence; nothing inside macro bodies or strings is
+    /// ever touched.
+    fn update_code_with_variables(&mut self) {
+        let mut unwritten = self.update_code_with_markers();
+
+        let Ok(ast) = parse_rust_code(&self.code) else {
+            // Nothing can be located, so no edited value gets written.
+            unwritten.extend(
+                self.variables
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, variable)| {
+                        variable.kind != VariableKind::Marker
+                            && variable.edited
+                            && !self.is_computed(*index)
+                            && !self.is_locked(*index)
+                    })
+                    .map(|(_, variable)| variable.name.clone()),
+            );
+            self.unwritten = unwritten;
+            return;
+        };
+        let declarations = let_variables(&ast);
+        let mut edits = Vec::new();
+        for (index, variable) in self.variables.iter().enumerate() {
+            if variable.kind == VariableKind::Marker || variable.approximate {
+                continue;
+            }
+            let same = |v: &&Variable| {
+                v.kind == variable.kind
+                    && v.name == variable.name
+                    && v.function == variable.function
+                    && v.var_type == variable.var_type
+            };
+            // Only what was edited or typed over is written, so every other
+            // declaration keeps its text byte for byte. Computed initializers
+            // stay expressions, and locked variables keep what's in the
+            // source, whatever the panel holds.
+            if (!variable.edited && variable.raw.is_none())
+                || self.is_computed(index)
+                || self.is_locked(index)
+            {
+                continue;
+            }
+            let occurrence = self.variables[..index].iter().filter(same).count();
+            let Some((declaration, span)) = declarations
+                .iter()
+                .filter(same)
+                .nth(occurrence)
+                .and_then(|declaration| Some((declaration, declaration.span.clone()?)))
+            else {
+                if variable.edited {
+                    unwritten.push(variable.name.clone());
+                }
+                continue;
+            };
+            // A suffix may be all an untyped binding's type comes from.
+            let suffix = syn::parse_str::<syn::Expr>(&self.code[span.clone()])
+                .ok()
+                .as_ref()
+                .and_then(numeric_literal)
+                .map(|(lit, _)| written_suffix(lit))
+                .unwrap_or_default();
+            let cast = match syn::parse_str::<syn::Expr>(&self.code[span.clone()]) {
+                Ok(syn::Expr::Cast(cast)) => Some(cast),
+                _ => None,
+            };
+            let literal = match &variable.value {
+                // Casts of a literal stay casts.
+                value if cast.is_some() => cast.as_ref().and_then(|cast| cast_literal(value, cast)),
+                VariableValue::Int(val) if is_byte_literal(&self.code[span.clone()]) => {
+                    Some(byte_literal(*val))
+                }
+                VariableValue::Int(val) => Some(format!(
+                    "{}{}",
+                    numeric::format_like(
+                        self.radix_of(index),
+                        numeric::clamp_int(&variable.var_type, *val),
+                        &self.code[span.clone()]
+                    ),
+                    suffix
+                )),
+                VariableValue::Wide(val) => Some(format!(
+                    "{}{}",
+                    numeric::format_like(self.radix_of(index), *val, &self.code[span.clone()]),
+                    suffix
+                )),
+                VariableValue::UInt(val) => Some(format!(
+                    "{}{}",
+                    numeric::format_unsigned_like(
+                        self.radix_of(index),
+                        *val,
+                        &self.code[span.clone()]
+                    ),
+                    suffix
+                )),
+                VariableValue::Float(val) => self
+                    .float_text(index, *val, &self.code[span.clone()])
+                    .map(|text| format!("{}{}", text, suffix)),
+                VariableValue::Bool(val) => Some(val.to_string()),
+                VariableValue::Str(_) => syn::parse_str(&self.code[span.clone()])
+                    .ok()
+                    .and_then(|source| literal_like(&variable.value, &variable.var_type, &source)),
+                VariableValue::Char(val) => Some(format!("{:?}", val)),
+                VariableValue::Bytes(val) => {
+                    bytes_literal(val, &variable.var_type, &self.code[span.clone()])
+                }
+                VariableValue::List(items) => list::rewrite(
+                    items,
+                    list::element_type(&variable.var_type).unwrap_or_default(),
+                    &self.code[span.clone()],
+                ),
+                VariableValue::Tuple(items) => tuple::element_types(&variable.var_type)
+                    .and_then(|types| tuple::literal(items, &types, &self.code[span.clone()])),
+                VariableValue::Optional { some, value } => optional::inner_type(&variable.var_type)
+                    .and_then(|inner| {
+                        optional::literal(*some, value, inner, &self.code[span.clone()])
+                    }),
+                VariableValue::Enum { .. } => field_literal(&self.code, variable, span.clone()),
+                VariableValue::Struct { fields, .. } => {
+                    if let VariableValue::Struct {
+                        fields: declared, ..
+                    } = &declaration.value
+                    {
+                        field_edits(&self.code, fields, declared, &mut edits);
+                    }
+                    continue;
+                }
+                // Typed over as is.
+                VariableValue::Unknown => match &variable.raw {
+                    Some(raw) => Some(raw.clone()),
+                    None => continue,
+                },
+            };
+            let Some(literal) = literal else {
+                if variable.edited {
+                    unwritten.push(variable.name.clone());
+                }
+                continue;
+            };
+            if self.code[span.clone()] != literal {
+                edits.push((span, literal));
+            }
+        }
+        self.unwritten = unwritten;
+
+        // Where each edit starts once the ones before it changed lengths.
+        edits.sort_by_key(|(span, _)| span.start);
+        let mut shift = 0isize;
+        let starts: Vec<usize> = edits
+            .iter()
+            .map(|(span, literal)| {
+                let start = span.start.saturating_add_signed(shift);
+                shift += literal.len() as isize - span.len() as isize;
+                start
+            })
+            .collect();
+        for (span, literal) in edits.into_iter().rev() {
+            self.code.replace_range(span, &literal);
+        }
+
+        if !self.format_rewrites
+            || starts.is_empty()
+            || !self.capabilities.allows(Capability::ExternalProcesses)
+        {
+            return;
+        }
+        let Ok(ast) = parse_rust_code(&self.code) else {
+            return;
+        };
+        let statements = format::statements_at(&ast, &starts);
+        match format::format_statements(
+            self.formatter.as_ref(),
+            &self.code,
+            &statements,
+            self.indent_style(),
+        ) {
+            Ok(code) => self.code = code,
+            Err(e) => self.export_message = Some(format!("Values written unformatted: {}", e)),
+        }
+    }
+
+    fn run_code(&mut self) {
+        let inputs = self.run_inputs.clone();
+        self.run_code_with(&inputs);
+    }
+
+    /// Starts a build and run on a background thread, picked up again by
+    /// `poll_run` once it's done.
+    fn run_code_with(&mut self, inputs: &RunInputs) {
+        if !self.capabilities.allows(Capability::ExternalProcesses) {
+            self.output = "Compiling and running is disabled in safe mode".to_string();
+            return;
+        }
+        if self.is_running() {
+            self.export_message = Some("Wait for the current run to finish".to_string());
+            return;
+        }
+        // The build would only compete with a background check.
+        self.checker.cancel();
+        let started = history::now();
+        let environment = self.record_environment.then(|| {
+            environment::EnvironmentCapture::capture(&environment::parse_redactions(
+                &self.redaction_patterns,
+            ))
+        });
+        let mut inputs = inputs.clone();
+        let base = outputs::base_dir(self.output_location, inputs.working_dir.as_deref());
+        match outputs::create_run_dir(&base, started) {
+            Ok(dir) => inputs.out_dir = Some(dir),
+            Err(e) => {
+                self.export_message = Some(format!("Couldn't create an output folder: {}", e))
+            }
+        }
+        let (rustc_version, host) = provenance::rustc_info();
+        let provenance = provenance::Provenance {
+            crowbar_version: env!("CARGO_PKG_VERSION").to_string(),
+            rustc_version,
+            host,
+            profile: self.build_config.profile.label().to_string(),
+            flags: self.build_config.flags.clone(),
+            file: self
+                .opened_file
+                .as_ref()
+                .map(|file| file.display().to_string()),
+            source_hash: provenance::source_hash(&self.code),
+            overrides: self
+                .variables
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !self.is_computed(*index))
+                .filter_map(|(_, v)| Some((v.name.clone(), format_literal(&v.value, "")?)))
+                .collect(),
+            started,
+            duration_ms: 0,
+            exit_code: None,
+        };
+
+        debug_log::record(format!(
+            "run started ({}, flags \"{}\")",
+            provenance.profile, provenance.flags
+        ));
+        let source = script::compile_source(&self.code).into_owned();
+        let config = self.build_config.clone();
+        let file = self.opened_file.clone();
+        let out_dir = inputs.out_dir.clone();
+        self.start_background(None, out_dir, move |runner, progress| {
+            let result = runner.run(&source, &config, &inputs, file.as_deref(), progress);
+            Finished::Run {
+                result,
+                provenance,
+                environment,
+            }
+        });
+    }
+
+    /// Hands the runner to a thread running `job`, whose outcome
+    /// [`Self::poll_run`] takes in. The HUD shows its progress, with `label`
+    /// over it, and its Stop button stops it.
+    fn start_background(
+        &mut self,
+        label: Option<&'static str>,
+        out_dir: Option<PathBuf>,
+        job: impl FnOnce(&mut Runner<Rustc>, &Arc<Mutex<runner::Progress>>) -> Finished + Send + 'static,
+    ) {
+        let progress = Arc::new(Mutex::new(runner::Progress::default()));
+        let (sender, result) = mpsc::channel();
+        let mut runner = std::mem::take(&mut self.runner);
+        let thread_progress = progress.clone();
+        std::thread::spawn(move || {
+            let finished = job(&mut runner, &thread_progress);
+            let _ = sender.send((runner, finished));
+        });
+        self.finished_run = None;
+        self.active_run = Some(ActiveRun {
+            progress,
+            result,
+            timer: Instant::now(),
+            label,
+            out_dir,
+        });
+    }
+
+    /// Whether a run or a sweep has the runner on a background thread.
+    fn is_running(&self) -> bool {
+        self.active_run.is_some() || self.sweep.as_ref().is_some_and(|s| s.result.is_some())
+    }
+
+    /// Takes in the run in flight if it has finished.
+    fn poll_run(&mut self) {
+        let Some(run) = &self.active_run else {
+            return;
+        };
+        let (runner, finished) = match run.result.try_recv() {
+            Ok(done) => done,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                debug_log::record("run thread ended without a result");
+                self.active_run = None;
+                self.output = "The run ended unexpectedly".to_string();
+                return;
+            }
+        };
+        let Some(run) = self.active_run.take() else {
+            return;
+        };
+        self.runner = runner;
+        let elapsed = run.timer.elapsed();
+        let stopped = run.progress.lock().unwrap().stop_requested;
+        match finished {
+            Finished::Run {
+                result,
+                provenance,
+                environment,
+            } => self.record_run(
+                result,
+                provenance,
+                environment,
+                run.out_dir,
+                elapsed,
+                stopped,
+            ),
+            Finished::Determinism(report) => {
+                let verdict = match &report.verdict {
+                    determinism::Verdict::Deterministic => "same output",
+                    determinism::Verdict::Differs { .. } => "outputs differ",
+                    determinism::Verdict::Failed(_) => "failed",
+                };
+                debug_log::record(format!("determinism probe: {}", verdict));
+                let failed = matches!(report.verdict, determinism::Verdict::Failed(_));
+                let status = hud::final_status(failed, stopped, Some(0), elapsed);
+                self.finished_run = Some((Instant::now(), status));
+                // A stopped probe didn't compare anything.
+                if !stopped {
+                    self.determinism = Some(report);
+                    self.show_determinism = true;
+                }
+            }
+            Finished::Both(comparison) => {
+                let status = hud::final_status(false, comparison.is_none(), Some(0), elapsed);
+                self.finished_run = Some((Instant::now(), status));
+                if comparison.is_some() {
+                    self.output_comparison = comparison;
+                }
+            }
+        }
+    }
+
+    /// Puts a finished plain run in the output and the history.
+    fn record_run(
+        &mut self,
+        result: runner::RunResult,
+        mut provenance: provenance::Provenance,
+        environment: Option<environment::EnvironmentCapture>,
+        mut out_dir: Option<PathBuf>,
+        elapsed: Duration,
+        stopped: bool,
+    ) {
+        // Nothing ran, so the folder is empty and not worth keeping.
+        if result.build_failed {
+            if let Some(dir) = out_dir.take() {
+                let _ = std::fs::remove_dir(dir);
+            }
+        }
+        let status = hud::final_status(result.build_failed, stopped, result.exit_code, elapsed);
+        debug_log::record(format!("run finished: {}", status));
+        self.finished_run = Some((Instant::now(), status));
+        self.output = format!("[{}]\n{}", result.header, result.output);
+        self.build_failed = result.build_failed;
+        self.import_suggestions = if result.build_failed {
+            imports::parse_suggestions(&result.output)
+        } else {
+            Vec::new()
+        };
+        provenance.duration_ms = elapsed.as_millis() as u64;
+        provenance.exit_code = result.exit_code;
+        history::push(
+            &mut self.history,
+            history::RunRecord {
+                started: provenance.started,
+                header: result.header,
+                output: result.output,
+                environment,
+                provenance: Some(provenance),
+                out_dir,
+            },
+        );
+        self.storage_stale = true;
+        self.notify_tour(tour::Event::RunFinished);
+    }
+
+    /// Elapsed time, the latest output line and a stop button over the
+    /// corner of `editor` while a run is going, and its outcome for a moment
+    /// after. Moves to another corner rather than cover `caret_line`.
+    fn show_run_hud(
+        &mut self,
+        ctx: &egui::Context,
+        editor: egui::Rect,
+        caret_line: Option<egui::Rect>,
+    ) {
+        let since_finished = match (&self.active_run, &self.finished_run) {
+            (Some(_), _) => None,
+            (None, Some((at, _))) => Some(at.elapsed()),
+            (None, None) => return,
+        };
+        let Some(repaint) = hud::next_repaint(since_finished) else {
+            self.finished_run = None;
+            return;
+        };
+        ctx.request_repaint_after(repaint);
+
+        let id = egui::Id::new("run_hud");
+        let size = ctx
+            .memory(|m| m.area_rect(id))
+            .map_or(egui::vec2(260.0, 60.0), |rect| rect.size());
+        let rect = hud::placement(editor, size, caret_line);
+        let mut stop = false;
+        let frame = egui::Area::new(id)
+            .order(egui::Order::Foreground)
+            .fixed_pos(rect.min)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style())
+                    .show(ui, |ui| {
+                        ui.set_max_width(260.0);
+                        match (&self.active_run, &self.finished_run) {
+                            (Some(run), _) => {
+                                let (stage, output) = {
+                                    let progress = run.progress.lock().unwrap();
+                                    let stage = if progress.waiting {
+                                        "Waiting for another build…"
+                                    } else if progress.running {
+                                        "Running"
+                                    } else {
+                                        "Compiling"
+                                    };
+                                    (stage, hud::last_line(&progress.output))
+                                };
+                                if let Some(label) = run.label {
+                                    ui.strong(label);
+                                }
+                                ui.horizontal(|ui| {
+                                    ui.spinner();
+                                    ui.label(format!(
+                                        "{} {}",
+                                        stage,
+                                        hud::format_elapsed(run.timer.elapsed())
+                                    ));
+                                    stop = ui.small_button("Stop").clicked();
+                                });
+                                if !output.is_empty() {
+                                    ui.label(egui::RichText::new(output).monospace().weak());
+                                }
+                            }
+                            (None, Some((_, status))) => {
+                                ui.label(status);
+                            }
+                            (None, None) => {}
+                        }
+                    })
+                    .response
+            })
+            .inner;
+        if stop {
+            if let Some(run) = &self.active_run {
+                run.progress.lock().unwrap().stop_requested = true;
+            }
+        } else if frame
+            .interact(egui::Sense::click())
+            .on_hover_text("Show the output")
+            .clicked()
+        {
+            self.reveal_output = true;
+        }
+    }
+
+    /// Runs again with exactly the environment recorded in a history entry,
+    /// instead of the one Crowbar was started with.
+    fn rerun_with_environment(&mut self, index: usize) {
+        let Some(capture) = self.history[index].environment.clone() else {
+            return;
+        };
+        let mut inputs = self.run_inputs.clone();
+        inputs.base_env = Some(capture.replay_vars(&environment::current_vars()));
+        inputs.working_dir = Some(capture.working_dir.clone()).filter(|dir| dir.is_dir());
+        self.update_code_with_variables();
+        self.run_code_with(&inputs);
+    }
+
+    /// Actions on the latest run's output. Anything leaving Crowbar gets the
+    /// run's provenance block on top.
+    fn show_output_toolbar(&mut self, ui: &mut egui::Ui) {
+        let Some(block) = self.history.last().and_then(provenance::Provenance::block) else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            if ui.button("Copy provenance").clicked() {
+                ui.ctx().copy_text(block);
+            }
+            if ui.button("Save output…").clicked() {
+                let mut dialog = FileDialog::save_file(None).default_filename("output.txt");
+                dialog.open();
+                self.output_save_dialog = Some(dialog);
+            }
+            if ui
+                .button("Compare with saved output…")
+                .on_hover_text("Checks whether the latest run reproduced a saved one")
+                .clicked()
+            {
+                let mut dialog = FileDialog::open_file(None);
+                dialog.open();
+                self.output_compare_dialog = Some(dialog);
+            }
+        });
+    }
+
+    fn show_output_save_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.output_save_dialog else {
+            return;
+        };
+        if !dialog.show(ctx).selected() {
+            return;
+        }
+        let (Some(path), Some(record)) = (dialog.path(), self.history.last()) else {
+            return;
+        };
+        let contents = format!(
+            "{}{}",
+            provenance::Provenance::block(record).unwrap_or_default(),
+            record.output
+        );
+        self.export_message = Some(match std::fs::write(path, contents) {
+            Ok(()) => format!("Saved output to {}", path.display()),
+            Err(e) => format!("Failed to save output: {}", e),
+        });
+    }
+
+    fn show_output_compare_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.output_compare_dialog else {
+            return;
+        };
+        if !dialog.show(ctx).selected() {
+            return;
+        }
+        let (Some(path), Some(record)) = (dialog.path(), self.history.last()) else {
+            return;
+        };
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.export_message = Some(format!("Failed to read {}: {}", path.display(), e));
+                return;
+            }
+        };
+        let (saved, saved_output) = provenance::split_saved(&text);
+        self.export_message = Some(match (saved, &record.provenance) {
+            (Some(saved), Some(latest)) => {
+                let differences = saved.differences(latest);
+                let output = if saved_output == record.output {
+                    "same output"
+                } else {
+                    "different output"
+                };
+                if differences.is_empty() {
+                    format!("Same inputs as the saved run, {}", output)
+                } else {
+                    format!("Differs in {}, {}", differences.join(", "), output)
+                }
+            }
+            _ => format!("{} has no provenance block", path.display()),
+        });
+    }
+
+    fn show_compare_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.compare_dialog else {
+            return;
+        };
+        if !dialog.show(ctx).selected() {
+            return;
+        }
+        let Some(path) = dialog.path().map(PathBuf::from) else {
+            return;
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.export_message = Some(format!("Failed to read {}: {}", path.display(), e));
+                return;
+            }
+        };
+        let side = |path: Option<PathBuf>, text: String| {
+            let (syntax, _) = syntax::find_syntax(&self.syntax_set, file_extension(&path));
+            let sections = highlight::sections(&text, syntax, &self.syntax_set, &self.theme);
+            let title = path.as_ref().map_or("Buffer".to_string(), |path| {
+                path.file_name()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy()
+                    .to_string()
+            });
+            compare::Side::new(title, path, text, sections)
+        };
+        let left = side(self.opened_file.clone(), self.code.clone());
+        let right = side(Some(path), text);
+        self.comparison = Some(compare::Comparison::new(left, right));
+    }
+
+    /// The file comparison and, once both sides ran, the output comparison.
+    fn show_comparison_windows(&mut self, ctx: &egui::Context) {
+        let mut run_both = false;
+        if let Some(comparison) = &mut self.comparison {
+            let mut open = true;
+            egui::Window::new("Compare")
+                .open(&mut open)
+                .default_size([900.0, 600.0])
+                .show(ctx, |ui| {
+                    run_both = ui
+                        .button("Run both and diff outputs")
+                        .on_hover_text(
+                            "Compiles and runs each side with the current build settings",
+                        )
+                        .clicked();
+                    comparison_view(ui, "compare", comparison);
+                });
+            if !open {
+                self.comparison = None;
+            }
+        }
+        if run_both {
+            self.run_both();
+        }
+        if let Some(comparison) = &mut self.output_comparison {
+            let mut open = true;
+            egui::Window::new("Compare outputs")
+                .open(&mut open)
+                .default_size([900.0, 400.0])
+                .show(ctx, |ui| comparison_view(ui, "compare_outputs", comparison));
+            if !open {
+                self.output_comparison = None;
+            }
+        }
+    }
+
+    /// Runs each side of the comparison and compares what they printed.
+    fn run_both(&mut self) {
+        if !self.capabilities.allows(Capability::ExternalProcesses) {
+            self.export_message =
+                Some("Compiling and running is disabled in safe mode".to_string());
+            return;
+        }
+        if self.is_running() {
+            self.export_message = Some("Wait for the current run to finish".to_string());
+            return;
+        }
+        let Some(comparison) = &self.comparison else {
+            return;
+        };
+        self.checker.cancel();
+        let sides = [&comparison.left, &comparison.right].map(|side| {
+            (
+                side.title.clone(),
+                script::compile_source(&side.text).into_owned(),
+                side.path.clone(),
+            )
+        });
+        let config = self.build_config.clone();
+        let inputs = self.run_inputs.clone();
+        self.start_background(Some("Running both sides"), None, move |runner, progress| {
+            let mut outputs = Vec::new();
+            for (title, source, path) in sides {
+                {
+                    let mut progress = progress.lock().unwrap();
+                    if progress.stop_requested {
+                        return Finished::Both(None);
+                    }
+                    progress.running = false;
+                    progress.output.clear();
+                }
+                let result = runner.run(&source, &config, &inputs, path.as_deref(), progress);
+                let output = format!("[{}]\n{}", result.header, result.output);
+                outputs.push(compare::Side::new(title, None, output, Vec::new()));
+            }
+            if progress.lock().unwrap().stop_requested {
+                return Finished::Both(None);
+            }
+            let right = outputs.pop();
+            let left = outputs.pop();
+            Finished::Both(
+                left.zip(right)
+                    .map(|(left, right)| compare::Comparison::new(left, right)),
+            )
+        });
+    }
+
+    /// Runs the buffer twice with the current inputs on a background thread
+    /// and scans it for likely sources of nondeterminism.
+    fn check_determinism(&mut self, ctx: &egui::Context) {
+        if !self.capabilities.allows(Capability::ExternalProcesses) {
+            self.export_message =
+                Some("Compiling and running is disabled in safe mode".to_string());
+            return;
+        }
+        if self.is_running() {
+            self.export_message = Some("Wait for the current run to finish".to_string());
+            return;
+        }
+        self.checker.cancel();
+        push_undo_point(ctx, &self.code);
+        self.update_code_with_variables();
+        let causes = parse_rust_code(&self.code)
+            .map(|ast| determinism::likely_causes(&ast))
+            .unwrap_or_default();
+        let source_hash = provenance::source_hash(&self.code);
+        let source = script::compile_source(&self.code).into_owned();
+        let config = self.build_config.clone();
+        let inputs = self.run_inputs.clone();
+        let file = self.opened_file.clone();
+        self.start_background(
+            Some("Checking determinism"),
+            None,
+            move |runner, progress| {
+                let verdict = determinism::probe(
+                    runner,
+                    &source,
+                    &config,
+                    &inputs,
+                    file.as_deref(),
+                    progress,
+                );
+                Finished::Determinism(DeterminismReport {
+                    source_hash,
+                    verdict,
+                    causes,
+                })
+            },
+        );
+    }
+
+    /// The probe's report if it's about the buffer as it is now.
+    fn current_determinism(&self) -> Option<&DeterminismReport> {
+        self.determinism
+            .as_ref()
+            .filter(|report| report.source_hash == provenance::source_hash(&self.code))
+    }
+
+    fn is_nondeterministic(&self) -> bool {
+        self.current_determinism()
+            .is_some_and(|report| matches!(report.verdict, determinism::Verdict::Differs { .. }))
+    }
+
+    /// Warns that runs of this buffer can't be compared with each other.
+    fn nondeterminism_warning(&self, ui: &mut egui::Ui) {
+        if self.is_nondeterministic() {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "⚠ This code printed different output for identical runs, so \
+                 differences between runs may not come from the inputs.",
+            );
+        }
+    }
+
+    fn show_determinism_window(&mut self, ctx: &egui::Context) {
+        let Some(report) = &self.determinism else {
+            return;
+        };
+        let mut open = self.show_determinism;
+        let mut goto = None;
+        let mut diff = None;
+        egui::Window::new("Determinism")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if report.source_hash != provenance::source_hash(&self.code) {
+                    ui.weak("The code changed since this check.");
+                }
+                match &report.verdict {
+                    determinism::Verdict::Deterministic => {
+                        ui.label("✔ Both runs printed the same output.");
+                    }
+                    determinism::Verdict::Differs {
+                        line,
+                        first,
+                        second,
+                    } => {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            if first.output == second.output {
+                                "⚠ Both runs printed the same output but exited differently."
+                                    .to_string()
+                            } else {
+                                format!("⚠ The outputs differ from line {} on.", line)
+                            },
+                        );
+                        if ui.button("Compare outputs").clicked() {
+                            diff = Some((first.clone(), second.clone()));
+                        }
+                    }
+                    determinism::Verdict::Failed(error) => {
+                        ui.colored_label(ui.visuals().error_fg_color, "The check didn't run:");
+                        ui.monospace(error);
+                    }
+                }
+                ui.separator();
+                if report.causes.is_empty() {
+                    ui.weak("No likely causes found in the code.");
+                } else {
+                    ui.label("Likely causes:");
+                }
+                for cause in &report.causes {
+                    ui.horizontal(|ui| {
+                        if ui.link(format!("line {}", cause.line)).clicked() {
+                            goto = Some(cause.offset);
+                        }
+                        ui.label(&cause.what);
+                    });
+                }
+            });
+        self.show_determinism = open;
+        if let Some(offset) = goto {
+            self.jump_to(ctx, offset);
+        }
+        if let Some((first, second)) = diff {
+            let side = |title: &str, result: runner::RunResult| {
+                let output = format!("[{}]\n{}", result.header, result.output);
+                compare::Side::new(title.to_string(), None, output, Vec::new())
+            };
+            self.output_comparison = Some(compare::Comparison::new(
+                side("First run", first),
+                side("Second run", second),
+            ));
+        }
+    }
+
+    /// Opens the sweep window on a number, set to go over its annotated
+    /// range, or else a little way up from its value.
+    fn open_sweep(&mut self, index: usize) {
+        if self.sweep.as_ref().is_some_and(|s| s.result.is_some()) {
+            self.show_sweep = true;
+            return;
+        }
+        let variable = &self.variables[index];
+        let (value, integer) = match variable.value {
+            VariableValue::Int(val) => (val as f64, true),
+            VariableValue::UInt(val) => (val as f64, true),
+            VariableValue::Float(val) => (val, false),
+            _ => return,
+        };
+        let (start, end) = variable.annotation.range.unwrap_or(if integer {
+            (value, value + 10.0)
+        } else {
+            (value, value + value.abs().max(1.0))
+        });
+        let step = variable.annotation.step.unwrap_or(if integer {
+            ((end - start) / 10.0).round().max(1.0)
+        } else {
+            (end - start) / 10.0
+        });
+        self.sweep = Some(SweepWindow {
+            key: panel::VariableKey::of(variable),
+            start: start.to_string(),
+            end: end.to_string(),
+            step: step.to_string(),
+            list: String::new(),
+            error: None,
+            total: 0,
+            progress: Arc::default(),
+            result: None,
+        });
+        self.show_sweep = true;
+    }
+
+    /// Builds and runs the program once per value of the swept variable,
+    /// one after another on a background thread, picked up again by
+    /// `poll_sweep` once they're done.
+    fn start_sweep(&mut self, ctx: &egui::Context) {
+        let Some(key) = self.sweep.as_ref().map(|sweep| sweep.key.clone()) else {
+            return;
+        };
+        let sources = match self.prepare_sweep(ctx) {
+            Ok(sources) => sources,
+            Err(e) => {
+                if let Some(sweep) = &mut self.sweep {
+                    sweep.error = Some(e);
+                }
+                return;
+            }
+        };
+        let progress = Arc::new(Mutex::new(sweep::Progress::default()));
+        let (sender, result) = mpsc::channel();
+        let mut runner = std::mem::take(&mut self.runner);
+        let config = self.build_config.clone();
+        let inputs = self.run_inputs.clone();
+        let file = self.opened_file.clone();
+        let total = sources.len();
+        let thread_progress = progress.clone();
+        std::thread::spawn(move || {
+            sweep::run(
+                &mut runner,
+                sources,
+                &config,
+                &inputs,
+                file.as_deref(),
+                &thread_progress,
+            );
+            let _ = sender.send(runner);
+        });
+        debug_log::record(format!("sweep of `{}` started, {} values", key.name, total));
+        if let Some(sweep) = &mut self.sweep {
+            sweep.error = None;
+            sweep.total = total;
+            sweep.progress = progress;
+            sweep.result = Some(result);
+        }
+    }
+
+    /// Checks the sweep window's settings, writes pending edits back and
+    /// builds the code for each value to run.
+    fn prepare_sweep(&mut self, ctx: &egui::Context) -> Result<Vec<(String, String)>, String> {
+        if !self.capabilities.allows(Capability::ExternalProcesses) {
+            return Err("Compiling and running is disabled in safe mode".to_string());
+        }
+        if self.is_running() {
+            return Err("Wait for the current run to finish".to_string());
+        }
+        let Some(sweep) = &self.sweep else {
+            return Err("Open the sweep window first".to_string());
+        };
+        let number = |text: &str, what: &str| {
+            text.trim()
+                .replace('_', "")
+                .parse::<f64>()
+                .map_err(|_| format!("The {} isn't a number", what))
+        };
+        let values = if sweep.list.trim().is_empty() {
+            number(&sweep.start, "start").and_then(|start| {
+                sweep::range(
+                    start,
+                    number(&sweep.end, "end")?,
+                    number(&sweep.step, "step")?,
+                )
+            })
+        } else {
+            sweep::list(&sweep.list)
+        };
+        let key = sweep.key.clone();
+        let values = values?;
+        let Some(index) = self
+            .variables
+            .iter()
+            .position(|variable| panel::VariableKey::of(variable) == key)
+        else {
+            return Err(format!("`{}` isn't in the code anymore", key.name));
+        };
+        if !self.is_editable_number(index) {
+            return Err(format!("`{}` can't be edited right now", key.name));
+        }
+
+        self.checker.cancel();
+        // Pending edits are written back first, so Ctrl+Z can undo them.
+        push_undo_point(ctx, &self.code);
+        self.update_code_with_variables();
+        Ok(self.sweep_sources(index, &values))
+    }
+
+    /// The code with each of `values` written into the variable at `index`,
+    /// after the value as written, skipping repeats. The buffer and the
+    /// variable are left as they were.
+    fn sweep_sources(&mut self, index: usize, values: &[f64]) -> Vec<(String, String)> {
+        let code = self.code.clone();
+        let variable = &self.variables[index];
+        let (value, edited) = (variable.value.clone(), variable.edited);
+        let ty = variable.var_type.clone();
+        let mut sources: Vec<(String, String)> = Vec::new();
+        for &swept in values {
+            let (swept, label) = match value {
+                VariableValue::Int(_) => {
+                    let val = numeric::clamp_int(&ty, swept.round() as i64);
+                    (VariableValue::Int(val), val.to_string())
+                }
+                VariableValue::UInt(_) => {
+                    let highest = numeric::uint_max(&ty).unwrap_or(u128::MAX);
+                    let val = (swept.round().max(0.0) as u128).min(highest);
+                    (VariableValue::UInt(val), val.to_string())
+                }
+                _ => (VariableValue::Float(swept), format!("{:?}", swept)),
+            };
+            if sources.iter().any(|(done, _)| *done == label) {
+                continue;
+            }
+            self.variables[index].value = swept;
+            self.variables[index].edited = true;
+            self.update_code_with_variables();
+            sources.push((label, script::compile_source(&self.code).into_owned()));
+            self.code = code.clone();
+        }
+        self.variables[index].value = value;
+        self.variables[index].edited = edited;
+        sources
+    }
+
+    /// Takes the runner back once the sweep in flight is done.
+    fn poll_sweep(&mut self, ctx: &egui::Context) {
+        let Some(sweep) = &mut self.sweep else {
+            return;
+        };
+        let Some(result) = &sweep.result else {
+            return;
+        };
+        match result.try_recv() {
+            Ok(runner) => {
+                self.runner = runner;
+                sweep.result = None;
+                let done = sweep.progress.lock().unwrap().points.len();
+                debug_log::record(format!(
+                    "sweep finished, {} of {} values",
+                    done, sweep.total
+                ));
+            }
+            Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+            Err(TryRecvError::Disconnected) => {
+                debug_log::record("sweep thread ended without handing the runner back");
+                sweep.result = None;
+                sweep.error = Some("The sweep ended unexpectedly".to_string());
+            }
+        }
+    }
+
+    /// The range or values to sweep, progress while it goes and a table of
+    /// what each value printed.
+    fn show_sweep_window(&mut self, ctx: &egui::Context) {
+        let Some(sweep) = &mut self.sweep else {
+            return;
+        };
+        let mut open = self.show_sweep;
+        let mut start = false;
+        let running = sweep.result.is_some();
+        egui::Window::new(format!("Sweep {}", sweep.key.name))
+            .id(egui::Id::new("sweep_window"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.add_enabled_ui(!running, |ui| {
+                    egui::Grid::new("sweep_form").num_columns(2).show(ui, |ui| {
+                        ui.label("From");
+                        ui.text_edit_singleline(&mut sweep.start);
+                        ui.end_row();
+                        ui.label("To");
+                        ui.text_edit_singleline(&mut sweep.end);
+                        ui.end_row();
+                        ui.label("Step");
+                        ui.text_edit_singleline(&mut sweep.step);
+                        ui.end_row();
+                        ui.label("Or values");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut sweep.list).hint_text("1, 2, 5, 10"),
+                        )
+                        .on_hover_text("Used instead of the range when given");
+                        ui.end_row();
+                    });
+                });
+                if let Some(error) = &sweep.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                let points = sweep.progress.lock().unwrap().points.clone();
+                ui.horizontal(|ui| {
+                    if running {
+                        ui.spinner();
+                        ui.add(
+                            egui::ProgressBar::new(points.len() as f32 / sweep.total.max(1) as f32)
+                                .text(format!("{} of {}", points.len(), sweep.total))
+                                .desired_width(160.0),
+                        );
+                        if ui.button("Cancel").clicked() {
+                            sweep.progress.lock().unwrap().stop();
+                        }
+                    } else {
+                        start = ui
+                            .button("Run sweep")
+                            .on_hover_text("Builds and runs the program once for each value")
+                            .clicked();
+                    }
+                    if ui
+                        .add_enabled(!points.is_empty(), egui::Button::new("Copy as CSV"))
+                        .clicked()
+                    {
+                        ui.output_mut(|o| {
+                            o.copied_text = sweep::to_csv(&sweep.key.name, &points);
+                        });
+                    }
+                });
+                if points.is_empty() {
+                    return;
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_source("sweep_results")
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("sweep_results_grid")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong(&sweep.key.name);
+                                ui.strong("Exit");
+                                ui.strong("Time");
+                                ui.strong("Output");
+                                ui.end_row();
+                                for point in &points {
+                                    ui.monospace(&point.value);
+                                    match (point.build_failed, point.exit_code) {
+                                        (true, _) => ui.colored_label(
+                                            ui.visuals().error_fg_color,
+                                            "build failed",
+                                        ),
+                                        (false, Some(code)) => ui.label(code.to_string()),
+                                        (false, None) => ui.weak("killed"),
+                                    };
+                                    ui.label(hud::format_elapsed(point.duration))
+                                        .on_hover_text("Building and running");
+                                    ui.add(
+                                        egui::Label::new(
+                                            egui::RichText::new(point.output.trim_end())
+                                                .monospace(),
+                                        )
+                                        .truncate(),
+                                    )
+                                    .on_hover_text(egui::RichText::new(&point.output).monospace());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+        self.show_sweep = open;
+        if start {
+            self.start_sweep(ctx);
+        }
+    }
+
+    /// One "Add import" action per unresolved name rustc had suggestions for.
+    fn show_import_suggestions(&mut self, ui: &mut egui::Ui) {
+        let mut chosen = None;
+        for (index, suggestion) in self.import_suggestions.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("`{}` is not in scope", suggestion.name));
+                match suggestion.candidates.as_slice() {
+                    [candidate] => {
+                        if ui.button("Add import").on_hover_text(candidate).clicked() {
+                            chosen = Some((index, candidate.clone()));
+                        }
+                    }
+                    candidates => {
+                        ui.menu_button("Add import…", |ui| {
+                            for candidate in candidates {
+                                if ui.button(candidate).clicked() {
+                                    chosen = Some((index, candidate.clone()));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+                }
+            });
+        }
+
+        if let Some((index, use_line)) = chosen {
+            self.import_suggestions.remove(index);
+            if let Some(code) = imports::add_import(&self.code, &use_line) {
+                push_undo_point(ui.ctx(), &self.code);
+                self.code = code;
+                self.parse_variables();
+            }
+        }
+    }
+
+    fn show_history(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing(format!("History ({})", self.history.len()), |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.record_environment, "Record environment")
+                    .on_hover_text(
+                        "Store the environment, working directory and toolchain of each run",
+                    );
+                if ui.button("Import environment…").clicked() {
+                    let mut dialog = FileDialog::open_file(None);
+                    dialog.open();
+                    self.env_import_dialog = Some(dialog);
+                }
+                if ui.button("Sensitivity…").clicked() {
+                    self.show_sensitivity = !self.show_sensitivity;
+                }
+                let check = ui
+                    .add_enabled(!self.is_running(), egui::Button::new("Check determinism"))
+                    .on_hover_text("Runs the program twice with the same inputs and compares");
+                if check.clicked() {
+                    self.check_determinism(&ui.ctx().clone());
+                }
+            });
+            if let Some(error) = &self.history_error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            self.nondeterminism_warning(ui);
+
+            let current = environment::current_vars();
+            let mut rerun = None;
+            let mut file_action = None;
+            for (index, record) in self.history.iter().enumerate().rev() {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{}  {}",
+                        history::format_time(record.started),
+                        record.header
+                    ))
+                    .on_hover_text(&record.output);
+                    if let Some(capture) = &record.environment {
+                        let differences = environment::diff(&capture.vars, &current);
+                        ui.label(environment::diff_summary(&differences))
+                            .on_hover_text(differences.join("\n"));
+                        if ui.button("Re-run with recorded environment").clicked() {
+                            rerun = Some(index);
+                        }
+                        if ui.button("Export environment…").clicked() {
+                            let mut dialog = FileDialog::save_file(None);
+                            dialog.open();
+                            self.env_export_dialog = Some((dialog, index));
+                        }
+                    }
+                });
+                if let Some(dir) = &record.out_dir {
+                    egui::CollapsingHeader::new("Output files")
+                        .id_source(("run_outputs", index))
+                        .show(ui, |ui| {
+                            if !dir.is_dir() {
+                                ui.weak("The folder has been removed.");
+                                return;
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.button("Open folder").clicked() {
+                                    file_action = Some(FileAction::Open(dir.clone()));
+                                }
+                                if ui.button("Delete folder").clicked() {
+                                    file_action = Some(FileAction::Delete(dir.clone()));
+                                }
+                            });
+                            let files = outputs::list(dir);
+                            if files.is_empty() {
+                                ui.weak("No files.");
+                            }
+                            for (file, size) in files {
+                                ui.horizontal(|ui| {
+                                    let name = file.strip_prefix(dir).unwrap_or(&file);
+                                    ui.label(format!(
+                                        "{}  {}",
+                                        name.display(),
+                                        storage::format_size(size)
+                                    ));
+                                    if ui.small_button("Open").clicked() {
+                                        file_action = Some(FileAction::Open(file.clone()));
+                                    }
+                                    if ui.small_button("Reveal").clicked() {
+                                        let parent = file.parent().unwrap_or(dir).to_path_buf();
+                                        file_action = Some(FileAction::Open(parent));
+                                    }
+                                    if ui.small_button("Delete").clicked() {
+                                        file_action = Some(FileAction::Delete(file.clone()));
+                                    }
+                                });
+                            }
+                        });
+                }
+            }
+            if let Some(index) = rerun {
+                self.rerun_with_environment(index);
+            }
+            let result = match file_action {
+                Some(FileAction::Open(_))
+                    if !self.capabilities.allows(Capability::ExternalProcesses) =>
+                {
+                    Err("Opening files is disabled in safe mode".to_string())
+                }
+                Some(FileAction::Open(path)) => outputs::open(&path)
+                    .map_err(|e| format!("Couldn't open {}: {}", path.display(), e)),
+                Some(FileAction::Delete(path)) => storage::Disk
+                    .remove(&path)
+                    .map_err(|e| format!("Couldn't delete {}: {}", path.display(), e)),
+                None => Ok(()),
+            };
+            if let Err(error) = result {
+                self.history_error = Some(error);
+            }
+        });
+    }
+
+    /// The newest run's output folder, kept out of storage cleanup.
+    fn latest_out_dir(&self) -> Vec<PathBuf> {
+        let running = self.active_run.as_ref().and_then(|run| run.out_dir.clone());
+        self.history
+            .iter()
+            .rev()
+            .find_map(|record| record.out_dir.clone())
+            .into_iter()
+            .chain(running)
+            .collect()
+    }
+
+    fn show_environment_dialogs(&mut self, ctx: &egui::Context) {
+        if let Some((dialog, index)) = &mut self.env_export_dialog {
+            if dialog.show(ctx).selected() {
+                if let (Some(path), Some(capture)) =
+                    (dialog.path(), &self.history[*index].environment)
+                {
+                    self.history_error = std::fs::write(path, capture.to_text())
+                        .err()
+                        .map(|e| format!("Failed to export environment: {}", e));
+                }
+            }
+        }
+        if let Some(dialog) = &mut self.env_import_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(path) = dialog.path() {
+                    match std::fs::read_to_string(path) {
+                        Ok(text) => {
+                            self.history_error = None;
+                            history::push(
+                                &mut self.history,
+                                history::RunRecord {
+                                    started: history::now(),
+                                    header: format!("imported {}", path.display()),
+                                    output: String::new(),
+                                    environment: Some(environment::EnvironmentCapture::from_text(
+                                        &text,
+                                    )),
+                                    provenance: None,
+                                    out_dir: None,
+                                },
+                            );
+                        }
+                        Err(e) => {
+                            self.history_error =
+                                Some(format!("Failed to import environment: {}", e))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the substituted program as a single `.rs` file, or as a zipped
+    /// cargo project when the run configuration needs one.
+    fn show_export_dialog(&mut self, ctx: &egui::Context) {
+        let Some((dialog, as_project)) = &mut self.export_dialog else {
+            return;
+        };
+        if !dialog.show(ctx).selected() {
+            return;
+        }
+        let Some(path) = dialog.path() else {
+            return;
+        };
+        let result = if *as_project {
+            let name = export::package_name(self.opened_file.as_deref());
+            export::write_zip(
+                path,
+                &export::cargo_project(
+                    &name,
+                    &self.code,
+                    &self.build_config,
+                    &self.run_inputs,
+                    self.script
+                        .as_ref()
+                        .map_or(&[][..], |script| &script.dependencies),
+                    &self
+                        .variables
+                        .iter()
+                        .filter_map(|var| {
+                            let literal = format_literal(&var.value, "")?;
+                            Some((var.name.clone(), var.var_type.clone(), literal))
+                        })
+                        .collect::<Vec<_>>(),
+                    self.history.last().and_then(provenance::Provenance::block),
+                ),
+            )
+        } else {
+            std::fs::write(path, &self.code)
+        };
+        self.export_message = Some(match result {
+            Ok(()) => format!("Exported to {}", path.display()),
+            Err(e) => format!("Failed to export: {}", e),
+        });
+    }
+
+    /// Diagnostics of the line whose gutter marker was clicked, with their
+    /// quick actions and the `rustc --explain` text of a chosen error code.
+    fn show_diagnostics_window(
+        &mut self,
+        ctx: &egui::Context,
+        diagnostics: &[checker::Diagnostic],
+    ) {
+        let Some(line) = self.diagnostic_line else {
+            return;
+        };
+        let on_line = diagnostics::on_line(&self.code, diagnostics, line);
+        let entries = diagnostics::popup_entries(&self.code, &on_line);
+        // Spans from an older buffer would edit the wrong text.
+        let current = self.checker.is_current(&script::compile_source(&self.code));
+        let mut open = !entries.is_empty();
+        let mut chosen = None;
+        egui::Window::new(format!("Line {}", line))
+            .id(egui::Id::new("diagnostics_window"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                for entry in &entries {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(entry.severity.icon());
+                        if let Some(code) = entry.code.as_deref() {
+                            if diagnostics::is_error_code(code) {
+                                if ui.link(code).clicked() {
+                                    self.explain_code = Some(code.to_string());
+                                }
+                            } else {
+                                ui.monospace(code);
+                            }
+                        }
+                        ui.label(&entry.message);
+                    });
+                    for (severity, message) in &entry.children {
+                        ui.horizontal_wrapped(|ui| {
+                            ui.add_space(16.0);
+                            ui.weak(format!("{} {}", severity.icon(), message));
+                        });
+                    }
+                    ui.horizontal_wrapped(|ui| {
+                        for action in &entry.actions {
+                            if ui
+                                .add_enabled(current, egui::Button::new(action.label()))
+                                .on_disabled_hover_text("Wait for the check to finish")
+                                .clicked()
+                            {
+                                chosen = Some(action.clone());
+                            }
+                        }
+                    });
+                    ui.separator();
+                }
+
+                if let Some(code) = self.explain_code.clone() {
+                    ui.horizontal(|ui| {
+                        ui.strong(format!("rustc --explain {}", code));
+                        if ui.small_button("Close").clicked() {
+                            self.explain_code = None;
+                        }
+                    });
+                    egui::ScrollArea::vertical()
+                        .id_source("explanation_scroll_area")
+                        .max_height(240.0)
+                        .show(ui, |ui| match self.explanations.get(ctx, &code) {
+                            Some(text) => {
+                                ui.label(egui::RichText::new(text).monospace());
+                            }
+                            None => {
+                                ui.spinner();
+                            }
+                        });
+                }
+            });
+
+        if let Some(action) = chosen {
+            push_undo_point(ctx, &self.code);
+            self.code = action.apply(&self.code);
+        }
+        if !open {
+            self.diagnostic_line = None;
+            self.explain_code = None;
+        }
+    }
+
+    /// Ranks the panel variables by how much the runs' output followed them.
+    fn show_sensitivity_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_sensitivity;
+        egui::Window::new("Sensitivity")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Output value after");
+                    ui.text_edit_singleline(&mut self.sensitivity_label)
+                        .on_hover_text("Leave empty to use the last number each run printed");
+                });
+                let samples: Vec<analysis::Sample> = self
+                    .history
+                    .iter()
+                    .filter_map(|record| {
+                        let provenance = record.provenance.as_ref()?;
+                        let inputs = provenance
+                            .overrides
+                            .iter()
+                            .filter_map(|(name, literal)| {
+                                let value = match literal_value(literal).1 {
+                                    VariableValue::Int(value) => value as f64,
+                                    VariableValue::UInt(value) => value as f64,
+                                    VariableValue::Float(value) => value,
+                                    VariableValue::Bool(value) => value as u8 as f64,
+                                    _ => return None,
+                                };
+                                Some((name.clone(), value))
+                            })
+                            .collect();
+                        let output = (provenance.exit_code == Some(0))
+                            .then(|| {
+                                analysis::parse_output(&record.output, &self.sensitivity_label)
+                            })
+                            .flatten();
+                        Some(analysis::Sample { inputs, output })
+                    })
+                    .collect();
+                let report = analysis::sensitivity(&samples);
+
+                let format =
+                    |value: Option<f64>| value.map_or("–".to_string(), |v| format!("{:.3}", v));
+                egui::Grid::new("sensitivity_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Variable");
+                        ui.strong("Correlation");
+                        ui.strong("Alone");
+                        ui.strong("Runs");
+                        ui.strong("Output by value");
+                        ui.end_row();
+                        for stats in &report.variables {
+                            ui.label(&stats.name);
+                            if stats.is_constant() {
+                                ui.weak("constant");
+                            } else {
+                                ui.label(format(stats.correlation));
+                            }
+                            ui.label(format(stats.isolated_effect))
+                                .on_hover_text(format!(
+                                    "Largest output change with every other variable fixed, \
+                                 over {} group(s) of runs",
+                                    stats.isolated_groups
+                                ));
+                            ui.label(stats.runs.to_string());
+                            sparkline(ui, &stats.points);
+                            ui.end_row();
+                        }
+                    });
+                if report.skipped > 0 {
+                    ui.weak(format!(
+                        "{} run(s) left out: they failed or printed no number.",
+                        report.skipped
+                    ));
+                }
+                for limitation in &report.limitations {
+                    ui.colored_label(ui.visuals().warn_fg_color, limitation);
+                }
+                self.nondeterminism_warning(ui);
+            });
+        self.show_sensitivity = open;
+    }
+
+    fn start_scan(&mut self, ctx: &egui::Context) {
+        let Some(root) = self.scan_root.clone() else {
+            return;
+        };
+        self.project_scan.start(
+            ctx,
+            root,
+            project::Limits::default(),
+            self.scan_include.clone(),
+            scanned_variables,
+        );
+    }
+
+    /// The folder picker of "Scan folder…" and the window listing the
+    /// variables it found.
+    fn show_scan_window(&mut self, ctx: &egui::Context) {
+        if let Some(dialog) = &mut self.scan_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(root) = dialog.path() {
+                    self.scan_root = Some(root.to_path_buf());
+                    self.scan_include.clear();
+                    self.start_scan(ctx);
+                }
+            }
+        }
+        let Some(root) = self.scan_root.clone() else {
+            return;
+        };
+        let mut open = true;
+        let mut opened = None;
+        let mut include = None;
+        let mut rescan = false;
+        egui::Window::new("Folder variables")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(root.display().to_string());
+                if self.project_scan.is_scanning() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Scanning…");
+                        if ui.button("Cancel").clicked() {
+                            self.project_scan.cancel();
+                        }
+                    });
+                    return;
+                }
+                let (walk, files) = self.project_scan.results();
+                let Some(walk) = walk else {
+                    if ui.button("Scan").clicked() {
+                        rescan = true;
+                    }
+                    return;
+                };
+                if walk.cancelled {
+                    ui.label("Scan cancelled.");
+                }
+                if walk.truncated {
+                    ui.label(format!(
+                        "Stopped after {} files; pick a smaller folder to see the rest.",
+                        walk.files.len()
+                    ));
+                }
+                let skipped = walk.ignored.len() + walk.too_large.len();
+                if skipped > 0 {
+                    egui::CollapsingHeader::new(format!(
+                        "{} files skipped ({} ignored, {} too large)",
+                        skipped,
+                        walk.ignored.len(),
+                        walk.too_large.len()
+                    ))
+                    .id_source("scan_skipped")
+                    .show(ui, |ui| {
+                        egui::ScrollArea::vertical()
+                            .id_source("scan_skipped_list")
+                            .max_height(150.0)
+                            .show(ui, |ui| {
+                                for path in &walk.ignored {
+                                    ui.horizontal(|ui| {
+                                        ui.label(relative_to(path, &root));
+                                        if ui.small_button("Include").clicked() {
+                                            include = Some(path.clone());
+                                        }
+                                    });
+                                }
+                                for path in &walk.too_large {
+                                    ui.label(format!("{} (too large)", relative_to(path, &root)));
+                                }
+                            });
+                    });
+                }
+                ui.separator();
+                if files.is_empty() {
+                    ui.label("No variables found.");
+                }
+                egui::ScrollArea::vertical()
+                    .id_source("scan_files")
+                    .show(ui, |ui| {
+                        for file in &files {
+                            egui::CollapsingHeader::new(relative_to(&file.path, &root))
+                                .id_source(&file.path)
+                                .show(ui, |ui| {
+                                    if ui.small_button("Open").clicked() {
+                                        opened = Some(file.path.clone());
+                                    }
+                                    for (name, var_type, value) in &file.variables {
+                                        ui.monospace(format!("{}: {} = {}", name, var_type, value));
+                                    }
+                                });
+                        }
+                    });
+                if ui.button("Rescan").clicked() {
+                    rescan = true;
+                }
+            });
+        if let Some(path) = include {
+            if let Ok(relative) = path.strip_prefix(&root) {
+                self.scan_include.push(relative.to_path_buf());
+            }
+            rescan = true;
+        }
+        if rescan {
+            self.start_scan(ctx);
+        }
+        if let Some(path) = opened {
+            match std::fs::read_to_string(&path) {
+                Ok(content) => {
+                    self.opened_file = Some(path);
+                    self.code = content;
+                    self.variables.clear();
+                    self.last_parsed.clear();
+                    self.parse_variables();
+                }
+                Err(e) => {
+                    self.export_message = Some(format!("Failed to read {}: {}", path.display(), e));
+                }
+            }
+        }
+        if !open {
+            self.project_scan.cancel();
+            self.scan_root = None;
+        }
+    }
+
+    fn new_bug_report(&self) -> BugReport {
+        let toolchain = if !self.toolchain.is_empty() {
+            self.toolchain.clone()
+        } else if self.capabilities.allows(Capability::ExternalProcesses) {
+            detect_toolchain()
+        } else {
+            Vec::new()
+        };
+        let run_env: Vec<(String, String)> = self
+            .run_inputs
+            .env
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+            .collect();
+        let vetoed: Vec<&str> = self
+            .capabilities
+            .vetoed()
+            .iter()
+            .map(|c| c.label())
+            .collect();
+        let settings = vec![
+            ("theme", self.theme_name.clone()),
+            ("syntax_folder", self.syntax_folder.clone()),
+            ("profile", self.build_config.profile.label().to_string()),
+            ("flags", self.build_config.flags.clone()),
+            (
+                "run_env",
+                run_env
+                    .iter()
+                    .map(|(key, _)| key.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            ("output_location", self.output_location.label().to_string()),
+            ("format_rewrites", self.format_rewrites.to_string()),
+            ("spell_check", self.spell_check.to_string()),
+            ("record_environment", self.record_environment.to_string()),
+            ("redaction_patterns", self.redaction_patterns.clone()),
+            ("snippets", self.snippets.len().to_string()),
+            ("safe_mode", vetoed.join(", ")),
+        ];
+        let patterns = environment::parse_redactions(&self.redaction_patterns);
+        let secrets = bugreport::secret_values(std::env::vars(), &patterns)
+            .into_iter()
+            .chain(run_env.into_iter().map(|(_, value)| value));
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from);
+        BugReport {
+            sources: bugreport::Sources {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                os: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+                toolchain: toolchain
+                    .into_iter()
+                    .map(|(tool, version)| (tool.to_string(), version))
+                    .collect(),
+                settings: settings
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value))
+                    .collect(),
+                log: debug_log::recent(),
+                crash_log: debug_log::last_crash(),
+                buffer: (!self.code.is_empty()).then(|| self.code.clone()),
+                provenance: self.history.last().and_then(provenance::Provenance::block),
+            },
+            redactor: bugreport::Redactor::new(home.as_deref(), secrets),
+            include_code: false,
+            dialog: None,
+            message: None,
+        }
+    }
+
+    /// Previews exactly what the bundle will contain, then writes it where
+    /// the user picks.
+    fn show_bug_report_window(&mut self, ctx: &egui::Context) {
+        let Some(report) = &mut self.bug_report else {
+            return;
+        };
+        let mut open = true;
+        let entries = bugreport::collect(&report.sources, report.include_code, &report.redactor);
+        egui::Window::new("Bug report")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label("Paths in your home folder and secret environment values are redacted.");
+                ui.checkbox(
+                    &mut report.include_code,
+                    "Include the current buffer and the last run's provenance",
+                );
+                ui.separator();
+                ui.label("The bundle will contain:");
+                egui::ScrollArea::vertical()
+                    .id_source("bug_report_entries")
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for entry in &entries {
+                            egui::CollapsingHeader::new(format!(
+                                "{} ({} bytes)",
+                                entry.name,
+                                entry.contents.len()
+                            ))
+                            .id_source(&entry.name)
+                            .show(ui, |ui| {
+                                ui.monospace(&entry.contents);
+                            });
+                        }
+                    });
+                ui.separator();
+                if ui.button("Save…").clicked() {
+                    let mut dialog =
+                        FileDialog::save_file(None).default_filename("crowbar-bug-report.zip");
+                    dialog.open();
+                    report.dialog = Some(dialog);
+                }
+                if let Some(message) = &report.message {
+                    ui.label(message);
+                }
+            });
+        if let Some(dialog) = &mut report.dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(path) = dialog.path() {
+                    report.message = Some(match bugreport::write(path, &entries) {
+                        Ok(()) => format!("Saved to {}", path.display()),
+                        Err(e) => format!("Failed to write {}: {}", path.display(), e),
+                    });
+                }
+                report.dialog = None;
+            }
+        }
+        if !open {
+            self.bug_report = None;
+        }
+    }
+
+    fn show_processes_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_processes;
+        egui::Window::new("Processes")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                let mut killed = Vec::new();
+                let running = processes::running();
+                if running.is_empty() {
+                    ui.label("No running processes.");
+                } else if let Some(entry) = process_table(ui, "running_processes", &running) {
+                    killed.push(entry);
+                }
+
+                if !self.leftover_processes.is_empty() {
+                    ui.separator();
+                    ui.label("Left over from a previous session:");
+                    if let Some(entry) =
+                        process_table(ui, "leftover_processes", &self.leftover_processes)
+                    {
+                        killed.push(entry);
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Kill all").clicked() {
+                            killed.extend(self.leftover_processes.iter().cloned());
+                        }
+                        if ui.button("Dismiss").clicked() {
+                            self.leftover_processes.clear();
+                            processes::forget_leftovers();
+                        }
+                    });
+                }
+
+                // Each is checked to still be what was listed right before
+                // it's killed, since the pid may have been reused since.
+                for entry in killed {
+                    if let Err(e) = processes::kill(&entry) {
+                        self.process_error = Some(e);
+                    }
+                    self.leftover_processes.retain(|e| e.pid != entry.pid);
+                    if self.leftover_processes.is_empty() {
+                        processes::forget_leftovers();
+                    }
+                }
+                if let Some(error) = &self.process_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+            });
+        self.show_processes = open;
+    }
+
+    fn show_run_configuration(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing("Run configuration", |ui| {
+            egui::Grid::new("run_configuration")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Profile");
+                    ui.horizontal(|ui| {
+                        for profile in [Profile::Debug, Profile::Release] {
+                            ui.radio_value(
+                                &mut self.build_config.profile,
+                                profile,
+                                profile.label(),
+                            );
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("rustc flags");
+                    ui.text_edit_singleline(&mut self.build_config.flags);
+                    ui.end_row();
+
+                    ui.label("Arguments");
+                    ui.text_edit_singleline(&mut self.run_inputs.args);
+                    ui.end_row();
+
+                    ui.label("Environment")
+                        .on_hover_text("One KEY=VALUE pair per line");
+                    ui.add(egui::TextEdit::multiline(&mut self.run_inputs.env).desired_rows(2));
+                    ui.end_row();
+
+                    ui.label("Stdin");
+                    ui.add(egui::TextEdit::multiline(&mut self.run_inputs.stdin).desired_rows(2));
+                    ui.end_row();
+
+                    ui.label("Output folders").on_hover_text(format!(
+                        "Each run gets a new folder in {}, passed to the program as {}",
+                        outputs::FOLDER,
+                        runner::OUT_DIR_VAR
+                    ));
+                    ui.horizontal(|ui| {
+                        for location in [outputs::Location::WorkingDir, outputs::Location::Temp] {
+                            ui.radio_value(&mut self.output_location, location, location.label());
+                        }
+                    });
+                    ui.end_row();
+                });
+            if ui
+                .add_enabled(!self.is_running(), egui::Button::new("Clear build cache"))
+                .clicked()
+            {
+                self.runner.clear_cache();
+            }
+        });
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_run();
+        self.poll_sweep(ctx);
+        // Top panel for the header and buttons
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            ui.heading("Crowbar");
+            ui.separator();
+            ui.horizontal(|ui| {
+                let load = ui.button("Load File");
+                self.tour_anchor("load_button", load.rect);
+                if load.clicked() {
+                    let mut dialog = FileDialog::open_file(self.opened_file.clone());
+                    dialog.open();
+                    self.open_file_dialog = Some(dialog);
+                }
+                let run = ui.add_enabled(!self.is_running(), egui::Button::new("Run Code"));
+                self.tour_anchor("run_button", run.rect);
+                if run.clicked() {
+                    self.update_code_with_variables();
+                    self.run_code();
+                }
+                if ui.button("Export as…").clicked() {
+                    self.update_code_with_variables();
+                    let dependencies = self
+                        .script
+                        .as_ref()
+                        .map_or(&[][..], |script| &script.dependencies);
+                    let as_project =
+                        export::needs_project(&self.build_config, &self.run_inputs, dependencies);
+                    let name = export::package_name(self.opened_file.as_deref());
+                    let mut dialog = FileDialog::save_file(None).default_filename(if as_project {
+                        format!("{}.zip", name)
+                    } else {
+                        format!("{}.rs", name)
+                    });
+                    dialog.open();
+                    self.export_dialog = Some((dialog, as_project));
+                }
+                if ui.button("Compare with…").clicked() {
+                    let mut dialog = FileDialog::open_file(self.opened_file.clone());
+                    dialog.open();
+                    self.compare_dialog = Some(dialog);
+                }
+                if ui.button("Scan folder…").clicked() {
+                    let mut dialog = FileDialog::select_folder(self.scan_root.clone());
+                    dialog.open();
+                    self.scan_dialog = Some(dialog);
+                }
+                if ui.button("Settings").clicked() {
+                    self.show_settings = !self.show_settings;
+                }
+                if ui.button("Processes").clicked() {
+                    self.show_processes = !self.show_processes;
+                }
+                ui.menu_button("Help", |ui| {
+                    if ui.button("Create bug report bundle…").clicked() {
+                        self.bug_report = Some(self.new_bug_report());
+                        ui.close_menu();
+                    }
+                });
+            });
+            ui.add_space(10.0);
+        });
+        self.show_safe_mode_banner(ctx);
+
+        // Handle file dialog outside the main panels to ensure it works
+        if let Some(dialog) = &mut self.open_file_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(file) = dialog.path() {
+                    debug_log::record(format!("opened {}", file.display()));
+                    self.opened_file = Some(file.to_path_buf());
+                    if let Ok(content) = std::fs::read_to_string(file) {
+                        self.code = content;
+                        self.variables.clear();
+                        self.last_parsed.clear();
+                        self.parse_variables();
+                        self.notify_tour(tour::Event::FileLoaded);
+                    }
+                }
+            }
+        }
+
+        self.show_settings_window(ctx);
+        self.show_environment_dialogs(ctx);
+        self.show_export_dialog(ctx);
+        self.show_output_save_dialog(ctx);
+        self.show_output_compare_dialog(ctx);
+        self.show_compare_dialog(ctx);
+        self.show_comparison_windows(ctx);
+        self.show_scan_window(ctx);
+        self.show_processes_window(ctx);
+        self.show_symbol_search(ctx);
+        self.show_bug_report_window(ctx);
+        self.show_sensitivity_window(ctx);
+        self.show_determinism_window(ctx);
+        self.show_sweep_window(ctx);
+        self.request_check(ctx);
+        self.apply_live_edits(ctx);
+        self.reparse_if_changed(ctx);
+        self.sync_snippet_session();
+        if self.storage_stale && !self.storage.is_busy() {
+            self.storage_stale = false;
+            self.storage
+                .enforce(ctx, &self.storage_caps, self.latest_out_dir());
+        }
+        let colors = self.editor_colors();
+        let diagnostics = self.checker.diagnostics();
+        self.show_diagnostics_window(ctx, &diagnostics);
+
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            let (syntax, syntax_warning) =
+                syntax::find_syntax(&self.syntax_set, file_extension(&self.opened_file));
+            let syntax_name = syntax.map(|syntax| syntax.name.clone());
+            ui.horizontal(|ui| {
+                match syntax_name {
+                    Some(name) => ui.label(name),
+                    None => ui.label("No highlighting"),
+                };
+                if let Some(script) = &self.script {
+                    ui.separator();
+                    let mut details = match &script.shebang {
+                        Some(shebang) => format!("{}\n", shebang),
+                        None => String::new(),
+                    };
+                    for (name, spec) in &script.dependencies {
+                        details.push_str(&format!("{} = {}\n", name, spec));
+                    }
+                    details.push_str(
+                        "\nRuns use plain rustc, so dependencies only go into exported \
+                         cargo projects.",
+                    );
+                    ui.colored_label(colors.annotation, "rust-script file detected")
+                        .on_hover_text(details);
+                }
+                for warning in syntax_warning.iter().chain(&self.syntax_load_error) {
+                    ui.separator();
+                    ui.colored_label(colors.annotation, warning);
+                }
+                if self.is_nondeterministic() {
+                    ui.separator();
+                    let flag = ui
+                        .add(
+                            egui::Label::new(
+                                egui::RichText::new("⚠ Nondeterministic").color(colors.error),
+                            )
+                            .sense(egui::Sense::click()),
+                        )
+                        .on_hover_text(
+                            "Identical runs printed different output. Click for details.",
+                        );
+                    if flag.clicked() {
+                        self.show_determinism = true;
+                    }
+                }
+                let errors = diagnostics
+                    .iter()
+                    .filter(|d| d.severity == checker::Severity::Error)
+                    .count();
+                ui.separator();
+                if self.checker.is_checking() {
+                    ui.spinner();
+                }
+                ui.label(format!(
+                    "{} errors, {} warnings",
+                    errors,
+                    diagnostics.len() - errors
+                ))
+                .on_hover_text(
+                    diagnostics
+                        .iter()
+                        .map(|d| d.message.as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                );
+                ui.separator();
+                self.show_indent_menu(ui);
+                if let Some(message) = &self.export_message {
+                    ui.separator();
+                    ui.label(message);
+                }
+            });
+        });
+
+        // Where the run HUD goes, found while laying out the editor
+        let mut hud_area = None;
+        let mut caret_line = None;
+
+        // Variables down the right, at a width that can be dragged
+        egui::SidePanel::right("variables_panel")
+            .resizable(true)
+            .default_width(400.0)
+            .width_range(250.0..=800.0)
+            .show(ctx, |ui| {
+                let panel = egui::ScrollArea::vertical()
+                    .id_source("variables_scroll_area")
+                    .show(ui, |ui| {
+                        for warning in &self.marker_warnings {
+                            ui.colored_label(colors.annotation, warning);
+                        }
+                        for name in &self.unwritten {
+                            ui.colored_label(
+                                colors.error,
+                                format!(
+                                    "Couldn't locate the declaration of `{}`, so its value \
+                                     wasn't written into the code",
+                                    name
+                                ),
+                            );
+                        }
+                        if let Some((offset, message)) = self.parse_error.clone() {
+                            let line = lexer::line_of(&self.code, offset);
+                            let error = ui
+                                .add(
+                                    egui::Label::new(
+                                        egui::RichText::new(format!(
+                                            "Parse error at line {}: {}",
+                                            line, message
+                                        ))
+                                        .color(colors.error),
+                                    )
+                                    .sense(egui::Sense::click()),
+                                )
+                                .on_hover_text("Go to the error");
+                            if error.clicked() {
+                                self.jump_to(ui.ctx(), offset);
+                            }
+                            ui.colored_label(
+                                colors.annotation,
+                                "Until it parses, variables and functions are \
+                                 approximate and can't be edited.",
+                            );
+                        }
+                        self.show_variables_panel(ui);
+                        self.show_outline(ui);
+                    });
+                self.tour_anchor("variables_panel", panel.inner_rect);
+            });
+
+        // Output under the editor, at a height that can be dragged
+        egui::TopBottomPanel::bottom("output_panel")
+            .resizable(true)
+            .default_height(200.0)
+            .height_range(80.0..=600.0)
+            .show(ctx, |ui| {
+                self.show_run_configuration(ui);
+
+                // Output section
+                let output_area = egui::ScrollArea::vertical()
+                    .id_source("output_scroll_area")
+                    .show(ui, |ui| {
+                        let reveal = std::mem::take(&mut self.reveal_output);
+                        let section = egui::CollapsingHeader::new("Output")
+                            .open(reveal.then_some(true))
+                            .show(ui, |ui| {
+                                ui.with_layout(
+                                    egui::Layout::top_down(egui::Align::Min).with_main_wrap(false),
+                                    |ui| {
+                                        self.show_output_toolbar(ui);
+                                        self.show_import_suggestions(ui);
+                                        if self.build_failed {
+                                            ui.colored_label(colors.error, &self.output);
+                                        } else {
+                                            ui.label(&self.output);
+                                        }
+                                    },
+                                );
+                            });
+                        if reveal {
+                            section.header_response.scroll_to_me(Some(egui::Align::TOP));
+                        }
+                        self.show_history(ui);
+                    });
+                self.tour_anchor("output", output_area.inner_rect);
+            });
+
+        // The editor takes what's left
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(path) = &self.opened_file {
+                ui.label(format!("Current File: {:?}", path.display()));
+            }
+
+            // Scroll area for the code editor and line numbers
+            let editor_area = egui::ScrollArea::vertical()
+                .id_source("code_scroll_area")
+                .show(ui, |ui| {
+                    ui.visuals_mut().extreme_bg_color = colors.background;
+                    ui.visuals_mut().selection.bg_fill = colors.selection;
+                    ui.horizontal(|ui| {
+                        // Numbers are painted once the code is laid out
+                        let (gutter, _) = ui.allocate_exact_size(
+                            egui::vec2(GUTTER_WIDTH, 0.0),
+                            egui::Sense::hover(),
+                        );
+
+                        // Tab expands snippets and moves through their
+                        // placeholders before anything else
+                        if ui.memory(|m| m.has_focus(code_editor_id()))
+                            && ui.input(|i| i.key_pressed(egui::Key::Tab) && i.modifiers.is_none())
+                            && self.snippet_tab(ui.ctx())
+                        {
+                            ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab));
+                        }
+
+                        let (syntax, _) = syntax::find_syntax(
+                            &self.syntax_set,
+                            file_extension(&self.opened_file),
+                        );
+                        let indent_unit = self.indent_style().unit();
+                        let highlight_cache = &mut self.highlight_cache;
+                        let dictionary = self.spell_check.then_some(&self.dictionary);
+                        let mut layouter = |ui: &egui::Ui, string: &str, wrap_width: f32| {
+                            let highlight = highlight_cache.highlight(
+                                string,
+                                syntax,
+                                &self.syntax_set,
+                                &self.theme,
+                                dictionary,
+                            );
+                            let mut job = egui::text::LayoutJob::default();
+                            for (range, color) in &highlight.sections {
+                                job.append(
+                                    &string[range.clone()],
+                                    0.0,
+                                    egui::TextFormat {
+                                        color: *color,
+                                        ..Default::default()
+                                    },
+                                );
+                            }
+                            job.wrap.max_width = wrap_width;
+                            ui.fonts(|f| f.layout_job(job))
+                        };
+
+                        // Tab indents in the buffer's style; the editor itself
+                        // would always insert a tab character
+                        if ui.memory(|m| m.has_focus(code_editor_id())) {
+                            ui.input_mut(|i| {
+                                for event in &mut i.events {
+                                    if let egui::Event::Key {
+                                        key: egui::Key::Tab,
+                                        pressed: true,
+                                        modifiers,
+                                        ..
+                                    } = event
+                                    {
+                                        if modifiers.is_none() {
+                                            *event = egui::Event::Text(indent_unit.clone());
+                                        }
+                                    }
+                                }
+                            });
+                        }
+
+                        // Painted behind the editor once we know where it ended up
+                        let background = ui.painter().add(egui::Shape::Noop);
+                        let output = egui::TextEdit::multiline(&mut self.code)
+                            .id(code_editor_id())
+                            .font(egui::TextStyle::Monospace)
+                            .code_editor()
+                            .frame(false)
+                            .lock_focus(true)
+                            .desired_width(f32::INFINITY)
+                            .layouter(&mut layouter)
+                            .show(ui);
+
+                        let editor_rect = output.response.rect;
+                        let line_map = gutter::LineMap::new(
+                            output
+                                .galley
+                                .rows
+                                .iter()
+                                .map(|row| gutter::RowMetrics {
+                                    top: row.rect.top() + output.galley_pos.y,
+                                    bottom: row.rect.bottom() + output.galley_pos.y,
+                                    ends_with_newline: row.ends_with_newline,
+                                })
+                                .collect(),
+                        );
+                        let gutter =
+                            egui::Rect::from_x_y_ranges(gutter.x_range(), editor_rect.y_range());
+                        let mut shapes = vec![egui::Shape::rect_filled(
+                            editor_rect,
+                            2.0,
+                            colors.background,
+                        )];
+                        // The whole logical line, over every row it wraps onto
+                        if let Some(cursor_range) = output.cursor_range {
+                            let line = line_map.line_of_row(cursor_range.primary.rcursor.row);
+                            if let Some(y_range) = line_map.y_range(line) {
+                                let line = egui::Rect::from_x_y_ranges(
+                                    editor_rect.x_range(),
+                                    y_range.start..=y_range.end,
+                                );
+                                caret_line = Some(line);
+                                shapes.push(egui::Shape::rect_filled(
+                                    line,
+                                    0.0,
+                                    colors.current_line,
+                                ));
+                            }
+                        }
+                        // The line just jumped to, fading out
+                        if let Some((line, at)) = self.flash_line {
+                            let age = at.elapsed().as_secs_f32();
+                            match line_map.y_range(line) {
+                                Some(y_range) if age < FLASH_SECS => {
+                                    shapes.push(egui::Shape::rect_filled(
+                                        egui::Rect::from_x_y_ranges(
+                                            editor_rect.x_range(),
+                                            y_range.start..=y_range.end,
+                                        ),
+                                        0.0,
+                                        colors
+                                            .annotation
+                                            .gamma_multiply(0.4 * (1.0 - age / FLASH_SECS)),
+                                    ));
+                                    ui.ctx().request_repaint();
+                                }
+                                _ => self.flash_line = None,
+                            }
+                        }
+                        ui.painter().set(background, egui::Shape::Vec(shapes));
+
+                        // A number on the first row of each line, a hanging
+                        // marker on the rows it wraps onto
+                        let font = egui::TextStyle::Monospace.resolve(ui.style());
+                        for (y_range, label) in line_map.labels() {
+                            let text = match label {
+                                gutter::Label::Number(line) => line.to_string(),
+                                gutter::Label::Continuation => "↳".to_string(),
+                            };
+                            ui.painter().text(
+                                egui::pos2(
+                                    gutter.right() - GUTTER_MARKER_SPACE,
+                                    (y_range.start + y_range.end) / 2.0,
+                                ),
+                                egui::Align2::RIGHT_CENTER,
+                                text,
+                                font.clone(),
+                                colors.gutter,
+                            );
+                        }
+                        if let Some(line) = self.reveal_line.take() {
+                            if let Some(y_range) = line_map.y_range(line) {
+                                ui.scroll_to_rect(
+                                    egui::Rect::from_x_y_ranges(
+                                        editor_rect.x_range(),
+                                        y_range.start..=y_range.end,
+                                    ),
+                                    Some(egui::Align::Center),
+                                );
+                            }
+                        }
+
+                        // Dotted underlines below misspelled words
+                        let mut chars = 0;
+                        let mut counted = 0;
+                        for range in self.highlight_cache.misspelled() {
+                            chars += self.code[counted..range.start].chars().count();
+                            counted = range.start;
+                            let word_chars = self.code[range.clone()].chars().count();
+                            let start = output
+                                .galley
+                                .pos_from_ccursor(egui::text::CCursor::new(chars));
+                            let end = output
+                                .galley
+                                .pos_from_ccursor(egui::text::CCursor::new(chars + word_chars));
+                            let offset = output.galley_pos.to_vec2();
+                            ui.painter().extend(egui::Shape::dotted_line(
+                                &[
+                                    start.left_bottom() + offset,
+                                    egui::pos2(end.left(), start.bottom()) + offset,
+                                ],
+                                egui::Color32::from_rgb(80, 140, 255),
+                                3.0,
+                                0.8,
+                            ));
+                        }
+
+                        // Underline check diagnostics, skipping spans that no
+                        // longer fit the buffer after later edits
+                        let hover_offset = output.response.hover_pos().map(|pos| {
+                            let cursor = output.galley.cursor_from_pos(pos - output.galley_pos);
+                            byte_offset(&self.code, cursor.ccursor.index)
+                        });
+                        let mut hovered = Vec::new();
+                        for diagnostic in &diagnostics {
+                            let span = &diagnostic.span;
+                            if self.code.get(span.clone()).is_none() {
+                                continue;
+                            }
+                            let color = match diagnostic.severity {
+                                checker::Severity::Error => colors.error,
+                                _ => colors.annotation,
+                            };
+                            let start = output.galley.from_ccursor(egui::text::CCursor::new(
+                                self.code[..span.start].chars().count(),
+                            ));
+                            let end = output.galley.from_ccursor(egui::text::CCursor::new(
+                                self.code[..span.end].chars().count(),
+                            ));
+                            let start_rect = output.galley.pos_from_cursor(&start);
+                            // Multi-line spans are underlined to the end of their
+                            // first row
+                            let end_x = if end.rcursor.row == start.rcursor.row {
+                                output.galley.pos_from_cursor(&end).left()
+                            } else {
+                                output.galley.rows[start.rcursor.row].rect.right()
+                            };
+                            let offset = output.galley_pos.to_vec2();
+                            ui.painter().line_segment(
+                                [
+                                    start_rect.left_bottom() + offset,
+                                    egui::pos2(
+                                        end_x.max(start_rect.left() + 4.0),
+                                        start_rect.bottom(),
+                                    ) + offset,
+                                ],
+                                egui::Stroke::new(1.5, color),
+                            );
+                            if hover_offset
+                                .is_some_and(|offset| (span.start..=span.end).contains(&offset))
+                            {
+                                hovered.push(diagnostic.rendered.clone());
+                            }
+                        }
+                        if !hovered.is_empty() {
+                            output.response.clone().on_hover_ui(|ui| {
+                                ui.label(egui::RichText::new(hovered.join("\n")).monospace());
+                            });
+                        }
+
+                        // A marker in the gutter per line with diagnostics; hover
+                        // lists them, click opens them with quick actions
+                        let mut marked_lines: Vec<usize> = diagnostics
+                            .iter()
+                            .filter(|d| self.code.get(d.span.clone()).is_some())
+                            .map(|d| lexer::line_of(&self.code, d.span.start))
+                            .collect();
+                        marked_lines.sort_unstable();
+                        marked_lines.dedup();
+                        for line in marked_lines {
+                            let on_line = diagnostics::on_line(&self.code, &diagnostics, line);
+                            let entries = diagnostics::popup_entries(&self.code, &on_line);
+                            let Some(first) = entries.first() else {
+                                continue;
+                            };
+                            let Some(row) = line_map.first_row_y(line) else {
+                                continue;
+                            };
+                            let center =
+                                egui::pos2(gutter.right() - 6.0, (row.start + row.end) / 2.0);
+                            let color = match first.severity {
+                                checker::Severity::Error => colors.error,
+                                _ => colors.annotation,
+                            };
+                            ui.painter().circle_filled(center, 3.5, color);
+                            let marker = ui.interact(
+                                egui::Rect::from_center_size(center, egui::vec2(10.0, 10.0)),
+                                ui.id().with(("diagnostic_marker", line)),
+                                egui::Sense::click(),
+                            );
+                            if marker.clicked() {
+                                self.diagnostic_line = Some(line);
+                            }
+                            marker.on_hover_ui(|ui| {
+                                show_diagnostic_entries(ui, &entries);
+                                ui.weak("Click for quick actions.");
+                            });
+                        }
+
+                        if output.response.secondary_clicked() {
+                            let offset = output.response.interact_pointer_pos().map(|pos| {
+                                let cursor = output.galley.cursor_from_pos(pos - output.galley_pos);
+                                byte_offset(&self.code, cursor.ccursor.index)
+                            });
+                            self.spell_target = offset.and_then(|offset| {
+                                self.highlight_cache
+                                    .misspelled()
+                                    .iter()
+                                    .find(|range| (range.start..=range.end).contains(&offset))
+                                    .cloned()
+                            });
+                            self.extract_target =
+                                offset.filter(|offset| tunable::can_extract(&self.code, *offset));
+                        }
+                        if self.spell_target.is_some() {
+                            output.response.context_menu(|ui| {
+                                self.show_spelling_menu(ui);
+                            });
+                        } else if self.extract_target.is_some() {
+                            output.response.context_menu(|ui| {
+                                self.show_extract_menu(ui);
+                            });
+                        }
+
+                        // Triple-click selects the statement under the cursor; Ctrl+L
+                        // does too and then expands to the enclosing block and item.
+                        let expand = output.response.has_focus()
+                            && ui.input_mut(|i| {
+                                i.consume_key(egui::Modifiers::COMMAND, egui::Key::L)
+                            });
+                        if let Some(cursor_range) = output.cursor_range {
+                            let selection = if output.response.triple_clicked() {
+                                let cursor = cursor_range.primary.ccursor.index;
+                                Some(cursor..cursor)
+                            } else {
+                                expand.then(|| cursor_range.as_sorted_char_range())
+                            };
+                            if let Some(selection) = selection {
+                                let selection = byte_offset(&self.code, selection.start)
+                                    ..byte_offset(&self.code, selection.end);
+                                if let Some(next) =
+                                    structure::next_selection(&self.code, &selection)
+                                {
+                                    set_editor_selection(ui.ctx(), &self.code, next);
+                                }
+                            }
+                        }
+
+                        // Ctrl+wheel over a numeric literal nudges it
+                        let (steps, coarse) = ui.input(wheel_nudge_steps);
+                        let hover_pos = ui.input(|i| i.pointer.hover_pos());
+                        if let (true, Some(pos)) =
+                            (steps != 0 && output.response.hovered(), hover_pos)
+                        {
+                            let cursor = output.galley.cursor_from_pos(pos - output.galley_pos);
+                            let offset = byte_offset(&self.code, cursor.ccursor.index);
+                            // The cursor lands between characters, so also try the
+                            // character to its left.
+                            let offset = if nudge::numeric_literal_at(&self.code, offset).is_none()
+                            {
+                                self.code[..offset]
+                                    .char_indices()
+                                    .next_back()
+                                    .map_or(offset, |(offset, _)| offset)
+                            } else {
+                                offset
+                            };
+                            self.nudge_literal(ui.ctx(), offset, steps, coarse);
+                        }
+                    });
+                });
+
+            hud_area = Some(editor_area.inner_rect);
+        });
+
+        if let Some(editor) = hud_area {
+            self.show_run_hud(ctx, editor, caret_line);
+        }
+
+        // Last, so it sits above the panels it points at
+        self.show_tour(ctx);
+    }
+}
+
+fn onboarding_flag_path() -> PathBuf {
+    paths::config_dir().join("onboarding-done")
+}
+
+fn detect_toolchain() -> Vec<(&'static str, String)> {
+    ["rustc", "cargo"]
+        .into_iter()
+        .map(|tool| (tool, environment::tool_version(tool)))
+        .collect()
+}
+
+fn onboarding_tour() -> tour::Tour {
+    tour::Tour::new(vec![
+        tour::Step {
+            title: "Welcome to Crowbar",
+            text: "Crowbar compiles your code with the Rust toolchain on your PATH.",
+            anchor: None,
+            advance_on: None,
+        },
+        tour::Step {
+            title: "Open some code",
+            text: "Load one of your own files, or start with the bundled example.",
+            anchor: Some(tour::anchor_id("load_button")),
+            advance_on: Some(tour::Event::FileLoaded),
+        },
+        tour::Step {
+            title: "Tweak a value",
+            text:
+                "Drag a value in the variables panel. It's written into the code when you run it.",
+            anchor: Some(tour::anchor_id("variables_panel")),
+            advance_on: Some(tour::Event::ValueEdited),
+        },
+        tour::Step {
+            title: "Run it",
+            text: "Press Run Code to compile and run the program with your values.",
+            anchor: Some(tour::anchor_id("run_button")),
+            advance_on: Some(tour::Event::RunFinished),
+        },
+        tour::Step {
+            title: "See the output",
+            text: "The program's output shows up here, with earlier runs under History.",
+            anchor: Some(tour::anchor_id("output")),
+            advance_on: None,
+        },
+    ])
+}
+
+/// Lists processes with a Kill button per row, returning the pid to kill.
+fn process_table(
+    ui: &mut egui::Ui,
+    id: &str,
+    entries: &[processes::ProcessEntry],
+) -> Option<processes::ProcessEntry> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut killed = None;
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for entry in entries {
+            ui.label(entry.pid.to_string());
+            ui.label(entry.role.label());
+            ui.label(format!("{}s ago", now.saturating_sub(entry.started)));
+            ui.label(
+                entry
+                    .file
+                    .as_ref()
+                    .map(|f| f.display().to_string())
+                    .unwrap_or_default(),
+            )
+            .on_hover_text(entry.executable.display().to_string());
+            if ui.button("Kill").clicked() {
+                killed = Some(entry.clone());
+            }
+            ui.end_row();
+        }
+    });
+    killed
+}
+
+fn file_extension(path: &Option<PathBuf>) -> Option<&str> {
+    path.as_ref()
+        .and_then(|path| path.extension())
+        .and_then(|ext| ext.to_str())
+}
+
+fn parse_rust_code(code: &str) -> Result<SynFile, syn::Error> {
+    // Spans are converted to byte ranges right after parsing, so there's no
+    // need to keep every previously parsed source alive.
+    proc_macro2::extra::invalidate_current_thread_spans();
+    // syn drops a shebang before lexing, which would shift every span; a
+    // blanked one keeps them lined up with the buffer.
+    parse_file(&script::compile_source(code))
+}
+
+fn code_editor_id() -> egui::Id {
+    egui::Id::new("code_editor")
+}
+
+/// Makes the current state of the code editor an undo point, so a
+/// programmatic edit that follows can be undone with Ctrl+Z.
+fn push_undo_point(ctx: &egui::Context, code: &str) {
+    if let Some(mut state) = egui::TextEdit::load_state(ctx, code_editor_id()) {
+        let mut undoer = state.undoer();
+        let cursor = state.cursor.char_range().unwrap_or_default();
+        undoer.add_undo(&(cursor, code.to_owned()));
+        state.set_undoer(undoer);
+        state.store(ctx, code_editor_id());
+    }
+}
+
+enum FileAction {
+    Open(PathBuf),
+    Delete(PathBuf),
+}
+
+enum PanelAction {
+    Pin(usize),
+    Lock(usize),
+    Hide(panel::VariableKey),
+    Goto(usize),
+    /// Scroll the panel to a variable's row.
+    Reveal(usize),
+    Reset(usize),
+    /// Give a number a random value within its bounds.
+    Randomize(usize),
+    /// Open the sweep window on a number.
+    Sweep(usize),
+    /// Set a variable to a value pasted in.
+    Paste(usize, VariableValue),
+    Radix(panel::VariableKey, numeric::Radix),
+    /// Write a float to this many decimals, or as in the source for `None`.
+    Decimals(panel::VariableKey, Option<usize>),
+    /// Drag a number this much per pixel, or as annotated for `None`.
+    DragSpeed(usize, Option<f64>),
+    /// Keep a number to multiples of this, or as annotated for `None`.
+    Step(usize, Option<f64>),
+    Edited,
+}
+
+/// What the text being typed into a numeric field evaluates to, shown as
+/// a tooltip next to it while it has focus.
+/// A text field for a value that's only taken once what's typed parses.
+/// The text is kept while the field has focus, with the parse error shown
+/// beside it, and the response only reports a change for a valid value.
+fn parsed_field<T>(
+    ui: &mut egui::Ui,
+    draft_id: egui::Id,
+    size: [f32; 2],
+    value: &mut T,
+    show: impl Fn(&T) -> String,
+    parse: impl Fn(&str) -> Result<T, String>,
+    error_color: egui::Color32,
+) -> egui::Response {
+    let mut text = ui
+        .data(|data| data.get_temp::<String>(draft_id))
+        .unwrap_or_else(|| show(value));
+    let mut response = ui.add_sized(size, egui::TextEdit::singleline(&mut text));
+    let parsed = parse(&text);
+    if !response.has_focus() {
+        ui.data_mut(|data| data.remove::<String>(draft_id));
+    } else {
+        ui.data_mut(|data| data.insert_temp(draft_id, text));
+    }
+    match parsed {
+        Ok(parsed) => *value = parsed,
+        Err(e) => {
+            response.changed = false;
+            if response.has_focus() {
+                egui::show_tooltip_for(
+                    ui.ctx(),
+                    ui.layer_id(),
+                    response.id.with("preview"),
+                    &response.rect,
+                    |ui| {
+                        ui.colored_label(error_color, e);
+                    },
+                );
+            }
+        }
+    }
+    response
+}
+
+/// `text` if it's an initializer that parses for a binding of type `ty`,
+/// else the parser's complaint.
+fn parse_raw_initializer(text: &str, ty: &str) -> Result<String, String> {
+    let text = text.trim();
+    let complaint = |e: syn::Error| match e.to_string() {
+        message if message.contains("token stream") => {
+            "Unbalanced brackets or an unterminated literal".to_string()
+        }
+        message => message,
+    };
+    syn::parse_str::<syn::Expr>(text).map_err(complaint)?;
+    syn::parse_str::<syn::Stmt>(&format!("let _: {} = {};", ty, text)).map_err(complaint)?;
+    Ok(text.to_string())
+}
+
+/// An initializer the panel can't edit, on one line and truncated to
+/// `width`, with a button copying it. The whole `declaration` is its hover
+/// text.
+fn show_initializer(
+    ui: &mut egui::Ui,
+    text: &str,
+    declaration: &str,
+    width: f32,
+) -> egui::Response {
+    ui.horizontal(|ui| {
+        if ui
+            .small_button("📋")
+            .on_hover_text("Copy the initializer")
+            .clicked()
+        {
+            ui.output_mut(|output| output.copied_text = text.to_string());
+        }
+        let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        ui.set_max_width(width);
+        ui.add(egui::Label::new(egui::RichText::new(line).monospace().weak()).truncate())
+            .on_hover_text(egui::RichText::new(declaration).monospace())
+    })
+    .inner
+}
+
+/// A compact editor for one element of a list or tuple of type `ty`.
+fn edit_element(
+    ui: &mut egui::Ui,
+    item: &mut VariableValue,
+    ty: &str,
+    width: f32,
+) -> egui::Response {
+    match item {
+        VariableValue::Int(val) => {
+            let (min, max) = numeric::int_range(ty).unwrap_or((i64::MIN, i64::MAX));
+            ui.add(egui::DragValue::new(val).speed(1).range(min..=max))
+        }
+        VariableValue::UInt(val) if *val <= numeric::MAX_EXACT => {
+            let max = numeric::uint_max(ty).unwrap_or(u128::MAX);
+            let mut value = *val as f64;
+            let response = ui.add(
+                egui::DragValue::new(&mut value)
+                    .speed(1)
+                    .range(0.0..=max as f64)
+                    .fixed_decimals(0),
+            );
+            if response.changed() {
+                *val = (value.round() as u128).min(max);
+            }
+            response
+        }
+        VariableValue::UInt(val) => ui.label(egui::RichText::new(val.to_string()).monospace()),
+        VariableValue::Float(val) => ui.add(egui::DragValue::new(val).speed(0.1)),
+        VariableValue::Bool(val) => ui.checkbox(val, ""),
+        VariableValue::Str(val) => ui.add(egui::TextEdit::singleline(val).desired_width(width)),
+        VariableValue::Char(val) => ui.label(egui::RichText::new(format!("{:?}", val)).monospace()),
+        _ => ui.label("…"),
+    }
+}
+
+/// Whether `value`, of type `ty`, is three or four `u8` channels, or three
+/// or four `f32` or `f64` ones from 0 to 1, that a color picker can edit.
+fn is_color(value: &VariableValue, ty: &str) -> bool {
+    let (items, types) = match value {
+        VariableValue::List(items) => {
+            let element = list::element_type(ty).unwrap_or_default().to_string();
+            (items, vec![element; items.len()])
+        }
+        VariableValue::Tuple(items) => (items, tuple::element_types(ty).unwrap_or_default()),
+        _ => return false,
+    };
+    matches!(items.len(), 3 | 4)
+        && types.len() == items.len()
+        && (items
+            .iter()
+            .zip(&types)
+            .all(|(item, ty)| matches!(item, VariableValue::Int(_)) && ty == "u8")
+            || items.iter().zip(&types).all(|(item, ty)| {
+                matches!(item, VariableValue::Float(val) if (0.0..=1.0).contains(val))
+                    && matches!(ty.as_str(), "f32" | "f64")
+            }))
+}
+
+/// A color picker for the channels of a value `is_color` accepts, with
+/// alpha if there's a fourth. Float channels that didn't change keep
+/// their exact values rather than going through `f32`.
+fn edit_color(ui: &mut egui::Ui, items: &mut [VariableValue]) -> egui::Response {
+    let ints: Vec<u8> = items
+        .iter()
+        .filter_map(|item| match item {
+            VariableValue::Int(val) => u8::try_from(*val).ok(),
+            _ => None,
+        })
+        .collect();
+    if ints.len() == items.len() {
+        let mut rgba = [
+            ints[0],
+            ints[1],
+            ints[2],
+            ints.get(3).copied().unwrap_or(255),
+        ];
+        let response = if items.len() == 4 {
+            ui.color_edit_button_srgba_unmultiplied(&mut rgba)
+        } else {
+            let mut rgb = [rgba[0], rgba[1], rgba[2]];
+            let response = ui.color_edit_button_srgb(&mut rgb);
+            rgba[..3].copy_from_slice(&rgb);
+            response
+        };
+        for (item, channel) in items.iter_mut().zip(rgba) {
+            *item = VariableValue::Int(channel as i64);
+        }
+        return response;
+    }
+    let floats: Vec<f32> = items
+        .iter()
+        .map(|item| match item {
+            VariableValue::Float(val) => *val as f32,
+            _ => 0.0,
+        })
+        .collect();
+    let mut rgba = [
+        floats[0],
+        floats[1],
+        floats[2],
+        floats.get(3).copied().unwrap_or(1.0),
+    ];
+    let response = if items.len() == 4 {
+        ui.color_edit_button_rgba_unmultiplied(&mut rgba)
+    } else {
+        let mut rgb = [rgba[0], rgba[1], rgba[2]];
+        let response = ui.color_edit_button_rgb(&mut rgb);
+        rgba[..3].copy_from_slice(&rgb);
+        response
+    };
+    for ((item, before), channel) in items.iter_mut().zip(floats).zip(rgba) {
+        if channel != before {
+            *item = VariableValue::Float((channel as f64 * 10_000.0).round() / 10_000.0);
+        }
+    }
+    response
+}
+
+/// The elements of a tuple side by side, each labelled with its index.
+fn edit_tuple(
+    ui: &mut egui::Ui,
+    items: &mut [VariableValue],
+    element_types: &[String],
+    width: f32,
+) -> egui::Response {
+    let mut changed = false;
+    let element_width = width / items.len().max(1) as f32;
+    let mut response = ui
+        .horizontal(|ui| {
+            for (index, (item, ty)) in items.iter_mut().zip(element_types).enumerate() {
+                ui.label(egui::RichText::new(format!(".{}", index)).weak());
+                changed |= edit_element(ui, item, ty, element_width).changed();
+            }
+        })
+        .response;
+    if changed {
+        response.mark_changed();
+    }
+    response
+}
+
+/// An editor per field of a struct literal, labelled with the field's
+/// name. Fields set to anything but a literal are shown as written.
+fn edit_struct(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    fields: &mut [Variable],
+    width: f32,
+) -> egui::Response {
+    let mut changed = false;
+    let mut response = ui
+        .vertical(|ui| {
+            for field in fields.iter_mut() {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(format!("{}:", field.name)).monospace());
+                    let id = id.with(&field.name);
+                    let edited = match (&field.formula, &mut field.value) {
+                        (Some(expr), _) => ui.label(
+                            egui::RichText::new(quote::ToTokens::to_token_stream(expr).to_string())
+                                .monospace()
+                                .weak(),
+                        ),
+                        (
+                            None,
+                            VariableValue::Enum {
+                                variant, variants, ..
+                            },
+                        ) => edit_variant(ui, id, variant, variants, width - 64.0),
+                        (None, VariableValue::Optional { some, value }) => {
+                            let inner = optional::inner_type(&field.var_type).unwrap_or_default();
+                            edit_optional(ui, some, value, inner, width - 64.0)
+                        }
+                        (None, VariableValue::Struct { fields, .. }) => {
+                            edit_struct(ui, id, fields, width - 16.0)
+                        }
+                        (None, value) => edit_element(ui, value, &field.var_type, width - 64.0),
+                    };
+                    changed |= edited.changed();
+                });
+            }
+        })
+        .response;
+    if changed {
+        response.mark_changed();
+    }
+    response
+}
+
+/// A combo box choosing one of an enum's unit variants.
+fn edit_variant(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    variant: &mut String,
+    variants: &[String],
+    width: f32,
+) -> egui::Response {
+    let before = variant.clone();
+    let mut response = egui::ComboBox::from_id_source(id)
+        .width(width)
+        .selected_text(variant.as_str())
+        .show_ui(ui, |ui| {
+            for choice in variants {
+                ui.selectable_value(variant, choice.clone(), choice);
+            }
+        })
+        .response;
+    if *variant != before {
+        response.mark_changed();
+    }
+    response
+}
+
+/// A checkbox switching between `Some` and `None`, and the value's editor
+/// while it's `Some`.
+fn edit_optional(
+    ui: &mut egui::Ui,
+    some: &mut bool,
+    value: &mut VariableValue,
+    inner_type: &str,
+    width: f32,
+) -> egui::Response {
+    let mut changed = false;
+    let mut response = ui
+        .horizontal(|ui| {
+            changed |= ui
+                .checkbox(some, "Some")
+                .on_hover_text("Unchecked writes None")
+                .changed();
+            if *some {
+                changed |= edit_element(ui, value, inner_type, width - 64.0).changed();
+            }
+        })
+        .response;
+    if changed {
+        response.mark_changed();
+    }
+    response
+}
+
+/// An editor per element of a list, each with a button to remove it, and
+/// one to add an element at the end unless the length is `fixed`.
+fn edit_list(
+    ui: &mut egui::Ui,
+    items: &mut Vec<VariableValue>,
+    element_type: &str,
+    fixed: bool,
+    width: f32,
+) -> egui::Response {
+    let mut changed = false;
+    let mut remove = None;
+    let mut response = ui
+        .vertical(|ui| {
+            for (index, item) in items.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    changed |= edit_element(ui, item, element_type, width - 32.0).changed();
+                    if !fixed && ui.small_button("✖").on_hover_text("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                });
+            }
+            let added = items
+                .last()
+                .cloned()
+                .unwrap_or_else(|| default_value(element_type));
+            if !fixed
+                && !matches!(added, VariableValue::Unknown)
+                && ui
+                    .small_button("+")
+                    .on_hover_text("Add an element")
+                    .clicked()
+            {
+                items.push(added);
+                changed = true;
+            }
+        })
+        .response;
+    if let Some(index) = remove {
+        items.remove(index);
+        changed = true;
+    }
+    if changed {
+        response.mark_changed();
+    }
+    response
+}
+
+fn show_entry_preview(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    var_type: &str,
+    error_color: egui::Color32,
+) {
+    if !response.has_focus() {
+        return;
+    }
+    let Some(text) = ui.data(|data| data.get_temp::<String>(response.id)) else {
+        return;
+    };
+    let preview = match numeric::parse_entry(&text, var_type) {
+        Ok(VariableValue::Int(value)) => egui::RichText::new(format!("= {}", value)),
+        Ok(VariableValue::UInt(value)) => egui::RichText::new(format!("= {}", value)),
+        Ok(VariableValue::Float(value)) => egui::RichText::new(format!("= {:?}", value)),
+        Ok(_) => return,
+        Err(e) => egui::RichText::new(e).color(error_color),
+    };
+    egui::show_tooltip_for(
+        ui.ctx(),
+        ui.layer_id(),
+        response.id.with("preview"),
+        &response.rect,
+        |ui| {
+            ui.label(preview);
+        },
+    );
+}
+
+/// Each diagnostic with its level icon and code, followed by its notes.
+fn show_diagnostic_entries(ui: &mut egui::Ui, entries: &[diagnostics::PopupEntry]) {
+    for entry in entries {
+        let code = entry
+            .code
+            .as_deref()
+            .map_or(String::new(), |code| format!("[{}] ", code));
+        ui.label(format!(
+            "{} {}{}",
+            entry.severity.icon(),
+            code,
+            entry.message
+        ));
+        for (severity, message) in &entry.children {
+            ui.weak(format!("    {} {}", severity.icon(), message));
+        }
+    }
+}
+
+/// A tiny plot of `(value, output)` points, scaled to fit.
+fn sparkline(ui: &mut egui::Ui, points: &[(f64, f64)]) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(80.0, 18.0), egui::Sense::hover());
+    let bounds = |pick: fn(&(f64, f64)) -> f64| {
+        points
+            .iter()
+            .map(pick)
+            .fold((f64::MAX, f64::MIN), |(min, max), v| {
+                (min.min(v), max.max(v))
+            })
+    };
+    let ((min_x, max_x), (min_y, max_y)) = (bounds(|p| p.0), bounds(|p| p.1));
+    let scale = |v: f64, min: f64, max: f64| {
+        if max > min {
+            ((v - min) / (max - min)) as f32
+        } else {
+            0.5
+        }
+    };
+    let color = ui.visuals().selection.stroke.color;
+    for (x, y) in points {
+        let pos = egui::pos2(
+            rect.left() + scale(*x, min_x, max_x) * rect.width(),
+            rect.bottom() - scale(*y, min_y, max_y) * rect.height(),
+        );
+        ui.painter().circle_filled(pos, 1.5, color);
+    }
+}
+
+/// A clickable icon that stays out of the Tab order.
+/// Header, scroll lock and the two aligned panes of `comparison`.
+fn comparison_view(ui: &mut egui::Ui, id: &str, comparison: &mut compare::Comparison) {
+    let (changed, removed, added) = compare::summary(&comparison.rows);
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut comparison.locked, "Lock scrolling");
+        ui.separator();
+        if changed + removed + added == 0 {
+            ui.label("No differences");
+        } else {
+            ui.label(format!(
+                "{} changed, {} only left, {} only right",
+                changed, removed, added
+            ));
+        }
+    });
+    let offset = comparison.locked.then_some(comparison.scroll);
+    let mut offsets = [0.0; 2];
+    ui.columns(2, |columns| {
+        for (index, ui) in columns.iter_mut().enumerate() {
+            ui.strong(if index == 0 {
+                &comparison.left.title
+            } else {
+                &comparison.right.title
+            });
+            offsets[index] = comparison_pane(ui, (id, index), comparison, index == 0, offset);
+        }
+    });
+    // Whichever pane moved sets the offset both use next frame.
+    if let Some(moved) = offsets.into_iter().find(|y| *y != comparison.scroll) {
+        comparison.scroll = moved;
+        if comparison.locked {
+            ui.ctx().request_repaint();
+        }
+    }
+}
+
+/// One side of `comparison`, row by row with gaps and tints. Returns the
+/// vertical scroll offset.
+fn comparison_pane(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    comparison: &compare::Comparison,
+    left: bool,
+    offset: Option<f32>,
+) -> f32 {
+    let side = if left {
+        &comparison.left
+    } else {
+        &comparison.right
+    };
+    let other = if left {
+        &comparison.right
+    } else {
+        &comparison.left
+    };
+    let (line_tint, word_tint) = if left {
+        (
+            egui::Color32::from_rgba_unmultiplied(220, 60, 60, 36),
+            egui::Color32::from_rgba_unmultiplied(220, 60, 60, 90),
+        )
+    } else {
+        (
+            egui::Color32::from_rgba_unmultiplied(60, 180, 80, 36),
+            egui::Color32::from_rgba_unmultiplied(60, 180, 80, 90),
+        )
+    };
+    let gap_tint = egui::Color32::from_rgba_unmultiplied(128, 128, 128, 24);
+    let font = egui::TextStyle::Monospace.resolve(ui.style());
+    let text_color = ui.visuals().text_color();
+    let weak_color = ui.visuals().weak_text_color();
+    let row_height = ui.fonts(|f| f.row_height(&font));
+    let digits = side.lines.len().max(1).to_string().len();
+
+    ui.spacing_mut().item_spacing.y = 0.0;
+    let mut area = egui::ScrollArea::both()
+        .id_source(id)
+        .auto_shrink([false, false]);
+    if let Some(offset) = offset {
+        area = area.vertical_scroll_offset(offset);
+    }
+    let output = area.show_rows(ui, row_height, comparison.rows.len(), |ui, visible| {
+        for row in &comparison.rows[visible] {
+            let (line, other_line) = if left {
+                (row.left, row.right)
+            } else {
+                (row.right, row.left)
+            };
+            let mut job = egui::text::LayoutJob::default();
+            let number = line.map_or(String::new(), |line| (line + 1).to_string());
+            job.append(
+                &format!("{:>width$} ", number, width = digits),
+                0.0,
+                egui::TextFormat::simple(font.clone(), weak_color),
+            );
+            let tint = match (line, row.kind) {
+                (None, _) => gap_tint,
+                (Some(_), compare::RowKind::Same) => egui::Color32::TRANSPARENT,
+                (Some(_), _) => line_tint,
+            };
+            if let Some(line) = line {
+                let changed = match other_line.filter(|_| row.kind == compare::RowKind::Changed) {
+                    Some(other_line) => {
+                        let (left_words, right_words) = if left {
+                            compare::word_diff(side.line(line), other.line(other_line))
+                        } else {
+                            compare::word_diff(other.line(other_line), side.line(line))
+                        };
+                        if left {
+                            left_words
+                        } else {
+                            right_words
+                        }
+                    }
+                    None => Vec::new(),
+                };
+                append_compare_line(&mut job, side, line, &changed, &font, text_color, word_tint);
+            }
+            let galley = ui.fonts(|f| f.layout_job(job));
+            let width = galley.size().x.max(ui.available_width());
+            let (rect, _) =
+                ui.allocate_exact_size(egui::vec2(width, row_height), egui::Sense::hover());
+            ui.painter().rect_filled(rect, 0.0, tint);
+            ui.painter().galley(rect.min, galley, text_color);
+        }
+    });
+    output.state.offset.y
+}
+
+/// Appends line `line` of `side` in its syntax colors, with `changed` byte
+/// ranges (relative to the line) on a `word_tint` background.
+fn append_compare_line(
+    job: &mut egui::text::LayoutJob,
+    side: &compare::Side,
+    line: usize,
+    changed: &[Range<usize>],
+    font: &egui::FontId,
+    text_color: egui::Color32,
+    word_tint: egui::Color32,
+) {
+    let range = side.lines[line].clone();
+    let first = side
+        .sections
+        .partition_point(|(section, _)| section.end <= range.start);
+    let sections = side.sections[first..]
+        .iter()
+        .take_while(|(section, _)| section.start < range.end);
+
+    let mut cuts = vec![range.start, range.end];
+    for (section, _) in sections.clone() {
+        cuts.extend([section.start, section.end]);
+    }
+    for word in changed {
+        cuts.extend([range.start + word.start, range.start + word.end]);
+    }
+    cuts.retain(|cut| range.contains(cut) || *cut == range.end);
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    for pair in cuts.windows(2) {
+        let start = pair[0];
+        let color = sections
+            .clone()
+            .find(|(section, _)| section.contains(&start))
+            .map_or(text_color, |(_, color)| *color);
+        let background = if changed
+            .iter()
+            .any(|word| (range.start + word.start..range.start + word.end).contains(&start))
+        {
+            word_tint
+        } else {
+            egui::Color32::TRANSPARENT
+        };
+        job.append(
+            &side.text[start..pair[1]],
+            0.0,
+            egui::TextFormat {
+                font_id: font.clone(),
+                color,
+                background,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+fn icon_toggle(ui: &mut egui::Ui, icon: &str, active: bool, hover: &str) -> egui::Response {
+    let color = if active {
+        ui.visuals().strong_text_color()
+    } else {
+        ui.visuals().weak_text_color()
+    };
+    ui.add(
+        egui::Label::new(egui::RichText::new(icon).color(color)).sense(egui::Sense {
+            click: true,
+            drag: false,
+            focusable: false,
+        }),
+    )
+    .on_hover_text(hover)
+}
+
+/// Focuses the code editor with the cursor at byte `offset`.
+fn move_editor_cursor(ctx: &egui::Context, code: &str, offset: usize) {
+    set_editor_selection(ctx, code, offset..offset);
+}
+
+/// Focuses the code editor with the byte range `selection` selected.
+fn set_editor_selection(ctx: &egui::Context, code: &str, selection: Range<usize>) {
+    let mut state = egui::TextEdit::load_state(ctx, code_editor_id()).unwrap_or_default();
+    let char_index = |offset: usize| code[..offset.min(code.len())].chars().count();
+    state
+        .cursor
+        .set_char_range(Some(egui::text::CCursorRange::two(
+            egui::text::CCursor::new(char_index(selection.start)),
+            egui::text::CCursor::new(char_index(selection.end)),
+        )));
+    state.store(ctx, code_editor_id());
+    ctx.memory_mut(|m| m.request_focus(code_editor_id()));
+}
+
+/// Moves the editor's cursor and selection over the change from `before`
+/// to `after`, so they stay on the same text, without taking focus.
+fn keep_editor_cursor(ctx: &egui::Context, before: &str, after: &str) {
+    let Some(mut state) = egui::TextEdit::load_state(ctx, code_editor_id()) else {
+        return;
+    };
+    let Some(range) = state.cursor.char_range() else {
+        return;
+    };
+    let prefix = before
+        .char_indices()
+        .zip(after.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(before.len().min(after.len()), |((at, _), _)| at);
+    let suffix = before[prefix..]
+        .chars()
+        .rev()
+        .zip(after[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+    let map = |cursor: egui::text::CCursor| {
+        let offset = byte_offset(before, cursor.index);
+        let offset = if offset <= prefix {
+            offset
+        } else if offset >= before.len() - suffix {
+            offset + after.len() - before.len()
+        } else {
+            after.len() - suffix
+        };
+        egui::text::CCursor::new(after[..offset].chars().count())
+    };
+    state
+        .cursor
+        .set_char_range(Some(egui::text::CCursorRange::two(
+            map(range.secondary),
+            map(range.primary),
+        )));
+    state.store(ctx, code_editor_id());
+}
+
+/// Byte offset of the character at `char_index`, or the end of `text`.
+fn byte_offset(text: &str, char_index: usize) -> usize {
+    text.char_indices()
+        .nth(char_index)
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+/// Capitalizes `suggestion` like `word` was.
+fn match_case(suggestion: &str, word: &str) -> String {
+    if word.chars().all(|c| !c.is_lowercase()) {
+        suggestion.to_uppercase()
+    } else if word.starts_with(char::is_uppercase) {
+        let mut chars = suggestion.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        suggestion.to_string()
+    }
+}
+
+/// Counts Ctrl+wheel notches this frame: `(steps, shift held)`.
+fn wheel_nudge_steps(input: &egui::InputState) -> (i32, bool) {
+    let mut steps = 0;
+    let mut coarse = false;
+    for event in &input.events {
+        if let egui::Event::MouseWheel {
+            delta, modifiers, ..
+        } = event
+        {
+            let amount = delta.x + delta.y;
+            if modifiers.command && amount != 0.0 {
+                steps += amount.signum() as i32;
+                coarse |= modifiers.shift;
+            }
+        }
+    }
+    (steps, coarse)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum VariableKind {
+    /// A `let` binding found by [`VariableVisitor`].
+    Let,
+    /// A `const` item found by [`VariableVisitor`], at the top level or in
+    /// a function, `impl` or trait.
+    Const,
+    /// A `static` item found by [`VariableVisitor`]; editing it changes
+    /// global state.
+    Static { mutable: bool },
+    /// A literal tagged with a `/* crowbar: name */` comment.
+    Marker,
+}
+
+#[derive(Clone)]
+struct Variable {
+    name: String,
+    var_type: String,
+    value: VariableValue,
+    kind: VariableKind,
+    /// Byte range of the initializer (or tagged literal) when it was parsed.
+    span: Option<Range<usize>>,
+    /// Found by [`fallback`] while the code doesn't parse: good for display
+    /// and navigation, never for rewriting.
+    approximate: bool,
+    /// Found by [`fallback`] inside a macro body, which only means something
+    /// once the macro expands.
+    in_macro: bool,
+    /// Changed in the panel since it was parsed, so re-parses keep the value.
+    edited: bool,
+    /// Value when the file was opened, kept through the re-parses that
+    /// follow writing values back, for resetting to. `None` until
+    /// [`MyApp::parse_variables`] records it, and for struct fields.
+    original_value: Option<VariableValue>,
+    /// The initializer as written when the file was opened, which resetting
+    /// every variable puts back.
+    original_text: Option<String>,
+    /// Initializer typed over one the panel can't edit, written in place of
+    /// it as is.
+    raw: Option<String>,
+    /// Initializer that isn't a plain literal. When it references other
+    /// variables the value is computed from theirs instead of edited.
+    formula: Option<syn::Expr>,
+    /// When a recomputation last changed the value, for a brief highlight.
+    updated_at: Option<Instant>,
+    /// Radix the integer literal was written in, kept when writing it back
+    /// unless the panel picks another.
+    radix: numeric::Radix,
+    /// Function the variable is declared in, `Type::method` for methods;
+    /// `None` outside any function.
+    function: Option<String>,
+    /// Blocks between the function and the declaration, outermost first,
+    /// like `for` or `closure`.
+    scope: Vec<&'static str>,
+    /// Settings from a `// crowbar: …` comment on the declaration's line.
+    annotation: annotation::Annotation,
+}
+
+/// Width of the line number gutter, and the part of it kept free for
+/// diagnostic markers.
+const GUTTER_WIDTH: f32 = 48.0;
+const GUTTER_MARKER_SPACE: f32 = 12.0;
+
+/// A top-level function, for jumping to it from the panel.
+struct Function {
+    name: String,
+    /// Byte range of the function's name.
+    span: Range<usize>,
+}
+
+fn relative_to(path: &std::path::Path, root: &std::path::Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Name, type and value of each `let` binding in `code`, for the folder scan.
+fn scanned_variables(code: &str) -> Vec<(String, String, String)> {
+    parse_let_variables(code)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|variable| {
+            let value = format_literal(&variable.value, "").unwrap_or_else(|| "…".to_string());
+            (variable.name, variable.var_type, value)
+        })
+        .collect()
+}
+
+/// Parses the `let` bindings of `code`, or `None` when it doesn't parse.
+fn parse_let_variables(code: &str) -> Option<Vec<Variable>> {
+    let ast = parse_rust_code(code).ok()?;
+    Some(let_variables(&ast))
+}
+
+fn let_variables(ast: &SynFile) -> Vec<Variable> {
+    let mut visitor = VariableVisitor::new();
+    visitor.visit_file(ast);
+    visitor.resolve_definitions();
+    visitor.variables
+}
+
+/// Every function and method with the byte range of its body, named like
+/// [`Variable::function`].
+fn function_scopes(ast: &SynFile) -> Vec<(String, Range<usize>)> {
+    let mut visitor = VariableVisitor::new();
+    visitor.visit_file(ast);
+    visitor.scopes
+}
+
+/// The innermost of `scopes` containing `offset`.
+fn enclosing_function(scopes: &[(String, Range<usize>)], offset: usize) -> Option<String> {
+    scopes
+        .iter()
+        .filter(|(_, range)| range.contains(&offset))
+        .max_by_key(|(_, range)| range.start)
+        .map(|(name, _)| name.clone())
+}
+
+fn functions(ast: &SynFile) -> Vec<Function> {
+    ast.items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Fn(item_fn) => Some(Function {
+                name: item_fn.sig.ident.to_string(),
+                span: item_fn.sig.ident.span().byte_range(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Marker variables in `code`, plus warnings for markers without a literal.
+fn parse_marker_variables(code: &str) -> (Vec<Variable>, Vec<String>) {
+    let (markers, warnings) = markers::find_markers(code);
+    let variables = markers
+        .into_iter()
+        .map(|marker| marker_variable(&marker.name, code, marker.span))
+        .collect();
+    (variables, warnings)
+}
+
+/// Builds the panel entry for a marker from the literal it tags.
+fn marker_variable(name: &str, code: &str, span: Range<usize>) -> Variable {
+    let (var_type, value) = literal_value(&code[span.clone()]);
+    Variable {
+        name: name.to_string(),
+        var_type,
+        value,
+        kind: VariableKind::Marker,
+        radix: numeric::Radix::of(&code[span.clone()]),
+        span: Some(span),
+        approximate: false,
+        in_macro: false,
+        edited: false,
+        original_value: None,
+        original_text: None,
+        raw: None,
+        formula: None,
+        updated_at: None,
+        function: None,
+        scope: Vec::new(),
+        annotation: annotation::Annotation::default(),
+    }
+}
+
+/// Type (from the suffix, or the default for the literal) and value of a
+/// possibly negated literal.
+fn literal_value(text: &str) -> (String, VariableValue) {
+    syn::parse_str::<syn::Expr>(text)
+        .ok()
+        .and_then(|expr| typed_literal(&expr))
+        .unwrap_or_else(|| ("Unsupported".to_string(), VariableValue::Unknown))
+}
+
+/// Type and value of an initializer the panel can edit without a type
+/// annotation: a possibly negated literal, typed by its suffix or Rust's
+/// default for it, a number literal cast to another number type, a
+/// `String` built from a string literal, a byte or byte string literal, a
+/// `vec!` or array of literals, or a tuple of them.
+fn typed_literal(expr: &syn::Expr) -> Option<(String, VariableValue)> {
+    if let syn::Expr::Macro(_) | syn::Expr::Array(_) | syn::Expr::Repeat(_) = expr {
+        let (element, items) = list::parse(expr, None)?;
+        let ty = list::list_type(expr, &element, items.len());
+        return Some((ty, VariableValue::List(items)));
+    }
+    if let syn::Expr::Tuple(_) = expr {
+        let (types, items) = tuple::parse(expr, None)?;
+        return Some((tuple::written(&types), VariableValue::Tuple(items)));
+    }
+    if let syn::Expr::Cast(cast) = expr {
+        numeric_literal(&cast.expr)?;
+        let ty = extract_type(&cast.ty);
+        let value = dependencies::evaluate(expr, &|_| None)
+            .map(|value| numeric::of_type(value, &ty))
+            .filter(|value| fits_type(value, &ty))?;
+        return Some((ty, value));
+    }
+    if let Some((lit, negative)) = numeric_literal(expr) {
+        let default = if matches!(lit, syn::Lit::Int(_)) {
+            "i32"
+        } else {
+            "f64"
+        };
+        let ty = type_or_default(lit.suffix(), default);
+        let value = number_value(lit, negative, &ty);
+        return Some((ty, value));
+    }
+    match expr {
+        syn::Expr::Lit(syn::ExprLit { lit, .. }) => match lit {
+            syn::Lit::Bool(lit_bool) => {
+                Some(("bool".to_string(), VariableValue::Bool(lit_bool.value)))
+            }
+            syn::Lit::Str(lit_str) => {
+                Some(("&str".to_string(), VariableValue::Str(lit_str.value())))
+            }
+            syn::Lit::Char(lit_char) => {
+                Some(("char".to_string(), VariableValue::Char(lit_char.value())))
+            }
+            syn::Lit::Byte(lit_byte) => Some((
+                "u8".to_string(),
+                VariableValue::Int(i64::from(lit_byte.value())),
+            )),
+            syn::Lit::ByteStr(lit_bytes) => {
+                let bytes = lit_bytes.value();
+                Some((
+                    format!("&[u8; {}]", bytes.len()),
+                    VariableValue::Bytes(bytes),
+                ))
+            }
+            _ => None,
+        },
+        syn::Expr::Call(syn::ExprCall { func, args, .. }) => match (&**func, args.first()) {
+            (
+                syn::Expr::Path(path),
+                Some(syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(lit_str),
+                    ..
+                })),
+            ) if args.len() == 1 && path_is(&path.path, &["String", "from"]) => {
+                Some(("String".to_string(), VariableValue::Str(lit_str.value())))
+            }
+            (syn::Expr::Path(path), None) if path_is(&path.path, &["String", "new"]) => {
+                Some(("String".to_string(), VariableValue::Str(String::new())))
+            }
+            _ => None,
+        },
+        syn::Expr::MethodCall(call)
+            if (call.method == "to_string" || call.method == "to_owned")
+                && call.args.is_empty() =>
+        {
+            match &*call.receiver {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(lit_str),
+                    ..
+                }) => Some(("String".to_string(), VariableValue::Str(lit_str.value()))),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether `path` ends in `segments`, so `String::from` also matches
+/// `std::string::String::from`.
+fn path_is(path: &syn::Path, segments: &[&str]) -> bool {
+    path.segments.len() >= segments.len()
+        && path
+            .segments
+            .iter()
+            .rev()
+            .zip(segments.iter().rev())
+            .all(|(segment, name)| segment.ident == name)
+}
+
+/// Radix of the integer literal `expr` is, decimal for anything else.
+fn literal_radix(expr: &syn::Expr) -> numeric::Radix {
+    match numeric_literal(expr) {
+        Some((syn::Lit::Int(lit_int), _)) => numeric::Radix::of(&lit_int.to_string()),
+        _ => numeric::Radix::Decimal,
+    }
+}
+
+/// How a char is shown for editing: itself, or its escape when it's
+/// invisible or a control character.
+fn char_entry_text(c: char) -> String {
+    match c {
+        '\'' | '"' => c.to_string(),
+        _ => c.escape_debug().to_string(),
+    }
+}
+
+/// What was typed into a char field: one character, or an escape as it's
+/// written inside a char literal, like `\n` or `\u{1F600}`.
+fn parse_char_entry(text: &str) -> Result<char, String> {
+    let mut chars = text.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(c);
+    }
+    if text.starts_with('\\') {
+        if let Ok(literal) = syn::parse_str::<syn::LitChar>(&format!("'{}'", text)) {
+            return Ok(literal.value());
+        }
+    }
+    Err("One character, or an escape like \\n".to_string())
+}
+
+/// What was typed into a byte string field: ASCII, with anything else
+/// escaped as it's written inside a byte string literal, like `\x7f`.
+fn parse_bytes_entry(text: &str) -> Result<Vec<u8>, String> {
+    syn::parse_str::<syn::LitByteStr>(&format!("b\"{}\"", text))
+        .map(|literal| literal.value())
+        .map_err(|_| "ASCII only, or escapes like \\x7f and \\\"".to_string())
+}
+
+/// A value as text for the clipboard: strings and chars as they are,
+/// everything else as a literal.
+fn clipboard_text(value: &VariableValue) -> Option<String> {
+    match value {
+        VariableValue::Str(val) => Some(val.clone()),
+        VariableValue::Char(val) => Some(val.to_string()),
+        value => format_literal(value, ""),
+    }
+}
+
+/// Pasted text as a new value for `variable`, written the way
+/// [`clipboard_text`] writes it, or why it isn't one.
+fn parse_pasted(text: &str, variable: &Variable) -> Result<VariableValue, String> {
+    let ty = variable.var_type.as_str();
+    let not_one = || format!("Not a {} literal", ty);
+    let expr = || syn::parse_str::<syn::Expr>(text.trim()).map_err(|_| not_one());
+    match &variable.value {
+        VariableValue::Int(_) | VariableValue::UInt(_) | VariableValue::Float(_) => {
+            numeric::parse_entry(text, ty)
+        }
+        VariableValue::Wide(_) => numeric::parse_wide(text, ty).map(VariableValue::Wide),
+        VariableValue::Bool(_) => text
+            .trim()
+            .parse()
+            .map(VariableValue::Bool)
+            .map_err(|_| "Not true or false".to_string()),
+        VariableValue::Str(_) => Ok(VariableValue::Str(text.to_string())),
+        VariableValue::Char(_) => parse_char_entry(text).map(VariableValue::Char),
+        VariableValue::Bytes(_) => parse_bytes_entry(text).map(VariableValue::Bytes),
+        VariableValue::List(items) => {
+            let element = list::element_type(ty);
+            let (element, pasted) = list::parse(&expr()?, element)
+                .filter(|(element, pasted)| pasted.iter().all(|item| fits_type(item, element)))
+                .ok_or_else(not_one)?;
+            if list::is_fixed(ty) && pasted.len() != items.len() {
+                return Err(format!("Needs {} elements of {}", items.len(), element));
+            }
+            Ok(VariableValue::List(pasted))
+        }
+        VariableValue::Tuple(_) => {
+            let types = tuple::element_types(ty);
+            tuple::parse(&expr()?, types.as_deref())
+                .filter(|(types, items)| {
+                    items
+                        .iter()
+                        .zip(types)
+                        .all(|(item, ty)| fits_type(item, ty))
+                })
+                .map(|(_, items)| VariableValue::Tuple(items))
+                .ok_or_else(not_one)
+        }
+        VariableValue::Optional { .. } => {
+            let inner = optional::inner_type(ty).unwrap_or_default();
+            optional::parse(&expr()?, inner)
+                .filter(|value| match value {
+                    VariableValue::Optional { some: true, value } => fits_type(value, inner),
+                    _ => true,
+                })
+                .ok_or_else(not_one)
+        }
+        VariableValue::Enum { name, variants, .. } => {
+            let variant = text.trim().rsplit("::").next().unwrap_or_default();
+            if variants.iter().any(|choice| choice == variant) {
+                Ok(VariableValue::Enum {
+                    name: name.clone(),
+                    variant: variant.to_string(),
+                    variants: variants.clone(),
+                })
+            } else {
+                Err(format!("Not a variant of {}", name))
+            }
+        }
+        VariableValue::Struct { .. } | VariableValue::Unknown => {
+            Err("Can't be pasted into".to_string())
+        }
+    }
+}
+
+/// Value of an integer or float literal, negated when `negative`, for a
+/// variable of type `ty`. `i128`s are wide, and `u64`s, `usize`s and
+/// `u128`s unsigned. Integers out of their type's range are listed, but
+/// left alone.
+fn number_value(lit: &syn::Lit, negative: bool, ty: &str) -> VariableValue {
+    match lit {
+        syn::Lit::Int(lit_int) => {
+            let Ok(magnitude) = lit_int.base10_parse::<u128>() else {
+                return VariableValue::Unknown;
+            };
+            if let Some(max) = numeric::uint_max(ty) {
+                return match magnitude {
+                    0 => VariableValue::UInt(0),
+                    _ if negative || magnitude > max => VariableValue::Unknown,
+                    _ => VariableValue::UInt(magnitude),
+                };
+            }
+            let value = if negative {
+                0i128.checked_sub_unsigned(magnitude)
+            } else {
+                i128::try_from(magnitude).ok()
+            };
+            match value {
+                Some(value) if ty == "i128" => VariableValue::Wide(value),
+                Some(value) => {
+                    i64::try_from(value).map_or(VariableValue::Unknown, VariableValue::Int)
+                }
+                None => VariableValue::Unknown,
+            }
+        }
+        syn::Lit::Float(lit_float) => {
+            let value: f64 = lit_float.base10_parse().unwrap_or(0.0);
+            VariableValue::Float(if negative { -value } else { value })
+        }
+        _ => VariableValue::Unknown,
+    }
+}
+
+/// The numeric literal `expr` is, possibly negated, and whether it is.
+fn numeric_literal(expr: &syn::Expr) -> Option<(&syn::Lit, bool)> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: lit @ (syn::Lit::Int(_) | syn::Lit::Float(_)),
+            ..
+        }) => Some((lit, false)),
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => numeric_literal(expr)
+            .filter(|(_, negative)| !negative)
+            .map(|(lit, _)| (lit, true)),
+        _ => None,
+    }
+}
+
+fn type_or_default(suffix: &str, default: &str) -> String {
+    if suffix.is_empty() {
+        default.to_string()
+    } else {
+        suffix.to_string()
+    }
+}
+
+/// Where byte `offset` of `old` is in `new`, taking the two to differ in a
+/// single stretch, as after typing or pasting: text before it stays put,
+/// text after it moves by the change in length, and text inside it goes to
+/// where it starts. The stretch starts at the start of a line, so a line
+/// added above one like it is taken as new rather than the old one's end.
+fn moved_offset(old: &str, new: &str, offset: usize) -> usize {
+    let differs = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((at, _), _)| at);
+    let prefix = old[..differs].rfind('\n').map_or(0, |at| at + 1);
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+    if offset < prefix {
+        offset
+    } else if offset >= old.len() - suffix && offset <= old.len() {
+        offset + new.len() - old.len()
+    } else {
+        prefix
+    }
+}
+
+/// Whether `value` can be a value of `var_type`: the same kind as the
+/// type's values, and in range for an integer type.
+fn fits_type(value: &VariableValue, var_type: &str) -> bool {
+    let in_range = match (value, numeric::int_range(var_type)) {
+        (VariableValue::Int(value), Some((min, max))) => (min..=max).contains(value),
+        (VariableValue::UInt(value), _) => {
+            numeric::uint_max(var_type).is_some_and(|max| *value <= max)
+        }
+        _ => true,
+    };
+    in_range && std::mem::discriminant(value) == std::mem::discriminant(&default_value(var_type))
+}
+
+/// Formats `value` as a Rust literal, re-attaching a numeric type `suffix`.
+fn format_literal(value: &VariableValue, suffix: &str) -> Option<String> {
+    match value {
+        VariableValue::Int(val) => Some(format!("{}{}", val, suffix)),
+        VariableValue::Wide(val) => Some(format!("{}{}", val, suffix)),
+        VariableValue::UInt(val) => Some(format!("{}{}", val, suffix)),
+        VariableValue::Float(val) => val.is_finite().then(|| format!("{:?}{}", val, suffix)),
+        VariableValue::Bool(val) => Some(val.to_string()),
+        VariableValue::Str(val) => Some(str_literal(val, None)),
+        VariableValue::Char(val) => Some(format!("{:?}", val)),
+        VariableValue::Bytes(val) => Some(format!("b\"{}\"", val.escape_ascii())),
+        VariableValue::List(items) => list::literal(items, "", &list::Form::Vec, &[]),
+        VariableValue::Tuple(items) => {
+            let elements = items
+                .iter()
+                .map(|item| format_literal(item, ""))
+                .collect::<Option<Vec<_>>>()?;
+            Some(tuple::written(&elements))
+        }
+        VariableValue::Optional { some: true, value } => {
+            Some(format!("Some({})", format_literal(value, "")?))
+        }
+        VariableValue::Optional { some: false, .. } => Some("None".to_string()),
+        VariableValue::Enum { name, variant, .. } => Some(format!("{}::{}", name, variant)),
+        VariableValue::Struct { name, fields } => {
+            let fields = fields
+                .iter()
+                .map(|field| {
+                    let value = match &field.formula {
+                        Some(expr) => quote::ToTokens::to_token_stream(expr).to_string(),
+                        None => format_literal(&field.value, "")?,
+                    };
+                    Some(format!("{}: {}", field.name, value))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(format!("{} {{ {} }}", name, fields.join(", ")))
+        }
+        VariableValue::Unknown => None,
+    }
+}
+
+/// What to write over `span` for a field of a struct literal, or an enum
+/// variable: the value as a literal written the way `span` is, `None` when
+/// it can't be written.
+fn field_literal(code: &str, field: &Variable, span: Range<usize>) -> Option<String> {
+    let source = code.get(span)?;
+    match &field.value {
+        // However the source spells the enum's path.
+        VariableValue::Enum { name, variant, .. } => Some(match source.rsplit_once("::") {
+            Some((path, _)) => format!("{}::{}", path, variant),
+            None => format!("{}::{}", name, variant),
+        }),
+        VariableValue::List(items) => list::rewrite(
+            items,
+            list::element_type(&field.var_type).unwrap_or_default(),
+            source,
+        ),
+        VariableValue::Tuple(items) => {
+            tuple::literal(items, &tuple::element_types(&field.var_type)?, source)
+        }
+        VariableValue::Optional { some, value } => {
+            optional::literal(*some, value, optional::inner_type(&field.var_type)?, source)
+        }
+        VariableValue::Struct { .. } | VariableValue::Unknown => None,
+        value => literal_like(value, &field.var_type, &syn::parse_str(source).ok()?),
+    }
+}
+
+/// Edits rewriting the struct literal fields that changed, matched by name
+/// to the `declared` fields just parsed. The others are left as written.
+fn field_edits(
+    code: &str,
+    fields: &[Variable],
+    declared: &[Variable],
+    edits: &mut Vec<(Range<usize>, String)>,
+) {
+    for field in fields.iter().filter(|field| field.formula.is_none()) {
+        let Some(source) = declared.iter().find(|source| source.name == field.name) else {
+            continue;
+        };
+        if let (
+            VariableValue::Struct { fields, .. },
+            VariableValue::Struct {
+                fields: declared, ..
+            },
+        ) = (&field.value, &source.value)
+        {
+            field_edits(code, fields, declared, edits);
+            continue;
+        }
+        let Some(span) = source.span.clone() else {
+            continue;
+        };
+        if let Some(literal) = field_literal(code, field, span.clone()) {
+            if code[span.clone()] != literal {
+                edits.push((span, literal));
+            }
+        }
+    }
+}
+
+/// `value` as a literal of type `ty` to write over `original`, keeping the
+/// suffix, radix and way of building a `String` it's written with.
+fn literal_like(value: &VariableValue, ty: &str, original: &syn::Expr) -> Option<String> {
+    if let syn::Expr::Cast(cast) = original {
+        return cast_literal(value, cast);
+    }
+    let suffix = numeric_literal(original)
+        .map(|(lit, _)| written_suffix(lit))
+        .unwrap_or_default();
+    let radix = literal_radix(original);
+    let sign = |negative| if negative { "-" } else { "" };
+    let written = match numeric_literal(original) {
+        Some((syn::Lit::Int(lit_int), negative)) => format!("{}{}", sign(negative), lit_int),
+        Some((syn::Lit::Float(lit_float), negative)) => {
+            format!("{}{}", sign(negative), lit_float)
+        }
+        _ => String::new(),
+    };
+    match value {
+        VariableValue::Int(val)
+            if matches!(
+                original,
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Byte(_),
+                    ..
+                })
+            ) =>
+        {
+            Some(byte_literal(*val))
+        }
+        VariableValue::Int(val) => Some(format!(
+            "{}{}",
+            numeric::format_like(radix, numeric::clamp_int(ty, *val), &written),
+            suffix
+        )),
+        VariableValue::Wide(val) => Some(format!(
+            "{}{}",
+            numeric::format_like(radix, *val, &written),
+            suffix
+        )),
+        VariableValue::UInt(val) => Some(format!(
+            "{}{}",
+            numeric::format_unsigned_like(radix, *val, &written),
+            suffix
+        )),
+        VariableValue::Float(val) => {
+            numeric::float_like(*val, ty, &written).map(|text| format!("{}{}", text, suffix))
+        }
+        VariableValue::Str(val) => {
+            let literal = str_literal(val, string_literal(original));
+            Some(match original {
+                // Whichever way the source builds it.
+                syn::Expr::Call(call) if ty == "String" => {
+                    format!("{}({})", string_constructor(&call.func), literal)
+                }
+                syn::Expr::MethodCall(call) if ty == "String" => {
+                    format!("{}.{}()", literal, call.method)
+                }
+                _ if ty == "String" => format!("{}.to_string()", literal),
+                _ => literal,
+            })
+        }
+        value => format_literal(value, &suffix),
+    }
+}
+
+/// The type suffix of the literal `lit` as written, with the `_` before it
+/// when there is one, like `_f64` in `2.0_f64`.
+fn written_suffix(lit: &syn::Lit) -> String {
+    let suffix = lit.suffix();
+    let separated = format!("_{}", suffix);
+    if !suffix.is_empty()
+        && quote::ToTokens::to_token_stream(lit)
+            .to_string()
+            .ends_with(&separated)
+    {
+        separated
+    } else {
+        suffix.to_string()
+    }
+}
+
+/// The path a `String` initializer like `String::new()` or
+/// `std::string::String::from(…)` was called through, ending in `from` so
+/// it takes the new value.
+fn string_constructor(func: &syn::Expr) -> String {
+    let syn::Expr::Path(path) = func else {
+        return "String::from".to_string();
+    };
+    let mut segments: Vec<String> = path
+        .path
+        .segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect();
+    if let Some(last) = segments.last_mut() {
+        *last = "from".to_string();
+    }
+    let leading = if path.path.leading_colon.is_some() {
+        "::"
+    } else {
+        ""
+    };
+    format!("{}{}", leading, segments.join("::"))
+}
+
+/// `value` written over the cast of a number literal `cast`, as a cast to
+/// the same type. The literal is written like the one it replaces while it
+/// can hold the value, and typed by the cast's suffix otherwise, since an
+/// unsuffixed literal in a cast is an `i32`.
+fn cast_literal(value: &VariableValue, cast: &syn::ExprCast) -> Option<String> {
+    let ty = extract_type(&cast.ty);
+    let (own_type, own) = typed_literal(&cast.expr)?;
+    // An unsuffixed literal would be cast with wrapping, so what's written
+    // is kept within the type cast to.
+    let clamped = match value {
+        VariableValue::Int(val) if numeric::uint_max(&ty).is_some() => {
+            VariableValue::Int((*val).max(0))
+        }
+        VariableValue::Int(val) => VariableValue::Int(numeric::clamp_int(&ty, *val)),
+        VariableValue::UInt(val) => {
+            VariableValue::UInt(numeric::uint_max(&ty).map_or(*val, |max| (*val).min(max)))
+        }
+        _ => value.clone(),
+    };
+    let value = &clamped;
+    let unchanged = dependencies::evaluate(&syn::Expr::Cast(cast.clone()), &|_| None)
+        .is_some_and(|cast_value| format_literal(&cast_value, "") == format_literal(value, ""));
+    let whole = match value {
+        VariableValue::Float(val) if val.fract() == 0.0 && val.abs() < 1e15 => {
+            Some(numeric::of_type(VariableValue::Int(*val as i64), &own_type))
+        }
+        VariableValue::UInt(_) => Some(numeric::of_type(
+            numeric::for_arithmetic(value.clone()),
+            &own_type,
+        )),
+        _ => None,
+    };
+    let literal = match whole {
+        _ if unchanged => literal_like(&own, &own_type, &cast.expr)?,
+        Some(whole) if fits_type(&whole, &own_type) => literal_like(&whole, &own_type, &cast.expr)?,
+        _ if fits_type(value, &own_type) => literal_like(value, &own_type, &cast.expr)?,
+        _ => match value {
+            VariableValue::Int(_) | VariableValue::UInt(_) if ty != "i32" => {
+                format_literal(value, &ty)?
+            }
+            _ => format_literal(value, "")?,
+        },
+    };
+    Some(format!("{} as {}", literal, ty))
+}
+
+/// Whether `text` is a byte literal like `b'A'`, written back as one.
+fn is_byte_literal(text: &str) -> bool {
+    matches!(syn::parse_str::<syn::Lit>(text), Ok(syn::Lit::Byte(_)))
+}
+
+fn byte_literal(val: i64) -> String {
+    format!(
+        "b'{}'",
+        (numeric::clamp_int("u8", val) as u8).escape_ascii()
+    )
+}
+
+/// The string literal `expr` is, or builds a `String` from.
+fn string_literal(expr: &syn::Expr) -> Option<&syn::LitStr> {
+    let expr = match expr {
+        syn::Expr::Call(call) if call.args.len() == 1 => &call.args[0],
+        syn::Expr::MethodCall(call) => &call.receiver,
+        expr => expr,
+    };
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit_str),
+            ..
+        }) => Some(lit_str),
+        _ => None,
+    }
+}
+
+/// `val` as a string literal, raw like `like` when that is, with as many
+/// more `#`s as it takes for a `"` in `val` not to end it. Otherwise it's
+/// escaped, unless it has enough backslashes that a raw string reads
+/// better. Line breaks stay as they are if `like` spans lines.
+fn str_literal(val: &str, like: Option<&syn::LitStr>) -> String {
+    let written = like.map(|lit| lit.token().to_string()).unwrap_or_default();
+    // Raw strings can't hold a carriage return or most other controls.
+    let rawable = !val
+        .chars()
+        .any(|c| c.is_control() && c != '\n' && c != '\t');
+    let raw = written
+        .strip_prefix('r')
+        .map(|rest| rest.chars().take_while(|c| *c == '#').count())
+        .or((val.matches('\\').count() > 2).then_some(0));
+    let Some(hashes) = raw.filter(|_| rawable) else {
+        return format!("\"{}\"", escape_str(val, written.contains('\n')));
+    };
+    let needed = val
+        .match_indices('"')
+        .map(|(at, _)| val[at + 1..].chars().take_while(|c| *c == '#').count() + 1)
+        .max()
+        .unwrap_or(0);
+    let hashes = "#".repeat(hashes.max(needed));
+    format!("r{}\"{}\"{}", hashes, val, hashes)
+}
+
+/// Byte string literal for `val`, raw if `like` is and every byte can go in
+/// one as is, or `None` if `ty` is an array reference of another length.
+fn bytes_literal(val: &[u8], ty: &str, like: &str) -> Option<String> {
+    if byte_array_len(ty).is_some_and(|len| len != val.len()) {
+        return None;
+    }
+    let raw = like
+        .trim()
+        .strip_prefix("br")
+        .map(|rest| rest.chars().take_while(|c| *c == '#').count());
+    let rawable = val
+        .iter()
+        .all(|b| b.is_ascii() && (!b.is_ascii_control() || matches!(b, b'\n' | b'\t')));
+    let Some(hashes) = raw.filter(|_| rawable) else {
+        return Some(format!("b\"{}\"", val.escape_ascii()));
+    };
+    let text = String::from_utf8_lossy(val);
+    let needed = text
+        .match_indices('"')
+        .map(|(at, _)| text[at + 1..].chars().take_while(|c| *c == '#').count() + 1)
+        .max()
+        .unwrap_or(0);
+    let hashes = "#".repeat(hashes.max(needed));
+    Some(format!("br{}\"{}\"{}", hashes, text, hashes))
+}
+
+/// `N` of a `&[u8; N]` type, with or without a lifetime.
+fn byte_array_len(ty: &str) -> Option<usize> {
+    let ty = ty.strip_prefix('&')?.trim_start();
+    let ty = match ty.strip_prefix('\'') {
+        Some(lifetime) => lifetime.split_once(char::is_whitespace)?.1.trim_start(),
+        None => ty,
+    };
+    let (element, len) = ty.strip_prefix('[')?.strip_suffix(']')?.split_once(';')?;
+    (element.trim() == "u8").then(|| len.trim().parse().ok())?
+}
+
+/// `val` escaped to go between the quotes of a string literal, keeping its
+/// line breaks if `keep_newlines`.
+fn escape_str(val: &str, keep_newlines: bool) -> String {
+    let mut escaped = String::with_capacity(val.len());
+    for c in val.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' if keep_newlines => escaped.push('\n'),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+struct VariableVisitor {
+    variables: Vec<Variable>,
+    /// Functions the visitor is inside, innermost last.
+    functions: Vec<String>,
+    /// Type of the `impl` block the visitor is in, for naming methods.
+    impl_type: Option<String>,
+    /// Every function seen, with the byte range of its body.
+    scopes: Vec<(String, Range<usize>)>,
+    /// Blocks the visitor is inside within the current function, outermost
+    /// first.
+    blocks: Vec<&'static str>,
+    /// Every enum seen that has unit variants, with their names.
+    enums: Vec<(String, Vec<String>)>,
+    /// Every struct seen with named fields, with their names and types.
+    structs: Vec<(String, Vec<(String, Type)>)>,
+}
+
+impl VariableVisitor {
+    fn new() -> Self {
+        Self {
+            variables: Vec::new(),
+            functions: Vec::new(),
+            impl_type: None,
+            scopes: Vec::new(),
+            blocks: Vec::new(),
+            enums: Vec::new(),
+            structs: Vec::new(),
+        }
+    }
+
+    /// Gives variables of an enum type initialized to one of its unit
+    /// variants the variant as their value, and variables of a struct type
+    /// initialized with a struct literal a variable per field. Done once the
+    /// whole file is visited, since types may be defined after they're used.
+    fn resolve_definitions(&mut self) {
+        for variable in &mut self.variables {
+            let Some(syn::Expr::Path(path)) = &variable.formula else {
+                continue;
+            };
+            let name = variable.var_type.rsplit("::").next().unwrap_or_default();
+            let Some((_, variants)) = self.enums.iter().find(|(enum_name, _)| enum_name == name)
+            else {
+                continue;
+            };
+            let segments: Vec<String> = path
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect();
+            let [.., enum_name, variant] = segments.as_slice() else {
+                continue;
+            };
+            if enum_name == name && variants.contains(variant) {
+                variable.value = VariableValue::Enum {
+                    name: name.to_string(),
+                    variant: variant.clone(),
+                    variants: variants.clone(),
+                };
+                variable.formula = None;
+            }
+        }
+        for variable in &mut self.variables {
+            let Some(syn::Expr::Struct(init)) = &variable.formula else {
+                continue;
+            };
+            let name = variable.var_type.rsplit("::").next().unwrap_or_default();
+            let Some((_, declared)) = self
+                .structs
+                .iter()
+                .find(|(struct_name, _)| struct_name == name)
+            else {
+                continue;
+            };
+            if init
+                .path
+                .segments
+                .last()
+                .is_none_or(|last| last.ident != name)
+            {
+                continue;
+            }
+            let mut fields = VariableVisitor {
+                enums: self.enums.clone(),
+                structs: self.structs.clone(),
+                ..VariableVisitor::new()
+            };
+            for field in &init.fields {
+                let syn::Member::Named(member) = &field.member else {
+                    continue;
+                };
+                if let Some((_, ty)) = declared.iter().find(|(field_name, _)| member == field_name)
+                {
+                    fields.push_typed(member.to_string(), VariableKind::Let, ty, Some(&field.expr));
+                }
+            }
+            fields.resolve_definitions();
+            variable.value = VariableValue::Struct {
+                name: name.to_string(),
+                fields: fields.variables,
+            };
+            variable.formula = None;
+        }
+    }
+
+    /// Visits a function's contents with `visit`, tracking it as the
+    /// innermost function.
+    fn in_function(&mut self, name: String, body: &syn::Block, visit: impl FnOnce(&mut Self)) {
+        self.scopes.push((name.clone(), body.span().byte_range()));
+        self.functions.push(name);
+        let outer = std::mem::take(&mut self.blocks);
+        visit(self);
+        self.blocks = outer;
+        self.functions.pop();
+    }
+
+    /// Visits the body of a closure, `else` or match arm, whose braces
+    /// aren't a block of their own.
+    fn visit_body(&mut self, body: &syn::Expr) {
+        match body {
+            syn::Expr::Block(block) if block.label.is_none() => self.visit_block(&block.block),
+            body => self.visit_expr(body),
+        }
+    }
+
+    /// Visits with `visit` inside a block of kind `block`.
+    fn in_block(&mut self, block: &'static str, visit: impl FnOnce(&mut Self)) {
+        self.blocks.push(block);
+        visit(self);
+        self.blocks.pop();
+    }
+
+    /// Lists an untyped binding to `init` if it's a literal.
+    fn push_literal(&mut self, name: String, init: &syn::Expr) {
+        let Some((var_type, value)) = typed_literal(init) else {
+            return;
+        };
+        self.variables.push(Variable {
+            name,
+            var_type,
+            value,
+            kind: VariableKind::Let,
+            span: Some(init.span().byte_range()),
+            approximate: false,
+            in_macro: false,
+            edited: false,
+            original_value: None,
+            original_text: None,
+            raw: None,
+            formula: None,
+            updated_at: None,
+            radix: literal_radix(init),
+            function: self.functions.last().cloned(),
+            scope: self.blocks.clone(),
+            annotation: annotation::Annotation::default(),
+        });
+    }
+
+    /// Lists a binding annotated with `ty`, its value read from `init` when
+    /// that's a literal and worked out from it otherwise.
+    fn push_typed(
+        &mut self,
+        name: String,
+        kind: VariableKind,
+        ty: &Type,
+        init: Option<&syn::Expr>,
+    ) {
+        let var_type = extract_type(ty);
+        let value = default_value(&var_type);
+
+        self.variables.push(Variable {
+            name,
+            var_type,
+            value,
+            kind,
+            span: init.map(|init| init.span().byte_range()),
+            approximate: false,
+            in_macro: false,
+            edited: false,
+            original_value: None,
+            original_text: None,
+            raw: None,
+            formula: None,
+            updated_at: None,
+            radix: numeric::Radix::Decimal,
+            function: self.functions.last().cloned(),
+            scope: self.blocks.clone(),
+            annotation: annotation::Annotation::default(),
+        });
+
+        if let Some(init) = init {
+            if let Some(variable) = self.variables.last_mut() {
+                if let Some((lit, negative)) = numeric_literal(init) {
+                    variable.value = number_value(lit, negative, &variable.var_type);
+                    if let syn::Lit::Int(lit_int) = lit {
+                        variable.radix = numeric::Radix::of(&lit_int.to_string());
+                    }
+                } else if let Some((_, items)) = list::element_type(&variable.var_type)
+                    .and_then(|element| list::parse(init, Some(element)))
+                {
+                    variable.value = VariableValue::List(items);
+                } else if let Some((_, items)) = tuple::element_types(&variable.var_type)
+                    .and_then(|types| tuple::parse(init, Some(&types)))
+                {
+                    variable.value = VariableValue::Tuple(items);
+                } else if let Some(value) = optional::inner_type(&variable.var_type)
+                    .and_then(|inner| optional::parse(init, inner))
+                {
+                    variable.value = value;
+                } else if let Some((_, value)) = typed_literal(init) {
+                    variable.value = value;
+                } else {
+                    variable.formula = Some(init.clone());
+                    // Arithmetic on literals alone shows what it comes to, or
+                    // nothing when it overflows or divides by zero.
+                    if dependencies::is_constant_arithmetic(init) {
+                        variable.value = dependencies::evaluate(init, &|_| None)
+                            .map(|value| numeric::of_type(value, &variable.var_type))
+                            .filter(|value| fits_type(value, &variable.var_type))
+                            .unwrap_or(VariableValue::Unknown);
+                    } else if let syn::Expr::Cast(_) = init {
+                        // Shown as written, rather than as a made up zero.
+                        variable.value = VariableValue::Unknown;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for VariableVisitor {
+    // Macro bodies are token streams that may not even be Rust until they
+    // expand, so nothing in them is listed or rewritten.
+    fn visit_item_macro(&mut self, _: &'ast syn::ItemMacro) {}
+
+    fn visit_macro(&mut self, _: &'ast syn::Macro) {}
+
+    fn visit_item_enum(&mut self, item: &'ast syn::ItemEnum) {
+        let variants: Vec<String> = item
+            .variants
+            .iter()
+            .filter(|variant| matches!(variant.fields, syn::Fields::Unit))
+            .map(|variant| variant.ident.to_string())
+            .collect();
+        if !variants.is_empty() && item.generics.params.is_empty() {
+            self.enums.push((item.ident.to_string(), variants));
+        }
+        syn::visit::visit_item_enum(self, item);
+    }
+
+    fn visit_item_struct(&mut self, item: &'ast syn::ItemStruct) {
+        if let (syn::Fields::Named(fields), true) = (&item.fields, item.generics.params.is_empty())
+        {
+            let fields = fields
+                .named
+                .iter()
+                .filter_map(|field| Some((field.ident.as_ref()?.to_string(), field.ty.clone())))
+                .collect();
+            self.structs.push((item.ident.to_string(), fields));
+        }
+        syn::visit::visit_item_struct(self, item);
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.in_function(item.sig.ident.to_string(), &item.block, |visitor| {
+            syn::visit::visit_item_fn(visitor, item);
+        });
+    }
+
+    fn visit_item_impl(&mut self, item: &'ast syn::ItemImpl) {
+        let ty = match &*item.self_ty {
+            Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        };
+        let outer = std::mem::replace(&mut self.impl_type, ty);
+        syn::visit::visit_item_impl(self, item);
+        self.impl_type = outer;
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        let name = match &self.impl_type {
+            Some(ty) => format!("{}::{}", ty, item.sig.ident),
+            None => item.sig.ident.to_string(),
+        };
+        self.in_function(name, &item.block, |visitor| {
+            syn::visit::visit_impl_item_fn(visitor, item);
+        });
+    }
+
+    fn visit_expr_closure(&mut self, expr: &'ast syn::ExprClosure) {
+        self.in_block("closure", |visitor| visitor.visit_body(&expr.body));
+    }
+
+    fn visit_expr_async(&mut self, expr: &'ast syn::ExprAsync) {
+        self.in_block("async", |visitor| {
+            syn::visit::visit_expr_async(visitor, expr)
+        });
+    }
+
+    fn visit_expr_block(&mut self, expr: &'ast syn::ExprBlock) {
+        self.in_block("block", |visitor| {
+            syn::visit::visit_expr_block(visitor, expr)
+        });
+    }
+
+    fn visit_expr_unsafe(&mut self, expr: &'ast syn::ExprUnsafe) {
+        self.in_block("unsafe", |visitor| {
+            syn::visit::visit_expr_unsafe(visitor, expr);
+        });
+    }
+
+    fn visit_expr_for_loop(&mut self, expr: &'ast syn::ExprForLoop) {
+        self.visit_expr(&expr.expr);
+        self.in_block("for", |visitor| visitor.visit_block(&expr.body));
+    }
+
+    fn visit_expr_while(&mut self, expr: &'ast syn::ExprWhile) {
+        self.visit_expr(&expr.cond);
+        self.in_block("while", |visitor| visitor.visit_block(&expr.body));
+    }
+
+    fn visit_expr_loop(&mut self, expr: &'ast syn::ExprLoop) {
+        self.in_block("loop", |visitor| visitor.visit_block(&expr.body));
+    }
+
+    fn visit_expr_if(&mut self, expr: &'ast syn::ExprIf) {
+        self.visit_expr(&expr.cond);
+        self.in_block("if", |visitor| visitor.visit_block(&expr.then_branch));
+        if let Some((_, else_branch)) = &expr.else_branch {
+            self.in_block("else", |visitor| visitor.visit_body(else_branch));
+        }
+    }
+
+    fn visit_arm(&mut self, arm: &'ast syn::Arm) {
+        if let Some((_, guard)) = &arm.guard {
+            self.visit_expr(guard);
+        }
+        self.in_block("match", |visitor| visitor.visit_body(&arm.body));
+    }
+
+    fn visit_item_const(&mut self, item: &'ast syn::ItemConst) {
+        self.push_typed(
+            item.ident.to_string(),
+            VariableKind::Const,
+            &item.ty,
+            Some(&item.expr),
+        );
+        syn::visit::visit_item_const(self, item);
+    }
+
+    fn visit_item_static(&mut self, item: &'ast syn::ItemStatic) {
+        let mutable = matches!(item.mutability, syn::StaticMutability::Mut(_));
+        self.push_typed(
+            item.ident.to_string(),
+            VariableKind::Static { mutable },
+            &item.ty,
+            Some(&item.expr),
+        );
+        syn::visit::visit_item_static(self, item);
+    }
+
+    fn visit_impl_item_const(&mut self, item: &'ast syn::ImplItemConst) {
+        self.push_typed(
+            item.ident.to_string(),
+            VariableKind::Const,
+            &item.ty,
+            Some(&item.expr),
+        );
+        syn::visit::visit_impl_item_const(self, item);
+    }
+
+    fn visit_trait_item_const(&mut self, item: &'ast syn::TraitItemConst) {
+        if let Some((_, default)) = &item.default {
+            self.push_typed(
+                item.ident.to_string(),
+                VariableKind::Const,
+                &item.ty,
+                Some(default),
+            );
+        }
+        syn::visit::visit_trait_item_const(self, item);
+    }
+
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        // Without a type, literals are listed with the type the literal
+        // gives them, and computed values when their initializer references
+        // a variable listed before.
+        if let (Pat::Tuple(pats), Some(init)) = (&local.pat, &local.init) {
+            if let syn::Expr::Tuple(values) = &*init.expr {
+                if pats.elems.len() == values.elems.len() {
+                    for (pat, value) in pats.elems.iter().zip(&values.elems) {
+                        if let Pat::Ident(ident) = pat {
+                            self.push_literal(ident.ident.to_string(), value);
+                        }
+                    }
+                }
+            }
+        }
+        if let (Pat::Ident(ident), Some(init)) = (&local.pat, &local.init) {
+            let references = dependencies::references(&init.expr);
+            if typed_literal(&init.expr).is_some() {
+                self.push_literal(ident.ident.to_string(), &init.expr);
+            } else if !matches!(*init.expr, syn::Expr::Lit(_))
+                && self.variables.iter().any(|v| references.contains(&v.name))
+            {
+                self.variables.push(Variable {
+                    name: ident.ident.to_string(),
+                    var_type: "_".to_string(),
+                    value: VariableValue::Unknown,
+                    kind: VariableKind::Let,
+                    span: Some(init.expr.span().byte_range()),
+                    approximate: false,
+                    in_macro: false,
+                    edited: false,
+                    original_value: None,
+                    original_text: None,
+                    raw: None,
+                    formula: Some((*init.expr).clone()),
+                    updated_at: None,
+                    radix: numeric::Radix::Decimal,
+                    function: self.functions.last().cloned(),
+                    scope: self.blocks.clone(),
+                    annotation: annotation::Annotation::default(),
+                });
+            }
+        }
+        if let Pat::Type(PatType { pat, ty, .. }) = &local.pat {
+            let init = local.init.as_ref().map(|init| &*init.expr);
+            match (&**pat, &**ty, init) {
+                (Pat::Ident(ident), ty, init) => {
+                    self.push_typed(ident.ident.to_string(), VariableKind::Let, ty, init);
+                }
+                // One variable per element of a flat tuple, each with its own
+                // element of the initializer to rewrite.
+                (Pat::Tuple(pats), Type::Tuple(types), Some(syn::Expr::Tuple(values)))
+                    if pats.elems.len() == types.elems.len()
+                        && pats.elems.len() == values.elems.len() =>
+                {
+                    for ((pat, ty), value) in pats.elems.iter().zip(&types.elems).zip(&values.elems)
+                    {
+                        if let Pat::Ident(ident) = pat {
+                            self.push_typed(
+                                ident.ident.to_string(),
+                                VariableKind::Let,
+                                ty,
+                                Some(value),
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        // What `let Some(x) = … else { … }` binds is listed for reference,
+        // with its initializer as written.
+        if let Some(init) = local.init.as_ref().filter(|init| init.diverge.is_some()) {
+            let (pat, ty) = match &local.pat {
+                Pat::Type(PatType { pat, ty, .. }) => (&**pat, Some(&**ty)),
+                pat => (pat, None),
+            };
+            if let Some((name, var_type)) = let_else_binding(pat, ty) {
+                self.variables.push(Variable {
+                    name,
+                    var_type,
+                    value: VariableValue::Unknown,
+                    kind: VariableKind::Let,
+                    span: Some(init.expr.span().byte_range()),
+                    approximate: false,
+                    in_macro: false,
+                    edited: false,
+                    original_value: None,
+                    original_text: None,
+                    raw: None,
+                    formula: None,
+                    updated_at: None,
+                    radix: numeric::Radix::Decimal,
+                    function: self.functions.last().cloned(),
+                    scope: self.blocks.clone(),
+                    annotation: annotation::Annotation::default(),
+                });
+            }
+        }
+
+        syn::visit::visit_local(self, local);
+    }
+}
+
+/// Name and type of the variable a `Some(x)` or `Ok(x)` let-else pattern
+/// binds, the type being the `T` of the annotation's `Option<T>` or
+/// `Result<T, E>`, or `_` without one.
+fn let_else_binding(pat: &Pat, ty: Option<&Type>) -> Option<(String, String)> {
+    let Pat::TupleStruct(pat) = pat else {
+        return None;
+    };
+    let variant = &pat.path.segments.last()?.ident;
+    if (variant != "Some" && variant != "Ok") || pat.elems.len() != 1 {
+        return None;
+    }
+    let Pat::Ident(ident) = &pat.elems[0] else {
+        return None;
+    };
+    let inner = match ty {
+        Some(Type::Path(path)) => match &path.path.segments.last()?.arguments {
+            syn::PathArguments::AngleBracketed(args) => {
+                args.args.iter().find_map(|arg| match arg {
+                    syn::GenericArgument::Type(ty) => Some(ty),
+                    _ => None,
+                })
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    Some((
+        ident.ident.to_string(),
+        inner.map_or_else(|| "_".to_string(), extract_type),
+    ))
+}
+
+/// The value a variable of type `var_type` starts with before its
+/// initializer is read, `Unknown` for types the panel can't edit.
+fn default_value(var_type: &str) -> VariableValue {
+    match var_type {
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" => VariableValue::Int(0),
+        "i128" => VariableValue::Wide(0),
+        "u64" | "usize" | "u128" => VariableValue::UInt(0),
+        "f32" | "f64" => VariableValue::Float(0.0),
+        "bool" => VariableValue::Bool(false),
+        "&str" | "String" => VariableValue::Str(String::new()),
+        "char" => VariableValue::Char('\0'),
+        "&[u8]" => VariableValue::Bytes(Vec::new()),
+        ty if ty.starts_with("&[u8;") => VariableValue::Bytes(Vec::new()),
+        _ => VariableValue::Unknown,
+    }
+}
+
+fn extract_type(ty: &Type) -> String {
+    types::TypeDesc::of(ty, &|name| {
+        !matches!(default_value(name), VariableValue::Unknown)
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(code: &str) -> MyApp {
+        let mut app = MyApp {
+            code: code.to_string(),
+            ..MyApp::default()
+        };
+        app.parse_variables();
+        app
+    }
+
+    fn index(app: &MyApp, name: &str) -> usize {
+        app.variables
+            .iter()
+            .position(|v| v.name == name)
+            .unwrap_or_else(|| panic!("no variable `{}`", name))
+    }
+
+    /// The value of `name` as a literal, `None` if the panel can't edit it.
+    fn value(app: &MyApp, name: &str) -> Option<String> {
+        format_literal(&app.variables[index(app, name)].value, "")
+    }
+
+    fn edit(app: &mut MyApp, name: &str, value: VariableValue) {
+        let index = index(app, name);
+        app.variables[index].value = value;
+        app.variables[index].edited = true;
+        app.update_code_with_variables();
+    }
+
+    /// Stands in for rustfmt: squeezes runs of spaces and drops the space
+    /// before `;`, indenting the body one level.
+    struct Squeeze;
+
+    impl format::Formatter for Squeeze {
+        fn format(&self, source: &str) -> Result<String, String> {
+            let mut lines = source.lines();
+            let mut formatted = format!("{}\n", lines.next().unwrap_or_default());
+            for line in lines {
+                let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+                if line == "}" {
+                    formatted.push_str("}\n");
+                } else {
+                    formatted.push_str(&format!("    {}\n", line.replace(" ;", ";")));
+                }
+            }
+            Ok(formatted)
+        }
+    }
+
+    /// rustfmt missing from `PATH`.
+    struct Unavailable;
+
+    impl format::Formatter for Unavailable {
+        fn format(&self, _: &str) -> Result<String, String> {
+            Err("Failed to run rustfmt: not found".to_string())
+        }
+    }
+
+    #[test]
+    fn u64_max_displays_and_round_trips() {
+        let mut app = app("fn main() {\n    let hash: u64 = 18446744073709551615;\n}\n");
+        assert_eq!(value(&app, "hash").as_deref(), Some("18446744073709551615"));
+        edit(
+            &mut app,
+            "hash",
+            VariableValue::UInt(u128::from(u64::MAX) - 1),
+        );
+        assert!(app.code.contains("let hash: u64 = 18446744073709551614;"));
+        app.parse_variables();
+        assert_eq!(value(&app, "hash").as_deref(), Some("18446744073709551614"));
+    }
+
+    #[test]
+    fn unsigned_types_keep_their_full_range() {
+        let app = app("fn main() {\n\
+                       let a: usize = 0;\n\
+                       let b: u64 = 9223372036854775808;\n\
+                       let c: u128 = 340282366920938463463374607431768211455;\n\
+                       let d = 18446744073709551615u64;\n\
+                       let e = 0xFFFF_FFFF_FFFF_FFFFu64;\n\
+                       }\n");
+        assert_eq!(value(&app, "a").as_deref(), Some("0"));
+        assert_eq!(value(&app, "b").as_deref(), Some("9223372036854775808"));
+        assert_eq!(
+            value(&app, "c").as_deref(),
+            Some("340282366920938463463374607431768211455")
+        );
+        assert_eq!(value(&app, "d").as_deref(), Some("18446744073709551615"));
+        assert_eq!(value(&app, "e").as_deref(), Some("18446744073709551615"));
+    }
+
+    #[test]
+    fn out_of_range_unsigned_literals_are_left_alone() {
+        let app = app("fn main() {\n\
+                       let over: u64 = 18446744073709551616;\n\
+                       let negative: u64 = -1;\n\
+                       let zero: u64 = -0;\n\
+                       }\n");
+        assert_eq!(value(&app, "over"), None);
+        assert_eq!(value(&app, "negative"), None);
+        assert_eq!(value(&app, "zero").as_deref(), Some("0"));
+    }
+
+    #[test]
+    fn unsigned_write_back_keeps_radix_suffix_and_grouping() {
+        let mut app = app("fn main() {\n    let mask = 0x0000_FFFFu64;\n}\n");
+        edit(&mut app, "mask", VariableValue::UInt(u128::from(u64::MAX)));
+        assert!(
+            app.code.contains("let mask = 0xFFFF_FFFF_FFFF_FFFFu64;"),
+            "{}",
+            app.code
+        );
+    }
+
+    #[test]
+    fn u128_max_writes_back_whole() {
+        let mut app = app("fn main() {\n    let id: u128 = 1;\n}\n");
+        edit(&mut app, "id", VariableValue::UInt(u128::MAX));
+        assert!(app
+            .code
+            .contains("let id: u128 = 340282366920938463463374607431768211455;"));
+    }
+
+    #[test]
+    fn i128_extremes_round_trip() {
+        let mut app = app("fn main() {\n    let low: i128 = 0;\n}\n");
+        edit(&mut app, "low", VariableValue::Wide(i128::MIN));
+        assert!(app
+            .code
+            .contains("let low: i128 = -170141183460469231731687303715884105728;"));
+        app.parse_variables();
+        assert_eq!(
+            value(&app, "low").as_deref(),
+            Some("-170141183460469231731687303715884105728")
+        );
+    }
+
+    #[test]
+    fn computed_unsigned_values_follow_their_inputs() {
+        let mut app = app("fn main() {\n    let n: usize = 4;\n    let total: usize = n * 2;\n}\n");
+        assert_eq!(value(&app, "total").as_deref(), Some("8"));
+        edit(&mut app, "n", VariableValue::UInt(21));
+        app.recompute_variables(false);
+        assert_eq!(value(&app, "total").as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn unsigned_casts_stay_editable() {
+        let mut app = app("fn main() {\n    let n = 5 as u64;\n}\n");
+        assert_eq!(value(&app, "n").as_deref(), Some("5"));
+        edit(&mut app, "n", VariableValue::UInt(6));
+        assert!(app.code.contains("let n = 6 as u64;"), "{}", app.code);
+    }
+
+    #[test]
+    fn pasted_unsigned_values_are_checked_against_the_type() {
+        let app = app("fn main() {\n    let a: u64 = 1;\n    let b: usize = 1;\n}\n");
+        let a = &app.variables[index(&app, "a")];
+        assert!(matches!(
+            parse_pasted("18446744073709551615", a),
+            Ok(VariableValue::UInt(value)) if value == u128::from(u64::MAX)
+        ));
+        assert_eq!(
+            parse_pasted("18446744073709551616", a).err().as_deref(),
+            Some("Out of range for u64")
+        );
+        assert_eq!(
+            parse_pasted("-1", a).err().as_deref(),
+            Some("A u64 can't be negative")
+        );
+        let b = &app.variables[index(&app, "b")];
+        assert!(matches!(
+            parse_pasted("1 << 10", b),
+            Ok(VariableValue::UInt(1024))
+        ));
+    }
+
+    #[test]
+    fn unparsable_code_reports_every_edited_value_unwritten() {
+        let mut app = app("fn main() {\n\
+                           let a = 1;\n\
+                           let b = 2;\n\
+                           const C: i32 = 3;\n\
+                           static D: bool = false;\n\
+                           let e = a + 1;\n\
+                           }\n");
+        for (name, value) in [
+            ("a", VariableValue::Int(10)),
+            ("C", VariableValue::Int(30)),
+            ("D", VariableValue::Bool(true)),
+        ] {
+            let index = index(&app, name);
+            app.variables[index].value = value;
+            app.variables[index].edited = true;
+        }
+        let e = index(&app, "e");
+        app.variables[e].edited = true;
+        app.code = app.code.replace("let b = 2;", "let b = ;");
+        let before = app.code.clone();
+        app.update_code_with_variables();
+        assert_eq!(app.code, before);
+        assert_eq!(app.unwritten, vec!["a", "C", "D"]);
+
+        // Once it parses again they're written and the warning goes.
+        app.code = app.code.replace("let b = ;", "let b = 2;");
+        app.update_code_with_variables();
+        assert!(app.unwritten.is_empty());
+        assert!(app.code.contains("let a = 10;"));
+        assert!(app.code.contains("const C: i32 = 30;"));
+        assert!(app.code.contains("static D: bool = true;"));
+    }
+
+    #[test]
+    fn locked_values_are_not_reported_unwritten() {
+        let mut app = app("fn main() {\n    let a = 1;\n}\n");
+        app.variables[0].value = VariableValue::Int(2);
+        app.variables[0].edited = true;
+        app.panel.toggle_lock(&app.variables, &app.code, 0);
+        app.code.push('{');
+        app.update_code_with_variables();
+        assert!(app.unwritten.is_empty());
+    }
+
+    #[test]
+    fn fits_type_knows_unsigned_limits() {
+        assert!(fits_type(&VariableValue::UInt(u128::from(u64::MAX)), "u64"));
+        assert!(!fits_type(
+            &VariableValue::UInt(u128::from(u64::MAX) + 1),
+            "u64"
+        ));
+        assert!(fits_type(&VariableValue::UInt(u128::MAX), "u128"));
+        assert!(!fits_type(&VariableValue::Int(1), "u64"));
+        assert!(!fits_type(&VariableValue::UInt(1), "i64"));
+    }
+
+    const UNTIDY: &str = "fn main() {\n    let speed: f64 = 2.5 ;\n    let label   =   \"slow\" ;\n    if true {\n        let count  =  3 ;\n    }\n}\n";
+
+    fn edit_untidy(formatter: Box<dyn format::Formatter>, format_rewrites: bool) -> MyApp {
+        let mut app = MyApp {
+            code: UNTIDY.to_string(),
+            format_rewrites,
+            formatter,
+            ..MyApp::default()
+        };
+        app.parse_variables();
+        edit(&mut app, "speed", VariableValue::Float(4.0));
+        edit(&mut app, "count", VariableValue::Int(7));
+        app
+    }
+
+    #[test]
+    fn rewritten_statements_are_formatted() {
+        let app = edit_untidy(Box::new(Squeeze), true);
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let speed: f64 = 4.0;\n    let label   =   \"slow\" ;\n    if true {\n        let count = 7;\n    }\n}\n"
+        );
+        assert!(app.export_message.is_none());
+    }
+
+    #[test]
+    fn without_formatting_values_are_spliced_raw() {
+        let app = edit_untidy(Box::new(Squeeze), false);
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let speed: f64 = 4.0 ;\n    let label   =   \"slow\" ;\n    if true {\n        let count  =  7 ;\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn a_missing_formatter_falls_back_to_raw_splicing() {
+        let app = edit_untidy(Box::new(Unavailable), true);
+        assert_eq!(app.code, edit_untidy(Box::new(Squeeze), false).code);
+        assert_eq!(
+            app.export_message.as_deref(),
+            Some("Values written unformatted: Failed to run rustfmt: not found")
+        );
+    }
+
+    #[test]
+    fn safe_mode_never_runs_the_formatter() {
+        let mut app = MyApp {
+            code: UNTIDY.to_string(),
+            format_rewrites: true,
+            formatter: Box::new(Unavailable),
+            capabilities: Capabilities::safe_mode(),
+            ..MyApp::default()
+        };
+        app.parse_variables();
+        edit(&mut app, "speed", VariableValue::Float(4.0));
+        assert!(app.code.contains("let speed: f64 = 4.0 ;"));
+        assert!(app.export_message.is_none());
+    }
+
+    #[test]
+    fn tab_indented_declarations_are_rewritten_in_place() {
+        let mut app = app("fn main() {\n\tif true {\n\t\tlet mut x: i32 = 5;\n\t}\n}\n");
+        edit(&mut app, "x", VariableValue::Int(6));
+        assert_eq!(
+            app.code,
+            "fn main() {\n\tif true {\n\t\tlet mut x: i32 = 6;\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn declarations_without_spaces_are_rewritten() {
+        let mut app = app("fn main() {\n    let x:i32=5;\n}\n");
+        edit(&mut app, "x", VariableValue::Int(-12));
+        assert_eq!(app.code, "fn main() {\n    let x:i32=-12;\n}\n");
+    }
+
+    #[test]
+    fn declarations_split_across_lines_are_rewritten() {
+        let mut app =
+            app("fn main() {\n    let x\n        : i32\n        =\n        5\n        ;\n}\n");
+        edit(&mut app, "x", VariableValue::Int(9));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let x\n        : i32\n        =\n        9\n        ;\n}\n"
+        );
+    }
+
+    #[test]
+    fn unedited_declarations_keep_their_exact_text() {
+        let code =
+            "fn main() {\n    let a: f64 = 1.50;\n    let b = 0x10;\n    let c: i32 = 1;\n}\n";
+        let mut app = app(code);
+        edit(&mut app, "c", VariableValue::Int(2));
+        assert_eq!(app.code, code.replace("c: i32 = 1", "c: i32 = 2"));
+    }
+
+    #[test]
+    fn shadowed_declarations_are_rewritten_separately() {
+        let mut app = app("fn main() {\n    let y = 2;\n    let y = 3;\n}\n");
+        let last = app.variables.iter().rposition(|v| v.name == "y").unwrap();
+        app.variables[last].value = VariableValue::Int(30);
+        app.variables[last].edited = true;
+        app.update_code_with_variables();
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let y = 2;\n    let y = 30;\n}\n"
+        );
+    }
+
+    #[test]
+    fn lets_in_comments_and_strings_are_left_alone() {
+        let code = "/// Example:\n/// let x: i32 = 1;\nfn main() {\n    // let x: i32 = 1;\n    /* let x: i32 = 1; */\n    let doc = \"let x: i32 = 1;\";\n    let x: i32 = 1;\n    println!(\"{} {}\", doc, x);\n}\n";
+        let mut app = app(code);
+        edit(&mut app, "x", VariableValue::Int(2));
+        assert_eq!(
+            app.code,
+            code.replace(
+                "    let x: i32 = 1;\n    println",
+                "    let x: i32 = 2;\n    println"
+            )
+        );
+        assert_eq!(app.code.matches("let x: i32 = 1;").count(), 4);
+    }
+
+    #[test]
+    fn a_string_holding_code_is_only_rewritten_as_a_whole() {
+        let mut app =
+            app("fn main() {\n    let src: &str = \"let n: i32 = 1;\";\n    let n: i32 = 1;\n}\n");
+        edit(&mut app, "n", VariableValue::Int(5));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let src: &str = \"let n: i32 = 1;\";\n    let n: i32 = 5;\n}\n"
+        );
+    }
+
+    #[test]
+    fn semicolons_inside_string_initializers_end_nothing() {
+        let mut app =
+            app("fn main() {\n    let q: &str = \"SELECT 1; SELECT 2\";\n    let n: i32 = 1;\n}\n");
+        assert_eq!(value(&app, "q").as_deref(), Some("\"SELECT 1; SELECT 2\""));
+        edit(
+            &mut app,
+            "q",
+            VariableValue::Str("SELECT 3; SELECT 4;".to_string()),
+        );
+        edit(&mut app, "n", VariableValue::Int(2));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let q: &str = \"SELECT 3; SELECT 4;\";\n    let n: i32 = 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn multi_line_initializers_are_replaced_whole() {
+        let mut app = app("fn main() {\n    let text: &str = \"first; \\\n        second;\";\n    let n: i32 = 1;\n}\n");
+        assert_eq!(value(&app, "text").as_deref(), Some("\"first; second;\""));
+        edit(&mut app, "text", VariableValue::Str("one".to_string()));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let text: &str = \"one\";\n    let n: i32 = 1;\n}\n"
+        );
+        app.parse_variables();
+        assert_eq!(value(&app, "n").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn trailing_line_comments_survive_write_back() {
+        let mut app = app("fn main() {\n    let retries: u32 = 3; // max attempts; then give up\n    let delay: u64 = 10;// ms\n}\n");
+        edit(&mut app, "retries", VariableValue::UInt(5));
+        edit(&mut app, "delay", VariableValue::UInt(250));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let retries: u32 = 5; // max attempts; then give up\n    let delay: u64 = 250;// ms\n}\n"
+        );
+    }
+
+    #[test]
+    fn block_comments_around_the_initializer_survive_write_back() {
+        let mut app = app("fn main() {\n    let rate: f64 = /* per second */ 0.5 /* tuned; see notes */;\n    let n: i32 = 1; /* trailing;\n       block */\n}\n");
+        edit(&mut app, "rate", VariableValue::Float(0.75));
+        edit(&mut app, "n", VariableValue::Int(4));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let rate: f64 = /* per second */ 0.75 /* tuned; see notes */;\n    let n: i32 = 4; /* trailing;\n       block */\n}\n"
+        );
+    }
+
+    fn string_of(app: &MyApp, name: &str) -> String {
+        match &app.variables[index(app, name)].value {
+            VariableValue::Str(value) => value.clone(),
+            _ => panic!("`{}` isn't a string", name),
+        }
+    }
+
+    /// Writes `value` into `s`, checks the literal compiles back to it and
+    /// that the panel reads the same value again.
+    fn round_trip(code: &str, value: &str) -> String {
+        let mut app = app(code);
+        edit(&mut app, "s", VariableValue::Str(value.to_string()));
+        let file = syn::parse_file(&app.code).expect("write-back left invalid code");
+        let mut literals = Vec::new();
+        struct Strings<'a>(&'a mut Vec<String>);
+        impl<'ast> Visit<'ast> for Strings<'_> {
+            fn visit_lit_str(&mut self, lit: &'ast syn::LitStr) {
+                self.0.push(lit.value());
+            }
+        }
+        Strings(&mut literals).visit_file(&file);
+        assert_eq!(literals, vec![value.to_string()]);
+        app.parse_variables();
+        assert_eq!(string_of(&app, "s"), value);
+        app.code
+    }
+
+    #[test]
+    fn escaped_literals_display_unescaped() {
+        let app = app(
+            "fn main() {\n    let s: &str = \"he said \\\"hi\\\"\\n\\tC:\\\\dir \\u{e9}\\x41\";\n}\n",
+        );
+        assert_eq!(string_of(&app, "s"), "he said \"hi\"\n\tC:\\dir \u{e9}A");
+    }
+
+    #[test]
+    fn quotes_backslashes_and_newlines_are_escaped() {
+        let code = round_trip(
+            "fn main() {\n    let s: &str = \"plain\";\n}\n",
+            "he said \"hi\"\nC:\\dir\r\n\ttab",
+        );
+        assert!(
+            code.contains(r#"let s: &str = "he said \"hi\"\nC:\\dir\r\n\ttab";"#),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn other_control_characters_are_escaped() {
+        round_trip(
+            "fn main() {\n    let s: String = String::from(\"x\");\n}\n",
+            "bell\u{7} nul\0 esc\u{1b} del\u{7f}",
+        );
+    }
+
+    #[test]
+    fn backslash_heavy_values_become_raw_strings() {
+        let code = round_trip(
+            "fn main() {\n    let s: &str = \"plain\";\n}\n",
+            "C:\\Users\\me\\Documents",
+        );
+        assert!(
+            code.contains(r#"let s: &str = r"C:\Users\me\Documents";"#),
+            "{}",
+            code
+        );
+    }
+
+    #[test]
+    fn raw_strings_grow_hashes_for_quotes() {
+        let code = round_trip(
+            "fn main() {\n    let s: &str = r\"a\\b\";\n}\n",
+            "say \"#hi\"# \\ there",
+        );
+        assert!(
+            code.contains("let s: &str = r##\"say \"#hi\"# \\ there\"##;"),
+            "{}",
+            code
+        );
+        // A carriage return can't go in a raw string.
+        round_trip(
+            "fn main() {\n    let s: &str = r\"a\\b\";\n}\n",
+            "line\r\nnext \\ \"q\"",
+        );
+    }
+
+    #[test]
+    fn markers_in_nested_calls_are_written_back() {
+        let mut app = app("fn main() {\n    outer(inner(/* crowbar: a */ 1, /* crowbar: b */ 2.0), /* crowbar: c */ -3);\n}\n");
+        assert_eq!(value(&app, "c").as_deref(), Some("-3"));
+        edit(&mut app, "a", VariableValue::Int(10));
+        edit(&mut app, "c", VariableValue::Int(4));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    outer(inner(/* crowbar: a */ 10, /* crowbar: b */ 2.0), /* crowbar: c */ 4);\n}\n"
+        );
+    }
+
+    /// Points the config dir at a fresh scratch folder holding `files`. Held
+    /// for the whole test since the config dir comes from the environment.
+    fn corrupted_config(
+        name: &str,
+        files: &[(&str, &[u8])],
+    ) -> (std::sync::MutexGuard<'static, ()>, PathBuf) {
+        static CONFIG_DIR: Mutex<()> = Mutex::new(());
+        let guard = CONFIG_DIR.lock().unwrap_or_else(|e| e.into_inner());
+        let base =
+            std::env::temp_dir().join(format!("crowbar-test-main-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&base);
+        std::env::set_var("XDG_CONFIG_HOME", &base);
+        let dir = paths::config_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+        (guard, base)
+    }
+
+    const GARBAGE: &[u8] = b"\xff\xfe{\"name\": \x00\x01 not json [[[";
+
+    #[test]
+    fn startup_survives_a_corrupted_settings_blob() {
+        let (_guard, base) = corrupted_config(
+            "corrupt",
+            &[
+                ("snippets.json", GARBAGE),
+                ("dictionary.txt", GARBAGE),
+                ("onboarding-done", GARBAGE),
+            ],
+        );
+        let app = MyApp::new(Capabilities::default());
+        assert!(app.snippets.is_empty());
+        let error = app.snippet_error.as_deref().unwrap();
+        assert!(error.starts_with("Failed to read snippets"), "{}", error);
+        assert!(app.dictionary.contains("variable"));
+        assert!(app.tour.is_none());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn invalid_json_settings_are_reported_not_fatal() {
+        let (_guard, base) = corrupted_config(
+            "invalid-json",
+            &[
+                ("snippets.json", b"{\"name\": \"unterminated"),
+                ("onboarding-done", b""),
+            ],
+        );
+        let app = MyApp::new(Capabilities::default());
+        assert!(app.snippets.is_empty());
+        assert!(app
+            .snippet_error
+            .as_deref()
+            .unwrap()
+            .starts_with("Snippets file is invalid"));
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn safe_mode_reads_nothing_until_enabled() {
+        let (_guard, base) = corrupted_config(
+            "safe",
+            &[("snippets.json", b"[1, 2"), ("dictionary.txt", b"zzyzx\n")],
+        );
+        let mut app = MyApp::new(Capabilities::from_args(
+            ["--safe-mode".to_string()].into_iter(),
+        ));
+        assert_eq!(app.capabilities.vetoed(), &Capability::ALL);
+        assert!(app.snippet_error.is_none());
+        assert!(app.tour.is_none());
+        assert!(app.leftover_processes.is_empty());
+        assert!(!app.dictionary.contains("zzyzx"));
+
+        app.enable_capability(Capability::UserDictionary);
+        assert!(app.dictionary.contains("zzyzx"));
+        app.enable_capability(Capability::StoredSettings);
+        assert!(app.snippet_error.is_some());
+        assert!(app.capabilities.allows(Capability::StoredSettings));
+        assert!(!app.capabilities.allows(Capability::ExternalProcesses));
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn resetting_backs_up_the_corrupted_state() {
+        let (_guard, base) = corrupted_config("reset", &[("snippets.json", GARBAGE)]);
+        let backup = paths::reset_config_dir().unwrap();
+        assert_eq!(
+            std::fs::read(backup.join("snippets.json")).unwrap(),
+            GARBAGE
+        );
+        assert!(!paths::config_dir().exists());
+        let app = MyApp::new(Capabilities::default());
+        assert!(app.snippet_error.is_none());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn approximate_variables_turn_exact_once_the_code_parses() {
+        let mut app = app("fn main() {\n    let speed: f64 = 2.5;\n    match speed {\n}\n");
+        assert!(app.variables.iter().all(|v| v.approximate));
+        assert_eq!(value(&app, "speed").as_deref(), Some("2.5"));
+        app.code = "fn main() {\n    let speed: f64 = 2.5;\n}\n".to_string();
+        app.parse_variables();
+        assert!(!app.variables[index(&app, "speed")].approximate);
+    }
+
+    #[test]
+    fn write_back_keeps_the_shebang_byte_exact() {
+        let mut app = app("#!/usr/bin/env -S rust-script --debug\r\n//! ```cargo\n//! [dependencies]\n//! rand = \"0.8\"\n//! ```\nfn main() {\n    let n: i32 = 1;\n}\n");
+        edit(&mut app, "n", VariableValue::Int(2));
+        assert!(app
+            .code
+            .starts_with("#!/usr/bin/env -S rust-script --debug\r\n//! ```cargo\n"));
+        assert!(app.code.contains("let n: i32 = 2;"), "{}", app.code);
+        app.parse_variables();
+        assert_eq!(value(&app, "n").as_deref(), Some("2"));
+    }
+
+    /// The macro fixtures from the request: nothing inside a macro body is
+    /// listed, and editing the real binding leaves every body untouched.
+    fn macro_fixture() -> &'static str {
+        "macro_rules! make {\n    ($v:expr) => {{ let made: i32 = $v; made }};\n}\n\
+         lazy_static! {\n    static ref LIMIT: i32 = 5;\n}\n\
+         fn main() {\n    let n: i32 = 1;\n    println!(\"let n: i32 = {}; let q: u8 = 3;\", n);\n    \
+         vec![{ let hidden: u8 = 4; hidden }];\n    make!({ let arg: i32 = 9; arg });\n}\n"
+    }
+
+    #[test]
+    fn bindings_inside_macros_are_not_listed() {
+        let app = app(macro_fixture());
+        assert!(app.parse_error.is_none(), "{:?}", app.parse_error);
+        let names: Vec<&str> = app.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["n"]);
+        assert!(app.variables.iter().all(|v| !v.in_macro && !v.approximate));
+    }
+
+    #[test]
+    fn editing_leaves_macro_bodies_alone() {
+        let mut app = app(macro_fixture());
+        edit(&mut app, "n", VariableValue::Int(2));
+        assert_eq!(
+            app.code,
+            macro_fixture().replace("    let n: i32 = 1;\n", "    let n: i32 = 2;\n")
+        );
+    }
+
+    #[test]
+    fn broken_code_marks_macro_bindings_as_not_editable() {
+        let code = format!("{}fn broken( {{\n", macro_fixture());
+        let mut app = app(&code);
+        let flagged: Vec<(&str, bool)> = app
+            .variables
+            .iter()
+            .filter(|v| v.kind == VariableKind::Let)
+            .map(|v| (v.name.as_str(), v.in_macro))
+            .collect();
+        assert_eq!(flagged, vec![("n", false), ("hidden", true), ("arg", true)]);
+        // Approximate bindings are never written back, in a macro or not.
+        for variable in &mut app.variables {
+            variable.value = VariableValue::Int(7);
+            variable.edited = true;
+        }
+        app.update_code_with_variables();
+        assert_eq!(app.code, code);
+    }
+
+    #[test]
+    fn string_initializers_are_recognized() {
+        let app = app("fn main() {\n\
+                       let a: String = String::from(\"from\");\n\
+                       let b: String = std::string::String::from(\"qualified\");\n\
+                       let c: String = \"method\".to_string();\n\
+                       let d: String = \"owned\".to_owned();\n\
+                       let e: String = String::new();\n\
+                       let f = String::from(\"untyped\");\n\
+                       let g = \"untyped\".to_string();\n\
+                       }\n");
+        for (name, expected) in [
+            ("a", "\"from\""),
+            ("b", "\"qualified\""),
+            ("c", "\"method\""),
+            ("d", "\"owned\""),
+            ("e", "\"\""),
+            ("f", "\"untyped\""),
+            ("g", "\"untyped\""),
+        ] {
+            assert_eq!(value(&app, name).as_deref(), Some(expected), "{}", name);
+            assert_eq!(app.variables[index(&app, name)].var_type, "String");
+        }
+    }
+
+    #[test]
+    fn string_initializers_keep_their_form_when_edited() {
+        let mut app = app("fn main() {\n\
+                           let a: String = String::from(\"x\");\n\
+                           let b: String = std::string::String::from(\"x\");\n\
+                           let c: String = \"x\".to_string();\n\
+                           let d: String = \"x\".to_owned();\n\
+                           let e: String = String::new();\n\
+                           let f = String::new();\n\
+                           }\n");
+        for name in ["a", "b", "c", "d", "e", "f"] {
+            let index = index(&app, name);
+            app.variables[index].value = VariableValue::Str("new".to_string());
+            app.variables[index].edited = true;
+        }
+        app.update_code_with_variables();
+        assert_eq!(
+            app.code,
+            "fn main() {\n\
+             let a: String = String::from(\"new\");\n\
+             let b: String = std::string::String::from(\"new\");\n\
+             let c: String = \"new\".to_string();\n\
+             let d: String = \"new\".to_owned();\n\
+             let e: String = String::from(\"new\");\n\
+             let f = String::from(\"new\");\n\
+             }\n"
+        );
+        app.parse_variables();
+        for name in ["a", "b", "c", "d", "e", "f"] {
+            assert_eq!(value(&app, name).as_deref(), Some("\"new\""), "{}", name);
+        }
+    }
+
+    #[test]
+    fn other_string_calls_are_computed_not_literals() {
+        let code = "fn main() {\n\
+                    let prefix = \"p\";\n\
+                    let a: String = String::from(prefix);\n\
+                    let b: String = format!(\"x\");\n\
+                    let c: String = \"x\".repeat(2);\n\
+                    let d: String = String::with_capacity(4);\n\
+                    }\n";
+        let mut app = app(code);
+        for name in ["a", "b", "c", "d"] {
+            assert!(
+                app.variables[index(&app, name)].formula.is_some(),
+                "{}",
+                name
+            );
+        }
+        app.update_code_with_variables();
+        assert_eq!(app.code, code);
+    }
+
+    /// The code after `name` is edited to `value`, starting from `let
+    /// name: ty = literal;`.
+    fn float_written(ty: &str, literal: &str, value: f64) -> String {
+        let declaration = if ty.is_empty() {
+            format!("let x = {};", literal)
+        } else {
+            format!("let x: {} = {};", ty, literal)
+        };
+        let mut app = app(&format!("fn main() {{\n    {}\n}}\n", declaration));
+        edit(&mut app, "x", VariableValue::Float(value));
+        let line = app.code.lines().nth(1).unwrap().trim();
+        line.to_string()
+    }
+
+    #[test]
+    fn whole_floats_keep_their_point() {
+        assert_eq!(float_written("f64", "2.0", 2.0), "let x: f64 = 2.0;");
+        assert_eq!(float_written("f64", "2.0", 3.0), "let x: f64 = 3.0;");
+        // Untyped, a missing `.0` would make it an integer.
+        assert_eq!(float_written("", "2.0", 5.0), "let x = 5.0;");
+        assert_eq!(float_written("", "2.", 5.0), "let x = 5.0;");
+        assert_eq!(float_written("", "2f32", 4.0), "let x = 4.0f32;");
+        assert_eq!(float_written("", "2.0_f64", 7.0), "let x = 7.0_f64;");
+        assert_eq!(float_written("", "2_f32", 0.5), "let x = 0.5_f32;");
+    }
+
+    #[test]
+    fn short_floats_stay_short() {
+        assert_eq!(float_written("f64", "0.1", 0.1), "let x: f64 = 0.1;");
+        assert_eq!(float_written("f64", "0.1", 0.1 + 0.2), "let x: f64 = 0.3;");
+        assert_eq!(
+            float_written("f32", "0.1", f64::from(0.1f32)),
+            "let x: f32 = 0.1;"
+        );
+        assert_eq!(
+            float_written("f32", "0.1", f64::from(0.1f32 + 0.2f32)),
+            "let x: f32 = 0.3;"
+        );
+        assert_eq!(float_written("f64", "1e-9", 1e-9), "let x: f64 = 1e-9;");
+        assert_eq!(float_written("f64", "1e-9", 2e-9), "let x: f64 = 2e-9;");
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn precision_of_the_original_is_kept() {
+        assert_eq!(
+            float_written("f64", "3.14159", 3.14159),
+            "let x: f64 = 3.14159;"
+        );
+        assert_eq!(
+            float_written("f64", "3.14159", 3.5),
+            "let x: f64 = 3.50000;"
+        );
+        assert_eq!(
+            float_written("f64", "3.14159", 2.718281828),
+            "let x: f64 = 2.718281828;"
+        );
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn drag_edited_floats_are_tidy() {
+        // Dragging moves by a hundredth of the original per pixel.
+        let speed = numeric::drag_speed(0.1, false);
+        let dragged = (0..7).fold(0.1, |value, _| value + speed);
+        assert_ne!(dragged.to_string(), "0.107");
+        assert_eq!(float_written("f64", "0.1", dragged), "let x: f64 = 0.107;");
+
+        let speed = numeric::drag_speed(2.0, false);
+        let dragged = (0..50).fold(2.0, |value, _| value + speed);
+        assert_eq!(float_written("", "2.0", dragged), "let x = 3.0;");
+
+        let speed = numeric::drag_speed(3.14159, false);
+        let dragged = (0..3).fold(3.14159, |value, _| value - speed);
+        assert_eq!(
+            float_written("f64", "3.14159", dragged),
+            "let x: f64 = 3.0473423;"
+        );
+    }
+
+    #[test]
+    fn picked_decimals_override_the_precision() {
+        let mut app = app("fn main() {\n    let x: f64 = 3.14159;\n    let y: f64 = 1.0;\n}\n");
+        for (name, decimals) in [("x", 2), ("y", 0)] {
+            let key = panel::VariableKey::of(&app.variables[index(&app, name)]);
+            app.panel.decimals.insert(key, decimals);
+        }
+        let x = index(&app, "x");
+        app.variables[x].value = VariableValue::Float(2.0 / 3.0);
+        app.variables[x].edited = true;
+        edit(&mut app, "y", VariableValue::Float(4.0));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let x: f64 = 0.67;\n    let y: f64 = 4.0;\n}\n"
+        );
+    }
+
+    #[test]
+    fn non_finite_floats_are_reported_not_written() {
+        let code =
+            "fn main() {\n    let x: f64 = 1.5;\n    let y: f32 = /* crowbar: y */ 2.0;\n}\n";
+        let mut app = app(code);
+        let y = app
+            .variables
+            .iter()
+            .position(|v| v.kind == VariableKind::Marker)
+            .unwrap();
+        app.variables[y].value = VariableValue::Float(f64::NAN);
+        app.variables[y].edited = true;
+        edit(&mut app, "x", VariableValue::Float(f64::INFINITY));
+        assert_eq!(app.code, code);
+        assert_eq!(app.unwritten, vec!["y".to_string(), "x".to_string()]);
+        assert_eq!(value(&app, "x"), None);
+    }
+
+    #[test]
+    fn integer_suffix_separators_are_kept() {
+        let mut app = app("fn main() {\n    let n = 5_u8;\n    let m = 0x10i64;\n}\n");
+        edit(&mut app, "n", VariableValue::Int(6));
+        edit(&mut app, "m", VariableValue::Int(32));
+        assert_eq!(
+            app.code,
+            "fn main() {\n    let n = 6_u8;\n    let m = 0x20i64;\n}\n"
+        );
+    }
+
+    #[test]
+    fn sweeps_make_their_write_back_undoable() {
+        let code = "fn main() {\n    let n: i32 = 1;\n    let k: i32 = 5;\n}\n";
+        let mut app = app(code);
+        // An edit made in the panel that wasn't written back yet.
+        let k = index(&app, "k");
+        app.variables[k].value = VariableValue::Int(7);
+        app.variables[k].edited = true;
+
+        let ctx = egui::Context::default();
+        egui::text_edit::TextEditState::default().store(&ctx, code_editor_id());
+        app.open_sweep(index(&app, "n"));
+        app.sweep.as_mut().unwrap().list = "2, 3, 2".to_string();
+        let sources = app.prepare_sweep(&ctx).unwrap();
+
+        let edited = code.replace("k: i32 = 5", "k: i32 = 7");
+        assert_eq!(app.code, edited);
+        let labels: Vec<&str> = sources.iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, vec!["2", "3"]);
+        assert!(sources[1]
+            .1
+            .contains("let n: i32 = 3;\n    let k: i32 = 7;"));
+
+        let state = egui::TextEdit::load_state(&ctx, code_editor_id()).unwrap();
+        let mut undoer = state.undoer();
+        let current = (egui::text::CCursorRange::default(), app.code.clone());
+        let undone = undoer.undo(&current).map(|(_, code)| code.clone());
+        assert_eq!(undone.as_deref(), Some(code));
+    }
+
+    #[test]
+    fn invalid_sweeps_leave_the_buffer_alone() {
+        let code = "fn main() {\n    let n: i32 = 1;\n}\n";
+        let mut app = app(code);
+        let ctx = egui::Context::default();
+        app.open_sweep(index(&app, "n"));
+        app.sweep.as_mut().unwrap().list = "one".to_string();
+        assert!(app.prepare_sweep(&ctx).is_err());
+        app.sweep.as_mut().unwrap().list = String::new();
+        app.sweep.as_mut().unwrap().step = "x".to_string();
+        assert_eq!(
+            app.prepare_sweep(&ctx),
+            Err("The step isn't a number".to_string())
+        );
+        assert_eq!(app.code, code);
+    }
+
+    #[test]
+    fn editing_one_variable_leaves_every_other_byte_alone() {
+        let code = "fn main() {\n\
+                    let c: char = '\\x41';\n\
+                    let e: char = '\\u{e9}';\n\
+                    let raw: &[u8] = br\"abc\";\n\
+                    let padded: i32 = 007;\n\
+                    let spaced: i32 = - 5;\n\
+                    let big: f64 = 1.50e3;\n\
+                    let x: i32 = 1;\n\
+                    let s: String = \"\\x41\".to_string();\n\
+                    let m = /* crowbar: m */ 0x0F;\n\
+                    }\n";
+        let mut app = app(code);
+        edit(&mut app, "x", VariableValue::Int(2));
+        assert_eq!(app.code, code.replace("let x: i32 = 1;", "let x: i32 = 2;"));
+        // Writing back again, as live sync does, changes nothing more.
+        app.parse_variables();
+        app.update_code_with_variables();
+        assert_eq!(app.code, code.replace("let x: i32 = 1;", "let x: i32 = 2;"));
+    }
+
+    #[test]
+    fn constant_arithmetic_fills_wide_and_unsigned_types() {
+        let app = app("fn main() {\n\
+                       let size: usize = 1024 * 1024;\n\
+                       let mask: u64 = 1 << 40;\n\
+                       let huge: u128 = 2 * 3;\n\
+                       let big: i128 = 2 * 3;\n\
+                       let low: i128 = -(1 << 40);\n\
+                       let small: u32 = 2 * 3;\n\
+                       let under: usize = 1 - 2;\n\
+                       }\n");
+        for (name, expected) in [
+            ("size", Some("1048576")),
+            ("mask", Some("1099511627776")),
+            ("huge", Some("6")),
+            ("big", Some("6")),
+            ("low", Some("-1099511627776")),
+            ("small", Some("6")),
+            ("under", None),
+        ] {
+            assert_eq!(value(&app, name).as_deref(), expected, "{}", name);
+        }
+        assert!(matches!(
+            app.variables[index(&app, "big")].value,
+            VariableValue::Wide(6)
+        ));
+        assert!(matches!(
+            app.variables[index(&app, "size")].value,
+            VariableValue::UInt(1048576)
+        ));
+    }
+
+    #[test]
+    fn locking_one_shadowed_binding_leaves_the_other_editable() {
+        let mut app = app("fn main() {\n    let x = 1;\n    let x = 2;\n}\n");
+        app.panel.toggle_lock(&app.variables, &app.code, 1);
+        assert!(!app.is_locked(0));
+        assert!(app.is_locked(1));
+        for index in 0..2 {
+            app.variables[index].value = VariableValue::Int(9);
+            app.variables[index].edited = true;
+        }
+        app.update_code_with_variables();
+        assert_eq!(app.code, "fn main() {\n    let x = 9;\n    let x = 2;\n}\n");
+
+        // The lock stays with its binding as lines move around it.
+        app.code = app.code.replace("{\n", "{\n    let y = 0;\n");
+        app.parse_variables();
+        let x: Vec<usize> = (0..app.variables.len())
+            .filter(|&index| app.variables[index].name == "x")
+            .collect();
+        assert!(!app.is_locked(x[0]));
+        assert!(app.is_locked(x[1]));
+        app.panel.toggle_lock(&app.variables, &app.code, x[1]);
+        assert!(app.panel.locked.is_empty());
+    }
+
+    #[test]
+    fn drag_settings_stay_with_their_shadowed_binding() {
+        let mut app = app("fn main() {\n    let x = 1;\n    let x = 2;\n}\n");
+        let (variables, code) = (&app.variables, &app.code);
+        panel::set_setting(&mut app.panel.speed, variables, code, 0, Some(0.5));
+        panel::set_setting(&mut app.panel.step, variables, code, 1, Some(4.0));
+        let speed =
+            |app: &MyApp, index| panel::setting(&app.panel.speed, &app.variables, &app.code, index);
+        let step =
+            |app: &MyApp, index| panel::setting(&app.panel.step, &app.variables, &app.code, index);
+        assert_eq!((speed(&app, 0), speed(&app, 1)), (Some(0.5), None));
+        assert_eq!((step(&app, 0), step(&app, 1)), (None, Some(4.0)));
+
+        app.code = app.code.replace("{\n", "{\n    let y = 0;\n");
+        app.parse_variables();
+        let x = index(&app, "x");
+        assert_eq!((speed(&app, x), speed(&app, x + 1)), (Some(0.5), None));
+        assert_eq!((step(&app, x), step(&app, x + 1)), (None, Some(4.0)));
+
+        // Picking a setting again replaces it, and clearing it drops it.
+        let (variables, code) = (&app.variables, &app.code);
+        panel::set_setting(&mut app.panel.step, variables, code, x + 1, Some(2.0));
+        assert_eq!(step(&app, x + 1), Some(2.0));
+        let (variables, code) = (&app.variables, &app.code);
+        panel::set_setting(&mut app.panel.step, variables, code, x + 1, None);
+        assert!(app.panel.step.is_empty());
+    }
+
+    #[test]
+    fn edits_stay_with_their_binding_when_one_is_declared_above() {
+        let mut app = app("fn main() {\n    let x = 1;\n    let y = 2;\n}\n");
+        app.variables[0].value = VariableValue::Int(5);
+        app.variables[0].edited = true;
+        app.code = app.code.replace("{\n", "{\n    let x = 0;\n");
+        app.parse_variables();
+        assert!(!app.variables[0].edited);
+        assert_eq!(
+            format_literal(&app.variables[0].value, ""),
+            Some("0".into())
+        );
+        assert!(app.variables[1].edited);
+        assert_eq!(
+            format_literal(&app.variables[1].value, ""),
+            Some("5".into())
+        );
+
+        // Declared below instead, the new one is the one left unedited.
+        app.code = app.code.replace("    let y", "    let x = 3;\n    let y");
+        app.parse_variables();
+        let edited: Vec<bool> = app.variables.iter().map(|v| v.edited).collect();
+        assert_eq!(edited, [false, true, false, false]);
+    }
+
+    #[test]
+    fn offsets_move_with_the_text_around_them() {
+        let old = "let a = 1;\nlet b = 2;\n";
+        let new = "let a = 1;\nlet é = 0;\nlet b = 2;\n";
+        assert_eq!(moved_offset(old, new, 0), 0);
+        assert_eq!(moved_offset(old, new, 11), new.find("let b").unwrap());
+        assert_eq!(moved_offset(old, new, old.len()), new.len());
+        assert_eq!(moved_offset(old, "", 4), 0);
+        assert_eq!(moved_offset(old, old, 15), 15);
+    }
+
+    #[test]
+    fn str_references_round_trip_whatever_their_lifetime() {
+        let code = round_trip("fn main() {\n    let s: &str = \"a\";\n}\n", "b \"c\"");
+        assert!(code.contains("let s: &str = \"b \\\"c\\\"\";"));
+        let code = round_trip("fn main() {\n    let s: &'static str = \"a\";\n}\n", "é\n");
+        assert!(code.contains("let s: &'static str = \"é\\n\";"));
+        let code = round_trip("fn f<'a>() {\n    let s: &'a str = \"a\";\n}\n", "b");
+        assert!(code.contains("let s: &'a str = \"b\";"));
+        let code = round_trip("const s: &str = r\"a\";\n", "b\\c");
+        assert!(code.contains("const s: &str = r\"b\\c\";"));
+        let code = round_trip("static s: &'static str = \"a\";\n", "b");
+        assert!(code.contains("static s: &'static str = \"b\";"));
+    }
+
+    #[test]
+    fn byte_arrays_keep_their_length_and_raw_form() {
+        let code = "fn main() {\n    let a = b\"abc\";\n    let r: &[u8; 3] = br#\"a\"b\"#;\n    let s: &[u8] = br\"x\";\n}\n";
+        let mut app = app(code);
+        edit(&mut app, "a", VariableValue::Bytes(b"xy".to_vec()));
+        assert_eq!(app.code, code);
+        assert_eq!(app.unwritten, ["a"]);
+
+        edit(&mut app, "a", VariableValue::Bytes(b"x\0y".to_vec()));
+        assert!(app.code.contains("let a = b\"x\\x00y\";"));
+        edit(&mut app, "r", VariableValue::Bytes(b"q\"#".to_vec()));
+        assert!(app.code.contains("let r: &[u8; 3] = br##\"q\"#\"##;"));
+        edit(&mut app, "s", VariableValue::Bytes(b"a\\b c".to_vec()));
+        assert!(app.code.contains("let s: &[u8] = br\"a\\b c\";"));
+        // Bytes a raw string can't hold are escaped instead.
+        edit(&mut app, "s", VariableValue::Bytes(vec![0xff]));
+        assert!(app.code.contains("let s: &[u8] = b\"\\xff\";"));
+        assert!(syn::parse_file(&app.code).is_ok());
+
+        assert_eq!(byte_array_len("&'static [u8; 4]"), Some(4));
+        assert_eq!(byte_array_len("&[u8]"), None);
+        assert_eq!(byte_array_len("&[i8; 4]"), None);
+    }
+
+    #[test]
+    fn cast_literals_are_kept_within_the_cast_type() {
+        let mut app = app(
+            "fn main() {\n    let a = 200 as u8;\n    let b = 5 as u64;\n    let c = 1 as i8;\n}\n",
+        );
+        edit(&mut app, "a", VariableValue::Int(300));
+        assert!(app.code.contains("let a = 255 as u8;"));
+        edit(&mut app, "b", VariableValue::Int(-3));
+        assert!(app.code.contains("let b = 0 as u64;"));
+        edit(&mut app, "c", VariableValue::Int(-500));
+        assert!(app.code.contains("let c = -128 as i8;"));
+        edit(&mut app, "a", VariableValue::Int(7));
+        assert!(app.code.contains("let a = 7 as u8;"));
     }
 }
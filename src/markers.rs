@@ -0,0 +1,113 @@
+use crate::lexer::{line_of, tokenize, TokenKind};
+use std::ops::Range;
+
+/// A `/* crowbar: name */` comment followed by the literal it makes tweakable.
+pub struct Marker {
+    pub name: String,
+    /// Byte range of the literal, including a leading `-` for negative numbers.
+    pub span: Range<usize>,
+}
+
+/// Scans `code` for marker comments. Markers that aren't directly followed by
+/// a literal are returned as human-readable warnings instead.
+pub fn find_markers(code: &str) -> (Vec<Marker>, Vec<String>) {
+    let tokens = tokenize(code);
+    let mut markers = Vec::new();
+    let mut warnings = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::BlockComment {
+            continue;
+        }
+        let Some(name) = marker_name(token.text(code)) else {
+            continue;
+        };
+
+        let mut following = tokens[index + 1..].iter().filter(|t| !t.is_trivia());
+        let literal = match following.next() {
+            Some(t) if t.kind == TokenKind::Literal => Some(t.span.clone()),
+            Some(t) if t.text(code) == "-" => following
+                .next()
+                .filter(|next| next.kind == TokenKind::Literal && next.span.start == t.span.end)
+                .map(|next| t.span.start..next.span.end),
+            _ => None,
+        };
+
+        match literal {
+            Some(span) => markers.push(Marker {
+                name: name.to_string(),
+                span,
+            }),
+            None => warnings.push(format!(
+                "Marker `{}` at line {} is not followed by a literal",
+                name,
+                line_of(code, token.span.start)
+            )),
+        }
+    }
+
+    (markers, warnings)
+}
+
+fn marker_name(comment: &str) -> Option<&str> {
+    let inner = comment.strip_prefix("/*")?.strip_suffix("*/")?.trim();
+    let name = inner.strip_prefix("crowbar:")?.trim();
+    (!name.is_empty()).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(code: &str) -> Vec<(String, &str)> {
+        let (markers, warnings) = find_markers(code);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        markers
+            .into_iter()
+            .map(|marker| (marker.name, &code[marker.span]))
+            .collect()
+    }
+
+    #[test]
+    fn several_markers_on_one_line() {
+        let code = "do_work(/* crowbar: iterations */ 1000, /*crowbar:rate*/0.5, /* crowbar: label */ \"x\");";
+        assert_eq!(
+            found(code),
+            vec![
+                ("iterations".to_string(), "1000"),
+                ("rate".to_string(), "0.5"),
+                ("label".to_string(), "\"x\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn markers_inside_nested_calls() {
+        let code = "fn main() {\n    outer(inner(/* crowbar: depth */ 3, deeper(/* crowbar: scale */ -2.5e3)), 7);\n}\n";
+        assert_eq!(
+            found(code),
+            vec![("depth".to_string(), "3"), ("scale".to_string(), "-2.5e3"),]
+        );
+    }
+
+    #[test]
+    fn markers_on_non_literals_are_warned_about() {
+        let code = "fn main() {\n    f(/* crowbar: n */ count);\n    g(/* crowbar: m */ - 1);\n    h(/* crowbar: ok */ 1);\n}\n";
+        let (markers, warnings) = find_markers(code);
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].name, "ok");
+        assert_eq!(
+            warnings,
+            vec![
+                "Marker `n` at line 2 is not followed by a literal",
+                "Marker `m` at line 3 is not followed by a literal",
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_that_are_not_markers_are_ignored() {
+        let code = "// crowbar: line\nlet a = 1;\nlet s = \"/* crowbar: in_string */ 2\";\nf(/* crowbar: */ 3, /* crowbar */ 4, /* note */ 5);";
+        assert!(found(code).is_empty());
+    }
+}
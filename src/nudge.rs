@@ -0,0 +1,278 @@
+use crate::lexer::{tokenize, TokenKind};
+use std::ops::Range;
+
+const INT_SUFFIXES: [&str; 12] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+/// The replacement for a nudged literal.
+pub struct Nudge {
+    /// Byte range of the literal in the source, including a leading `-`.
+    pub span: Range<usize>,
+    pub text: String,
+}
+
+/// Inclusive bounds of an integer type, if `ty` names one. The maximum is
+/// unsigned so that `u128`'s fits.
+pub fn int_bounds(ty: &str) -> Option<(i128, u128)> {
+    Some(match ty {
+        "i8" => (i8::MIN as i128, i8::MAX as u128),
+        "i16" => (i16::MIN as i128, i16::MAX as u128),
+        "i32" => (i32::MIN as i128, i32::MAX as u128),
+        "i64" | "isize" => (i64::MIN as i128, i64::MAX as u128),
+        "i128" => (i128::MIN, i128::MAX as u128),
+        "u8" => (0, u8::MAX as u128),
+        "u16" => (0, u16::MAX as u128),
+        "u32" => (0, u32::MAX as u128),
+        "u64" | "usize" => (0, u64::MAX as u128),
+        "u128" => (0, u128::MAX),
+        _ => return None,
+    })
+}
+
+/// Byte range of the numeric literal at `offset`, including a leading unary
+/// minus.
+pub fn numeric_literal_at(code: &str, offset: usize) -> Option<Range<usize>> {
+    let tokens = tokenize(code);
+    let index = tokens.iter().position(|t| t.span.contains(&offset))?;
+    let token = &tokens[index];
+    if token.kind != TokenKind::Literal
+        || !token.text(code).starts_with(|c: char| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let mut before = tokens[..index].iter().rev().filter(|t| !t.is_trivia());
+    let is_negated = match (before.next(), before.next()) {
+        (Some(minus), operand) if minus.text(code) == "-" && minus.span.end == token.span.start => {
+            // `a -1` is a subtraction, `(-1` or `= -1` is a negative literal.
+            !matches!(
+                operand,
+                Some(t) if matches!(t.kind, TokenKind::Ident | TokenKind::Literal)
+                    || matches!(t.text(code), ")" | "]")
+            )
+        }
+        _ => false,
+    };
+
+    Some(if is_negated {
+        token.span.start - 1..token.span.end
+    } else {
+        token.span.clone()
+    })
+}
+
+/// Steps the numeric literal at `offset` by `steps` increments. Integers move
+/// by 1 and floats by a step derived from their precision and magnitude;
+/// `coarse` multiplies the step by ten. `type_hint` bounds unsuffixed integers.
+pub fn nudge_literal(
+    code: &str,
+    offset: usize,
+    steps: i32,
+    coarse: bool,
+    type_hint: Option<&str>,
+) -> Option<Nudge> {
+    let span = numeric_literal_at(code, offset)?;
+    let text = &code[span.clone()];
+    let (negative, unsigned_text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let factor = if coarse { 10 } else { 1 };
+
+    let new_text = if is_float(unsigned_text) {
+        nudge_float(negative, unsigned_text, steps * factor)?
+    } else {
+        nudge_int(negative, unsigned_text, steps * factor, type_hint)?
+    };
+    (new_text != text).then_some(Nudge {
+        span,
+        text: new_text,
+    })
+}
+
+fn is_float(text: &str) -> bool {
+    let is_radix_prefixed =
+        text.starts_with("0x") || text.starts_with("0o") || text.starts_with("0b");
+    // `isize` and `usize` have an `e` of their own.
+    let (body, _) = split_suffix(text, &INT_SUFFIXES);
+    !is_radix_prefixed
+        && (text.contains('.')
+            || body.contains(['e', 'E'])
+            || text.ends_with("f32")
+            || text.ends_with("f64"))
+}
+
+fn split_suffix<'a>(body: &'a str, suffixes: &[&str]) -> (&'a str, &'a str) {
+    suffixes
+        .iter()
+        .find_map(|suffix| {
+            body.strip_suffix(suffix)
+                .map(|rest| (rest, &body[rest.len()..]))
+        })
+        .unwrap_or((body, ""))
+}
+
+fn nudge_int(negative: bool, text: &str, delta: i32, type_hint: Option<&str>) -> Option<String> {
+    let (body, suffix) = split_suffix(text, &INT_SUFFIXES);
+    let (radix, prefix, digits) = match body.get(..2) {
+        Some("0x") => (16, "0x", &body[2..]),
+        Some("0o") => (8, "0o", &body[2..]),
+        Some("0b") => (2, "0b", &body[2..]),
+        _ => (10, "", body),
+    };
+    let digits = digits.replace('_', "");
+    let magnitude = u128::from_str_radix(&digits, radix).ok()?;
+
+    let bounds = int_bounds(if suffix.is_empty() {
+        type_hint.unwrap_or("")
+    } else {
+        suffix
+    });
+    let (mut negative, mut abs) = offset(negative, magnitude, delta);
+    if let Some((min, max)) = bounds {
+        if negative && abs > min.unsigned_abs() {
+            abs = min.unsigned_abs();
+            negative = abs > 0;
+        } else if !negative && abs > max {
+            abs = max;
+        }
+    }
+
+    let uppercase = digits.chars().any(|c| c.is_ascii_uppercase());
+    let formatted = match radix {
+        16 if uppercase => format!("{:X}", abs),
+        16 => format!("{:x}", abs),
+        8 => format!("{:o}", abs),
+        2 => format!("{:b}", abs),
+        _ => abs.to_string(),
+    };
+    let sign = if negative { "-" } else { "" };
+    Some(format!("{}{}{}{}", sign, prefix, formatted, suffix))
+}
+
+/// `-magnitude` if `negative`, else `magnitude`, plus `delta`, as a sign and
+/// a magnitude so both `i128::MIN` and `u128::MAX` can be reached. Zero is
+/// never negative.
+fn offset(negative: bool, magnitude: u128, delta: i32) -> (bool, u128) {
+    let step = u128::from(delta.unsigned_abs());
+    let (negative, magnitude) = if negative == (delta < 0) {
+        (negative, magnitude.saturating_add(step))
+    } else if magnitude >= step {
+        (negative, magnitude - step)
+    } else {
+        (!negative, step - magnitude)
+    };
+    (negative && magnitude > 0, magnitude)
+}
+
+fn nudge_float(negative: bool, text: &str, delta: i32) -> Option<String> {
+    let (body, suffix) = split_suffix(text, &["f32", "f64"]);
+    let body = body.trim_end_matches('_').replace('_', "");
+    let (mantissa_text, exponent) = match body.find(['e', 'E']) {
+        Some(i) => (&body[..i], Some(&body[i..])),
+        None => (body.as_str(), None),
+    };
+    let magnitude: f64 = mantissa_text.parse().ok()?;
+    let mantissa = if negative { -magnitude } else { magnitude };
+
+    let decimals = mantissa_text
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len()) as i32;
+    // Large values move in bigger steps than their last printed digit.
+    let step_decimals = if mantissa == 0.0 {
+        decimals
+    } else {
+        decimals.min((2 - mantissa.abs().log10().floor() as i32).max(0))
+    };
+    let scale = 10f64.powi(decimals);
+    let new_mantissa =
+        ((mantissa + 10f64.powi(-step_decimals) * delta as f64) * scale).round() / scale;
+    // Avoid printing `-0.0`.
+    let new_mantissa = if new_mantissa == 0.0 {
+        0.0
+    } else {
+        new_mantissa
+    };
+
+    let mut formatted = format!("{:.*}", decimals as usize, new_mantissa);
+    if exponent.is_none() && !formatted.contains('.') && suffix.is_empty() {
+        // Keep it a float literal.
+        formatted.push_str(".0");
+    }
+    Some(format!("{}{}{}", formatted, exponent.unwrap_or(""), suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nudged(code: &str, steps: i32, hint: Option<&str>) -> String {
+        let offset = code.find(|c: char| c.is_ascii_digit()).unwrap();
+        match nudge_literal(code, offset, steps, false, hint) {
+            Some(nudge) => format!(
+                "{}{}{}",
+                &code[..nudge.span.start],
+                nudge.text,
+                &code[nudge.span.end..]
+            ),
+            None => code.to_string(),
+        }
+    }
+
+    #[test]
+    fn u128_reaches_its_maximum() {
+        let max = u128::MAX.to_string();
+        assert_eq!(
+            nudged("340282366920938463463374607431768211454u128", 1, None),
+            format!("{}u128", max)
+        );
+        assert_eq!(
+            nudged(&format!("{}u128", max), 1, None),
+            format!("{}u128", max)
+        );
+        assert_eq!(
+            nudged(&format!("{}u128", max), -1, None),
+            "340282366920938463463374607431768211454u128"
+        );
+    }
+
+    #[test]
+    fn u64_stops_at_its_maximum_and_zero() {
+        assert_eq!(
+            nudged("18446744073709551615", 1, Some("u64")),
+            "18446744073709551615"
+        );
+        assert_eq!(nudged("0u64", -1, None), "0u64");
+        assert_eq!(nudged("1usize", -5, None), "0usize");
+    }
+
+    #[test]
+    fn i128_stops_at_its_minimum() {
+        let min = i128::MIN.to_string();
+        assert_eq!(
+            nudged(&format!("{}i128", min), -1, None),
+            format!("{}i128", min)
+        );
+        assert_eq!(
+            nudged(&format!("{}i128", min), 1, None),
+            "-170141183460469231731687303715884105727i128"
+        );
+    }
+
+    #[test]
+    fn crossing_zero_flips_the_sign() {
+        assert_eq!(nudged("-1", 2, None), "1");
+        assert_eq!(nudged("1", -2, None), "-1");
+        assert_eq!(nudged("-1", 1, None), "0");
+        assert_eq!(nudged("-128i8", -1, None), "-128i8");
+        assert_eq!(nudged("127i8", 1, None), "127i8");
+    }
+
+    #[test]
+    fn radix_and_case_are_kept() {
+        assert_eq!(nudged("0xFFu8", 1, None), "0xFFu8");
+        assert_eq!(nudged("0xfe", 1, None), "0xff");
+        assert_eq!(nudged("0b1", 1, None), "0b10");
+    }
+}
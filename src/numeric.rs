@@ -0,0 +1,688 @@
+use crate::dependencies;
+use crate::nudge;
+use crate::VariableValue;
+
+/// Up to here an `f64` holds every integer exactly.
+pub const MAX_EXACT: u128 = 1 << 53;
+
+/// How an integer literal is written back into the source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    Hex,
+    Octal,
+    Binary,
+}
+
+impl Radix {
+    pub const ALL: [Radix; 4] = [Radix::Decimal, Radix::Hex, Radix::Octal, Radix::Binary];
+
+    /// The radix `literal` is written in, from its `0x`/`0o`/`0b` prefix.
+    pub fn of(literal: &str) -> Self {
+        let digits = literal.trim_start_matches('-').trim_start();
+        match digits.get(..2) {
+            Some("0x" | "0X") => Radix::Hex,
+            Some("0o" | "0O") => Radix::Octal,
+            Some("0b" | "0B") => Radix::Binary,
+            _ => Radix::Decimal,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Radix::Decimal => "Decimal",
+            Radix::Hex => "Hexadecimal",
+            Radix::Octal => "Octal",
+            Radix::Binary => "Binary",
+        }
+    }
+
+    /// `value` as a literal in this radix, e.g. `-0x1F`.
+    pub fn format(self, value: impl Into<i128>) -> String {
+        let value = value.into();
+        let sign = if value < 0 { "-" } else { "" };
+        format!("{}{}", sign, self.format_unsigned(value.unsigned_abs()))
+    }
+
+    /// [`Self::format`] for the unsigned values past `i128::MAX`.
+    pub fn format_unsigned(self, value: u128) -> String {
+        match self {
+            Radix::Decimal => value.to_string(),
+            Radix::Hex => format!("0x{:X}", value),
+            Radix::Octal => format!("0o{:o}", value),
+            Radix::Binary => format!("0b{:b}", value),
+        }
+    }
+}
+
+/// `value` in `radix`, written like the integer literal `like` when that's
+/// in the same radix: with the same case of hex digits, padded with zeros
+/// to as many digits when it was, and grouped by `_` the same way.
+pub fn format_like(radix: Radix, value: impl Into<i128>, like: &str) -> String {
+    styled_like(radix, radix.format(value), like)
+}
+
+/// [`format_like`] for the unsigned values past `i128::MAX`.
+pub fn format_unsigned_like(radix: Radix, value: u128, like: &str) -> String {
+    styled_like(radix, radix.format_unsigned(value), like)
+}
+
+fn styled_like(radix: Radix, mut text: String, like: &str) -> String {
+    if Radix::of(like) != radix {
+        return text;
+    }
+    let own: String = like[integer_digits(like)]
+        .chars()
+        .filter(|c| *c != '_')
+        .collect();
+    if own.chars().any(|c| c.is_ascii_lowercase()) && !own.chars().any(|c| c.is_ascii_uppercase()) {
+        text = text.to_lowercase();
+    }
+    let digits = integer_digits(&text);
+    if radix != Radix::Decimal && own.len() > digits.len() && own.starts_with('0') {
+        let padded = format!("{:0>width$}", &text[digits.clone()], width = own.len());
+        text.replace_range(digits, &padded);
+    }
+    group_like(&text, like)
+}
+
+/// `value` written like the float literal `like`, without a suffix: as it
+/// is when that's still the value, else in the same notation, with at
+/// least as many decimals and grouped by `_` the same way. An `f32` is
+/// written as the shortest text that reads back as the same `f32`, and an
+/// `f64` to 15 significant digits, so neither picks up the stray digits
+/// arithmetic on them leaves, like `0.30000000000000004`. `None` for
+/// infinity and NaN, which no literal spells, and for values that only
+/// become one once rounded or narrowed to `ty`.
+pub fn float_like(value: f64, ty: &str, like: &str) -> Option<String> {
+    let written = like
+        .strip_suffix("f32")
+        .or_else(|| like.strip_suffix("f64"))
+        .unwrap_or(like)
+        .trim_end_matches('_');
+    let plain: String = written.chars().filter(|c| *c != '_').collect();
+    if !value.is_finite() {
+        return None;
+    }
+    if plain.parse::<f64>().ok() == Some(value) {
+        return Some(written.to_string());
+    }
+    let value: f64 = format!("{:.14e}", value).parse().unwrap_or(value);
+    if !is_finite_as(value, ty) {
+        return None;
+    }
+    let text = if plain.contains(['e', 'E']) {
+        let text = if ty == "f32" {
+            format!("{:e}", value as f32)
+        } else {
+            format!("{:e}", value)
+        };
+        if plain.contains('E') {
+            text.to_uppercase()
+        } else {
+            text
+        }
+    } else {
+        let mut text = if ty == "f32" {
+            (value as f32).to_string()
+        } else {
+            value.to_string()
+        };
+        if !text.contains('.') {
+            text.push_str(".0");
+        }
+        let decimals = plain
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.len());
+        let own = text
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.len());
+        text.extend(std::iter::repeat_n('0', decimals.saturating_sub(own)));
+        text
+    };
+    Some(group_like(&text, like))
+}
+
+/// `value` with `decimals` decimals, or one when that's zero so it stays
+/// a float literal, in the notation of the literal `like` and grouped the
+/// same way. `None` for infinity and NaN, and past the range of `ty`.
+pub fn fixed_like(value: f64, ty: &str, decimals: usize, like: &str) -> Option<String> {
+    if !is_finite_as(value, ty) {
+        return None;
+    }
+    let decimals = decimals.max(1);
+    let text = if like.contains('E') {
+        format!("{:.*e}", decimals, value).to_uppercase()
+    } else if like.contains('e') && !like.ends_with("f32") && !like.ends_with("f64") {
+        format!("{:.*e}", decimals, value)
+    } else {
+        format!("{:.*}", decimals, value)
+    };
+    Some(group_like(&text, like))
+}
+
+/// Whether `value` is still finite as a value of the float type `ty`.
+fn is_finite_as(value: f64, ty: &str) -> bool {
+    if ty == "f32" {
+        (value as f32).is_finite()
+    } else {
+        value.is_finite()
+    }
+}
+
+/// The values a float variable of type `ty` can be dragged or typed to.
+pub fn float_range(ty: &str) -> std::ops::RangeInclusive<f64> {
+    if ty == "f32" {
+        f64::from(f32::MIN)..=f64::from(f32::MAX)
+    } else {
+        f64::MIN..=f64::MAX
+    }
+}
+
+/// `text` with its integer digits grouped by `_` the way the literal `like`
+/// groups its own, if it does: in threes for `1_000_000`, in fours for
+/// `0xFFFF_0000`.
+fn group_like(text: &str, like: &str) -> String {
+    let own = &like[integer_digits(like)];
+    let group = own.rsplit('_').next().map_or(0, str::len);
+    if !own.contains('_') || group == 0 {
+        return text.to_string();
+    }
+    let digits = integer_digits(text);
+    let plain: Vec<char> = text[digits.clone()].chars().filter(|c| *c != '_').collect();
+    let mut grouped = String::new();
+    for (index, c) in plain.iter().enumerate() {
+        if index > 0 && (plain.len() - index).is_multiple_of(group) {
+            grouped.push('_');
+        }
+        grouped.push(*c);
+    }
+    format!(
+        "{}{}{}",
+        &text[..digits.start],
+        grouped,
+        &text[digits.end..]
+    )
+}
+
+/// Byte range of the integer digits of the literal `text`, underscores
+/// included: after any sign and radix prefix, and before any fraction,
+/// exponent or suffix.
+fn integer_digits(text: &str) -> std::ops::Range<usize> {
+    let unsigned = text.trim_start_matches('-').trim_start();
+    let radix = Radix::of(unsigned);
+    let start = text.len() - unsigned.len() + if radix == Radix::Decimal { 0 } else { 2 };
+    let is_digit = |c: char| match radix {
+        Radix::Hex => c.is_ascii_hexdigit(),
+        _ => c.is_ascii_digit(),
+    };
+    let end = text[start..]
+        .find(|c: char| c != '_' && !is_digit(c))
+        .map_or(text.len(), |at| start + at);
+    start..end
+}
+
+/// Smallest and largest value of the integer type `ty`, if the panel keeps
+/// its values in an `i64`.
+pub fn int_range(ty: &str) -> Option<(i64, i64)> {
+    Some(match ty {
+        "i8" => (i8::MIN.into(), i8::MAX.into()),
+        "i16" => (i16::MIN.into(), i16::MAX.into()),
+        "i32" => (i32::MIN.into(), i32::MAX.into()),
+        "i64" | "isize" => (i64::MIN, i64::MAX),
+        "u8" => (0, u8::MAX.into()),
+        "u16" => (0, u16::MAX.into()),
+        "u32" => (0, u32::MAX.into()),
+        _ => return None,
+    })
+}
+
+/// Largest value of the unsigned type `ty`, if the panel keeps its values
+/// in a `u128` because they can be past `i64::MAX`.
+pub fn uint_max(ty: &str) -> Option<u128> {
+    match ty {
+        "u64" | "usize" => Some(u64::MAX.into()),
+        "u128" => Some(u128::MAX),
+        _ => None,
+    }
+}
+
+/// `value` the way [`dependencies::evaluate`] works with numbers: unsigned
+/// and `i128` values that fit become `i64`s.
+pub fn for_arithmetic(value: VariableValue) -> VariableValue {
+    match value {
+        VariableValue::UInt(value) => {
+            i64::try_from(value).map_or(VariableValue::Unknown, VariableValue::Int)
+        }
+        VariableValue::Wide(value) => {
+            i64::try_from(value).map_or(VariableValue::Unknown, VariableValue::Int)
+        }
+        value => value,
+    }
+}
+
+/// An evaluated `value` as a value of the type `ty`, undoing
+/// [`for_arithmetic`].
+pub fn of_type(value: VariableValue, ty: &str) -> VariableValue {
+    match (value, uint_max(ty)) {
+        (VariableValue::Int(value), Some(max)) => u128::try_from(value)
+            .ok()
+            .filter(|value| *value <= max)
+            .map_or(VariableValue::Unknown, VariableValue::UInt),
+        (VariableValue::Int(value), None) if ty == "i128" => VariableValue::Wide(value.into()),
+        (value, _) => value,
+    }
+}
+
+/// How much dragging changes a number around `original` per pixel: a
+/// hundredth of it, at least one for an integer, or a tenth for a float
+/// that's zero.
+pub fn drag_speed(original: f64, integer: bool) -> f64 {
+    let speed = original.abs() / 100.0;
+    if integer {
+        speed.max(1.0)
+    } else if speed > 0.0 {
+        speed
+    } else {
+        0.1
+    }
+}
+
+/// `value` brought into the range of the integer type `ty`, so a written
+/// back literal never overflows it.
+pub fn clamp_int(ty: &str, value: i64) -> i64 {
+    match int_range(ty) {
+        Some((min, max)) => value.clamp(min, max),
+        None => value,
+    }
+}
+
+/// Parses what was typed for an `i128` variable: an integer literal in
+/// any radix, possibly negative. Unlike [`parse_entry`] there's no
+/// arithmetic, which is worked out in `i64`.
+pub fn parse_wide(text: &str, ty: &str) -> Result<i128, String> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, text),
+    };
+    let literal: syn::LitInt = syn::parse_str(digits).map_err(|_| "Not an integer".to_string())?;
+    let out_of_range = || format!("Out of range for {}", ty);
+    let magnitude: u128 = literal.base10_parse().map_err(|_| out_of_range())?;
+    let value = if negative {
+        0i128.checked_sub_unsigned(magnitude)
+    } else {
+        i128::try_from(magnitude).ok()
+    };
+    let (min, max) = nudge::int_bounds(ty).unwrap_or((i128::MIN, i128::MAX as u128));
+    value
+        .filter(|value| *value >= min && (*value < 0 || value.unsigned_abs() <= max))
+        .ok_or_else(out_of_range)
+}
+
+/// Parses what was typed for a `u64`, `usize` or `u128` variable: an
+/// integer literal in any radix, up to the type's maximum.
+pub fn parse_uint(text: &str, ty: &str) -> Result<u128, String> {
+    let text = text.trim();
+    let out_of_range = || format!("Out of range for {}", ty);
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, text),
+    };
+    let literal: syn::LitInt = syn::parse_str(digits).map_err(|_| "Not an integer".to_string())?;
+    let value: u128 = literal.base10_parse().map_err(|_| out_of_range())?;
+    if negative && value > 0 {
+        return Err(format!("A {} can't be negative", ty));
+    }
+    Some(value)
+        .filter(|value| *value <= uint_max(ty).unwrap_or(u128::MAX))
+        .ok_or_else(out_of_range)
+}
+
+/// Evaluates what was typed into the edit box of a numeric variable of type
+/// `ty`: literals in any radix or scientific notation and constant
+/// arithmetic on them, like `1e6`, `0x1F` or `1 << 20`.
+pub fn parse_entry(text: &str, ty: &str) -> Result<VariableValue, String> {
+    // A plain literal reaches past `i64::MAX`, which the arithmetic doesn't.
+    if uint_max(ty).is_some() {
+        let digits = text.trim().trim_start_matches('-').trim_start();
+        if syn::parse_str::<syn::LitInt>(digits).is_ok() {
+            return parse_uint(text, ty).map(VariableValue::UInt);
+        }
+    }
+    let expr: syn::Expr =
+        syn::parse_str(text.trim()).map_err(|_| "Not a number or expression".to_string())?;
+    let value = dependencies::evaluate(&expr, &|_| None)
+        .ok_or_else(|| "Can't be worked out, or overflows".to_string())?;
+    let is_float = matches!(ty, "f32" | "f64");
+    match value {
+        VariableValue::Int(value) if is_float => Ok(VariableValue::Float(value as f64)),
+        VariableValue::Float(value) if is_float => {
+            // No literal spells infinity or NaN, and an `f32` overflows sooner.
+            let finite = if ty == "f32" {
+                (value as f32).is_finite()
+            } else {
+                value.is_finite()
+            };
+            if finite {
+                Ok(VariableValue::Float(value))
+            } else if value.is_nan() {
+                Err("Not a number".to_string())
+            } else {
+                Err(format!("Out of range for {}", ty))
+            }
+        }
+        VariableValue::Int(value) if uint_max(ty).is_some() => {
+            match of_type(VariableValue::Int(value), ty) {
+                VariableValue::UInt(value) => Ok(VariableValue::UInt(value)),
+                _ => Err(format!("Out of range for {}", ty)),
+            }
+        }
+        VariableValue::Int(value) => {
+            let (min, max) = int_range(ty).unwrap_or((i64::MIN, i64::MAX));
+            if (min..=max).contains(&value) {
+                Ok(VariableValue::Int(value))
+            } else {
+                Err(format!("Out of range for {}", ty))
+            }
+        }
+        VariableValue::Float(_) => Err(format!("Not an integer, {} needs one", ty)),
+        _ => Err("Not a number".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uint_limits() {
+        assert_eq!(uint_max("u64"), Some(u128::from(u64::MAX)));
+        assert_eq!(uint_max("usize"), Some(u128::from(u64::MAX)));
+        assert_eq!(uint_max("u128"), Some(u128::MAX));
+        assert_eq!(uint_max("u32"), None);
+        assert_eq!(int_range("u64"), None);
+        assert_eq!(int_range("u32"), Some((0, i64::from(u32::MAX))));
+    }
+
+    #[test]
+    fn parse_uint_boundaries() {
+        assert_eq!(
+            parse_uint("18446744073709551615", "u64"),
+            Ok(u128::from(u64::MAX))
+        );
+        assert_eq!(
+            parse_uint("18446744073709551616", "u64"),
+            Err("Out of range for u64".to_string())
+        );
+        assert_eq!(
+            parse_uint("0xFFFF_FFFF_FFFF_FFFF", "usize"),
+            Ok(u128::from(u64::MAX))
+        );
+        assert_eq!(
+            parse_uint("340282366920938463463374607431768211455", "u128"),
+            Ok(u128::MAX)
+        );
+        assert_eq!(
+            parse_uint("340282366920938463463374607431768211456", "u128"),
+            Err("Out of range for u128".to_string())
+        );
+        assert_eq!(parse_uint("-0", "u64"), Ok(0));
+        assert_eq!(
+            parse_uint("-1", "u64"),
+            Err("A u64 can't be negative".to_string())
+        );
+        assert_eq!(parse_uint("1.5", "u64"), Err("Not an integer".to_string()));
+    }
+
+    #[test]
+    fn parse_wide_boundaries() {
+        assert_eq!(
+            parse_wide("-170141183460469231731687303715884105728", "i128"),
+            Ok(i128::MIN)
+        );
+        assert_eq!(
+            parse_wide("170141183460469231731687303715884105727", "i128"),
+            Ok(i128::MAX)
+        );
+        assert!(parse_wide("170141183460469231731687303715884105728", "i128").is_err());
+        assert!(parse_wide("-170141183460469231731687303715884105729", "i128").is_err());
+    }
+
+    #[test]
+    fn parse_entry_reaches_unsigned_maximums() {
+        assert!(matches!(
+            parse_entry("18446744073709551615", "u64"),
+            Ok(VariableValue::UInt(value)) if value == u128::from(u64::MAX)
+        ));
+        assert!(matches!(
+            parse_entry("2 * 21", "u64"),
+            Ok(VariableValue::UInt(42))
+        ));
+        assert_eq!(
+            parse_entry("0 - 1", "usize").err().as_deref(),
+            Some("Out of range for usize")
+        );
+        assert_eq!(
+            parse_entry("-1", "u128").err().as_deref(),
+            Some("A u128 can't be negative")
+        );
+        assert!(matches!(
+            parse_entry("255", "u8"),
+            Ok(VariableValue::Int(255))
+        ));
+        assert!(parse_entry("256", "u8").is_err());
+    }
+
+    #[test]
+    fn arithmetic_round_trip() {
+        assert!(matches!(
+            for_arithmetic(VariableValue::UInt(7)),
+            VariableValue::Int(7)
+        ));
+        assert!(matches!(
+            for_arithmetic(VariableValue::UInt(u128::from(u64::MAX))),
+            VariableValue::Unknown
+        ));
+        assert!(matches!(
+            of_type(VariableValue::Int(7), "u64"),
+            VariableValue::UInt(7)
+        ));
+        assert!(matches!(
+            of_type(VariableValue::Int(-1), "u64"),
+            VariableValue::Unknown
+        ));
+        assert!(matches!(
+            of_type(VariableValue::Int(-1), "i64"),
+            VariableValue::Int(-1)
+        ));
+        assert!(matches!(
+            of_type(VariableValue::Int(-6), "i128"),
+            VariableValue::Wide(-6)
+        ));
+        assert!(matches!(
+            for_arithmetic(VariableValue::Wide(6)),
+            VariableValue::Int(6)
+        ));
+        assert!(matches!(
+            for_arithmetic(VariableValue::Wide(i128::MAX)),
+            VariableValue::Unknown
+        ));
+    }
+
+    #[test]
+    fn unsigned_formatting() {
+        assert_eq!(
+            Radix::Hex.format_unsigned(u128::MAX),
+            format!("0x{}", "F".repeat(32))
+        );
+        assert_eq!(Radix::Binary.format_unsigned(5), "0b101");
+        assert_eq!(
+            format_unsigned_like(Radix::Hex, u128::from(u64::MAX), "0xffff_ffff"),
+            "0xffff_ffff_ffff_ffff"
+        );
+        assert_eq!(
+            format_unsigned_like(Radix::Decimal, u128::from(u64::MAX), "1_000"),
+            "18_446_744_073_709_551_615"
+        );
+        assert_eq!(Radix::Hex.format(-31), "-0x1F");
+    }
+
+    fn entry(text: &str, ty: &str) -> Result<String, String> {
+        parse_entry(text, ty).map(|value| crate::format_literal(&value, "").unwrap())
+    }
+
+    #[test]
+    fn entries_accept_any_radix_and_scientific_notation() {
+        assert_eq!(entry("0x1F", "i32"), Ok("31".to_string()));
+        assert_eq!(entry("0o17", "i32"), Ok("15".to_string()));
+        assert_eq!(entry("0b1010_1010", "u8"), Ok("170".to_string()));
+        assert_eq!(entry("-0x10", "i8"), Ok("-16".to_string()));
+        assert_eq!(entry("1_000_000", "i64"), Ok("1000000".to_string()));
+        assert_eq!(entry("  42  ", "i32"), Ok("42".to_string()));
+        assert_eq!(entry("1e6", "f64"), Ok("1000000.0".to_string()));
+        assert_eq!(entry("2.5E-3", "f32"), Ok("0.0025".to_string()));
+        assert_eq!(entry("7", "f64"), Ok("7.0".to_string()));
+        assert_eq!(entry("0xFF_u8", "u8"), Ok("255".to_string()));
+    }
+
+    #[test]
+    fn entries_evaluate_constant_expressions() {
+        assert_eq!(entry("1 << 20", "i32"), Ok("1048576".to_string()));
+        assert_eq!(entry("60 * 60 * 24", "u32"), Ok("86400".to_string()));
+        assert_eq!(entry("(1 + 2) * -3", "i16"), Ok("-9".to_string()));
+        assert_eq!(entry("0xFF & !0x0F", "i32"), Ok("240".to_string()));
+        assert_eq!(entry("1e3 / 8.0", "f64"), Ok("125.0".to_string()));
+        assert_eq!(entry("10 / 4", "f64"), Ok("2.0".to_string()));
+        assert_eq!(entry("1 << 40", "u64"), Ok("1099511627776".to_string()));
+    }
+
+    #[test]
+    fn entries_are_checked_against_the_type() {
+        assert_eq!(entry("128", "i8"), Err("Out of range for i8".to_string()));
+        assert_eq!(entry("-129", "i8"), Err("Out of range for i8".to_string()));
+        assert_eq!(
+            entry("1 << 16", "u16"),
+            Err("Out of range for u16".to_string())
+        );
+        assert_eq!(entry("-1", "u32"), Err("Out of range for u32".to_string()));
+        assert_eq!(
+            entry("1e6", "i32"),
+            Err("Not an integer, i32 needs one".to_string())
+        );
+        assert_eq!(
+            entry("i64::MAX + 1", "i64"),
+            Err("Can't be worked out, or overflows".to_string())
+        );
+        assert_eq!(
+            entry("9223372036854775807 + 1", "i64"),
+            Err("Can't be worked out, or overflows".to_string())
+        );
+        assert_eq!(
+            entry("1 / 0", "i32"),
+            Err("Can't be worked out, or overflows".to_string())
+        );
+        assert_eq!(entry("true", "i32"), Err("Not a number".to_string()));
+    }
+
+    #[test]
+    fn entries_never_produce_non_finite_floats() {
+        assert_eq!(
+            entry("1.0 / 0.0", "f64"),
+            Err("Out of range for f64".to_string())
+        );
+        assert_eq!(
+            entry("-1e308 * 10.0", "f64"),
+            Err("Out of range for f64".to_string())
+        );
+        assert_eq!(entry("0.0 / 0.0", "f32"), Err("Not a number".to_string()));
+        assert_eq!(
+            entry("1e39", "f32"),
+            Err("Out of range for f32".to_string())
+        );
+        assert_eq!(entry("1e39", "f64"), Ok("1e39".to_string()));
+    }
+
+    #[test]
+    fn malformed_entries_are_rejected() {
+        for text in ["", "1 +", "0x", "0xG", "abc", "1..2", "(1", "1 2"] {
+            assert!(parse_entry(text, "i32").is_err(), "{:?}", text);
+        }
+        assert_eq!(
+            parse_entry("abc", "i32").err().as_deref(),
+            Some("Can't be worked out, or overflows")
+        );
+        assert_eq!(
+            parse_entry("1 +", "f64").err().as_deref(),
+            Some("Not a number or expression")
+        );
+    }
+
+    #[test]
+    fn floats_are_written_like_their_literal() {
+        let float = |value, ty, like| float_like(value, ty, like).unwrap();
+        assert_eq!(float(2.0, "f64", "2.0"), "2.0");
+        assert_eq!(float(3.0, "f64", "2."), "3.0");
+        assert_eq!(float(0.1 + 0.2, "f64", "0.1"), "0.3");
+        assert_eq!(float(3.5, "f64", "3.14159"), "3.50000");
+        assert_eq!(float(1500.0, "f64", "1E3"), "1.5E3");
+        assert_eq!(float(1234567.5, "f64", "1_000.0"), "1_234_567.5");
+        assert_eq!(float(1.0, "f32", "1.0f32"), "1.0");
+        assert_eq!(float(1e-9, "f64", "1e-9_f64"), "1e-9");
+    }
+
+    #[test]
+    fn fixed_decimals_keep_a_point() {
+        assert_eq!(
+            fixed_like(2.0 / 3.0, "f64", 2, "1.0").as_deref(),
+            Some("0.67")
+        );
+        assert_eq!(fixed_like(4.0, "f64", 0, "1.0").as_deref(), Some("4.0"));
+        assert_eq!(
+            fixed_like(1500.0, "f64", 1, "1e3").as_deref(),
+            Some("1.5e3")
+        );
+        assert_eq!(
+            fixed_like(1500.0, "f32", 1, "1.0f32").as_deref(),
+            Some("1500.0")
+        );
+    }
+
+    #[test]
+    fn non_finite_floats_have_no_literal() {
+        for value in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+            assert_eq!(float_like(value, "f64", "1.0"), None);
+            assert_eq!(float_like(value, "f32", "inf"), None);
+            assert_eq!(fixed_like(value, "f64", 2, "1.0"), None);
+        }
+    }
+
+    #[test]
+    fn floats_at_the_edge_of_their_type_have_no_overflowing_literal() {
+        // Rounding to 15 digits takes the largest f64 past the largest.
+        assert_eq!(float_like(f64::MAX, "f64", "1.0"), None);
+        assert_eq!(float_like(-f64::MAX, "f64", "1e3"), None);
+        let written = float_like(1e308, "f64", "1.0").unwrap();
+        assert_eq!(written.parse::<f64>(), Ok(1e308));
+        // An f32 overflows long before an f64 does.
+        assert_eq!(float_like(1e39, "f32", "1.0"), None);
+        assert_eq!(fixed_like(1e39, "f32", 1, "1.0"), None);
+        assert!(fixed_like(1e39, "f64", 1, "1.0").is_some());
+        let largest = f64::from(f32::MAX);
+        let written = float_like(largest, "f32", "1.0").unwrap();
+        assert!(written.parse::<f32>().unwrap().is_finite(), "{}", written);
+        let written = fixed_like(largest, "f32", 1, "1.0").unwrap();
+        assert!(written.parse::<f32>().unwrap().is_finite(), "{}", written);
+    }
+
+    #[test]
+    fn float_ranges_follow_the_type() {
+        assert_eq!(
+            float_range("f32"),
+            f64::from(f32::MIN)..=f64::from(f32::MAX)
+        );
+        assert_eq!(float_range("f64"), f64::MIN..=f64::MAX);
+        assert_eq!(float_range("_"), f64::MIN..=f64::MAX);
+    }
+}
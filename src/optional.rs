@@ -0,0 +1,78 @@
+use syn::Expr;
+
+use crate::{
+    default_value, literal_like, number_value, numeric_literal, typed_literal, VariableValue,
+};
+
+/// Inner type of an `Option<T>` type as [`crate::extract_type`] writes it.
+pub fn inner_type(var_type: &str) -> Option<&str> {
+    var_type.strip_prefix("Option<")?.strip_suffix('>')
+}
+
+/// Value of `Some` of a literal, or of `None`, for an option of
+/// `inner_type`. Only options of values the panel edits on one line are
+/// editable. Untyped bindings aren't listed, since `None` alone wouldn't
+/// say what it's an option of.
+pub fn parse(expr: &Expr, inner_type: &str) -> Option<VariableValue> {
+    let (some, value) = match expr {
+        Expr::Path(path) if is_variant(&path.path, "None") => (false, default_value(inner_type)),
+        Expr::Call(call) if call.args.len() == 1 => {
+            let Expr::Path(path) = &*call.func else {
+                return None;
+            };
+            if !is_variant(&path.path, "Some") {
+                return None;
+            }
+            let inner = &call.args[0];
+            let value = match numeric_literal(inner) {
+                Some((lit, negative)) => number_value(lit, negative, inner_type),
+                None => typed_literal(inner)?.1,
+            };
+            (true, value)
+        }
+        _ => return None,
+    };
+    let scalar = !matches!(
+        value,
+        VariableValue::List(_)
+            | VariableValue::Tuple(_)
+            | VariableValue::Optional { .. }
+            | VariableValue::Unknown
+    );
+    let same_kind =
+        std::mem::discriminant(&value) == std::mem::discriminant(&default_value(inner_type));
+    (scalar && same_kind).then(|| VariableValue::Optional {
+        some,
+        value: Box::new(value),
+    })
+}
+
+/// `Some(value)` or `None`, written over the option `source` so that the
+/// value keeps the suffix, radix and way of building a `String` it's
+/// written with.
+pub fn literal(
+    some: bool,
+    value: &VariableValue,
+    inner_type: &str,
+    source: &str,
+) -> Option<String> {
+    if !some {
+        return Some("None".to_string());
+    }
+    let original = match syn::parse_str::<Expr>(source) {
+        Ok(Expr::Call(call)) if call.args.len() == 1 => call.args[0].clone(),
+        _ => syn::parse_quote!(()),
+    };
+    Some(format!(
+        "Some({})",
+        literal_like(value, inner_type, &original)?
+    ))
+}
+
+/// Whether `path` ends in `variant`, as `Some`, `Option::Some` and
+/// `std::option::Option::Some` all do.
+fn is_variant(path: &syn::Path, variant: &str) -> bool {
+    path.segments
+        .last()
+        .is_some_and(|last| last.ident == variant && last.arguments.is_none())
+}
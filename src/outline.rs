@@ -0,0 +1,96 @@
+use quote::ToTokens;
+
+use crate::lexer::line_of;
+
+/// One item of the outline, with the methods of an `impl` block under it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// How the item starts, like `fn main` or `impl Display for Point`.
+    pub label: String,
+    /// Byte offset of the item's name, or of the `impl` keyword.
+    pub offset: usize,
+    pub line: usize,
+    pub children: Vec<Entry>,
+}
+
+/// The functions, structs, enums, traits and `impl` blocks at the top level
+/// of `file`, parsed from `code`, in source order.
+pub fn entries(file: &syn::File, code: &str) -> Vec<Entry> {
+    file.items
+        .iter()
+        .filter_map(|item| {
+            let (label, offset, children) = match item {
+                syn::Item::Fn(item) => (
+                    format!("fn {}", item.sig.ident),
+                    item.sig.ident.span(),
+                    Vec::new(),
+                ),
+                syn::Item::Struct(item) => (
+                    format!("struct {}", item.ident),
+                    item.ident.span(),
+                    Vec::new(),
+                ),
+                syn::Item::Enum(item) => (
+                    format!("enum {}", item.ident),
+                    item.ident.span(),
+                    Vec::new(),
+                ),
+                syn::Item::Trait(item) => (
+                    format!("trait {}", item.ident),
+                    item.ident.span(),
+                    Vec::new(),
+                ),
+                syn::Item::Impl(item) => {
+                    let methods = item
+                        .items
+                        .iter()
+                        .filter_map(|item| match item {
+                            syn::ImplItem::Fn(method) => Some(entry(
+                                code,
+                                format!("fn {}", method.sig.ident),
+                                method.sig.ident.span(),
+                                Vec::new(),
+                            )),
+                            _ => None,
+                        })
+                        .collect();
+                    (impl_label(item), item.impl_token.span, methods)
+                }
+                _ => return None,
+            };
+            Some(entry(code, label, offset, children))
+        })
+        .collect()
+}
+
+fn entry(code: &str, label: String, span: proc_macro2::Span, children: Vec<Entry>) -> Entry {
+    let offset = span.byte_range().start;
+    Entry {
+        label,
+        offset,
+        line: line_of(code, offset),
+        children,
+    }
+}
+
+/// `impl Type` or `impl Trait for Type`, without generics.
+fn impl_label(item: &syn::ItemImpl) -> String {
+    let name = |ty: &syn::Type| match ty {
+        syn::Type::Path(path) => path.path.segments.last().map_or_else(
+            || ty.to_token_stream().to_string(),
+            |last| last.ident.to_string(),
+        ),
+        ty => ty.to_token_stream().to_string(),
+    };
+    match &item.trait_ {
+        Some((negative, path, _)) => format!(
+            "impl {}{} for {}",
+            if negative.is_some() { "!" } else { "" },
+            path.segments
+                .last()
+                .map_or_else(String::new, |last| last.ident.to_string()),
+            name(&item.self_ty)
+        ),
+        None => format!("impl {}", name(&item.self_ty)),
+    }
+}
@@ -0,0 +1,165 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Name of the folder that holds one subfolder per run.
+pub const FOLDER: &str = "crowbar-out";
+
+/// Where per-run output folders are created.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Location {
+    /// Under the run's working directory, next to what the program reads.
+    #[default]
+    WorkingDir,
+    Temp,
+}
+
+impl Location {
+    pub fn label(self) -> &'static str {
+        match self {
+            Location::WorkingDir => "working directory",
+            Location::Temp => "temp folder",
+        }
+    }
+}
+
+/// The folder run folders go into for `location`, given the run's working
+/// directory (`None` for Crowbar's own).
+pub fn base_dir(location: Location, working_dir: Option<&Path>) -> PathBuf {
+    match location {
+        Location::WorkingDir => working_dir
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default()
+            .join(FOLDER),
+        Location::Temp => std::env::temp_dir().join(FOLDER),
+    }
+}
+
+/// Creates a new, empty folder for a run that started at `started` (seconds
+/// since the epoch). Runs in the same second get `-2`, `-3`, … suffixes.
+pub fn create_run_dir(base: &Path, started: u64) -> io::Result<PathBuf> {
+    std::fs::create_dir_all(base)?;
+    let name = format!("run-{}", started);
+    for attempt in 1.. {
+        let dir = if attempt == 1 {
+            base.join(&name)
+        } else {
+            base.join(format!("{}-{}", name, attempt))
+        };
+        // create_dir fails if it exists, so concurrent runs never share one.
+        match std::fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// Files below `dir` with their sizes, sorted by path. Empty if `dir` is gone.
+pub fn list(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push((entry.path(), metadata.len()));
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Opens `path` with the desktop's default application.
+pub fn open(path: &Path) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(path).spawn().map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "crowbar-test-outputs-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn runs_in_the_same_second_get_suffixes() {
+        let base = scratch_dir("collide");
+        let dirs: Vec<PathBuf> = (0..3).map(|_| create_run_dir(&base, 42).unwrap()).collect();
+        assert_eq!(
+            dirs,
+            vec![
+                base.join("run-42"),
+                base.join("run-42-2"),
+                base.join("run-42-3")
+            ]
+        );
+        assert_eq!(create_run_dir(&base, 43).unwrap(), base.join("run-43"));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn a_file_in_the_way_is_skipped_too() {
+        let base = scratch_dir("file");
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("run-7"), "").unwrap();
+        assert_eq!(create_run_dir(&base, 7).unwrap(), base.join("run-7-2"));
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn concurrent_runs_never_share_a_folder() {
+        let base = scratch_dir("threads");
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let base = base.clone();
+                std::thread::spawn(move || create_run_dir(&base, 1).unwrap())
+            })
+            .collect();
+        let mut dirs: Vec<PathBuf> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        dirs.sort();
+        dirs.dedup();
+        assert_eq!(dirs.len(), 8);
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn list_walks_subfolders_and_tolerates_a_missing_folder() {
+        let base = scratch_dir("list");
+        std::fs::create_dir_all(base.join("nested")).unwrap();
+        std::fs::write(base.join("a.txt"), "abc").unwrap();
+        std::fs::write(base.join("nested").join("b.txt"), "hello").unwrap();
+        assert_eq!(
+            list(&base),
+            vec![
+                (base.join("a.txt"), 3),
+                (base.join("nested").join("b.txt"), 5)
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&base);
+        assert!(list(&base).is_empty());
+    }
+}
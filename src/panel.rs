@@ -0,0 +1,566 @@
+use std::collections::{HashMap, HashSet};
+
+use eframe::egui::Color32;
+
+use crate::lexer::line_of;
+use crate::numeric::Radix;
+use crate::{Variable, VariableKind, VariableValue};
+
+/// Identifies a variable across re-parses, which rebuild the variable list.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct VariableKey {
+    pub kind: VariableKind,
+    pub name: String,
+    /// Tells apart same-named bindings in different functions.
+    pub function: Option<String>,
+}
+
+impl VariableKey {
+    pub fn of(variable: &Variable) -> Self {
+        Self {
+            kind: variable.kind,
+            name: variable.name.clone(),
+            function: variable.function.clone(),
+        }
+    }
+}
+
+/// A pinned variable, told apart from others with the same key by the line
+/// it's declared on and which of them it is, both of which follow it as the
+/// code around it changes. Locks and drag settings find their variable the
+/// same way, so shadowed bindings each keep their own.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pin {
+    pub key: VariableKey,
+    pub line: usize,
+    /// How many variables with the same key are declared before it.
+    pub occurrence: usize,
+    /// How many variables with the same key there are, itself included.
+    pub of: usize,
+}
+
+/// Per-variable panel settings that outlive a single parse.
+#[derive(Default)]
+pub struct PanelState {
+    /// Pinned variables, in the order they were pinned.
+    pub pinned: Vec<Pin>,
+    pub locked: Vec<Pin>,
+    pub hidden: HashSet<VariableKey>,
+    pub show_hidden: bool,
+    /// Radix picked for an integer, overriding the one in the source.
+    pub radix: HashMap<VariableKey, Radix>,
+    /// Decimals picked for a float, overriding the ones in the source.
+    pub decimals: HashMap<VariableKey, usize>,
+    /// Drag speed picked for a number, overriding its annotation's.
+    pub speed: Vec<(Pin, f64)>,
+    /// Step picked for a number, overriding its annotation's.
+    pub step: Vec<(Pin, f64)>,
+    /// Only rows whose name contains this, ignoring case, are shown.
+    pub filter: String,
+    /// Only rows of this kind are shown.
+    pub type_filter: Option<TypeFilter>,
+    /// Rows without an editor are shown too.
+    pub show_unsupported: bool,
+}
+
+impl PanelState {
+    pub fn toggle(set: &mut HashSet<VariableKey>, key: VariableKey) {
+        if !set.remove(&key) {
+            set.insert(key);
+        }
+    }
+
+    /// Pins the variable at `index`, or unpins it if it's pinned.
+    pub fn toggle_pin(&mut self, variables: &[Variable], code: &str, index: usize) {
+        match self
+            .pinned
+            .iter()
+            .position(|pin| pinned_index(pin, variables, code) == Some(index))
+        {
+            Some(at) => {
+                self.pinned.remove(at);
+            }
+            None => self.pinned.push(Pin::at(variables, code, index)),
+        }
+    }
+
+    /// Locks the variable at `index`, or unlocks it if it's locked.
+    pub fn toggle_lock(&mut self, variables: &[Variable], code: &str, index: usize) {
+        match self
+            .locked
+            .iter()
+            .position(|pin| pinned_index(pin, variables, code) == Some(index))
+        {
+            Some(at) => {
+                self.locked.remove(at);
+            }
+            None => self.locked.push(Pin::at(variables, code, index)),
+        }
+    }
+
+    pub fn is_locked(&self, variables: &[Variable], code: &str, index: usize) -> bool {
+        self.locked
+            .iter()
+            .any(|pin| pinned_index(pin, variables, code) == Some(index))
+    }
+
+    /// Moves each pin, lock and drag setting to the line its variable is
+    /// declared on after a re-parse. Those whose variable is gone stay put,
+    /// in case it comes back.
+    pub fn follow_pins(&mut self, variables: &[Variable], code: &str) {
+        let settings = self.speed.iter_mut().chain(&mut self.step);
+        for pin in self
+            .pinned
+            .iter_mut()
+            .chain(&mut self.locked)
+            .chain(settings.map(|(pin, _)| pin))
+        {
+            if let Some(index) = pinned_index(pin, variables, code) {
+                *pin = Pin::at(variables, code, index);
+            }
+        }
+    }
+}
+
+impl Pin {
+    /// Pin on the variable at `index`.
+    pub fn at(variables: &[Variable], code: &str, index: usize) -> Self {
+        let key = VariableKey::of(&variables[index]);
+        let same = |variable: &&Variable| VariableKey::of(variable) == key;
+        Self {
+            line: declared_line(&variables[index], code),
+            occurrence: variables[..index].iter().filter(same).count(),
+            of: variables.iter().filter(same).count(),
+            key,
+        }
+    }
+}
+
+/// The setting kept for the variable at `index`, if one was picked.
+pub fn setting<T: Copy>(
+    settings: &[(Pin, T)],
+    variables: &[Variable],
+    code: &str,
+    index: usize,
+) -> Option<T> {
+    settings
+        .iter()
+        .find(|(pin, _)| pinned_index(pin, variables, code) == Some(index))
+        .map(|(_, value)| *value)
+}
+
+/// Keeps `value` as the setting for the variable at `index`, or drops its
+/// setting for `None`.
+pub fn set_setting<T>(
+    settings: &mut Vec<(Pin, T)>,
+    variables: &[Variable],
+    code: &str,
+    index: usize,
+    value: Option<T>,
+) {
+    settings.retain(|(pin, _)| pinned_index(pin, variables, code) != Some(index));
+    if let Some(value) = value {
+        settings.push((Pin::at(variables, code, index), value));
+    }
+}
+
+/// Line `variable` is declared on, or 0 if it has no place in `code`.
+fn declared_line(variable: &Variable, code: &str) -> usize {
+    variable
+        .span
+        .as_ref()
+        .filter(|span| code.is_char_boundary(span.start))
+        .map_or(0, |span| line_of(code, span.start))
+}
+
+/// Index of the variable `pin` is on: of those with its key, the one in the
+/// same place among them while there are as many as there were, since lines
+/// above may have moved them all; else the one still declared on the same
+/// line, else the one in the same place, else the one declared nearest.
+fn pinned_index(pin: &Pin, variables: &[Variable], code: &str) -> Option<usize> {
+    let candidates: Vec<(usize, usize)> = variables
+        .iter()
+        .enumerate()
+        .filter(|(_, variable)| VariableKey::of(variable) == pin.key)
+        .map(|(index, variable)| (index, declared_line(variable, code)))
+        .collect();
+    candidates
+        .get(pin.occurrence)
+        .filter(|_| candidates.len() == pin.of)
+        .or_else(|| candidates.iter().find(|(_, line)| *line == pin.line))
+        .or_else(|| candidates.get(pin.occurrence))
+        .or_else(|| {
+            candidates
+                .iter()
+                .min_by_key(|(_, line)| line.abs_diff(pin.line))
+        })
+        .map(|(index, _)| *index)
+}
+
+/// The kinds of value the panel can be narrowed to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TypeFilter {
+    Ints,
+    Floats,
+    Bools,
+    Strings,
+    Unsupported,
+}
+
+impl TypeFilter {
+    pub const ALL: [TypeFilter; 5] = [
+        TypeFilter::Ints,
+        TypeFilter::Floats,
+        TypeFilter::Bools,
+        TypeFilter::Strings,
+        TypeFilter::Unsupported,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TypeFilter::Ints => "Ints",
+            TypeFilter::Floats => "Floats",
+            TypeFilter::Bools => "Bools",
+            TypeFilter::Strings => "Strings",
+            TypeFilter::Unsupported => "Unsupported",
+        }
+    }
+
+    fn matches(self, badge: Badge) -> bool {
+        match self {
+            TypeFilter::Ints => badge == Badge::Int,
+            TypeFilter::Floats => badge == Badge::Float,
+            TypeFilter::Bools => badge == Badge::Bool,
+            TypeFilter::Strings => matches!(badge, Badge::Str | Badge::Char | Badge::Bytes),
+            TypeFilter::Unsupported => badge == Badge::Unknown,
+        }
+    }
+}
+
+/// Kind of editor a row gets, which also picks its type badge color.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Badge {
+    Int,
+    Float,
+    Bool,
+    Str,
+    Char,
+    Bytes,
+    List,
+    Tuple,
+    Optional,
+    Enum,
+    Struct,
+    Unknown,
+}
+
+impl Badge {
+    fn of(value: &VariableValue) -> Self {
+        match value {
+            VariableValue::Int(_) | VariableValue::Wide(_) | VariableValue::UInt(_) => Badge::Int,
+            VariableValue::Float(_) => Badge::Float,
+            VariableValue::Bool(_) => Badge::Bool,
+            VariableValue::Str(_) => Badge::Str,
+            VariableValue::Char(_) => Badge::Char,
+            VariableValue::Bytes(_) => Badge::Bytes,
+            VariableValue::List(_) => Badge::List,
+            VariableValue::Tuple(_) => Badge::Tuple,
+            VariableValue::Optional { .. } => Badge::Optional,
+            VariableValue::Enum { .. } => Badge::Enum,
+            VariableValue::Struct { .. } => Badge::Struct,
+            VariableValue::Unknown => Badge::Unknown,
+        }
+    }
+
+    pub fn color(self) -> Color32 {
+        match self {
+            Badge::Int => Color32::from_rgb(110, 170, 240),
+            Badge::Float => Color32::from_rgb(100, 200, 190),
+            Badge::Bool => Color32::from_rgb(230, 160, 90),
+            Badge::Str => Color32::from_rgb(150, 200, 110),
+            Badge::Char => Color32::from_rgb(210, 150, 210),
+            Badge::Bytes => Color32::from_rgb(170, 210, 150),
+            Badge::List => Color32::from_rgb(200, 190, 120),
+            Badge::Tuple => Color32::from_rgb(180, 160, 230),
+            Badge::Optional => Color32::from_rgb(220, 140, 140),
+            Badge::Enum => Color32::from_rgb(140, 200, 230),
+            Badge::Struct => Color32::from_rgb(190, 170, 140),
+            Badge::Unknown => Color32::GRAY,
+        }
+    }
+}
+
+/// One line of the variables panel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableRow {
+    /// Index into the variable list.
+    pub index: usize,
+    pub key: VariableKey,
+    pub type_label: String,
+    pub badge: Badge,
+    pub pinned: bool,
+    pub locked: bool,
+    pub hidden: bool,
+    /// Found while the code doesn't parse; shown but not editable.
+    pub approximate: bool,
+    /// Found inside a macro body; never editable.
+    pub in_macro: bool,
+    /// Another variable of the same kind and name is declared, in the same
+    /// function or another one, so the name alone doesn't say which this is.
+    pub shadowed: bool,
+}
+
+/// Rows in display order: pinned variables first, in the order they were
+/// pinned, otherwise source order. Hidden variables are left out unless
+/// `state.show_hidden` is set. Unless they're pinned, so are unsupported
+/// ones unless `state.show_unsupported` is set or they're filtered for, and
+/// ones the filters don't match.
+pub fn rows(variables: &[Variable], code: &str, state: &PanelState) -> Vec<VariableRow> {
+    let filter = state.filter.trim().to_lowercase();
+    let pinned: Vec<usize> = state
+        .pinned
+        .iter()
+        .filter_map(|pin| pinned_index(pin, variables, code))
+        .collect();
+    let mut rows: Vec<VariableRow> = variables
+        .iter()
+        .enumerate()
+        .map(|(index, variable)| {
+            let key = VariableKey::of(variable);
+            VariableRow {
+                index,
+                type_label: match variable.kind {
+                    VariableKind::Const => format!("const {}", variable.var_type),
+                    VariableKind::Static { mutable: false } => {
+                        format!("static {}", variable.var_type)
+                    }
+                    VariableKind::Static { mutable: true } => {
+                        format!("static mut {}", variable.var_type)
+                    }
+                    _ => variable.var_type.clone(),
+                },
+                badge: Badge::of(&variable.value),
+                pinned: pinned.contains(&index),
+                locked: state.is_locked(variables, code, index),
+                hidden: state.hidden.contains(&key),
+                approximate: variable.approximate,
+                in_macro: variable.in_macro,
+                shadowed: variables
+                    .iter()
+                    .filter(|other| other.kind == key.kind && other.name == key.name)
+                    .nth(1)
+                    .is_some(),
+                key,
+            }
+        })
+        .filter(|row| state.show_hidden || !row.hidden)
+        .filter(|row| {
+            row.pinned
+                || state.show_unsupported
+                || row.badge != Badge::Unknown
+                || state.type_filter == Some(TypeFilter::Unsupported)
+        })
+        .filter(|row| row.pinned || row.key.name.to_lowercase().contains(&filter))
+        .filter(|row| row.pinned || state.type_filter.is_none_or(|kind| kind.matches(row.badge)))
+        .collect();
+    rows.sort_by_key(|row| {
+        pinned
+            .iter()
+            .position(|&index| index == row.index)
+            .unwrap_or(usize::MAX)
+    });
+    rows
+}
+
+/// A collapsible section of the panel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub title: String,
+    pub rows: Vec<VariableRow>,
+}
+
+/// `rows` split into sections: pinned ones first, then items and markers
+/// outside any function, then one per function in order of appearance.
+pub fn groups(rows: Vec<VariableRow>) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    for row in rows {
+        let title = if row.pinned {
+            "📌 Pinned".to_string()
+        } else {
+            match &row.key.function {
+                Some(function) => format!("fn {}", function),
+                None => "Global".to_string(),
+            }
+        };
+        match groups.iter_mut().find(|group| group.title == title) {
+            Some(group) => group.rows.push(row),
+            None => groups.push(Group {
+                title,
+                rows: vec![row],
+            }),
+        }
+    }
+    // Pinned rows come first already; globals go right after them.
+    if let Some(global) = groups.iter().position(|group| group.title == "Global") {
+        let group = groups.remove(global);
+        let at = usize::from(groups.first().is_some_and(|g| g.rows[0].pinned));
+        groups.insert(at, group);
+    }
+    groups
+}
+
+/// Names of the variables without an editor, which the panel leaves out
+/// unless asked to show them.
+pub fn unsupported(variables: &[Variable]) -> Vec<&str> {
+    variables
+        .iter()
+        .filter(|variable| Badge::of(&variable.value) == Badge::Unknown)
+        .map(|variable| variable.name.as_str())
+        .collect()
+}
+
+/// How many variables the panel currently leaves out.
+pub fn hidden_count(variables: &[Variable], state: &PanelState) -> usize {
+    variables
+        .iter()
+        .filter(|variable| state.hidden.contains(&VariableKey::of(variable)))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "const LIMIT: u32 = 3;
+fn main() {
+    let speed = 1;
+    let ratio = 2.5;
+    let now: Instant = Instant::now();
+}
+fn other() {
+    let speed = true;
+}
+";
+
+    fn variables() -> Vec<Variable> {
+        crate::parse_let_variables(CODE).unwrap()
+    }
+
+    fn names(rows: &[VariableRow]) -> Vec<String> {
+        rows.iter()
+            .map(|row| format!("{}:{}", row.key.name, row.index))
+            .collect()
+    }
+
+    #[test]
+    fn rows_come_in_source_order_without_unsupported_ones() {
+        let variables = variables();
+        let shown = rows(&variables, CODE, &PanelState::default());
+        assert_eq!(names(&shown), ["LIMIT:0", "speed:1", "ratio:2", "speed:4"]);
+        assert_eq!(shown[0].type_label, "const u32");
+        assert!(shown[1].shadowed && !shown[2].shadowed);
+        assert_eq!(unsupported(&variables), ["now"]);
+
+        let state = PanelState {
+            show_unsupported: true,
+            ..PanelState::default()
+        };
+        assert_eq!(rows(&variables, CODE, &state).len(), 5);
+    }
+
+    #[test]
+    fn pinned_rows_come_first_in_pin_order_and_escape_filters() {
+        let variables = variables();
+        let mut state = PanelState::default();
+        state.toggle_pin(&variables, CODE, 4);
+        state.toggle_pin(&variables, CODE, 2);
+        state.toggle_pin(&variables, CODE, 3);
+        let shown = rows(&variables, CODE, &state);
+        assert_eq!(
+            names(&shown),
+            ["speed:4", "ratio:2", "now:3", "LIMIT:0", "speed:1"]
+        );
+        assert!(shown[..3].iter().all(|row| row.pinned));
+
+        state.filter = "LIM".to_string();
+        state.type_filter = Some(TypeFilter::Bools);
+        let shown = rows(&variables, CODE, &state);
+        assert_eq!(names(&shown), ["speed:4", "ratio:2", "now:3"]);
+
+        state.toggle_pin(&variables, CODE, 2);
+        let shown = rows(&variables, CODE, &state);
+        assert_eq!(names(&shown), ["speed:4", "now:3"]);
+    }
+
+    #[test]
+    fn filters_match_names_ignoring_case_and_value_kinds() {
+        let variables = variables();
+        let mut state = PanelState {
+            filter: " SPE ".to_string(),
+            ..PanelState::default()
+        };
+        assert_eq!(
+            names(&rows(&variables, CODE, &state)),
+            ["speed:1", "speed:4"]
+        );
+        state.type_filter = Some(TypeFilter::Ints);
+        assert_eq!(names(&rows(&variables, CODE, &state)), ["speed:1"]);
+
+        state.filter.clear();
+        state.type_filter = Some(TypeFilter::Unsupported);
+        assert_eq!(names(&rows(&variables, CODE, &state)), ["now:3"]);
+        state.type_filter = Some(TypeFilter::Floats);
+        assert_eq!(names(&rows(&variables, CODE, &state)), ["ratio:2"]);
+    }
+
+    #[test]
+    fn hidden_rows_are_left_out_unless_shown() {
+        let variables = variables();
+        let mut state = PanelState::default();
+        PanelState::toggle(&mut state.hidden, VariableKey::of(&variables[2]));
+        assert_eq!(
+            names(&rows(&variables, CODE, &state)),
+            ["LIMIT:0", "speed:1", "speed:4"]
+        );
+        assert_eq!(hidden_count(&variables, &state), 1);
+
+        state.show_hidden = true;
+        let shown = rows(&variables, CODE, &state);
+        assert_eq!(shown.len(), 4);
+        assert!(shown[2].hidden);
+
+        PanelState::toggle(&mut state.hidden, VariableKey::of(&variables[2]));
+        assert_eq!(hidden_count(&variables, &state), 0);
+    }
+
+    #[test]
+    fn groups_put_pinned_then_globals_then_each_function() {
+        let variables = variables();
+        let mut state = PanelState::default();
+        let titles = |state: &PanelState| -> Vec<(String, usize)> {
+            groups(rows(&variables, CODE, state))
+                .into_iter()
+                .map(|group| (group.title, group.rows.len()))
+                .collect()
+        };
+        assert_eq!(
+            titles(&state),
+            [
+                ("Global".to_string(), 1),
+                ("fn main".to_string(), 2),
+                ("fn other".to_string(), 1),
+            ]
+        );
+
+        state.toggle_pin(&variables, CODE, 1);
+        assert_eq!(
+            titles(&state),
+            [
+                ("📌 Pinned".to_string(), 1),
+                ("Global".to_string(), 1),
+                ("fn main".to_string(), 1),
+                ("fn other".to_string(), 1),
+            ]
+        );
+    }
+}
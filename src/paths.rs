@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+/// Start of the name of each instance's build directory, followed by its pid.
+pub const SESSION_PREFIX: &str = "crowbar-session-";
+
+/// Directory for Crowbar's persisted user data (dictionary, settings, …).
+pub fn config_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(std::env::temp_dir);
+    base.join("crowbar")
+}
+
+/// This instance's own directory for build files in the temp dir.
+pub fn session_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("{}{}", SESSION_PREFIX, std::process::id()))
+}
+
+/// Moves the config dir aside so Crowbar starts from a clean slate, returning
+/// where the backup went.
+pub fn reset_config_dir() -> std::io::Result<PathBuf> {
+    let dir = config_dir();
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let backup = dir.with_file_name(format!("crowbar.backup-{}", stamp));
+    std::fs::rename(&dir, &backup)?;
+    Ok(backup)
+}
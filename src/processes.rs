@@ -0,0 +1,468 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What a tracked child process was spawned for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    Compiler,
+    Program,
+}
+
+impl Role {
+    pub fn label(self) -> &'static str {
+        match self {
+            Role::Compiler => "compiler",
+            Role::Program => "program",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "compiler" => Some(Role::Compiler),
+            "program" => Some(Role::Program),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    /// Pid of the Crowbar instance that spawned the process.
+    pub owner: u32,
+    pub role: Role,
+    /// Seconds since the Unix epoch.
+    pub started: u64,
+    pub file: Option<PathBuf>,
+    /// Path (or bare file name) of the executable, used to make sure a pid
+    /// from an old session hasn't been reused by an unrelated process.
+    pub executable: PathBuf,
+}
+
+impl ProcessEntry {
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.pid,
+            self.owner,
+            self.role.label(),
+            self.started,
+            self.file
+                .as_ref()
+                .map(|f| f.display().to_string())
+                .unwrap_or_default(),
+            self.executable.display()
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let pid = fields.next()?.parse().ok()?;
+        let owner = fields.next()?.parse().ok()?;
+        let role = Role::parse(fields.next()?)?;
+        let started = fields.next()?.parse().ok()?;
+        let file = fields.next()?;
+        let executable = fields.next()?;
+        Some(Self {
+            pid,
+            owner,
+            role,
+            started,
+            file: (!file.is_empty()).then(|| PathBuf::from(file)),
+            executable: PathBuf::from(executable),
+        })
+    }
+}
+
+static REGISTRY: Mutex<Vec<ProcessEntry>> = Mutex::new(Vec::new());
+
+/// Lives in the temp dir on purpose: pids don't survive a reboot either.
+fn registry_path() -> PathBuf {
+    std::env::temp_dir().join("crowbar-processes.tsv")
+}
+
+/// Writes this session's entries, keeping those of other instances that are
+/// still alive so concurrent instances don't clobber each other.
+fn save(entries: &[ProcessEntry]) {
+    save_to(&registry_path(), std::process::id(), entries, executable_of);
+}
+
+/// [`save`] for the registry at `path`, written by instance `own`.
+/// `executable_of` looks up what a pid is running, if anything.
+fn save_to(
+    path: &Path,
+    own: u32,
+    entries: &[ProcessEntry],
+    executable_of: impl Fn(u32) -> Option<PathBuf>,
+) {
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    let contents: String = parse_registry(&existing)
+        .iter()
+        .filter(|e| e.owner != own && executable_of(e.owner).is_some())
+        .chain(entries)
+        .map(|e| e.to_line() + "\n")
+        .collect();
+    let _ = std::fs::write(path, contents);
+}
+
+pub fn parse_registry(contents: &str) -> Vec<ProcessEntry> {
+    contents
+        .lines()
+        .filter_map(ProcessEntry::from_line)
+        .collect()
+}
+
+/// Unregisters its process when dropped, i.e. once the caller has waited on it.
+pub struct Tracked {
+    pid: u32,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let mut entries = REGISTRY.lock().unwrap();
+        entries.retain(|e| e.pid != self.pid);
+        save(&entries);
+    }
+}
+
+pub fn track(pid: u32, role: Role, file: Option<&Path>, executable: &Path) -> Tracked {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut entries = REGISTRY.lock().unwrap();
+    entries.push(ProcessEntry {
+        pid,
+        owner: std::process::id(),
+        role,
+        started,
+        file: file.map(Path::to_path_buf),
+        executable: executable
+            .canonicalize()
+            .unwrap_or_else(|_| executable.to_path_buf()),
+    });
+    save(&entries);
+    Tracked { pid }
+}
+
+/// Processes spawned by this session that haven't exited yet.
+pub fn running() -> Vec<ProcessEntry> {
+    REGISTRY.lock().unwrap().clone()
+}
+
+/// Reads the registry left behind by sessions that are no longer running and
+/// keeps only the entries whose pid still belongs to the recorded executable.
+pub fn leftovers() -> Vec<ProcessEntry> {
+    leftovers_in(&registry_path(), std::process::id(), executable_of)
+}
+
+/// [`leftovers`] of the registry at `path`, as seen by instance `own`.
+fn leftovers_in(
+    path: &Path,
+    own: u32,
+    executable_of: impl Fn(u32) -> Option<PathBuf>,
+) -> Vec<ProcessEntry> {
+    let contents = std::fs::read_to_string(path).unwrap_or_default();
+    parse_registry(&contents)
+        .into_iter()
+        .filter(|e| e.owner != own && executable_of(e.owner).is_none())
+        .filter(|e| matches_executable(e, executable_of(e.pid).as_deref()))
+        .collect()
+}
+
+/// Rewrites the registry file without the entries of dead sessions, dropping
+/// leftovers that are gone or were dismissed.
+pub fn forget_leftovers() {
+    save(&REGISTRY.lock().unwrap());
+}
+
+/// Absolute path of the rustc that `rustc` runs, found through its sysroot
+/// so a rustup proxy resolves to the toolchain's binary it hands over to,
+/// or plain `rustc` if it can't be asked.
+pub fn rustc_path() -> &'static Path {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    PATH.get_or_init(|| {
+        Command::new("rustc")
+            .args(["--print", "sysroot"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| {
+                let sysroot = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                Path::new(&sysroot)
+                    .join("bin")
+                    .join(format!("rustc{}", std::env::consts::EXE_SUFFIX))
+            })
+            .filter(|path| path.is_file())
+            .unwrap_or_else(|| PathBuf::from("rustc"))
+    })
+}
+
+pub fn matches_executable(entry: &ProcessEntry, actual: Option<&Path>) -> bool {
+    let Some(actual) = actual else {
+        return false;
+    };
+    if entry.executable.is_absolute() && actual.is_absolute() {
+        actual == entry.executable
+    } else {
+        actual.file_name() == entry.executable.file_name()
+    }
+}
+
+/// Kills `entry`'s process, checking first that its pid still runs the
+/// executable it was registered with, in case it exited and the pid was
+/// handed to something else since the list was shown.
+pub fn kill(entry: &ProcessEntry) -> Result<(), String> {
+    let pid = entry.pid;
+    if !matches_executable(entry, executable_of(pid).as_deref()) {
+        return Err(format!(
+            "Not killing {}: it's no longer {}",
+            pid,
+            entry.executable.display()
+        ));
+    }
+    #[cfg(windows)]
+    let status = Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/F"])
+        .status();
+    #[cfg(not(windows))]
+    let status = Command::new("kill").args(["-9", &pid.to_string()]).status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("Failed to kill {}: {}", pid, status)),
+        Err(e) => Err(format!("Failed to kill {}: {}", pid, e)),
+    }
+}
+
+/// Whether any process is running as `pid`, including other users'.
+pub fn is_alive(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    return Path::new(&format!("/proc/{}", pid)).exists();
+    #[cfg(not(target_os = "linux"))]
+    return executable_of(pid).is_some();
+}
+
+/// Best-effort lookup of the executable currently running as `pid`.
+#[cfg(target_os = "linux")]
+fn executable_of(pid: u32) -> Option<PathBuf> {
+    let link = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    // The temp executable gets replaced by every build, which the kernel
+    // reports with a suffix on the old inode's path.
+    let text = link.to_string_lossy();
+    Some(PathBuf::from(
+        text.strip_suffix(" (deleted)").unwrap_or(&text).to_string(),
+    ))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn executable_of(pid: u32) -> Option<PathBuf> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "comm="])
+        .output()
+        .ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!name.is_empty()).then(|| PathBuf::from(name))
+}
+
+#[cfg(windows)]
+fn executable_of(pid: u32) -> Option<PathBuf> {
+    let output = Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let name = stdout.split(',').next()?.trim().trim_matches('"');
+    (!name.is_empty() && !name.starts_with("INFO:")).then(|| PathBuf::from(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "crowbar-test-processes-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("crowbar-processes.tsv")
+    }
+
+    fn entry(pid: u32, owner: u32, executable: &str) -> ProcessEntry {
+        ProcessEntry {
+            pid,
+            owner,
+            role: Role::Program,
+            started: 1_700_000_000,
+            file: Some(PathBuf::from("/work/demo.rs")),
+            executable: PathBuf::from(executable),
+        }
+    }
+
+    /// A process table: pid to executable.
+    fn table(processes: &[(u32, &str)]) -> impl Fn(u32) -> Option<PathBuf> {
+        let processes: Vec<(u32, PathBuf)> = processes
+            .iter()
+            .map(|(pid, exe)| (*pid, PathBuf::from(exe)))
+            .collect();
+        move |pid| {
+            processes
+                .iter()
+                .find(|(p, _)| *p == pid)
+                .map(|(_, exe)| exe.clone())
+        }
+    }
+
+    #[test]
+    fn registry_lines_round_trip() {
+        let entries = vec![
+            entry(10, 1, "/tmp/crowbar-session-1/main"),
+            ProcessEntry {
+                role: Role::Compiler,
+                file: None,
+                ..entry(11, 1, "rustc")
+            },
+        ];
+        let text: String = entries.iter().map(|e| e.to_line() + "\n").collect();
+        assert_eq!(parse_registry(&text), entries);
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let good = entry(10, 1, "/tmp/main").to_line();
+        let text = format!(
+            "garbage\n{}\n12\t1\tdaemon\t0\t\t/tmp/x\nx\t1\tprogram\t0\t\t/tmp/x\n13\t1\tprogram\n",
+            good
+        );
+        assert_eq!(parse_registry(&text), vec![entry(10, 1, "/tmp/main")]);
+    }
+
+    #[test]
+    fn leftovers_come_from_dead_sessions_only() {
+        let path = scratch_file("dead");
+        let text: String = [
+            // Owner 1 crashed; its program still runs.
+            entry(10, 1, "/tmp/crowbar-session-1/main"),
+            // Owner 2 is alive and manages its own children.
+            entry(20, 2, "/tmp/crowbar-session-2/main"),
+            // Our own entries are never leftovers.
+            entry(30, 3, "/tmp/crowbar-session-3/main"),
+        ]
+        .iter()
+        .map(|e| e.to_line() + "\n")
+        .collect();
+        std::fs::write(&path, text).unwrap();
+        let processes = table(&[
+            (2, "/usr/bin/crowbar"),
+            (3, "/usr/bin/crowbar"),
+            (10, "/tmp/crowbar-session-1/main"),
+            (20, "/tmp/crowbar-session-2/main"),
+            (30, "/tmp/crowbar-session-3/main"),
+        ]);
+        let found = leftovers_in(&path, 3, processes);
+        assert_eq!(found, vec![entry(10, 1, "/tmp/crowbar-session-1/main")]);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn reused_and_exited_pids_are_not_leftovers() {
+        let path = scratch_file("reused");
+        let text: String = [
+            entry(10, 1, "/tmp/crowbar-session-1/main"),
+            entry(11, 1, "/tmp/crowbar-session-1/main"),
+            entry(12, 1, "rustc"),
+        ]
+        .iter()
+        .map(|e| e.to_line() + "\n")
+        .collect();
+        std::fs::write(&path, text).unwrap();
+        // 10 now belongs to something else, 11 is gone, 12 is still a rustc.
+        let processes = table(&[(10, "/usr/bin/bash"), (12, "/home/me/.cargo/bin/rustc")]);
+        assert_eq!(
+            leftovers_in(&path, 3, processes),
+            vec![entry(12, 1, "rustc")]
+        );
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn a_missing_registry_has_no_leftovers() {
+        let path = scratch_file("missing");
+        assert!(leftovers_in(&path, 3, table(&[])).is_empty());
+    }
+
+    #[test]
+    fn saving_keeps_live_instances_and_drops_dead_ones() {
+        let path = scratch_file("save");
+        let text: String = [
+            entry(10, 1, "/tmp/a"),
+            entry(20, 2, "/tmp/b"),
+            entry(30, 3, "/tmp/old"),
+        ]
+        .iter()
+        .map(|e| e.to_line() + "\n")
+        .collect();
+        std::fs::write(&path, text).unwrap();
+        let mine = vec![entry(31, 3, "/tmp/new")];
+        save_to(&path, 3, &mine, table(&[(2, "/usr/bin/crowbar")]));
+        let saved = parse_registry(&std::fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            saved,
+            vec![entry(20, 2, "/tmp/b"), entry(31, 3, "/tmp/new")]
+        );
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn executables_match_by_path_or_by_name() {
+        let absolute = entry(1, 1, "/tmp/crowbar-session-1/main");
+        assert!(matches_executable(
+            &absolute,
+            Some(Path::new("/tmp/crowbar-session-1/main"))
+        ));
+        assert!(!matches_executable(
+            &absolute,
+            Some(Path::new("/tmp/crowbar-session-2/main"))
+        ));
+        assert!(!matches_executable(&absolute, None));
+        // `ps` and `tasklist` only report names.
+        assert!(matches_executable(&absolute, Some(Path::new("main"))));
+        assert!(matches_executable(
+            &entry(1, 1, "rustc"),
+            Some(Path::new("/usr/bin/rustc"))
+        ));
+        assert!(!matches_executable(
+            &entry(1, 1, "rustc"),
+            Some(Path::new("bash"))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn a_live_child_is_found_and_killed() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        assert!(is_alive(pid));
+        let sleep = executable_of(pid).unwrap();
+        assert!(matches_executable(&entry(pid, 1, "sleep"), Some(&sleep)));
+        // A pid that no longer runs the registered executable is left alone.
+        assert!(kill(&entry(pid, 1, "/bin/not-sleep")).is_err());
+        assert!(is_alive(pid));
+        kill(&entry(pid, 1, &sleep.to_string_lossy())).unwrap();
+        child.wait().unwrap();
+        assert!(!is_alive(pid));
+        assert!(executable_of(pid).is_none());
+    }
+
+    #[test]
+    fn rustc_is_recorded_by_absolute_path() {
+        let rustc = rustc_path();
+        assert!(rustc.is_absolute(), "{}", rustc.display());
+        assert!(rustc.is_file());
+    }
+}
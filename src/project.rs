@@ -0,0 +1,569 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+
+/// One line of a `.gitignore` file.
+#[derive(Clone, Debug, PartialEq)]
+struct Pattern {
+    glob: String,
+    /// `!pattern`: re-includes what an earlier pattern ignored.
+    negated: bool,
+    /// `pattern/`: only matches directories.
+    dir_only: bool,
+    /// Contains a slash before its end, so it's matched against the path
+    /// from the `.gitignore`'s folder rather than any file name.
+    anchored: bool,
+}
+
+/// The patterns of one `.gitignore` file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Gitignore {
+    /// Folder of the file, relative to the scanned root.
+    base: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+impl Gitignore {
+    /// Parses `text` as the `.gitignore` in `base`, relative to the root.
+    pub fn parse(base: &Path, text: &str) -> Self {
+        let patterns = text
+            .lines()
+            .filter_map(|line| {
+                let line = trim_unescaped_spaces(line);
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let anchored = line.contains('/');
+                let glob = line.strip_prefix('/').unwrap_or(line).to_string();
+                (!glob.is_empty()).then_some(Pattern {
+                    glob,
+                    negated,
+                    dir_only,
+                    anchored,
+                })
+            })
+            .collect();
+        Self {
+            base: base.to_path_buf(),
+            patterns,
+        }
+    }
+
+    /// `Some(true)` when the last pattern matching `path` (relative to the
+    /// root) ignores it, `Some(false)` when it re-includes it.
+    fn decision(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let relative = slash_path(relative);
+        let name = relative.rsplit('/').next().unwrap_or_default();
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| {
+                (is_dir || !pattern.dir_only)
+                    && if pattern.anchored {
+                        glob_match(&pattern.glob, &relative)
+                    } else {
+                        glob_match(&pattern.glob, name)
+                    }
+            })
+            .map(|pattern| !pattern.negated)
+    }
+}
+
+/// Trailing spaces end a pattern unless escaped with a backslash.
+fn trim_unescaped_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether `path` is ignored by `ignores`, ordered from the root down:
+/// deeper files override shallower ones, later patterns earlier ones.
+pub fn is_ignored(ignores: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .find_map(|ignore| ignore.decision(path, is_dir))
+        .unwrap_or(false)
+}
+
+/// Gitignore globbing: `*` and `?` stay within one path segment, `**`
+/// spans any number of them and `[a-z]` / `[!a-z]` match character sets.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no folder at all.
+            let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+            (0..=text.len()).any(|skip| {
+                (skip == 0 || skip == text.len() || text[skip - 1] == '/')
+                    && match_from(rest, &text[skip..])
+            }) || rest.is_empty()
+        }
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|skip| *skip == 0 || text[skip - 1] != '/')
+            .any(|skip| match_from(rest, &text[skip..])),
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(c) if *c != '/') && match_from(rest, &text[1..])
+        }
+        ['[', rest @ ..] => {
+            let Some(c) = text.first().filter(|c| **c != '/') else {
+                return false;
+            };
+            match class_match(rest, *c) {
+                Some((true, after)) => match_from(after, &text[1..]),
+                Some((false, _)) => false,
+                // No closing bracket: a literal `[`.
+                None => *c == '[' && match_from(rest, &text[1..]),
+            }
+        }
+        ['\\', literal, rest @ ..] => text.first() == Some(literal) && match_from(rest, &text[1..]),
+        [literal, rest @ ..] => text.first() == Some(literal) && match_from(rest, &text[1..]),
+    }
+}
+
+/// Whether `c` is in the set starting after a `[`, and the pattern after
+/// the closing `]`.
+fn class_match(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negated, mut rest) = match pattern {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, pattern),
+    };
+    let mut found = false;
+    let mut first = true;
+    loop {
+        match rest {
+            [']', after @ ..] if !first => return Some((found != negated, after)),
+            [low, '-', high, after @ ..] if *high != ']' => {
+                found |= (*low..=*high).contains(&c);
+                rest = after;
+            }
+            [single, after @ ..] => {
+                found |= *single == c;
+                rest = after;
+            }
+            [] => return None,
+        }
+        first = false;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    /// Larger files are skipped without being read.
+    pub max_file_size: u64,
+    /// The walk stops once this many files were collected.
+    pub max_files: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 1024 * 1024,
+            max_files: 5000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Walk {
+    pub files: Vec<PathBuf>,
+    /// Ignored files and folders; a folder's contents aren't listed.
+    pub ignored: Vec<PathBuf>,
+    pub too_large: Vec<PathBuf>,
+    /// Whether `max_files` cut the walk short.
+    pub truncated: bool,
+    pub cancelled: bool,
+}
+
+/// Files under `root` that `wanted` accepts, honoring every `.gitignore`
+/// on the way and `limits`. Paths in `include` (relative to `root`) are
+/// walked even when ignored. Iterative, and stops soon after `cancel` is set.
+pub fn walk(
+    root: &Path,
+    limits: &Limits,
+    include: &[PathBuf],
+    cancel: &AtomicBool,
+    wanted: impl Fn(&Path) -> bool,
+) -> Walk {
+    let mut result = Walk::default();
+    let mut pending = vec![(root.to_path_buf(), Vec::<Gitignore>::new())];
+    while let Some((dir, mut ignores)) = pending.pop() {
+        if cancel.load(Ordering::Relaxed) {
+            result.cancelled = true;
+            break;
+        }
+        let relative_dir = dir.strip_prefix(root).unwrap_or(&dir).to_path_buf();
+        if let Ok(text) = std::fs::read_to_string(dir.join(".gitignore")) {
+            ignores.push(Gitignore::parse(&relative_dir, &text));
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+        let mut subdirs = Vec::new();
+        for entry in entries {
+            let path = entry.path();
+            let relative = relative_dir.join(entry.file_name());
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let is_dir = metadata.is_dir();
+            if is_dir && entry.file_name() == ".git" {
+                continue;
+            }
+            let included = include
+                .iter()
+                .any(|path| path.starts_with(&relative) || relative.starts_with(path));
+            if !included && is_ignored(&ignores, &relative, is_dir) {
+                result.ignored.push(path);
+                continue;
+            }
+            if is_dir {
+                subdirs.push(path);
+            } else if !wanted(&path) {
+                continue;
+            } else if metadata.len() > limits.max_file_size {
+                result.too_large.push(path);
+            } else if result.files.len() >= limits.max_files {
+                result.truncated = true;
+                return result;
+            } else {
+                result.files.push(path);
+            }
+        }
+        // Reversed so folders come off the stack in name order.
+        for subdir in subdirs.into_iter().rev() {
+            pending.push((subdir, ignores.clone()));
+        }
+    }
+    result
+}
+
+/// `let` bindings of one scanned file.
+#[derive(Clone, Debug)]
+pub struct FileVariables {
+    pub path: PathBuf,
+    /// Name, type and value as shown in the panel.
+    pub variables: Vec<(String, String, String)>,
+}
+
+#[derive(Default)]
+struct Shared {
+    scanning: bool,
+    walk: Option<Walk>,
+    files: Vec<FileVariables>,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Lists the `let` bindings of every Rust file under a folder on a
+/// background thread.
+#[derive(Default)]
+pub struct ProjectScan {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl ProjectScan {
+    /// Starts scanning `root`, cancelling a scan still in progress.
+    /// `variables` turns a file's source into its panel entries.
+    pub fn start(
+        &self,
+        ctx: &egui::Context,
+        root: PathBuf,
+        limits: Limits,
+        include: Vec<PathBuf>,
+        variables: fn(&str) -> Vec<(String, String, String)>,
+    ) {
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let mut shared = self.shared.lock().unwrap();
+            shared.cancel.store(true, Ordering::Relaxed);
+            shared.cancel = cancel.clone();
+            shared.scanning = true;
+        }
+        let shared = self.shared.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let walk = walk(&root, &limits, &include, &cancel, |path| {
+                path.extension().is_some_and(|extension| extension == "rs")
+            });
+            let mut files = Vec::new();
+            for path in &walk.files {
+                if cancel.load(Ordering::Relaxed) {
+                    break;
+                }
+                let Ok(source) = std::fs::read_to_string(path) else {
+                    continue;
+                };
+                let found = variables(&source);
+                if !found.is_empty() {
+                    files.push(FileVariables {
+                        path: path.clone(),
+                        variables: found,
+                    });
+                }
+            }
+            let mut shared = shared.lock().unwrap();
+            // A newer scan replaced this one.
+            if !Arc::ptr_eq(&shared.cancel, &cancel) {
+                return;
+            }
+            shared.scanning = false;
+            shared.walk = Some(walk);
+            shared.files = files;
+            ctx.request_repaint();
+        });
+    }
+
+    pub fn cancel(&self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.cancel.store(true, Ordering::Relaxed);
+        shared.scanning = false;
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.shared.lock().unwrap().scanning
+    }
+
+    /// The last finished walk and the files it found variables in.
+    pub fn results(&self) -> (Option<Walk>, Vec<FileVariables>) {
+        let shared = self.shared.lock().unwrap();
+        (shared.walk.clone(), shared.files.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "crowbar-test-project-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(root: &Path, path: &str, text: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    /// Paths of `walk`'s files relative to `root`, with slashes.
+    fn relative(root: &Path, paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| slash_path(path.strip_prefix(root).unwrap()))
+            .collect()
+    }
+
+    fn rust_files(root: &Path, include: &[PathBuf]) -> Walk {
+        walk(
+            root,
+            &Limits::default(),
+            include,
+            &AtomicBool::new(false),
+            |path| path.extension().is_some_and(|e| e == "rs"),
+        )
+    }
+
+    #[test]
+    fn patterns_are_parsed() {
+        let ignore = Gitignore::parse(
+            Path::new("sub"),
+            "# comment\n\n/target\nbuild/\n!keep.rs\n\\!bang\n\\#hash\nlogs/*.log  \nspace\\ \n/\n",
+        );
+        let globs: Vec<(&str, bool, bool, bool)> = ignore
+            .patterns
+            .iter()
+            .map(|p| (p.glob.as_str(), p.negated, p.dir_only, p.anchored))
+            .collect();
+        assert_eq!(
+            globs,
+            vec![
+                ("target", false, false, true),
+                ("build", false, true, false),
+                ("keep.rs", true, false, false),
+                ("!bang", false, false, false),
+                ("#hash", false, false, false),
+                ("logs/*.log", false, false, true),
+                ("space\\ ", false, false, false),
+            ]
+        );
+        assert_eq!(ignore.base, PathBuf::from("sub"));
+    }
+
+    #[test]
+    fn globs_follow_gitignore_rules() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "src/main.rs"));
+        assert!(glob_match("src/*.rs", "src/main.rs"));
+        assert!(glob_match("?.rs", "a.rs"));
+        assert!(!glob_match("?", "/"));
+        assert!(glob_match("**/gen.rs", "gen.rs"));
+        assert!(glob_match("**/gen.rs", "a/b/gen.rs"));
+        assert!(!glob_match("**/gen.rs", "a/xgen.rs"));
+        assert!(glob_match("a/**/b", "a/b"));
+        assert!(glob_match("a/**/b", "a/x/y/b"));
+        assert!(glob_match("a/**", "a/x/y"));
+        assert!(glob_match("[a-c]x", "bx"));
+        assert!(!glob_match("[!a-c]x", "bx"));
+        assert!(glob_match("[^a-c]x", "dx"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("[a-]", "-"));
+        assert!(glob_match("[ab", "[ab"));
+        assert!(glob_match("\\*", "*"));
+        assert!(!glob_match("\\*", "x"));
+        assert!(glob_match("space\\ ", "space "));
+    }
+
+    #[test]
+    fn later_patterns_and_negation_win() {
+        let ignores = vec![Gitignore::parse(
+            Path::new(""),
+            "*.rs\n!keep.rs\nbuild/\n/top.txt\n",
+        )];
+        assert!(is_ignored(&ignores, Path::new("a.rs"), false));
+        assert!(is_ignored(&ignores, Path::new("src/a.rs"), false));
+        assert!(!is_ignored(&ignores, Path::new("src/keep.rs"), false));
+        assert!(is_ignored(&ignores, Path::new("src/build"), true));
+        // `build/` only matches folders.
+        assert!(!is_ignored(&ignores, Path::new("src/build"), false));
+        // Anchored patterns only match from the file's folder.
+        assert!(is_ignored(&ignores, Path::new("top.txt"), false));
+        assert!(!is_ignored(&ignores, Path::new("src/top.txt"), false));
+        assert!(!is_ignored(&ignores, Path::new("other.txt"), false));
+    }
+
+    #[test]
+    fn deeper_files_override_shallower_ones() {
+        let ignores = vec![
+            Gitignore::parse(Path::new(""), "*.gen.rs\n"),
+            Gitignore::parse(Path::new("keep"), "!*.gen.rs\n/local.rs\n"),
+        ];
+        assert!(is_ignored(&ignores, Path::new("a.gen.rs"), false));
+        assert!(is_ignored(&ignores, Path::new("other/a.gen.rs"), false));
+        assert!(!is_ignored(&ignores, Path::new("keep/a.gen.rs"), false));
+        assert!(!is_ignored(
+            &ignores,
+            Path::new("keep/deep/a.gen.rs"),
+            false
+        ));
+        assert!(is_ignored(&ignores, Path::new("keep/local.rs"), false));
+        // Patterns are relative to their own folder.
+        assert!(!is_ignored(&ignores, Path::new("local.rs"), false));
+        assert!(!is_ignored(
+            &ignores,
+            Path::new("keep/deep/local.rs"),
+            false
+        ));
+    }
+
+    #[test]
+    fn walk_honors_nested_gitignores() {
+        let root = scratch_dir("nested");
+        write(&root, ".gitignore", "target/\n*.gen.rs\n");
+        write(&root, "main.rs", "");
+        write(&root, "notes.txt", "");
+        write(&root, "a.gen.rs", "");
+        write(&root, "target/debug/build.rs", "");
+        write(&root, "src/.gitignore", "!*.gen.rs\nscratch.rs\n");
+        write(&root, "src/lib.rs", "");
+        write(&root, "src/b.gen.rs", "");
+        write(&root, "src/scratch.rs", "");
+        write(&root, "src/deep/scratch.rs", "");
+        write(&root, "src/deep/c.gen.rs", "");
+        write(&root, "other/scratch.rs", "");
+        write(&root, ".git/hooks/x.rs", "");
+
+        // A folder's files come before its subfolders.
+        let result = rust_files(&root, &[]);
+        assert_eq!(
+            relative(&root, &result.files),
+            vec![
+                "main.rs",
+                "other/scratch.rs",
+                "src/b.gen.rs",
+                "src/lib.rs",
+                "src/deep/c.gen.rs",
+            ]
+        );
+        assert_eq!(
+            relative(&root, &result.ignored),
+            vec![
+                "a.gen.rs",
+                "target",
+                "src/scratch.rs",
+                "src/deep/scratch.rs",
+            ]
+        );
+        assert!(!result.truncated && !result.cancelled);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn negation_cannot_reach_into_an_ignored_folder() {
+        let root = scratch_dir("negation");
+        write(&root, ".gitignore", "vendor/\n!vendor/keep.rs\n");
+        write(&root, "vendor/keep.rs", "");
+        write(&root, "vendor/.gitignore", "!keep.rs\n");
+        assert!(rust_files(&root, &[]).files.is_empty());
+
+        // Unless the folder is explicitly included.
+        let result = rust_files(&root, &[PathBuf::from("vendor")]);
+        assert_eq!(relative(&root, &result.files), vec!["vendor/keep.rs"]);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn walk_stops_at_its_limits() {
+        let root = scratch_dir("limits");
+        write(&root, "a.rs", "");
+        write(&root, "b.rs", "0123456789");
+        write(&root, "c.rs", "");
+        write(&root, "d.rs", "");
+        let limits = Limits {
+            max_file_size: 5,
+            max_files: 2,
+        };
+        let result = walk(&root, &limits, &[], &AtomicBool::new(false), |_| true);
+        assert_eq!(relative(&root, &result.files), vec!["a.rs", "c.rs"]);
+        assert_eq!(relative(&root, &result.too_large), vec!["b.rs"]);
+        assert!(result.truncated);
+
+        let result = walk(&root, &limits, &[], &AtomicBool::new(true), |_| true);
+        assert!(result.cancelled && result.files.is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,357 @@
+use std::process::Command;
+
+use crate::history::RunRecord;
+
+/// Marks the start of a provenance block. The key names below are part of
+/// the format: they may gain siblings but are never renamed or removed.
+pub const TABLE: &str = "[crowbar-provenance]";
+pub const OVERRIDES_TABLE: &str = "[crowbar-provenance.overrides]";
+
+/// What went into a run, enough to tell whether another run reproduced it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Provenance {
+    pub crowbar_version: String,
+    pub rustc_version: String,
+    pub host: String,
+    pub profile: String,
+    pub flags: String,
+    pub file: Option<String>,
+    /// `fnv1a64:` followed by 16 hex digits, stable across Crowbar builds.
+    pub source_hash: String,
+    /// Panel values substituted into the source, as Rust literals.
+    pub overrides: Vec<(String, String)>,
+    /// Seconds since the Unix epoch.
+    pub started: u64,
+    pub duration_ms: u64,
+    /// Exit code of the program, `None` when it didn't run or was killed.
+    pub exit_code: Option<i32>,
+}
+
+/// Version and host triple of the `rustc` on `PATH`, empty if unavailable.
+pub fn rustc_info() -> (String, String) {
+    let output = Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+        .unwrap_or_default();
+    let version = output.lines().next().unwrap_or_default().to_string();
+    let host = output
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .unwrap_or_default()
+        .to_string();
+    (version, host)
+}
+
+/// FNV-1a, chosen over `DefaultHasher` because its output never changes.
+pub fn source_hash(source: &str) -> String {
+    let hash = source.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("fnv1a64:{:016x}", hash)
+}
+
+impl Provenance {
+    /// The record's provenance block, or `None` for records that weren't
+    /// produced by a run (e.g. imported environments).
+    pub fn block(record: &RunRecord) -> Option<String> {
+        record.provenance.as_ref().map(Provenance::to_block)
+    }
+
+    /// Serializes as TOML with every line commented out, so it can sit on
+    /// top of program output and still be parsed back by [`Self::parse`].
+    pub fn to_block(&self) -> String {
+        let mut lines = vec![
+            TABLE.to_string(),
+            format!("crowbar_version = {}", quote(&self.crowbar_version)),
+            format!("rustc_version = {}", quote(&self.rustc_version)),
+            format!("host = {}", quote(&self.host)),
+            format!("profile = {}", quote(&self.profile)),
+            format!("flags = {}", quote(&self.flags)),
+        ];
+        if let Some(file) = &self.file {
+            lines.push(format!("file = {}", quote(file)));
+        }
+        lines.push(format!("source_hash = {}", quote(&self.source_hash)));
+        lines.push(format!("started = {}", self.started));
+        lines.push(format!("duration_ms = {}", self.duration_ms));
+        if let Some(code) = self.exit_code {
+            lines.push(format!("exit_code = {}", code));
+        }
+        if !self.overrides.is_empty() {
+            lines.push(OVERRIDES_TABLE.to_string());
+            for (name, value) in &self.overrides {
+                lines.push(format!("{} = {}", key(name), quote(value)));
+            }
+        }
+        lines.iter().map(|line| format!("# {}\n", line)).collect()
+    }
+
+    /// Reads the first provenance block in `text`. Unknown keys are ignored
+    /// so newer blocks still parse.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = text
+            .lines()
+            .map_while(|line| line.strip_prefix('#').map(str::trim))
+            .skip_while(|line| *line != TABLE);
+        lines.next()?;
+
+        let mut provenance = Self::default();
+        let mut in_overrides = false;
+        for line in lines {
+            if line == OVERRIDES_TABLE {
+                in_overrides = true;
+                continue;
+            }
+            let Some((key, value)) = split_pair(line) else {
+                continue;
+            };
+            if in_overrides {
+                if let Some(value) = unquote(value) {
+                    provenance.overrides.push((key, value));
+                }
+                continue;
+            }
+            match key.as_str() {
+                "crowbar_version" => provenance.crowbar_version = unquote(value)?,
+                "rustc_version" => provenance.rustc_version = unquote(value)?,
+                "host" => provenance.host = unquote(value)?,
+                "profile" => provenance.profile = unquote(value)?,
+                "flags" => provenance.flags = unquote(value)?,
+                "file" => provenance.file = Some(unquote(value)?),
+                "source_hash" => provenance.source_hash = unquote(value)?,
+                "started" => provenance.started = value.trim().parse().ok()?,
+                "duration_ms" => provenance.duration_ms = value.trim().parse().ok()?,
+                "exit_code" => provenance.exit_code = Some(value.trim().parse().ok()?),
+                _ => {}
+            }
+        }
+        Some(provenance)
+    }
+
+    /// Names of the inputs that differ between two runs. Timing, exit code
+    /// and the Crowbar version don't affect what was run, so they're left out.
+    pub fn differences(&self, other: &Provenance) -> Vec<&'static str> {
+        [
+            ("rustc_version", self.rustc_version == other.rustc_version),
+            ("host", self.host == other.host),
+            ("profile", self.profile == other.profile),
+            ("flags", self.flags == other.flags),
+            ("source_hash", self.source_hash == other.source_hash),
+            ("overrides", self.overrides == other.overrides),
+        ]
+        .into_iter()
+        .filter(|(_, same)| !same)
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+/// Splits saved output into its provenance and the output after the block.
+pub fn split_saved(text: &str) -> (Option<Provenance>, &str) {
+    let block_len: usize = text
+        .split_inclusive('\n')
+        .take_while(|line| {
+            line.strip_prefix("# ")
+                .is_some_and(|line| line.starts_with('[') || line.contains(" = "))
+        })
+        .map(str::len)
+        .sum();
+    match Provenance::parse(text) {
+        Some(provenance) => (Some(provenance), &text[block_len..]),
+        None => (None, text),
+    }
+}
+
+/// Splits a `key = value` line, unquoting the key. Quoted keys may contain
+/// ` = ` themselves.
+fn split_pair(line: &str) -> Option<(String, &str)> {
+    if !line.starts_with('"') {
+        let (key, value) = line.split_once(" = ")?;
+        return Some((key.trim().to_string(), value));
+    }
+    let mut escaped = false;
+    let end = line[1..].find(|c| {
+        let closes = c == '"' && !escaped;
+        escaped = c == '\\' && !escaped;
+        closes
+    })? + 2;
+    let value = line[end..].trim_start().strip_prefix('=')?;
+    Some((unquote(&line[..end])?, value))
+}
+
+/// A TOML key, quoted unless it's a valid bare key.
+fn key(name: &str) -> String {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+/// A TOML basic string.
+fn quote(value: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unquote(value: &str) -> Option<String> {
+    let inner = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            't' => result.push('\t'),
+            'u' => {
+                let code: String = chars.by_ref().take(4).collect();
+                result.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            c => result.push(c),
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Provenance {
+        Provenance {
+            crowbar_version: "0.1.0".to_string(),
+            rustc_version: "rustc 1.80.0 (051478957 2024-07-21)".to_string(),
+            host: "x86_64-unknown-linux-gnu".to_string(),
+            profile: "release".to_string(),
+            flags: "-C target-cpu=native".to_string(),
+            file: Some("/home/me/\"odd\" dir\\demo.rs".to_string()),
+            source_hash: source_hash("fn main() {}\n"),
+            overrides: vec![
+                ("speed".to_string(), "2.5".to_string()),
+                ("label".to_string(), "\"a\\tb\\n\"".to_string()),
+                ("max rate = fast".to_string(), "3".to_string()),
+                ("say \"hi\" = \\".to_string(), "4".to_string()),
+                ("bell".to_string(), "\u{7}".to_string()),
+            ],
+            started: 1_700_000_000,
+            duration_ms: 1234,
+            exit_code: Some(-1),
+        }
+    }
+
+    #[test]
+    fn blocks_round_trip() {
+        let provenance = sample();
+        let block = provenance.to_block();
+        assert!(block.lines().all(|line| line.starts_with("# ")));
+        assert_eq!(Provenance::parse(&block), Some(provenance));
+    }
+
+    #[test]
+    fn optional_fields_round_trip_when_missing() {
+        let provenance = Provenance {
+            file: None,
+            exit_code: None,
+            overrides: Vec::new(),
+            ..sample()
+        };
+        let block = provenance.to_block();
+        assert!(!block.contains("# file ="));
+        assert!(!block.contains("exit_code"));
+        assert!(!block.contains(OVERRIDES_TABLE));
+        assert_eq!(Provenance::parse(&block), Some(provenance));
+    }
+
+    #[test]
+    fn field_names_are_stable() {
+        let block = sample().to_block();
+        let keys: Vec<&str> = block
+            .lines()
+            .skip(1)
+            .map_while(|line| line.strip_prefix("# ")?.split_once(" = "))
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "crowbar_version",
+                "rustc_version",
+                "host",
+                "profile",
+                "flags",
+                "file",
+                "source_hash",
+                "started",
+                "duration_ms",
+                "exit_code",
+            ]
+        );
+        assert!(block.starts_with("# [crowbar-provenance]\n"));
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        let block = sample()
+            .to_block()
+            .replace("# host = ", "# linker = \"lld\"\n# host = ");
+        assert_eq!(Provenance::parse(&block), Some(sample()));
+    }
+
+    #[test]
+    fn saved_output_splits_into_block_and_output() {
+        let text = format!("{}hello = world\n# not provenance\n", sample().to_block());
+        let (provenance, output) = split_saved(&text);
+        assert_eq!(provenance, Some(sample()));
+        assert_eq!(output, "hello = world\n# not provenance\n");
+
+        let (provenance, output) = split_saved("plain output\n");
+        assert_eq!(provenance, None);
+        assert_eq!(output, "plain output\n");
+    }
+
+    #[test]
+    fn hashes_never_change() {
+        assert_eq!(source_hash(""), "fnv1a64:cbf29ce484222325");
+        assert_eq!(source_hash("a"), "fnv1a64:af63dc4c8601ec8c");
+        assert_ne!(source_hash("fn main() {}"), source_hash("fn main() { }"));
+    }
+
+    #[test]
+    fn differences_ignore_timing_and_version() {
+        let other = Provenance {
+            crowbar_version: "0.2.0".to_string(),
+            started: 0,
+            duration_ms: 1,
+            exit_code: None,
+            ..sample()
+        };
+        assert!(sample().differences(&other).is_empty());
+        let other = Provenance {
+            profile: "debug".to_string(),
+            overrides: Vec::new(),
+            ..sample()
+        };
+        assert_eq!(sample().differences(&other), vec!["profile", "overrides"]);
+    }
+}
@@ -0,0 +1,44 @@
+/// A small seeded generator (SplitMix64), so the same seed draws the same
+/// values every time.
+#[derive(Clone, Debug, Default)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform between `min` and `max`.
+    pub fn float(&mut self, min: f64, max: f64) -> f64 {
+        let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        min + (max - min) * unit
+    }
+
+    /// Uniform in `min..=max`.
+    pub fn int(&mut self, min: i64, max: i64) -> i64 {
+        let span = (max as i128 - min as i128 + 1) as u128;
+        (min as i128 + (self.next() as u128 % span) as i128) as i64
+    }
+}
+
+/// Where a random value is drawn from: the annotated `range`, or else up to
+/// ten times the `original` value either way, within the type's `limits`.
+pub fn bounds(original: f64, range: Option<(f64, f64)>, limits: (f64, f64)) -> (f64, f64) {
+    let (min, max) = range.unwrap_or_else(|| {
+        let reach = if original == 0.0 {
+            10.0
+        } else {
+            original.abs() * 10.0
+        };
+        (-reach, reach)
+    });
+    (min.max(limits.0), max.min(limits.1))
+}
@@ -0,0 +1,803 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::lock;
+use crate::paths;
+use crate::processes::{self, Role};
+
+/// Names of the source and binary inside the runner's directory.
+pub const SOURCE_FILE: &str = "temp_code.rs";
+pub const ARTIFACT_FILE: &str = "temp_executable";
+/// Tells the program where to write its output files.
+pub const OUT_DIR_VAR: &str = "CROWBAR_OUT_DIR";
+/// How long a run waits for another instance to finish with the artifact.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Profile {
+    #[default]
+    Debug,
+    Release,
+}
+
+impl Profile {
+    pub fn label(self) -> &'static str {
+        match self {
+            Profile::Debug => "debug",
+            Profile::Release => "release",
+        }
+    }
+
+    fn rustc_flags(self) -> &'static [&'static str] {
+        match self {
+            Profile::Debug => &[],
+            Profile::Release => &["-C", "opt-level=3"],
+        }
+    }
+}
+
+/// Everything that affects the produced binary.
+#[derive(Clone, Default, Hash)]
+pub struct BuildConfig {
+    pub profile: Profile,
+    /// Extra rustc flags, whitespace separated.
+    pub flags: String,
+}
+
+/// Inputs that only affect execution, never the build.
+#[derive(Clone, Default)]
+pub struct RunInputs {
+    /// Command line arguments, whitespace separated.
+    pub args: String,
+    pub stdin: String,
+    /// One `KEY=VALUE` pair per line.
+    pub env: String,
+    /// Replaces the inherited environment entirely when replaying a run.
+    pub base_env: Option<Vec<(String, String)>>,
+    pub working_dir: Option<PathBuf>,
+    /// This run's own output folder, exported as [`OUT_DIR_VAR`].
+    pub out_dir: Option<PathBuf>,
+}
+
+impl RunInputs {
+    fn env_pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.env
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim(), value))
+            .filter(|(key, _)| !key.is_empty())
+    }
+}
+
+pub trait Compiler {
+    /// Identifies the toolchain so a switch invalidates cached builds.
+    fn toolchain(&self) -> String;
+
+    /// Compiles `source` into `output`, returning the compiler's stderr on
+    /// failure.
+    fn compile(&self, source: &Path, output: &Path, flags: &[String]) -> Result<(), String>;
+}
+
+/// Compiles with the `rustc` found on `PATH`.
+#[derive(Default)]
+pub struct Rustc;
+
+impl Compiler for Rustc {
+    fn toolchain(&self) -> String {
+        Command::new("rustc")
+            .arg("--version")
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .unwrap_or_default()
+    }
+
+    fn compile(&self, source: &Path, output: &Path, flags: &[String]) -> Result<(), String> {
+        let child = Command::new("rustc")
+            .arg(source)
+            .arg("-o")
+            .arg(output)
+            .args(flags)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to compile the code: {}", e))?;
+        let _tracked = processes::track(
+            child.id(),
+            Role::Compiler,
+            Some(source),
+            processes::rustc_path(),
+        );
+        let result = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to compile the code: {}", e))?;
+        if result.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Compilation error:\n{}",
+                String::from_utf8_lossy(&result.stderr)
+            ))
+        }
+    }
+}
+
+/// Remembers which build the artifact on disk belongs to.
+#[derive(Default)]
+pub struct BuildCache {
+    key: Option<u64>,
+    /// Modification time of the artifact we built, so one rebuilt by another
+    /// instance since isn't mistaken for ours.
+    modified: Option<SystemTime>,
+}
+
+impl BuildCache {
+    pub fn key(source: &str, config: &BuildConfig, toolchain: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        config.hash(&mut hasher);
+        toolchain.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn is_fresh(&self, key: u64, artifact: &Path) -> bool {
+        self.key == Some(key) && self.modified.is_some() && modified(artifact) == self.modified
+    }
+
+    pub fn record(&mut self, key: u64, artifact: &Path) {
+        self.key = Some(key);
+        self.modified = modified(artifact);
+    }
+
+    pub fn clear(&mut self) {
+        self.key = None;
+        self.modified = None;
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// A run in flight as seen from the UI thread.
+#[derive(Default)]
+pub struct Progress {
+    /// Whether another instance is using the artifact and this run waits.
+    pub waiting: bool,
+    /// Whether the build is done and the program started.
+    pub running: bool,
+    /// Standard output printed so far.
+    pub output: String,
+    pub stop_requested: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunResult {
+    /// One-line summary of how the binary was obtained.
+    pub header: String,
+    pub output: String,
+    pub build_failed: bool,
+    /// Exit code of the program, `None` if it didn't run or was killed.
+    pub exit_code: Option<i32>,
+}
+
+pub struct Runner<C: Compiler, E: Executor = Process> {
+    compiler: C,
+    executor: E,
+    cache: BuildCache,
+    /// Where the source is written and built, private to this session so
+    /// other instances never write the same files.
+    dir: PathBuf,
+}
+
+impl<C: Compiler + Default, E: Executor + Default> Default for Runner<C, E> {
+    fn default() -> Self {
+        Self::new(C::default(), E::default(), paths::session_dir())
+    }
+}
+
+impl<C: Compiler, E: Executor> Runner<C, E> {
+    pub fn new(compiler: C, executor: E, dir: PathBuf) -> Self {
+        Self {
+            compiler,
+            executor,
+            cache: BuildCache::default(),
+            dir,
+        }
+    }
+
+    /// Builds `source` unless the previous build used identical source,
+    /// config and toolchain, then runs the binary with `inputs`. `file` is the
+    /// user's file the source came from, if any. The program's output is
+    /// streamed into `progress`, which can also ask for it to be killed.
+    pub fn run(
+        &mut self,
+        source: &str,
+        config: &BuildConfig,
+        inputs: &RunInputs,
+        file: Option<&Path>,
+        progress: &Arc<Mutex<Progress>>,
+    ) -> RunResult {
+        let artifact = self.dir.join(ARTIFACT_FILE);
+        let source_path = self.dir.join(SOURCE_FILE);
+        if let Err(e) = std::fs::create_dir_all(&self.dir) {
+            return RunResult {
+                header: "build failed".to_string(),
+                output: format!("Failed to create {}: {}", self.dir.display(), e),
+                build_failed: true,
+                exit_code: None,
+            };
+        }
+        // Held until the program exits, so another instance can neither
+        // rebuild the binary under it nor have its own replaced.
+        let lock = lock::lock(&artifact, LOCK_TIMEOUT, &processes::is_alive, |_| {
+            let mut progress = progress.lock().unwrap();
+            progress.waiting = true;
+            !progress.stop_requested
+        });
+        progress.lock().unwrap().waiting = false;
+        let _lock = match lock {
+            Ok(lock) => lock,
+            Err(e) => {
+                let stopped = progress.lock().unwrap().stop_requested;
+                return RunResult {
+                    header: "build failed".to_string(),
+                    output: if stopped {
+                        "Stopped while waiting for another build".to_string()
+                    } else {
+                        e
+                    },
+                    build_failed: true,
+                    exit_code: None,
+                };
+            }
+        };
+        let key = BuildCache::key(source, config, &self.compiler.toolchain());
+
+        let header = if self.cache.is_fresh(key, &artifact) {
+            "reused cached build".to_string()
+        } else {
+            self.cache.clear();
+            if let Err(e) = std::fs::write(&source_path, source) {
+                return RunResult {
+                    header: "build failed".to_string(),
+                    output: format!("Failed to write code to file: {}", e),
+                    build_failed: true,
+                    exit_code: None,
+                };
+            }
+
+            let mut flags: Vec<String> = config
+                .profile
+                .rustc_flags()
+                .iter()
+                .map(|flag| flag.to_string())
+                .collect();
+            flags.extend(config.flags.split_whitespace().map(str::to_string));
+
+            let started = Instant::now();
+            if let Err(error) = self.compiler.compile(&source_path, &artifact, &flags) {
+                return RunResult {
+                    header: "build failed".to_string(),
+                    output: error,
+                    build_failed: true,
+                    exit_code: None,
+                };
+            }
+            self.cache.record(key, &artifact);
+            format!(
+                "compiled ({}) in {:.2}s",
+                config.profile.label(),
+                started.elapsed().as_secs_f32()
+            )
+        };
+
+        if progress.lock().unwrap().stop_requested {
+            return RunResult {
+                header,
+                output: "Stopped before running".to_string(),
+                build_failed: false,
+                exit_code: None,
+            };
+        }
+        progress.lock().unwrap().running = true;
+        let (output, exit_code) =
+            self.executor
+                .execute(&Launch::new(&artifact, inputs), file, progress);
+        RunResult {
+            header,
+            output,
+            build_failed: false,
+            exit_code,
+        }
+    }
+
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+        // Left alone while another instance builds or runs it.
+        let artifact = self.dir.join(ARTIFACT_FILE);
+        if let Ok(_lock) = lock::try_lock(&artifact, &processes::is_alive) {
+            let _ = std::fs::remove_file(&artifact);
+        }
+    }
+}
+
+/// Everything needed to start a built program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Launch {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    /// Replaces the inherited environment entirely when set.
+    pub base_env: Option<Vec<(String, String)>>,
+    /// Set on top of the inherited or base environment, later pairs winning.
+    pub env: Vec<(String, String)>,
+    pub working_dir: Option<PathBuf>,
+    pub stdin: String,
+}
+
+impl Launch {
+    pub fn new(artifact: &Path, inputs: &RunInputs) -> Self {
+        // Absolute, so a replayed working directory doesn't change what runs.
+        let program = artifact
+            .canonicalize()
+            .unwrap_or_else(|_| Path::new(".").join(artifact));
+        let mut env = Vec::new();
+        if let Some(dir) = &inputs.out_dir {
+            env.push((OUT_DIR_VAR.to_string(), dir.display().to_string()));
+        }
+        env.extend(
+            inputs
+                .env_pairs()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+        Self {
+            program,
+            args: inputs.args.split_whitespace().map(str::to_string).collect(),
+            base_env: inputs.base_env.clone(),
+            env,
+            working_dir: inputs.working_dir.clone(),
+            stdin: inputs.stdin.clone(),
+        }
+    }
+}
+
+/// Starts built programs, so tests can stand in for real processes.
+pub trait Executor {
+    /// Runs `launch` to completion, streaming its output into `progress` and
+    /// killing it if a stop is requested. Returns the output and exit code.
+    fn execute(
+        &self,
+        launch: &Launch,
+        file: Option<&Path>,
+        progress: &Arc<Mutex<Progress>>,
+    ) -> (String, Option<i32>);
+}
+
+/// Runs programs as child processes.
+#[derive(Default)]
+pub struct Process;
+
+impl Executor for Process {
+    fn execute(
+        &self,
+        launch: &Launch,
+        file: Option<&Path>,
+        progress: &Arc<Mutex<Progress>>,
+    ) -> (String, Option<i32>) {
+        execute(launch, file, progress)
+    }
+}
+
+fn execute(
+    launch: &Launch,
+    file: Option<&Path>,
+    progress: &Arc<Mutex<Progress>>,
+) -> (String, Option<i32>) {
+    let mut command = Command::new(&launch.program);
+    if let Some(base_env) = &launch.base_env {
+        command
+            .env_clear()
+            .envs(base_env.iter().map(|(k, v)| (k, v)));
+    }
+    if let Some(dir) = &launch.working_dir {
+        command.current_dir(dir);
+    }
+    let child = command
+        .args(&launch.args)
+        .envs(launch.env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => return (format!("Failed to run the code: {}", e), None),
+    };
+    let _tracked = processes::track(child.id(), Role::Program, file, &launch.program);
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(launch.stdin.as_bytes());
+    }
+
+    // Read on their own threads so neither pipe fills up while we poll for
+    // a stop request.
+    let stdout = child.stdout.take().map(|mut stdout| {
+        let progress = progress.clone();
+        std::thread::spawn(move || {
+            let mut bytes = Vec::new();
+            let mut buffer = [0; 4096];
+            while let Ok(read @ 1..) = stdout.read(&mut buffer) {
+                bytes.extend_from_slice(&buffer[..read]);
+                progress.lock().unwrap().output = String::from_utf8_lossy(&bytes).to_string();
+            }
+            String::from_utf8_lossy(&bytes).to_string()
+        })
+    });
+    let stderr = child.stderr.take().map(|mut stderr| {
+        std::thread::spawn(move || std::io::copy(&mut stderr, &mut std::io::sink()))
+    });
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if progress.lock().unwrap().stop_requested => {
+                let _ = child.kill();
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            Err(e) => break Err(e),
+        }
+    };
+    // A killed program's own children may still hold the pipes open, so
+    // settle for what was read so far instead of waiting for them.
+    let stopped = progress.lock().unwrap().stop_requested;
+    let output = if stopped {
+        progress.lock().unwrap().output.clone()
+    } else {
+        if let Some(reader) = stderr {
+            let _ = reader.join();
+        }
+        stdout
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default()
+    };
+    match status {
+        Ok(status) => (output, status.code()),
+        Err(e) => (format!("Failed to run the code: {}", e), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    /// Counts compilations and writes a placeholder binary so the build cache
+    /// sees a real artifact.
+    struct FakeCompiler {
+        compiles: Cell<usize>,
+        toolchain: RefCell<String>,
+        fail: Cell<bool>,
+    }
+
+    impl Default for FakeCompiler {
+        fn default() -> Self {
+            Self {
+                compiles: Cell::new(0),
+                toolchain: RefCell::new("rustc 1.0.0".to_string()),
+                fail: Cell::new(false),
+            }
+        }
+    }
+
+    impl Compiler for FakeCompiler {
+        fn toolchain(&self) -> String {
+            self.toolchain.borrow().clone()
+        }
+
+        fn compile(&self, _source: &Path, output: &Path, _flags: &[String]) -> Result<(), String> {
+            self.compiles.set(self.compiles.get() + 1);
+            if self.fail.get() {
+                return Err("Compilation error:\nexpected `;`".to_string());
+            }
+            std::fs::write(output, "binary").map_err(|e| e.to_string())
+        }
+    }
+
+    /// Records every launch instead of starting a process.
+    #[derive(Default)]
+    struct RecordingExecutor {
+        launches: Mutex<Vec<Launch>>,
+    }
+
+    impl Executor for RecordingExecutor {
+        fn execute(
+            &self,
+            launch: &Launch,
+            _file: Option<&Path>,
+            _progress: &Arc<Mutex<Progress>>,
+        ) -> (String, Option<i32>) {
+            self.launches.lock().unwrap().push(launch.clone());
+            (String::new(), Some(0))
+        }
+    }
+
+    fn runner(name: &str) -> Runner<FakeCompiler, RecordingExecutor> {
+        let dir = std::env::temp_dir().join(format!(
+            "crowbar-test-runner-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        Runner::new(FakeCompiler::default(), RecordingExecutor::default(), dir)
+    }
+
+    fn launches(runner: &Runner<FakeCompiler, RecordingExecutor>) -> Vec<Launch> {
+        runner.executor.launches.lock().unwrap().clone()
+    }
+
+    fn var<'a>(launch: &'a Launch, key: &str) -> Option<&'a str> {
+        launch
+            .env
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn out_dir_reaches_the_program() {
+        let mut runner = runner("out-dir");
+        let inputs = RunInputs {
+            out_dir: Some(PathBuf::from("/runs/run-1")),
+            ..RunInputs::default()
+        };
+        runner.run(
+            "fn main() {}",
+            &BuildConfig::default(),
+            &inputs,
+            None,
+            &Arc::default(),
+        );
+        assert_eq!(var(&launches(&runner)[0], OUT_DIR_VAR), Some("/runs/run-1"));
+    }
+
+    #[test]
+    fn no_out_dir_exports_nothing() {
+        let mut runner = runner("no-out-dir");
+        runner.run(
+            "fn main() {}",
+            &BuildConfig::default(),
+            &RunInputs::default(),
+            None,
+            &Arc::default(),
+        );
+        assert_eq!(var(&launches(&runner)[0], OUT_DIR_VAR), None);
+    }
+
+    #[test]
+    fn out_dir_survives_a_replayed_environment() {
+        let mut runner = runner("replay");
+        let inputs = RunInputs {
+            env: "MODE=fast\n=ignored\nnot a pair".to_string(),
+            base_env: Some(vec![("PATH".to_string(), "/bin".to_string())]),
+            out_dir: Some(PathBuf::from("/runs/run-2")),
+            ..RunInputs::default()
+        };
+        runner.run(
+            "fn main() {}",
+            &BuildConfig::default(),
+            &inputs,
+            None,
+            &Arc::default(),
+        );
+        let launch = &launches(&runner)[0];
+        assert_eq!(
+            launch.base_env,
+            Some(vec![("PATH".to_string(), "/bin".to_string())])
+        );
+        assert_eq!(var(launch, OUT_DIR_VAR), Some("/runs/run-2"));
+        assert_eq!(var(launch, "MODE"), Some("fast"));
+        assert_eq!(launch.env.len(), 2);
+    }
+
+    #[test]
+    fn each_run_gets_its_own_out_dir() {
+        let mut runner = runner("per-run");
+        for dir in ["/runs/run-3", "/runs/run-3-2"] {
+            let inputs = RunInputs {
+                out_dir: Some(PathBuf::from(dir)),
+                ..RunInputs::default()
+            };
+            runner.run(
+                "fn main() {}",
+                &BuildConfig::default(),
+                &inputs,
+                None,
+                &Arc::default(),
+            );
+        }
+        let dirs: Vec<_> = launches(&runner)
+            .iter()
+            .map(|launch| var(launch, OUT_DIR_VAR).map(str::to_string))
+            .collect();
+        assert_eq!(
+            dirs,
+            vec![
+                Some("/runs/run-3".to_string()),
+                Some("/runs/run-3-2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn launch_carries_args_stdin_and_working_dir() {
+        let inputs = RunInputs {
+            args: " --size  3 ".to_string(),
+            stdin: "input".to_string(),
+            working_dir: Some(PathBuf::from("/work")),
+            ..RunInputs::default()
+        };
+        let launch = Launch::new(Path::new("missing-artifact"), &inputs);
+        assert_eq!(launch.args, vec!["--size", "3"]);
+        assert_eq!(launch.stdin, "input");
+        assert_eq!(launch.working_dir, Some(PathBuf::from("/work")));
+        assert_eq!(launch.program, Path::new(".").join("missing-artifact"));
+    }
+
+    /// Runs `source` and returns the header and how many compilations
+    /// happened so far.
+    fn run(
+        runner: &mut Runner<FakeCompiler, RecordingExecutor>,
+        source: &str,
+        config: &BuildConfig,
+        inputs: &RunInputs,
+    ) -> (String, usize) {
+        let result = runner.run(source, config, inputs, None, &Arc::default());
+        (result.header, runner.compiler.compiles.get())
+    }
+
+    fn release() -> BuildConfig {
+        BuildConfig {
+            profile: Profile::Release,
+            ..BuildConfig::default()
+        }
+    }
+
+    #[test]
+    fn runtime_inputs_reuse_the_build() {
+        let mut runner = runner("reuse");
+        let config = BuildConfig::default();
+        let (header, count) = run(&mut runner, "fn main() {}", &config, &RunInputs::default());
+        assert!(header.starts_with("compiled (debug)"), "{}", header);
+        assert_eq!(count, 1);
+        for inputs in [
+            RunInputs {
+                args: "--verbose".to_string(),
+                ..RunInputs::default()
+            },
+            RunInputs {
+                stdin: "42".to_string(),
+                ..RunInputs::default()
+            },
+            RunInputs {
+                env: "MODE=fast".to_string(),
+                ..RunInputs::default()
+            },
+        ] {
+            assert_eq!(
+                run(&mut runner, "fn main() {}", &config, &inputs),
+                ("reused cached build".to_string(), 1)
+            );
+        }
+        assert_eq!(launches(&runner).len(), 4);
+    }
+
+    #[test]
+    fn source_change_rebuilds() {
+        let mut runner = runner("source");
+        let config = BuildConfig::default();
+        let inputs = RunInputs::default();
+        run(&mut runner, "const N: i32 = 1;", &config, &inputs);
+        assert_eq!(run(&mut runner, "const N: i32 = 2;", &config, &inputs).1, 2);
+        // Only the previous build is kept, so going back rebuilds too.
+        assert_eq!(run(&mut runner, "const N: i32 = 1;", &config, &inputs).1, 3);
+        assert_eq!(run(&mut runner, "const N: i32 = 1;", &config, &inputs).1, 3);
+    }
+
+    #[test]
+    fn profile_and_flag_changes_rebuild() {
+        let mut runner = runner("config");
+        let inputs = RunInputs::default();
+        run(
+            &mut runner,
+            "fn main() {}",
+            &BuildConfig::default(),
+            &inputs,
+        );
+        let (header, count) = run(&mut runner, "fn main() {}", &release(), &inputs);
+        assert!(header.starts_with("compiled (release)"), "{}", header);
+        assert_eq!(count, 2);
+        let flagged = BuildConfig {
+            flags: "-C debuginfo=2".to_string(),
+            ..release()
+        };
+        assert_eq!(run(&mut runner, "fn main() {}", &flagged, &inputs).1, 3);
+        assert_eq!(run(&mut runner, "fn main() {}", &flagged, &inputs).1, 3);
+    }
+
+    #[test]
+    fn toolchain_switch_rebuilds() {
+        let mut runner = runner("toolchain");
+        let (config, inputs) = (BuildConfig::default(), RunInputs::default());
+        run(&mut runner, "fn main() {}", &config, &inputs);
+        *runner.compiler.toolchain.borrow_mut() = "rustc 2.0.0".to_string();
+        assert_eq!(run(&mut runner, "fn main() {}", &config, &inputs).1, 2);
+        assert_eq!(run(&mut runner, "fn main() {}", &config, &inputs).1, 2);
+    }
+
+    #[test]
+    fn clearing_the_cache_rebuilds() {
+        let mut runner = runner("clear");
+        let (config, inputs) = (BuildConfig::default(), RunInputs::default());
+        run(&mut runner, "fn main() {}", &config, &inputs);
+        runner.clear_cache();
+        assert!(!runner.dir.join(ARTIFACT_FILE).exists());
+        assert_eq!(run(&mut runner, "fn main() {}", &config, &inputs).1, 2);
+    }
+
+    #[test]
+    fn missing_or_replaced_artifact_rebuilds() {
+        let mut runner = runner("artifact");
+        let (config, inputs) = (BuildConfig::default(), RunInputs::default());
+        let artifact = runner.dir.join(ARTIFACT_FILE);
+        run(&mut runner, "fn main() {}", &config, &inputs);
+        std::fs::remove_file(&artifact).unwrap();
+        assert_eq!(run(&mut runner, "fn main() {}", &config, &inputs).1, 2);
+
+        // As if another instance had rebuilt it since.
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&artifact)
+            .unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(run(&mut runner, "fn main() {}", &config, &inputs).1, 3);
+    }
+
+    #[test]
+    fn failed_build_is_not_cached() {
+        let mut runner = runner("failed");
+        let (config, inputs) = (BuildConfig::default(), RunInputs::default());
+        runner.compiler.fail.set(true);
+        let result = runner.run("fn main() {", &config, &inputs, None, &Arc::default());
+        assert!(result.build_failed);
+        assert_eq!(result.header, "build failed");
+        assert!(launches(&runner).is_empty());
+        assert_eq!(run(&mut runner, "fn main() {", &config, &inputs).1, 2);
+        runner.compiler.fail.set(false);
+        assert_eq!(run(&mut runner, "fn main() {", &config, &inputs).1, 3);
+        assert_eq!(run(&mut runner, "fn main() {", &config, &inputs).1, 3);
+    }
+
+    #[test]
+    fn stop_before_running_skips_the_program() {
+        let mut runner = runner("stop");
+        let progress = Arc::new(Mutex::new(Progress {
+            stop_requested: true,
+            ..Progress::default()
+        }));
+        let result = runner.run(
+            "fn main() {}",
+            &BuildConfig::default(),
+            &RunInputs::default(),
+            None,
+            &progress,
+        );
+        assert_eq!(result.output, "Stopped before running");
+        assert!(launches(&runner).is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+
+/// What a rust-script style file declares in front of its code.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script {
+    /// The `#!` line without its line break.
+    pub shebang: Option<String>,
+    /// `name = spec` pairs from the `[dependencies]` table of a
+    /// ``//! ```cargo`` block, with the spec as written.
+    pub dependencies: Vec<(String, String)>,
+}
+
+/// Length of a leading shebang line, excluding the line break. `#![...]` is
+/// an inner attribute, not a shebang.
+pub fn shebang_len(code: &str) -> Option<usize> {
+    let rest = code.strip_prefix("#!")?;
+    if rest.trim_start().starts_with('[') {
+        return None;
+    }
+    Some(code.find('\n').unwrap_or(code.len()))
+}
+
+/// The script header of `code`, or `None` for a plain Rust file.
+pub fn detect(code: &str) -> Option<Script> {
+    let shebang = shebang_len(code).map(|len| code[..len].trim_end_matches('\r').to_string());
+    let body = &code[shebang.as_ref().map_or(0, |shebang| shebang.len())..];
+    let dependencies = cargo_dependencies(body);
+    if shebang.is_none() && dependencies.is_empty() && !has_cargo_block(body) {
+        return None;
+    }
+    Some(Script {
+        shebang,
+        dependencies,
+    })
+}
+
+/// `code` as rustc should see it: a shebang is blanked out with spaces, so
+/// byte offsets in diagnostics still match the buffer.
+pub fn compile_source(code: &str) -> Cow<'_, str> {
+    match shebang_len(code) {
+        Some(len) => Cow::Owned(" ".repeat(len) + &code[len..]),
+        None => Cow::Borrowed(code),
+    }
+}
+
+/// Inner doc comment lines at the top of `body`, without their `//!`.
+fn doc_lines(body: &str) -> impl Iterator<Item = &str> {
+    body.lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty())
+        .map_while(|line| line.strip_prefix("//!"))
+        .map(str::trim)
+}
+
+fn has_cargo_block(body: &str) -> bool {
+    doc_lines(body).any(|line| line == "```cargo")
+}
+
+/// Dependencies from the cargo block. Lines that aren't `key = value` are
+/// skipped.
+fn cargo_dependencies(body: &str) -> Vec<(String, String)> {
+    let mut dependencies = Vec::new();
+    let mut in_dependencies = false;
+    for line in doc_lines(body)
+        .skip_while(|line| *line != "```cargo")
+        .skip(1)
+        .take_while(|line| *line != "```")
+    {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_dependencies = line == "[dependencies]";
+            continue;
+        }
+        if let Some((name, spec)) = line.split_once('=').filter(|_| in_dependencies) {
+            dependencies.push((name.trim().to_string(), spec.trim().to_string()));
+        }
+    }
+    dependencies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEBANG_ONLY: &str =
+        "#!/usr/bin/env rust-script\nfn main() {\n    println!(\"hi\");\n}\n";
+
+    const WITH_CARGO_BLOCK: &str = "#!/usr/bin/env rust-script\n//! Prints a random number.\n//!\n//! ```cargo\n//! [package]\n//! edition = \"2021\"\n//!\n//! [dependencies]\n//! rand = \"0.8\"\n//! # pinned for the demo\n//! serde = { version = \"1\", features = [\"derive\"] }\n//! [dev-dependencies]\n//! proptest = \"1\"\n//! ```\nfn main() {}\n";
+
+    const PLAIN: &str = "#![allow(unused)]\n//! Docs.\nfn main() {}\n";
+
+    #[test]
+    fn shebang_only_files_are_scripts_without_dependencies() {
+        assert_eq!(
+            detect(SHEBANG_ONLY),
+            Some(Script {
+                shebang: Some("#!/usr/bin/env rust-script".to_string()),
+                dependencies: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn cargo_blocks_list_their_dependencies() {
+        assert_eq!(
+            detect(WITH_CARGO_BLOCK),
+            Some(Script {
+                shebang: Some("#!/usr/bin/env rust-script".to_string()),
+                dependencies: vec![
+                    ("rand".to_string(), "\"0.8\"".to_string()),
+                    (
+                        "serde".to_string(),
+                        "{ version = \"1\", features = [\"derive\"] }".to_string()
+                    ),
+                ],
+            })
+        );
+        // A cargo block without a shebang still makes a script.
+        let without_shebang = &WITH_CARGO_BLOCK[WITH_CARGO_BLOCK.find('\n').unwrap() + 1..];
+        let script = detect(without_shebang).unwrap();
+        assert_eq!(script.shebang, None);
+        assert_eq!(script.dependencies.len(), 2);
+        let empty_block = "//! ```cargo\n//! ```\nfn main() {}\n";
+        assert_eq!(detect(empty_block), Some(Script::default()));
+    }
+
+    #[test]
+    fn plain_files_are_not_scripts() {
+        assert_eq!(detect(PLAIN), None);
+        assert_eq!(detect(""), None);
+        assert_eq!(shebang_len(PLAIN), None);
+        assert_eq!(shebang_len("#! [allow(unused)]\n"), None);
+        assert!(matches!(compile_source(PLAIN), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn rustc_sees_the_shebang_blanked_at_the_same_offsets() {
+        for code in [SHEBANG_ONLY, WITH_CARGO_BLOCK] {
+            let source = compile_source(code);
+            assert_eq!(source.len(), code.len());
+            let len = shebang_len(code).unwrap();
+            assert!(source[..len].chars().all(|c| c == ' '));
+            assert_eq!(&source[len..], &code[len..]);
+            assert!(syn::parse_file(&source).is_ok());
+        }
+    }
+
+    #[test]
+    fn crlf_shebangs_keep_their_line_break_out() {
+        let code = "#!/usr/bin/env rust-script\r\nfn main() {}\r\n";
+        assert_eq!(
+            detect(code).unwrap().shebang.as_deref(),
+            Some("#!/usr/bin/env rust-script")
+        );
+        assert_eq!(&compile_source(code)[27..], "\nfn main() {}\r\n");
+        assert_eq!(shebang_len("#!/bin/sh"), Some(9));
+    }
+}
@@ -0,0 +1,424 @@
+use std::ops::Range;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::paths;
+
+/// A user-defined snippet. The body marks tab stops as `$1` or
+/// `${1:default text}`, with `$0` for where the cursor ends up.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snippet {
+    pub name: String,
+    /// Typed before Tab to expand the snippet.
+    pub trigger: String,
+    pub body: String,
+}
+
+fn snippets_path() -> PathBuf {
+    paths::config_dir().join("snippets.json")
+}
+
+/// The saved snippets; none when nothing was saved yet.
+pub fn load() -> Result<Vec<Snippet>, String> {
+    match std::fs::read_to_string(snippets_path()) {
+        Ok(text) => from_json(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read snippets: {}", e)),
+    }
+}
+
+pub fn save(snippets: &[Snippet]) -> std::io::Result<()> {
+    let path = snippets_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, to_json(snippets))
+}
+
+pub fn to_json(snippets: &[Snippet]) -> String {
+    let entries: Vec<Value> = snippets
+        .iter()
+        .map(|snippet| {
+            json!({
+                "name": snippet.name,
+                "trigger": snippet.trigger,
+                "body": snippet.body,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&entries).unwrap_or_default()
+}
+
+pub fn from_json(text: &str) -> Result<Vec<Snippet>, String> {
+    let value: Value =
+        serde_json::from_str(text).map_err(|e| format!("Snippets file is invalid: {}", e))?;
+    let entries = value
+        .as_array()
+        .ok_or("Snippets file is invalid: expected a list")?;
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let field = |key: &str| entry[key].as_str().unwrap_or_default().to_string();
+            Snippet {
+                name: field("name"),
+                trigger: field("trigger"),
+                body: field("body"),
+            }
+        })
+        .collect())
+}
+
+/// The snippet whose trigger `text` ends with, as a whole word.
+pub fn matching<'a>(snippets: &'a [Snippet], text: &str) -> Option<&'a Snippet> {
+    snippets.iter().find(|snippet| {
+        !snippet.trigger.is_empty()
+            && text
+                .strip_suffix(&snippet.trigger)
+                .is_some_and(|before| !before.ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+    })
+}
+
+/// A snippet body with its placeholders filled in by their defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expansion {
+    pub text: String,
+    /// Byte ranges of the tab stops in visiting order: `$1`, `$2`, … and
+    /// `$0` (or the end of the text) last. A number used twice is a stop
+    /// only the first time.
+    pub stops: Vec<Range<usize>>,
+}
+
+/// Parses `body`. `\$` is a literal dollar sign, and a `$` that doesn't
+/// start a valid placeholder is kept as it is.
+pub fn expand(body: &str) -> Expansion {
+    let mut text = String::with_capacity(body.len());
+    let mut numbered: Vec<(usize, Range<usize>)> = Vec::new();
+    let mut rest = body;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("\\$") {
+            text.push('$');
+            rest = after;
+            continue;
+        }
+        if let Some((number, default, after)) = placeholder(rest) {
+            let start = text.len();
+            text.push_str(&expand(default).text);
+            if !numbered.iter().any(|(n, _)| *n == number) {
+                numbered.push((number, start..text.len()));
+            }
+            rest = after;
+            continue;
+        }
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    // `$0` goes last, the others in numeric order.
+    numbered.sort_by_key(|(number, _)| number.wrapping_sub(1));
+    let mut stops: Vec<Range<usize>> = numbered.into_iter().map(|(_, range)| range).collect();
+    if !body_has_final_stop(body) {
+        stops.push(text.len()..text.len());
+    }
+    Expansion { text, stops }
+}
+
+fn body_has_final_stop(body: &str) -> bool {
+    let mut rest = body;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("\\$") {
+            rest = after;
+        } else if let Some((number, _, after)) = placeholder(rest) {
+            if number == 0 {
+                return true;
+            }
+            rest = after;
+        } else {
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    false
+}
+
+/// `$N` or `${N:default}` at the start of `text`: the number, the default
+/// and what follows. Defaults may nest braces but not placeholders' stops.
+fn placeholder(text: &str) -> Option<(usize, &str, &str)> {
+    let rest = text.strip_prefix('$')?;
+    if let Some(inner) = rest.strip_prefix('{') {
+        let digits = inner.len() - inner.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let number = inner[..digits].parse().ok()?;
+        let after_number = &inner[digits..];
+        if let Some(after) = after_number.strip_prefix('}') {
+            return Some((number, "", after));
+        }
+        let default = after_number.strip_prefix(':')?;
+        let mut depth = 0;
+        for (i, c) in default.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Some((number, &default[..i], &default[i + 1..])),
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    } else {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let number = rest[..digits].parse().ok()?;
+        Some((number, "", &rest[digits..]))
+    }
+}
+
+/// Tab stops of an expanded snippet, kept pointing at the right text while
+/// the buffer is edited.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    /// Byte ranges in the buffer, in visiting order.
+    stops: Vec<Range<usize>>,
+    current: usize,
+}
+
+impl Session {
+    /// For `expansion` inserted at byte `at`, with the first stop current.
+    pub fn new(at: usize, expansion: &Expansion) -> Self {
+        Self {
+            stops: expansion
+                .stops
+                .iter()
+                .map(|stop| at + stop.start..at + stop.end)
+                .collect(),
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> Range<usize> {
+        self.stops[self.current].clone()
+    }
+
+    /// Moves to the next stop, `None` once the last one was visited.
+    pub fn advance(&mut self) -> Option<Range<usize>> {
+        self.current += 1;
+        (self.current < self.stops.len()).then(|| self.current())
+    }
+
+    /// Updates the stops for `range` of the buffer being replaced by
+    /// `inserted` bytes. Typing inside the current stop grows or shrinks it;
+    /// returns false when the edit cut across a stop, which ends the session.
+    pub fn edit(&mut self, range: Range<usize>, inserted: usize) -> bool {
+        let delta = inserted as isize - range.len() as isize;
+        let shift = |offset: usize| offset.saturating_add_signed(delta);
+        for (index, stop) in self.stops.iter_mut().enumerate() {
+            let inside = stop.start <= range.start && range.end <= stop.end;
+            if index == self.current && inside {
+                stop.end = shift(stop.end);
+            } else if range.end <= stop.start {
+                *stop = shift(stop.start)..shift(stop.end);
+            } else if range.start >= stop.end {
+                // After the stop, nothing to do.
+            } else if inside {
+                stop.end = shift(stop.end);
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The one contiguous change that turns `old` into `new`: the replaced byte
+/// range of `old` and how many bytes replaced it. `None` when equal.
+pub fn diff(old: &str, new: &str) -> Option<(Range<usize>, usize)> {
+    if old == new {
+        return None;
+    }
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    Some((prefix..old.len() - suffix, new.len() - suffix - prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(trigger: &str, body: &str) -> Snippet {
+        Snippet {
+            name: trigger.to_string(),
+            trigger: trigger.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    /// The text of each stop of `expansion`.
+    fn stop_texts(expansion: &Expansion) -> Vec<&str> {
+        expansion
+            .stops
+            .iter()
+            .map(|stop| &expansion.text[stop.clone()])
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_are_parsed() {
+        assert_eq!(placeholder("$1 rest"), Some((1, "", " rest")));
+        assert_eq!(placeholder("$12x"), Some((12, "", "x")));
+        assert_eq!(placeholder("${3}x"), Some((3, "", "x")));
+        assert_eq!(placeholder("${2:name}x"), Some((2, "name", "x")));
+        assert_eq!(placeholder("${1:{ a: {} }}!"), Some((1, "{ a: {} }", "!")));
+        assert_eq!(placeholder("$x"), None);
+        assert_eq!(placeholder("${x}"), None);
+        assert_eq!(placeholder("${1:unclosed"), None);
+        assert_eq!(placeholder("${1;x}"), None);
+        assert_eq!(placeholder("1"), None);
+    }
+
+    #[test]
+    fn stops_follow_numeric_order_with_zero_last() {
+        let expansion = expand("for ${2:item} in ${1:items} {\n    $0\n}");
+        assert_eq!(expansion.text, "for item in items {\n    \n}");
+        assert_eq!(stop_texts(&expansion), vec!["items", "item", ""]);
+        assert_eq!(expansion.stops[2], 24..24);
+    }
+
+    #[test]
+    fn without_zero_the_cursor_ends_at_the_end() {
+        let expansion = expand("let ${1:x} = $2;");
+        assert_eq!(expansion.text, "let x = ;");
+        assert_eq!(expansion.stops, vec![4..5, 8..8, 9..9]);
+    }
+
+    #[test]
+    fn repeated_numbers_are_stops_once() {
+        let expansion = expand("${1:a} + ${1:b} + $1");
+        assert_eq!(expansion.text, "a + b + ");
+        assert_eq!(expansion.stops, vec![0..1, 8..8]);
+    }
+
+    #[test]
+    fn nested_placeholders_expand_their_defaults() {
+        let expansion = expand("f(${1:x, ${2:y}})");
+        assert_eq!(expansion.text, "f(x, y)");
+        assert_eq!(stop_texts(&expansion), vec!["x, y", ""]);
+    }
+
+    #[test]
+    fn dollars_that_start_nothing_are_kept() {
+        assert_eq!(expand("cost: \\$1").text, "cost: $1");
+        assert_eq!(expand("\\$1").stops, vec![2..2]);
+        assert_eq!(expand("$ and $x and ${y}").text, "$ and $x and ${y}");
+        // An escaped `$0` isn't the final stop.
+        assert_eq!(expand("a\\$0b").stops, vec![4..4]);
+    }
+
+    #[test]
+    fn stops_are_byte_offsets_after_multibyte_text() {
+        let expansion = expand("é ${1:ü} →$0");
+        assert_eq!(expansion.text, "é ü →");
+        assert_eq!(stop_texts(&expansion), vec!["ü", ""]);
+        assert_eq!(expansion.stops, vec![3..5, 9..9]);
+    }
+
+    #[test]
+    fn triggers_match_whole_words_at_the_end() {
+        let snippets = vec![snippet("", "never"), snippet("fn", "fn $1() {}")];
+        assert_eq!(matching(&snippets, "    fn").unwrap().trigger, "fn");
+        assert_eq!(matching(&snippets, "fn").unwrap().trigger, "fn");
+        assert_eq!(matching(&snippets, "(fn").unwrap().trigger, "fn");
+        assert!(matching(&snippets, "my_fn").is_none());
+        assert!(matching(&snippets, "defn").is_none());
+        assert!(matching(&snippets, "fn ").is_none());
+        assert!(matching(&snippets, "").is_none());
+    }
+
+    #[test]
+    fn snippets_round_trip_through_json() {
+        let snippets = vec![
+            snippet("fn", "fn ${1:name}() {\n    $0\n}"),
+            snippet("q", "\"quoted\" \\$"),
+        ];
+        assert_eq!(from_json(&to_json(&snippets)).unwrap(), snippets);
+        assert_eq!(from_json("[]").unwrap(), vec![]);
+        assert_eq!(
+            from_json("[{\"trigger\": \"x\"}]").unwrap(),
+            vec![Snippet {
+                trigger: "x".to_string(),
+                ..Snippet::default()
+            }]
+        );
+        assert!(from_json("{}").unwrap_err().contains("expected a list"));
+        assert!(from_json("[").is_err());
+    }
+
+    #[test]
+    fn typing_in_the_current_stop_moves_the_others() {
+        // "let x = ;" inserted at 10.
+        let mut session = Session::new(10, &expand("let ${1:x} = $2;"));
+        assert_eq!(session.current(), 14..15);
+        // Replace "x" with "count".
+        assert!(session.edit(14..15, 5));
+        assert_eq!(session.current(), 14..19);
+        assert_eq!(session.advance(), Some(22..22));
+        // Type "1" then "0" at the second stop.
+        assert!(session.edit(22..22, 1));
+        assert!(session.edit(23..23, 1));
+        assert_eq!(session.current(), 22..24);
+        assert_eq!(session.advance(), Some(25..25));
+        assert_eq!(session.advance(), None);
+    }
+
+    #[test]
+    fn edits_elsewhere_shift_or_end_the_session() {
+        // "ab cd" inserted at 3.
+        let mut session = Session::new(3, &expand("${1:ab} ${2:cd}"));
+        // Typing before the snippet shifts every stop.
+        assert!(session.edit(1..1, 2));
+        assert_eq!(session.current(), 5..7);
+        // Deleting after the snippet changes nothing.
+        assert!(session.edit(11..12, 0));
+        assert_eq!(session.current(), 5..7);
+        // Deleting inside a later stop resizes it.
+        assert!(session.edit(8..9, 0));
+        assert_eq!(session.advance(), Some(8..9));
+        // Cutting across a stop ends the session.
+        let mut session = Session::new(0, &expand("${1:ab} ${2:cd}"));
+        assert!(!session.edit(1..4, 0));
+    }
+
+    #[test]
+    fn typing_at_the_edge_of_the_current_stop_grows_it() {
+        let mut session = Session::new(0, &expand("${1:ab} ${2:cd}"));
+        assert!(session.edit(0..0, 1));
+        assert!(session.edit(3..3, 1));
+        assert_eq!(session.current(), 0..4);
+        assert_eq!(session.advance(), Some(5..7));
+    }
+
+    #[test]
+    fn diff_finds_the_single_change() {
+        assert_eq!(diff("same", "same"), None);
+        assert_eq!(diff("let x;", "let xy;"), Some((5..5, 1)));
+        assert_eq!(diff("let xy;", "let x;"), Some((5..6, 0)));
+        assert_eq!(diff("abc", "aXc"), Some((1..2, 1)));
+        assert_eq!(diff("", "abc"), Some((0..0, 3)));
+        // Repeated text: the change is placed after the common prefix.
+        assert_eq!(diff("aa", "aaa"), Some((2..2, 1)));
+        // Multi-byte characters sharing leading bytes aren't split.
+        assert_eq!(diff("é", "è"), Some((0..2, 2)));
+        assert_eq!(diff("aé", "aéé"), Some((3..3, 2)));
+    }
+}
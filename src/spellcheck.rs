@@ -0,0 +1,340 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::paths;
+
+const BUNDLED_WORDS: &str = include_str!("../assets/words.txt");
+
+/// Words shorter than this are never flagged.
+const MIN_WORD_LEN: usize = 3;
+
+/// Bundled word list plus the user's own additions.
+pub struct Dictionary {
+    words: HashSet<String>,
+    user_words: Vec<String>,
+    /// Whether additions are saved to the user dictionary file.
+    persistent: bool,
+}
+
+/// The bundled words only; additions are kept in memory.
+impl Default for Dictionary {
+    fn default() -> Self {
+        Self {
+            words: BUNDLED_WORDS
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect(),
+            user_words: Vec::new(),
+            persistent: false,
+        }
+    }
+}
+
+impl Dictionary {
+    /// The bundled words plus the user dictionary file, which additions are
+    /// saved to.
+    pub fn with_user_dictionary() -> Self {
+        let user_words: Vec<String> = std::fs::read_to_string(user_dictionary_path())
+            .unwrap_or_default()
+            .lines()
+            .map(|line| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty())
+            .collect();
+        let mut words = Self::default().words;
+        words.extend(user_words.iter().cloned());
+        Self {
+            words,
+            user_words,
+            persistent: true,
+        }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        if self.words.contains(&word) {
+            return true;
+        }
+        // Accept common inflections of known words.
+        let stems = [
+            ("'s", ""),
+            ("ies", "y"),
+            ("es", ""),
+            ("s", ""),
+            ("ed", ""),
+            ("ed", "e"),
+            ("ing", ""),
+            ("ing", "e"),
+            ("ly", ""),
+            ("er", ""),
+            ("er", "e"),
+        ];
+        stems.iter().any(|(suffix, replacement)| {
+            word.strip_suffix(suffix)
+                .is_some_and(|stem| self.words.contains(&format!("{}{}", stem, replacement)))
+        })
+    }
+
+    pub fn add(&mut self, word: &str) -> std::io::Result<()> {
+        let word = word.to_lowercase();
+        if self.words.insert(word.clone()) {
+            self.user_words.push(word);
+            if !self.persistent {
+                return Ok(());
+            }
+            let path = user_dictionary_path();
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, self.user_words.join("\n") + "\n")?;
+        }
+        Ok(())
+    }
+
+    /// Up to `limit` known words within edit distance 2 of `word`, closest
+    /// first.
+    pub fn suggestions(&self, word: &str, limit: usize) -> Vec<String> {
+        let word = word.to_lowercase();
+        let mut candidates: Vec<(usize, &String)> = self
+            .words
+            .iter()
+            .filter(|candidate| candidate.len().abs_diff(word.len()) <= 2)
+            .map(|candidate| (edit_distance(&word, candidate), candidate))
+            .filter(|(distance, _)| *distance <= 2)
+            .collect();
+        candidates.sort();
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(_, candidate)| candidate.clone())
+            .collect()
+    }
+}
+
+fn user_dictionary_path() -> PathBuf {
+    paths::config_dir().join("dictionary.txt")
+}
+
+/// Splits prose into checkable words, returning byte ranges relative to
+/// `text`. Code-ish chunks (paths, calls, anything with digits) are skipped,
+/// and camelCase / snake_case identifiers are split into their parts.
+pub fn words(text: &str) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let mut offset = 0;
+    for chunk in text.split_inclusive(char::is_whitespace) {
+        let chunk_start = offset;
+        offset += chunk.len();
+        let chunk = chunk.trim_end();
+        if chunk.contains("::")
+            || chunk.contains("()")
+            || chunk.contains(|c: char| c.is_ascii_digit())
+        {
+            continue;
+        }
+
+        let mut word_start = None;
+        for (i, c) in chunk.char_indices().chain([(chunk.len(), ' ')]) {
+            let is_word_char = c.is_alphabetic() || c == '\'';
+            match (word_start, is_word_char) {
+                (None, true) => word_start = Some(i),
+                (Some(start), false) => {
+                    let word = chunk[start..i].trim_matches('\'');
+                    let start = start + chunk[start..i].find(word).unwrap_or(0);
+                    for part in split_case(word) {
+                        result
+                            .push(chunk_start + start + part.start..chunk_start + start + part.end);
+                    }
+                    word_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    result
+}
+
+/// Splits `camelCase` / `PascalCase` words at lowercase-to-uppercase
+/// boundaries. All-caps words come back whole.
+fn split_case(word: &str) -> Vec<Range<usize>> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut previous_lower = false;
+    for (i, c) in word.char_indices() {
+        if c.is_uppercase() && previous_lower {
+            parts.push(start..i);
+            start = i;
+        }
+        previous_lower = c.is_lowercase();
+    }
+    if start < word.len() {
+        parts.push(start..word.len());
+    }
+    parts
+}
+
+/// Misspelled words in `text`. Acronyms and very short words are ignored.
+pub fn misspellings(text: &str, dictionary: &Dictionary) -> Vec<Range<usize>> {
+    words(text)
+        .into_iter()
+        .filter(|range| {
+            let word = &text[range.clone()];
+            word.chars().count() >= MIN_WORD_LEN
+                && !word.chars().all(|c| !c.is_lowercase())
+                && !dictionary.contains(word)
+        })
+        .collect()
+}
+
+/// Remembers the misspellings of each prose line so unchanged lines aren't
+/// rechecked on every edit.
+#[derive(Default)]
+pub struct SpellCache {
+    lines: HashMap<String, Vec<Range<usize>>>,
+}
+
+impl SpellCache {
+    pub fn check(&mut self, text: &str, dictionary: &Dictionary) -> Vec<Range<usize>> {
+        if self.lines.len() > 10_000 {
+            self.lines.clear();
+        }
+        self.lines
+            .entry(text.to_string())
+            .or_insert_with(|| misspellings(text, dictionary))
+            .clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str) -> Vec<&str> {
+        words(text).into_iter().map(|range| &text[range]).collect()
+    }
+
+    fn dictionary(words: &[&str]) -> Dictionary {
+        Dictionary {
+            words: words.iter().map(|word| word.to_string()).collect(),
+            user_words: Vec::new(),
+            persistent: false,
+        }
+    }
+
+    #[test]
+    fn prose_splits_on_whitespace_and_punctuation() {
+        assert_eq!(
+            split("Hello, world! (It's fine.)"),
+            vec!["Hello", "world", "It's", "fine"]
+        );
+        assert_eq!(split("  \tspaced\n\nout  "), vec!["spaced", "out"]);
+        assert!(split("").is_empty());
+    }
+
+    #[test]
+    fn camel_and_snake_case_are_split() {
+        assert_eq!(split("parseVariables"), vec!["parse", "Variables"]);
+        assert_eq!(split("HighlightCache"), vec!["Highlight", "Cache"]);
+        assert_eq!(split("snake_case_name"), vec!["snake", "case", "name"]);
+        assert_eq!(split("mixed_camelCase"), vec!["mixed", "camel", "Case"]);
+        assert_eq!(split("HTTP"), vec!["HTTP"]);
+        assert_eq!(split("HTTPServer"), vec!["HTTPServer"]);
+    }
+
+    #[test]
+    fn code_ish_chunks_are_skipped() {
+        assert_eq!(
+            split("call std::mem::take or run() on v2 then stop"),
+            vec!["call", "or", "on", "then", "stop"]
+        );
+        assert!(split("x86_64 0xFF 1st").is_empty());
+    }
+
+    #[test]
+    fn quotes_around_words_are_trimmed() {
+        let text = "'quoted' don't";
+        assert_eq!(split(text), vec!["quoted", "don't"]);
+        assert_eq!(words(text)[0], 1..7);
+    }
+
+    #[test]
+    fn ranges_are_byte_offsets_into_the_text() {
+        let text = "naïve café_crème";
+        let ranges = words(text);
+        assert_eq!(split(text), vec!["naïve", "café", "crème"]);
+        assert_eq!(ranges[1].start, "naïve ".len());
+    }
+
+    #[test]
+    fn short_words_and_acronyms_are_never_flagged() {
+        let dictionary = dictionary(&["known"]);
+        let text = "known xq NASA unknwn";
+        let flagged: Vec<&str> = misspellings(text, &dictionary)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect();
+        assert_eq!(flagged, vec!["unknwn"]);
+    }
+
+    #[test]
+    fn inflections_of_known_words_are_accepted() {
+        let dictionary = dictionary(&["parse", "copy", "run", "quick", "box"]);
+        for word in [
+            "parses", "parsed", "parsing", "parser", "copies", "runs", "quickly", "boxes",
+            "Parse's",
+        ] {
+            assert!(dictionary.contains(word), "{}", word);
+        }
+        assert!(!dictionary.contains("parsez"));
+    }
+
+    #[test]
+    fn suggestions_are_closest_first() {
+        let dictionary = dictionary(&["variable", "variables", "available", "table"]);
+        assert_eq!(
+            dictionary.suggestions("varable", 2),
+            vec!["variable", "variables"]
+        );
+        assert!(dictionary.suggestions("zzzzzz", 5).is_empty());
+    }
+
+    #[test]
+    fn added_words_stop_being_flagged() {
+        let mut dictionary = dictionary(&[]);
+        let mut cache = SpellCache::default();
+        assert_eq!(cache.check("crowbar", &dictionary), vec![0..7]);
+        dictionary.add("Crowbar").unwrap();
+        assert_eq!(cache.check("crowbar", &dictionary), vec![0..7]);
+        cache.clear();
+        assert!(cache.check("crowbar", &dictionary).is_empty());
+        assert_eq!(dictionary.user_words, vec!["crowbar"]);
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
@@ -0,0 +1,582 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use eframe::egui;
+
+use crate::outputs::{self, Location};
+use crate::paths;
+use crate::processes;
+
+/// Kinds of files Crowbar leaves on disk, each sized and capped separately.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Category {
+    /// Build directories of this and earlier sessions, in the temp dir.
+    Build,
+    /// Files of background checks, including those of crashed instances.
+    Check,
+    /// Config dirs moved aside by "Reset settings".
+    Backups,
+    /// Per-run folders handed to programs for their output files.
+    RunOutputs,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Build,
+        Category::Check,
+        Category::Backups,
+        Category::RunOutputs,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Build => "Build artifacts",
+            Category::Check => "Background check files",
+            Category::Backups => "Settings backups",
+            Category::RunOutputs => "Run output folders",
+        }
+    }
+
+    fn dirs(self) -> Vec<PathBuf> {
+        match self {
+            Category::Build => vec![std::env::temp_dir()],
+            Category::Check => vec![std::env::temp_dir()],
+            Category::Backups => vec![paths::config_dir()
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default()],
+            // Folders under replayed working directories aren't tracked.
+            Category::RunOutputs => vec![
+                outputs::base_dir(Location::WorkingDir, None),
+                outputs::base_dir(Location::Temp, None),
+            ],
+        }
+    }
+
+    fn matches(self, name: &str) -> bool {
+        match self {
+            Category::Build => name.starts_with(paths::SESSION_PREFIX),
+            Category::Check => name.starts_with("crowbar-check-"),
+            Category::Backups => name.starts_with("crowbar.backup-"),
+            Category::RunOutputs => name.starts_with("run-"),
+        }
+    }
+}
+
+/// A file or directory owned by a category, removed as a whole.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub path: PathBuf,
+    /// Total size in bytes, including everything below a directory.
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+/// The few filesystem operations sizing and eviction need.
+pub trait Filesystem {
+    /// Entries directly inside `dir`, with their names.
+    fn list(&self, dir: &Path) -> Vec<(String, Entry)>;
+    fn remove(&self, path: &Path) -> io::Result<()>;
+}
+
+pub struct Disk;
+
+impl Filesystem for Disk {
+    fn list(&self, dir: &Path) -> Vec<(String, Entry)> {
+        let Ok(read_dir) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        read_dir
+            .filter_map(Result::ok)
+            .filter_map(|dir_entry| {
+                let metadata = dir_entry.metadata().ok()?;
+                let path = dir_entry.path();
+                Some((
+                    dir_entry.file_name().to_string_lossy().to_string(),
+                    Entry {
+                        size: disk_size(&path, &metadata),
+                        last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                        path,
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        }
+    }
+}
+
+fn disk_size(path: &Path, metadata: &std::fs::Metadata) -> u64 {
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?)))
+        .map(|(path, metadata)| disk_size(&path, &metadata))
+        .sum()
+}
+
+/// Size limits in bytes; `None` means unlimited.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Caps {
+    pub categories: Vec<(Category, Option<u64>)>,
+    pub total: Option<u64>,
+}
+
+impl Default for Caps {
+    fn default() -> Self {
+        const MB: u64 = 1024 * 1024;
+        Self {
+            categories: vec![
+                (Category::Build, Some(256 * MB)),
+                (Category::Check, Some(256 * MB)),
+                (Category::Backups, Some(64 * MB)),
+                (Category::RunOutputs, Some(512 * MB)),
+            ],
+            total: Some(1024 * MB),
+        }
+    }
+}
+
+impl Caps {
+    pub fn get(&self, category: Category) -> Option<u64> {
+        self.categories
+            .iter()
+            .find(|(c, _)| *c == category)
+            .and_then(|(_, cap)| *cap)
+    }
+}
+
+pub type Usage = Vec<(Category, Vec<Entry>)>;
+
+pub fn scan(fs: &impl Filesystem) -> Usage {
+    Category::ALL
+        .into_iter()
+        .map(|category| {
+            let entries = category
+                .dirs()
+                .iter()
+                .flat_map(|dir| fs.list(dir))
+                .filter(|(name, _)| category.matches(name))
+                .map(|(_, entry)| entry)
+                .collect();
+            (category, entries)
+        })
+        .collect()
+}
+
+pub fn total_size(entries: &[Entry]) -> u64 {
+    entries.iter().map(|entry| entry.size).sum()
+}
+
+/// Paths to delete so every category and the total fit their caps, least
+/// recently used first. `protected` paths are never chosen, even if that
+/// leaves a cap exceeded.
+pub fn plan_eviction(usage: &Usage, caps: &Caps, protected: &[PathBuf]) -> Vec<PathBuf> {
+    let mut evicted = Vec::new();
+    let mut kept = Vec::new();
+    for (category, entries) in usage {
+        let (gone, left) = evict_lru(entries.clone(), caps.get(*category), protected);
+        evicted.extend(gone);
+        kept.extend(left);
+    }
+    let (gone, _) = evict_lru(kept, caps.total, protected);
+    evicted.extend(gone);
+    evicted
+}
+
+fn evict_lru(
+    mut entries: Vec<Entry>,
+    cap: Option<u64>,
+    protected: &[PathBuf],
+) -> (Vec<PathBuf>, Vec<Entry>) {
+    let Some(cap) = cap else {
+        return (Vec::new(), entries);
+    };
+    entries.sort_by_key(|entry| entry.last_used);
+    let mut size = total_size(&entries);
+    let mut evicted = Vec::new();
+    entries.retain(|entry| {
+        if size <= cap || protected.contains(&entry.path) {
+            return true;
+        }
+        size -= entry.size;
+        evicted.push(entry.path.clone());
+        false
+    });
+    (evicted, entries)
+}
+
+/// Removes each of `paths`, carrying on past failures; returns the last one.
+fn remove_all(fs: &impl Filesystem, paths: &[PathBuf]) -> Option<String> {
+    let mut error = None;
+    for path in paths {
+        if let Err(e) = fs.remove(path) {
+            error = Some(format!("Couldn't remove {}: {}", path.display(), e));
+        }
+    }
+    error
+}
+
+/// This instance's build directory and check files, plus `keep`.
+pub fn protected_paths(keep: &[PathBuf]) -> Vec<PathBuf> {
+    let check = std::env::temp_dir();
+    let mut paths = vec![
+        paths::session_dir(),
+        check.join(format!("crowbar-check-{}.rs", std::process::id())),
+        check.join(format!("crowbar-check-{}.rmeta", std::process::id())),
+    ];
+    paths.extend_from_slice(keep);
+    paths
+}
+
+/// Build directories of other instances that are still running.
+fn live_sessions(usage: &Usage) -> Vec<PathBuf> {
+    usage
+        .iter()
+        .filter(|(category, _)| *category == Category::Build)
+        .flat_map(|(_, entries)| entries)
+        .filter(|entry| {
+            entry
+                .path
+                .file_name()
+                .and_then(|name| name.to_str()?.strip_prefix(paths::SESSION_PREFIX))
+                .and_then(|pid| pid.parse().ok())
+                .is_some_and(processes::is_alive)
+        })
+        .map(|entry| entry.path.clone())
+        .collect()
+}
+
+#[derive(Default)]
+struct Shared {
+    usage: Option<Usage>,
+    busy: bool,
+    error: Option<String>,
+}
+
+/// Sizes and trims the categories on a background thread, so walking large
+/// directories never stalls the UI.
+#[derive(Default)]
+pub struct StorageManager {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl StorageManager {
+    /// Evicts whatever exceeds `caps`, then re-measures. `keep` lists paths
+    /// still in use, like the latest run's output folder.
+    pub fn enforce(&self, ctx: &egui::Context, caps: &Caps, keep: Vec<PathBuf>) {
+        let caps = caps.clone();
+        self.spawn(ctx, move |usage| {
+            let mut protected = protected_paths(&keep);
+            protected.extend(live_sessions(usage));
+            plan_eviction(usage, &caps, &protected)
+        });
+    }
+
+    /// Removes everything in `category` except protected paths and `keep`.
+    pub fn clean(&self, ctx: &egui::Context, category: Category, keep: Vec<PathBuf>) {
+        self.spawn(ctx, move |usage| {
+            let mut protected = protected_paths(&keep);
+            protected.extend(live_sessions(usage));
+            usage
+                .iter()
+                .filter(|(c, _)| *c == category)
+                .flat_map(|(_, entries)| entries)
+                .map(|entry| entry.path.clone())
+                .filter(|path| !protected.contains(path))
+                .collect()
+        });
+    }
+
+    fn spawn(
+        &self,
+        ctx: &egui::Context,
+        choose: impl FnOnce(&Usage) -> Vec<PathBuf> + Send + 'static,
+    ) {
+        {
+            let mut shared = self.shared.lock().unwrap();
+            if shared.busy {
+                return;
+            }
+            shared.busy = true;
+        }
+        let shared = self.shared.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let usage = scan(&Disk);
+            let error = remove_all(&Disk, &choose(&usage));
+            let usage = scan(&Disk);
+            let mut shared = shared.lock().unwrap();
+            shared.usage = Some(usage);
+            shared.error = error;
+            shared.busy = false;
+            ctx.request_repaint();
+        });
+    }
+
+    /// Size of each category from the last scan, `None` before the first.
+    pub fn sizes(&self) -> Option<Vec<(Category, u64)>> {
+        let shared = self.shared.lock().unwrap();
+        let usage = shared.usage.as_ref()?;
+        Some(
+            usage
+                .iter()
+                .map(|(category, entries)| (*category, total_size(entries)))
+                .collect(),
+        )
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.shared.lock().unwrap().busy
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.shared.lock().unwrap().error.clone()
+    }
+}
+
+/// Human-readable byte count.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::time::Duration;
+
+    /// Files kept in memory as `(path, size, last used)`; removing a path in
+    /// `stuck` fails like a file held open by another process.
+    #[derive(Default)]
+    struct MemoryFs {
+        files: RefCell<Vec<(PathBuf, u64, SystemTime)>>,
+        stuck: Vec<PathBuf>,
+    }
+
+    impl MemoryFs {
+        fn add(&self, dir: &Path, name: &str, size: u64, age: u64) -> PathBuf {
+            let path = dir.join(name);
+            let last_used = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 - age);
+            self.files
+                .borrow_mut()
+                .push((path.clone(), size, last_used));
+            path
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.files.borrow().iter().any(|(p, _, _)| p == path)
+        }
+    }
+
+    impl Filesystem for MemoryFs {
+        fn list(&self, dir: &Path) -> Vec<(String, Entry)> {
+            self.files
+                .borrow()
+                .iter()
+                .filter(|(path, _, _)| path.parent() == Some(dir))
+                .map(|(path, size, last_used)| {
+                    (
+                        path.file_name().unwrap().to_string_lossy().to_string(),
+                        Entry {
+                            path: path.clone(),
+                            size: *size,
+                            last_used: *last_used,
+                        },
+                    )
+                })
+                .collect()
+        }
+
+        fn remove(&self, path: &Path) -> io::Result<()> {
+            if self.stuck.iter().any(|stuck| stuck == path) {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "in use"));
+            }
+            let mut files = self.files.borrow_mut();
+            let before = files.len();
+            files.retain(|(p, _, _)| p != path);
+            if files.len() == before {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            Ok(())
+        }
+    }
+
+    fn temp() -> PathBuf {
+        std::env::temp_dir()
+    }
+
+    fn caps(categories: &[(Category, Option<u64>)], total: Option<u64>) -> Caps {
+        Caps {
+            categories: categories.to_vec(),
+            total,
+        }
+    }
+
+    fn sizes(usage: &Usage) -> Vec<(Category, u64)> {
+        usage
+            .iter()
+            .map(|(category, entries)| (*category, total_size(entries)))
+            .collect()
+    }
+
+    fn size_of(usage: &Usage, category: Category) -> u64 {
+        sizes(usage)
+            .into_iter()
+            .find(|(c, _)| *c == category)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn scan_sorts_entries_by_prefix() {
+        let fs = MemoryFs::default();
+        fs.add(&temp(), "crowbar-session-1", 10, 0);
+        fs.add(&temp(), "crowbar-check-1.rs", 3, 0);
+        fs.add(&temp(), "someone-elses-file", 1000, 0);
+        let outputs = outputs::base_dir(Location::Temp, None);
+        fs.add(&outputs, "run-42", 7, 0);
+        fs.add(&outputs, "notes.txt", 1000, 0);
+        let usage = scan(&fs);
+        assert_eq!(
+            sizes(&usage),
+            vec![
+                (Category::Build, 10),
+                (Category::Check, 3),
+                (Category::Backups, 0),
+                (Category::RunOutputs, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn category_caps_evict_least_recently_used_first() {
+        let fs = MemoryFs::default();
+        let old = fs.add(&temp(), "crowbar-session-1", 40, 30);
+        let middle = fs.add(&temp(), "crowbar-session-2", 40, 20);
+        let new = fs.add(&temp(), "crowbar-session-3", 40, 10);
+        let check = fs.add(&temp(), "crowbar-check-9.rs", 500, 50);
+
+        let caps = caps(
+            &[(Category::Build, Some(50)), (Category::Check, None)],
+            None,
+        );
+        let plan = plan_eviction(&scan(&fs), &caps, &[]);
+        assert_eq!(plan, vec![old.clone(), middle.clone()]);
+        assert_eq!(remove_all(&fs, &plan), None);
+
+        assert!(!fs.exists(&old) && !fs.exists(&middle));
+        assert!(fs.exists(&new) && fs.exists(&check));
+        let usage = scan(&fs);
+        assert_eq!(size_of(&usage, Category::Build), 40);
+        assert!(plan_eviction(&usage, &caps, &[]).is_empty());
+    }
+
+    #[test]
+    fn the_total_cap_evicts_across_categories() {
+        let fs = MemoryFs::default();
+        let build = fs.add(&temp(), "crowbar-session-1", 30, 10);
+        let check = fs.add(&temp(), "crowbar-check-1.rs", 30, 40);
+        let run = fs.add(&outputs::base_dir(Location::Temp, None), "run-1", 30, 20);
+
+        let caps = caps(&[], Some(60));
+        let plan = plan_eviction(&scan(&fs), &caps, &[]);
+        assert_eq!(plan, vec![check.clone()]);
+        remove_all(&fs, &plan);
+        assert!(fs.exists(&build) && fs.exists(&run) && !fs.exists(&check));
+
+        let plan = plan_eviction(
+            &scan(&fs),
+            &Caps {
+                total: Some(0),
+                ..caps
+            },
+            &[],
+        );
+        assert_eq!(plan, vec![run, build]);
+    }
+
+    #[test]
+    fn category_eviction_counts_towards_the_total() {
+        let fs = MemoryFs::default();
+        let old_build = fs.add(&temp(), "crowbar-session-1", 50, 30);
+        fs.add(&temp(), "crowbar-session-2", 50, 10);
+        fs.add(&temp(), "crowbar-check-1.rs", 50, 20);
+
+        let caps = caps(&[(Category::Build, Some(50))], Some(100));
+        assert_eq!(plan_eviction(&scan(&fs), &caps, &[]), vec![old_build]);
+    }
+
+    #[test]
+    fn protected_paths_survive_even_over_the_cap() {
+        let fs = MemoryFs::default();
+        let mine = fs.add(&temp(), "crowbar-session-1", 100, 100);
+        let other = fs.add(&temp(), "crowbar-session-2", 100, 50);
+        let newest = fs.add(&temp(), "crowbar-session-3", 100, 0);
+
+        let caps = caps(&[(Category::Build, Some(0))], None);
+        let plan = plan_eviction(&scan(&fs), &caps, &[mine.clone(), newest.clone()]);
+        assert_eq!(plan, vec![other]);
+        remove_all(&fs, &plan);
+        assert_eq!(size_of(&scan(&fs), Category::Build), 200);
+        assert!(fs.exists(&mine) && fs.exists(&newest));
+    }
+
+    #[test]
+    fn unlimited_caps_evict_nothing() {
+        let fs = MemoryFs::default();
+        fs.add(&temp(), "crowbar-session-1", u64::MAX / 4, 0);
+        fs.add(&temp(), "crowbar-check-1.rs", u64::MAX / 4, 0);
+        let caps = caps(&[(Category::Build, None), (Category::Check, None)], None);
+        assert!(plan_eviction(&scan(&fs), &caps, &[]).is_empty());
+    }
+
+    #[test]
+    fn failed_removals_are_reported_and_the_rest_still_go() {
+        let stuck = temp().join("crowbar-session-1");
+        let fs = MemoryFs {
+            stuck: vec![stuck.clone()],
+            ..MemoryFs::default()
+        };
+        fs.add(&temp(), "crowbar-session-1", 10, 30);
+        let gone = fs.add(&temp(), "crowbar-session-2", 10, 20);
+        fs.add(&temp(), "crowbar-session-3", 10, 10);
+
+        let plan = plan_eviction(&scan(&fs), &caps(&[(Category::Build, Some(10))], None), &[]);
+        assert_eq!(plan, vec![stuck.clone(), gone.clone()]);
+        let error = remove_all(&fs, &plan).unwrap();
+        assert!(error.contains("crowbar-session-1"), "{}", error);
+        assert!(fs.exists(&stuck) && !fs.exists(&gone));
+    }
+
+    #[test]
+    fn sizes_are_human_readable() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024 * 1024), "3072.0 GB");
+    }
+}
@@ -0,0 +1,201 @@
+use std::ops::Range;
+
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+/// Byte ranges of the statements, blocks, closures and items in `code`,
+/// or `None` when it doesn't parse. Ranges only ever nest or are disjoint,
+/// so together they form a containment tree.
+pub fn syntax_ranges(code: &str) -> Option<Vec<Range<usize>>> {
+    let file = crate::parse_rust_code(code).ok()?;
+    let mut collector = RangeCollector::default();
+    collector.visit_file(&file);
+    Some(collector.ranges)
+}
+
+#[derive(Default)]
+struct RangeCollector {
+    ranges: Vec<Range<usize>>,
+}
+
+impl RangeCollector {
+    fn push(&mut self, node: &impl Spanned) {
+        let range = node.span().byte_range();
+        if !range.is_empty() {
+            self.ranges.push(range);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for RangeCollector {
+    fn visit_stmt(&mut self, stmt: &'ast syn::Stmt) {
+        self.push(stmt);
+        visit::visit_stmt(self, stmt);
+    }
+
+    fn visit_block(&mut self, block: &'ast syn::Block) {
+        self.push(block);
+        visit::visit_block(self, block);
+    }
+
+    fn visit_expr_closure(&mut self, closure: &'ast syn::ExprClosure) {
+        self.push(closure);
+        visit::visit_expr_closure(self, closure);
+    }
+
+    fn visit_item(&mut self, item: &'ast syn::Item) {
+        self.push(item);
+        visit::visit_item(self, item);
+    }
+
+    fn visit_impl_item(&mut self, item: &'ast syn::ImplItem) {
+        self.push(item);
+        visit::visit_impl_item(self, item);
+    }
+
+    fn visit_trait_item(&mut self, item: &'ast syn::TraitItem) {
+        self.push(item);
+        visit::visit_trait_item(self, item);
+    }
+}
+
+/// The smallest range in `ranges` that contains `selection` and is larger
+/// than it: statement, then enclosing block, then enclosing item.
+pub fn expand_selection(ranges: &[Range<usize>], selection: &Range<usize>) -> Option<Range<usize>> {
+    ranges
+        .iter()
+        .filter(|range| {
+            range.start <= selection.start && selection.end <= range.end && *range != selection
+        })
+        .min_by_key(|range| range.len())
+        .cloned()
+}
+
+/// Byte range of the physical line containing `offset`, without its line
+/// break.
+pub fn line_range(code: &str, offset: usize) -> Range<usize> {
+    let start = code[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = code[offset..].find('\n').map_or(code.len(), |i| offset + i);
+    start..end
+}
+
+/// What "select statement" should select next: the next structural level
+/// around `selection`, or the physical line when `code` doesn't parse.
+pub fn next_selection(code: &str, selection: &Range<usize>) -> Option<Range<usize>> {
+    match syntax_ranges(code) {
+        Some(ranges) => expand_selection(&ranges, selection),
+        None => {
+            let line = line_range(code, selection.start);
+            (line.start <= selection.start && selection.end <= line.end && line != *selection)
+                .then_some(line)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every selection from the caret at `needle` outward.
+    fn ladder<'a>(code: &'a str, needle: &str) -> Vec<&'a str> {
+        let at = code.find(needle).unwrap();
+        let mut selection = at..at;
+        let mut steps = Vec::new();
+        while let Some(next) = next_selection(code, &selection) {
+            steps.push(&code[next.clone()]);
+            selection = next;
+        }
+        steps
+    }
+
+    const NESTED: &str =
+        "fn main() {\n    let total = {\n        let a = 1;\n        a + 1\n    };\n}\n";
+
+    #[test]
+    fn nested_blocks_expand_statement_block_statement_item() {
+        assert_eq!(
+            ladder(NESTED, "1;"),
+            vec![
+                "let a = 1;",
+                "{\n        let a = 1;\n        a + 1\n    }",
+                "let total = {\n        let a = 1;\n        a + 1\n    };",
+                &NESTED[10..NESTED.len() - 1],
+                NESTED.trim_end(),
+            ]
+        );
+        // A tail expression is a statement too.
+        assert_eq!(ladder(NESTED, "a + 1")[0], "a + 1");
+    }
+
+    #[test]
+    fn closures_are_a_level_of_their_own() {
+        let code = "fn main() {\n    let f = |x: i32| {\n        let y = x * 2;\n        y\n    };\n    xs.map(|v| v + 1);\n}\n";
+        assert_eq!(
+            ladder(code, "x * 2")[..4],
+            [
+                "let y = x * 2;",
+                "{\n        let y = x * 2;\n        y\n    }",
+                "|x: i32| {\n        let y = x * 2;\n        y\n    }",
+                "let f = |x: i32| {\n        let y = x * 2;\n        y\n    };",
+            ]
+        );
+        assert_eq!(
+            ladder(code, "v + 1")[..2],
+            ["|v| v + 1", "xs.map(|v| v + 1);"]
+        );
+    }
+
+    #[test]
+    fn impl_and_trait_items_are_levels() {
+        let code = "impl S {\n    fn get(&self) -> i32 {\n        self.0\n    }\n}\ntrait T {\n    fn f() {}\n}\n";
+        assert_eq!(
+            ladder(code, "self.0"),
+            vec![
+                "self.0",
+                "{\n        self.0\n    }",
+                "fn get(&self) -> i32 {\n        self.0\n    }",
+                &code[..code.find("\ntrait").unwrap()],
+            ]
+        );
+        assert_eq!(
+            ladder(code, "{}"),
+            vec!["{}", "fn f() {}", "trait T {\n    fn f() {}\n}"]
+        );
+    }
+
+    #[test]
+    fn a_whole_selection_expands_to_its_parent() {
+        let at = NESTED.find("let a").unwrap();
+        let statement = at..at + "let a = 1;".len();
+        let block = next_selection(NESTED, &statement).unwrap();
+        assert!(NESTED[block].starts_with("{\n        let a"));
+        let whole = 0..NESTED.trim_end().len();
+        assert_eq!(next_selection(NESTED, &whole), None);
+    }
+
+    #[test]
+    fn ranges_nest_or_are_disjoint() {
+        let code = "fn a() { let x = || { 1 }; }\nstruct S;\nimpl S { fn b(&self) { if true { c(|y| y); } } }\n";
+        let ranges = syntax_ranges(code).unwrap();
+        for a in &ranges {
+            for b in &ranges {
+                let nested = (a.start <= b.start && b.end <= a.end)
+                    || (b.start <= a.start && a.end <= b.end);
+                let disjoint = a.end <= b.start || b.end <= a.start;
+                assert!(nested || disjoint, "{:?} and {:?} overlap", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn broken_code_selects_the_physical_line() {
+        let code = "fn main() {\n    let x = (1\n        + 2;\n";
+        let at = code.find("+ 2").unwrap();
+        assert_eq!(ladder(code, "+ 2"), vec!["        + 2;"]);
+        assert_eq!(
+            line_range(code, at),
+            code.find("        +").unwrap()..code.len() - 1
+        );
+        assert_eq!(line_range("", 0), 0..0);
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::runner::{self, BuildConfig, Compiler, Executor, RunInputs, Runner};
+
+/// Most values one sweep takes, so a small step can't queue up hours of
+/// builds.
+pub const MAX_VALUES: usize = 200;
+
+/// Values from `start` to `end` inclusive, `step` apart, counting down if
+/// `end` is below `start`.
+pub fn range(start: f64, end: f64, step: f64) -> Result<Vec<f64>, String> {
+    if !start.is_finite() || !end.is_finite() {
+        return Err("The start and end have to be numbers".to_string());
+    }
+    if !(step > 0.0 && step.is_finite()) {
+        return Err("The step has to be above zero".to_string());
+    }
+    // A little slack so an end that's a whole number of steps away isn't
+    // lost to rounding.
+    let steps = ((end - start).abs() / step + 1e-9).floor();
+    if steps >= MAX_VALUES as f64 {
+        return Err(format!(
+            "That's {} values; a sweep takes at most {}",
+            steps + 1.0,
+            MAX_VALUES
+        ));
+    }
+    let step = if end < start { -step } else { step };
+    Ok((0..=steps as usize)
+        .map(|i| round(start + step * i as f64))
+        .collect())
+}
+
+/// Values written one after another, separated by commas or whitespace.
+pub fn list(text: &str) -> Result<Vec<f64>, String> {
+    let values = text
+        .split([',', ' ', '\n', '\t'])
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .replace('_', "")
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| format!("`{}` isn't a number", value))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    match values.len() {
+        0 => Err("There are no values to sweep".to_string()),
+        n if n > MAX_VALUES => Err(format!(
+            "That's {} values; a sweep takes at most {}",
+            n, MAX_VALUES
+        )),
+        _ => Ok(values),
+    }
+}
+
+/// Drops the drift that adding up steps leaves, like `0.30000000000000004`.
+fn round(value: f64) -> f64 {
+    let rounded = (value * 1e10).round() / 1e10;
+    if rounded.is_finite() {
+        rounded
+    } else {
+        value
+    }
+}
+
+/// How one value of a sweep ran.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    /// The value, as written into the code.
+    pub value: String,
+    /// What the program printed, or the compiler's error.
+    pub output: String,
+    pub build_failed: bool,
+    pub exit_code: Option<i32>,
+    /// Building and running together.
+    pub duration: Duration,
+}
+
+/// A sweep in flight as seen from the UI thread.
+#[derive(Default)]
+pub struct Progress {
+    /// The values done so far, in order.
+    pub points: Vec<Point>,
+    /// The run in flight, which a stop request is passed on to.
+    pub run: Arc<Mutex<runner::Progress>>,
+    pub stop_requested: bool,
+}
+
+impl Progress {
+    /// Stops the run in flight and skips the rest.
+    pub fn stop(&mut self) {
+        self.stop_requested = true;
+        self.run.lock().unwrap().stop_requested = true;
+    }
+}
+
+/// Builds and runs each of `sources`, a value and the code with it written
+/// in, one after another, adding a point to `progress` as each finishes.
+/// A value stopped part way is left out.
+pub fn run<C: Compiler, E: Executor>(
+    runner: &mut Runner<C, E>,
+    sources: Vec<(String, String)>,
+    config: &BuildConfig,
+    inputs: &RunInputs,
+    file: Option<&Path>,
+    progress: &Arc<Mutex<Progress>>,
+) {
+    for (value, source) in sources {
+        let run = Arc::new(Mutex::new(runner::Progress::default()));
+        {
+            let mut progress = progress.lock().unwrap();
+            if progress.stop_requested {
+                return;
+            }
+            progress.run = run.clone();
+        }
+        let started = Instant::now();
+        let result = runner.run(&source, config, inputs, file, &run);
+        if run.lock().unwrap().stop_requested {
+            return;
+        }
+        progress.lock().unwrap().points.push(Point {
+            value,
+            output: result.output,
+            build_failed: result.build_failed,
+            exit_code: result.exit_code,
+            duration: started.elapsed(),
+        });
+    }
+}
+
+/// `points` as CSV, headed by the swept variable's name.
+pub fn to_csv(name: &str, points: &[Point]) -> String {
+    let mut csv = format!("{},output,exit code,duration (ms)\n", field(name));
+    for point in points {
+        let exit_code = match (point.build_failed, point.exit_code) {
+            (true, _) => "build failed".to_string(),
+            (false, Some(code)) => code.to_string(),
+            (false, None) => String::new(),
+        };
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            field(&point.value),
+            field(&point.output),
+            field(&exit_code),
+            point.duration.as_millis()
+        ));
+    }
+    csv
+}
+
+/// Quoted if it has anything CSV gives a meaning to.
+fn field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,134 @@
+use syn::visit::{self, Visit};
+
+use crate::lexer::line_of;
+
+/// A named item or binding that symbol search can jump to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    /// What declares it, like `fn`, `struct` or `let`.
+    pub kind: &'static str,
+    pub name: String,
+    /// Byte offset of the name.
+    pub offset: usize,
+    pub line: usize,
+}
+
+/// Every function, method, type, trait, module, constant, static and `let`
+/// binding in `file`, parsed from `code`, in source order.
+pub fn index(file: &syn::File, code: &str) -> Vec<Symbol> {
+    let mut collector = Collector {
+        code,
+        symbols: Vec::new(),
+    };
+    collector.visit_file(file);
+    collector.symbols
+}
+
+/// The symbols `query` matches, best first. Its characters have to appear
+/// in the name in order, ignoring case; names where they're closer
+/// together, nearer the start and shorter rank higher. An empty query
+/// matches everything, in source order.
+pub fn search<'a>(symbols: &'a [Symbol], query: &str) -> Vec<&'a Symbol> {
+    let query = query.trim().to_lowercase();
+    let mut found: Vec<(usize, &Symbol)> = symbols
+        .iter()
+        .filter_map(|symbol| Some((score(&symbol.name.to_lowercase(), &query)?, symbol)))
+        .collect();
+    found.sort_by_key(|(score, symbol)| (*score, symbol.name.len()));
+    found.into_iter().map(|(_, symbol)| symbol).collect()
+}
+
+/// How far apart the characters of `query` are in `name`, plus where the
+/// first one is; `None` if they aren't all there in order.
+fn score(name: &str, query: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut rest = name.char_indices();
+    for wanted in query.chars() {
+        let (at, _) = rest.find(|(_, c)| *c == wanted)?;
+        score += match previous {
+            Some(previous) => at - previous - 1,
+            None => at,
+        };
+        previous = Some(at);
+    }
+    Some(score)
+}
+
+struct Collector<'a> {
+    code: &'a str,
+    symbols: Vec<Symbol>,
+}
+
+impl Collector<'_> {
+    fn push(&mut self, kind: &'static str, ident: &syn::Ident) {
+        let offset = ident.span().byte_range().start;
+        self.symbols.push(Symbol {
+            kind,
+            name: ident.to_string(),
+            offset,
+            line: line_of(self.code, offset),
+        });
+    }
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.push("fn", &item.sig.ident);
+        visit::visit_item_fn(self, item);
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        self.push("fn", &item.sig.ident);
+        visit::visit_impl_item_fn(self, item);
+    }
+
+    fn visit_item_struct(&mut self, item: &'ast syn::ItemStruct) {
+        self.push("struct", &item.ident);
+        visit::visit_item_struct(self, item);
+    }
+
+    fn visit_item_enum(&mut self, item: &'ast syn::ItemEnum) {
+        self.push("enum", &item.ident);
+        visit::visit_item_enum(self, item);
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast syn::ItemTrait) {
+        self.push("trait", &item.ident);
+        visit::visit_item_trait(self, item);
+    }
+
+    fn visit_item_type(&mut self, item: &'ast syn::ItemType) {
+        self.push("type", &item.ident);
+        visit::visit_item_type(self, item);
+    }
+
+    fn visit_item_mod(&mut self, item: &'ast syn::ItemMod) {
+        self.push("mod", &item.ident);
+        visit::visit_item_mod(self, item);
+    }
+
+    fn visit_item_const(&mut self, item: &'ast syn::ItemConst) {
+        self.push("const", &item.ident);
+        visit::visit_item_const(self, item);
+    }
+
+    fn visit_item_static(&mut self, item: &'ast syn::ItemStatic) {
+        self.push("static", &item.ident);
+        visit::visit_item_static(self, item);
+    }
+
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        let mut pat = &local.pat;
+        if let syn::Pat::Type(typed) = pat {
+            pat = &typed.pat;
+        }
+        if let syn::Pat::Ident(ident) = pat {
+            self.push("let", &ident.ident);
+        }
+        visit::visit_local(self, local);
+    }
+
+    // Macro bodies aren't parsed, so there's nothing in them to find.
+    fn visit_macro(&mut self, _: &'ast syn::Macro) {}
+}
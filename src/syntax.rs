@@ -0,0 +1,153 @@
+use std::path::Path;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Builds the syntax set from syntect's bundled definitions plus any
+/// `.sublime-syntax` files found in `extra_folder`. A folder that fails to load
+/// is reported and the bundled set is used on its own.
+pub fn load_syntax_set(extra_folder: Option<&Path>) -> (SyntaxSet, Option<String>) {
+    let defaults = SyntaxSet::load_defaults_newlines();
+    let Some(folder) = extra_folder else {
+        return (defaults, None);
+    };
+
+    let mut builder = defaults.into_builder();
+    match builder.add_from_folder(folder, true) {
+        Ok(()) => (builder.build(), None),
+        Err(e) => (
+            SyntaxSet::load_defaults_newlines(),
+            Some(format!(
+                "Failed to load syntaxes from {}: {}",
+                folder.display(),
+                e
+            )),
+        ),
+    }
+}
+
+/// Picks the syntax for a file with the given extension, falling back to Rust
+/// and then to plain text. The warning is set whenever Rust highlighting
+/// wasn't available; `None` means the set doesn't even contain plain text.
+pub fn find_syntax<'a>(
+    syntax_set: &'a SyntaxSet,
+    extension: Option<&str>,
+) -> (Option<&'a SyntaxReference>, Option<String>) {
+    if let Some(syntax) = extension.and_then(|ext| syntax_set.find_syntax_by_extension(ext)) {
+        return (Some(syntax), None);
+    }
+    if let Some(syntax) = syntax_set.find_syntax_by_extension("rs") {
+        return (Some(syntax), None);
+    }
+    (
+        syntax_set.find_syntax_by_name("Plain Text"),
+        Some(format!(
+            "No syntax definition for `{}` files, highlighting as plain text",
+            extension.unwrap_or("rs")
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::highlight::{self, HighlightCache};
+    use std::path::PathBuf;
+    use syntect::highlighting::ThemeSet;
+    use syntect::parsing::SyntaxSetBuilder;
+
+    const CODE: &str = "fn main() {\n    // note\n    let x = \"s\";\n}\n";
+
+    fn without_rust() -> SyntaxSet {
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_plain_text_syntax();
+        builder.build()
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "crowbar-test-syntax-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Ranges cover `text` end to end, in order and without gaps.
+    fn assert_covers<T>(sections: &[(std::ops::Range<usize>, T)], text: &str) {
+        let mut end = 0;
+        for (range, _) in sections {
+            assert_eq!(range.start, end);
+            end = range.end;
+        }
+        assert_eq!(end, text.len());
+    }
+
+    #[test]
+    fn a_set_without_rust_falls_back_to_plain_text_and_warns() {
+        let set = without_rust();
+        let (syntax, warning) = find_syntax(&set, Some("rs"));
+        assert_eq!(syntax.map(|s| s.name.as_str()), Some("Plain Text"));
+        assert_eq!(
+            warning.as_deref(),
+            Some("No syntax definition for `rs` files, highlighting as plain text")
+        );
+        let (_, warning) = find_syntax(&set, None);
+        assert!(warning.unwrap().contains("`rs`"));
+    }
+
+    #[test]
+    fn the_editor_still_highlights_without_rust() {
+        let set = without_rust();
+        let theme = &ThemeSet::load_defaults().themes["base16-ocean.dark"];
+        let (syntax, _) = find_syntax(&set, Some("rs"));
+        assert_covers(&highlight::sections(CODE, syntax, &set, theme), CODE);
+
+        let mut cache = HighlightCache::default();
+        let highlighted = cache.highlight(CODE, syntax, &set, theme, None);
+        assert_covers(&highlighted.sections, CODE);
+        assert!(highlighted.misspelled.is_empty());
+    }
+
+    #[test]
+    fn an_empty_set_renders_unhighlighted() {
+        let set = SyntaxSetBuilder::new().build();
+        let (syntax, warning) = find_syntax(&set, Some("rs"));
+        assert!(syntax.is_none());
+        assert!(warning.is_some());
+        let theme = &ThemeSet::load_defaults().themes["InspiredGitHub"];
+        assert_covers(&highlight::sections(CODE, syntax, &set, theme), CODE);
+    }
+
+    #[test]
+    fn extra_folders_add_syntaxes() {
+        let dir = scratch_dir("extra");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("toy.sublime-syntax"),
+            "%YAML 1.2\n---\nname: Toy\nfile_extensions: [toy]\nscope: source.toy\ncontexts:\n  main:\n    - match: '\\bfoo\\b'\n      scope: keyword.toy\n",
+        )
+        .unwrap();
+        let (set, error) = load_syntax_set(Some(&dir));
+        assert!(error.is_none(), "{:?}", error);
+        let (syntax, _) = find_syntax(&set, Some("toy"));
+        assert_eq!(syntax.map(|s| s.name.as_str()), Some("Toy"));
+        let (syntax, _) = find_syntax(&set, Some("py"));
+        assert_eq!(syntax.map(|s| s.name.as_str()), Some("Python"));
+        // Unknown extensions still get Rust.
+        let (syntax, warning) = find_syntax(&set, Some("nope"));
+        assert_eq!(syntax.map(|s| s.name.as_str()), Some("Rust"));
+        assert!(warning.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_broken_folder_keeps_the_bundled_set() {
+        let dir = scratch_dir("broken");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("bad.sublime-syntax"), "name: [unclosed\n").unwrap();
+        let (set, error) = load_syntax_set(Some(&dir));
+        assert!(error.unwrap().starts_with("Failed to load syntaxes from"));
+        assert!(set.find_syntax_by_extension("rs").is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
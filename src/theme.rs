@@ -0,0 +1,283 @@
+use eframe::egui::Color32;
+use syntect::highlighting::{Color, Theme};
+
+/// Editor chrome colors that have to stay readable on top of the syntax
+/// theme's background.
+#[derive(Clone, Copy)]
+pub struct EditorColors {
+    pub background: Color32,
+    pub gutter: Color32,
+    pub current_line: Color32,
+    pub selection: Color32,
+    pub error: Color32,
+    pub annotation: Color32,
+}
+
+/// Per-color manual overrides set from the settings window.
+#[derive(Clone, Copy, Default)]
+pub struct ColorOverrides {
+    pub gutter: Option<Color32>,
+    pub current_line: Option<Color32>,
+    pub selection: Option<Color32>,
+    pub error: Option<Color32>,
+    pub annotation: Option<Color32>,
+}
+
+/// Minimum contrast ratio for text-like chrome (gutter numbers, errors,
+/// annotations) against the editor background.
+const MIN_TEXT_CONTRAST: f32 = 3.0;
+
+impl EditorColors {
+    /// Derives the chrome colors from a syntect theme, computing
+    /// contrast-safe substitutes for anything the theme leaves out.
+    pub fn from_theme(theme: &Theme) -> Self {
+        let settings = &theme.settings;
+        let background = settings
+            .background
+            .map(|c| over(c, Color32::BLACK))
+            .unwrap_or(Color32::from_gray(27));
+        let foreground = settings
+            .foreground
+            .map(|c| over(c, background))
+            .filter(|&c| contrast_ratio(c, background) >= MIN_TEXT_CONTRAST)
+            .unwrap_or_else(|| black_or_white(background));
+        let caret = settings
+            .caret
+            .map(|c| over(c, background))
+            .unwrap_or(foreground);
+
+        let gutter = settings
+            .gutter_foreground
+            .map(|c| over(c, background))
+            .filter(|&c| contrast_ratio(c, background) >= MIN_TEXT_CONTRAST)
+            .unwrap_or_else(|| readable_blend(foreground, background, 0.5));
+        let current_line = settings
+            .line_highlight
+            .map(|c| over(c, background))
+            .filter(|&c| c != background)
+            .unwrap_or_else(|| blend(background, foreground, 0.08));
+        let selection = settings
+            .selection
+            .map(|c| over(c, background))
+            .filter(|&c| c != background)
+            .unwrap_or_else(|| blend(background, caret, 0.3));
+        let error = ensure_contrast(
+            if is_dark(background) {
+                Color32::from_rgb(240, 90, 90)
+            } else {
+                Color32::from_rgb(190, 30, 30)
+            },
+            background,
+        );
+        let annotation = settings
+            .accent
+            .map(|c| over(c, background))
+            .filter(|&c| contrast_ratio(c, background) >= MIN_TEXT_CONTRAST)
+            .unwrap_or_else(|| ensure_contrast(blend(foreground, caret, 0.5), background));
+
+        Self {
+            background,
+            gutter,
+            current_line,
+            selection,
+            error,
+            annotation,
+        }
+    }
+
+    pub fn with_overrides(mut self, overrides: &ColorOverrides) -> Self {
+        self.gutter = overrides.gutter.unwrap_or(self.gutter);
+        self.current_line = overrides.current_line.unwrap_or(self.current_line);
+        self.selection = overrides.selection.unwrap_or(self.selection);
+        self.error = overrides.error.unwrap_or(self.error);
+        self.annotation = overrides.annotation.unwrap_or(self.annotation);
+        self
+    }
+}
+
+/// Composites a possibly translucent theme color over `base`.
+fn over(color: Color, base: Color32) -> Color32 {
+    blend(
+        base,
+        Color32::from_rgb(color.r, color.g, color.b),
+        color.a as f32 / 255.0,
+    )
+}
+
+fn blend(from: Color32, to: Color32, t: f32) -> Color32 {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color32::from_rgb(
+        mix(from.r(), to.r()),
+        mix(from.g(), to.g()),
+        mix(from.b(), to.b()),
+    )
+}
+
+/// Blends `color` toward `background` by `t`, backing off until the result is
+/// still readable.
+fn readable_blend(color: Color32, background: Color32, mut t: f32) -> Color32 {
+    while t > 0.0 {
+        let candidate = blend(color, background, t);
+        if contrast_ratio(candidate, background) >= MIN_TEXT_CONTRAST {
+            return candidate;
+        }
+        t -= 0.05;
+    }
+    ensure_contrast(color, background)
+}
+
+/// Pushes `color` toward black or white until it reaches the minimum contrast.
+fn ensure_contrast(color: Color32, background: Color32) -> Color32 {
+    let target = black_or_white(background);
+    let mut t = 0.0;
+    let mut candidate = color;
+    while contrast_ratio(candidate, background) < MIN_TEXT_CONTRAST && t < 1.0 {
+        t += 0.1;
+        candidate = blend(color, target, t);
+    }
+    candidate
+}
+
+fn black_or_white(background: Color32) -> Color32 {
+    if is_dark(background) {
+        Color32::WHITE
+    } else {
+        Color32::BLACK
+    }
+}
+
+fn is_dark(color: Color32) -> bool {
+    relative_luminance(color) < 0.5
+}
+
+/// WCAG 2.x relative luminance.
+fn relative_luminance(color: Color32) -> f32 {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r()) + 0.7152 * channel(color.g()) + 0.0722 * channel(color.b())
+}
+
+/// WCAG contrast ratio between two opaque colors, from 1.0 to 21.0.
+pub fn contrast_ratio(a: Color32, b: Color32) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntect::highlighting::ThemeSet;
+
+    const BUNDLED: [&str; 3] = ["base16-ocean.dark", "InspiredGitHub", "Solarized (light)"];
+
+    fn assert_readable(colors: &EditorColors, theme: &str) {
+        for (name, color) in [
+            ("gutter", colors.gutter),
+            ("error", colors.error),
+            ("annotation", colors.annotation),
+        ] {
+            let ratio = contrast_ratio(color, colors.background);
+            assert!(
+                ratio >= MIN_TEXT_CONTRAST,
+                "{} {} has contrast {:.2}",
+                theme,
+                name,
+                ratio
+            );
+        }
+        for (name, tint) in [
+            ("current line", colors.current_line),
+            ("selection", colors.selection),
+        ] {
+            assert_ne!(tint, colors.background, "{} {} is invisible", theme, name);
+        }
+    }
+
+    #[test]
+    fn bundled_themes_get_readable_chrome() {
+        let themes = ThemeSet::load_defaults().themes;
+        for name in BUNDLED {
+            let colors = EditorColors::from_theme(&themes[name]);
+            assert_readable(&colors, name);
+        }
+    }
+
+    #[test]
+    fn light_and_dark_themes_get_matching_error_colors() {
+        let themes = ThemeSet::load_defaults().themes;
+        let dark = EditorColors::from_theme(&themes["base16-ocean.dark"]);
+        let light = EditorColors::from_theme(&themes["InspiredGitHub"]);
+        assert!(is_dark(dark.background) && !is_dark(light.background));
+        assert!(relative_luminance(dark.error) > relative_luminance(light.error));
+    }
+
+    #[test]
+    fn a_theme_without_settings_falls_back() {
+        let colors = EditorColors::from_theme(&Theme::default());
+        assert_eq!(colors.background, Color32::from_gray(27));
+        assert_readable(&colors, "empty");
+    }
+
+    #[test]
+    fn unreadable_theme_colors_are_replaced() {
+        let white = Color {
+            r: 255,
+            g: 255,
+            b: 255,
+            a: 255,
+        };
+        let mut theme = Theme::default();
+        theme.settings.background = Some(white);
+        theme.settings.foreground = Some(Color { r: 250, ..white });
+        theme.settings.gutter_foreground = Some(Color { r: 240, ..white });
+        theme.settings.accent = Some(Color { b: 230, ..white });
+        theme.settings.line_highlight = Some(white);
+        theme.settings.selection = Some(Color { a: 0, ..white });
+        let colors = EditorColors::from_theme(&theme);
+        assert_eq!(colors.background, Color32::WHITE);
+        assert_readable(&colors, "washed out");
+    }
+
+    #[test]
+    fn translucent_colors_are_composited_over_the_background() {
+        let background = Color32::from_rgb(0, 0, 100);
+        let half_red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 128,
+        };
+        assert_eq!(over(half_red, background), Color32::from_rgb(128, 0, 50));
+    }
+
+    #[test]
+    fn overrides_win_over_derived_colors() {
+        let derived = EditorColors::from_theme(&Theme::default());
+        let overrides = ColorOverrides {
+            gutter: Some(Color32::RED),
+            selection: Some(Color32::GREEN),
+            ..ColorOverrides::default()
+        };
+        let colors = derived.with_overrides(&overrides);
+        assert_eq!(colors.gutter, Color32::RED);
+        assert_eq!(colors.selection, Color32::GREEN);
+        assert_eq!(colors.current_line, derived.current_line);
+        assert_eq!(colors.error, derived.error);
+    }
+
+    #[test]
+    fn contrast_ratio_spans_one_to_twenty_one() {
+        assert!((contrast_ratio(Color32::BLACK, Color32::WHITE) - 21.0).abs() < 0.01);
+        assert!((contrast_ratio(Color32::RED, Color32::RED) - 1.0).abs() < 0.001);
+        assert_eq!(
+            contrast_ratio(Color32::BLACK, Color32::GRAY),
+            contrast_ratio(Color32::GRAY, Color32::BLACK)
+        );
+    }
+}
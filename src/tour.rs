@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use eframe::egui;
+
+/// App events that complete a step without pressing Next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    FileLoaded,
+    ValueEdited,
+    RunFinished,
+}
+
+pub struct Step {
+    pub title: &'static str,
+    pub text: &'static str,
+    /// Widget the step points at, registered with [`Tour::anchor`].
+    pub anchor: Option<egui::Id>,
+    pub advance_on: Option<Event>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    Active,
+    Completed,
+    Skipped,
+}
+
+/// Ordered steps shown one at a time in an overlay next to the widget each
+/// step is about.
+pub struct Tour {
+    steps: Vec<Step>,
+    current: usize,
+    status: Status,
+    /// Widget rects from the current frame, keyed by anchor id.
+    anchors: HashMap<egui::Id, egui::Rect>,
+}
+
+pub fn anchor_id(name: &str) -> egui::Id {
+    egui::Id::new(("tour_anchor", name))
+}
+
+impl Tour {
+    pub fn new(steps: Vec<Step>) -> Self {
+        let status = if steps.is_empty() {
+            Status::Completed
+        } else {
+            Status::Active
+        };
+        Self {
+            steps,
+            current: 0,
+            status,
+            anchors: HashMap::new(),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn current(&self) -> Option<(usize, &Step)> {
+        (self.status == Status::Active).then(|| (self.current, &self.steps[self.current]))
+    }
+
+    pub fn next(&mut self) {
+        if self.status != Status::Active {
+            return;
+        }
+        if self.current + 1 < self.steps.len() {
+            self.current += 1;
+        } else {
+            self.status = Status::Completed;
+        }
+    }
+
+    pub fn skip(&mut self) {
+        if self.status == Status::Active {
+            self.status = Status::Skipped;
+        }
+    }
+
+    /// Advances when `event` is what the current step waits for.
+    pub fn notify(&mut self, event: Event) {
+        if self
+            .current()
+            .is_some_and(|(_, step)| step.advance_on == Some(event))
+        {
+            self.next();
+        }
+    }
+
+    /// Records where an anchored widget was drawn this frame.
+    pub fn anchor(&mut self, id: egui::Id, rect: egui::Rect) {
+        self.anchors.insert(id, rect);
+    }
+
+    /// Draws the current step, outlining its anchor and placing the card
+    /// below it (or centered when unanchored). `add_contents` adds
+    /// step-specific widgets given the step index.
+    pub fn show(&mut self, ctx: &egui::Context, add_contents: impl FnOnce(&mut egui::Ui, usize)) {
+        let Some((index, step)) = self.current() else {
+            return;
+        };
+        let anchor = step.anchor.and_then(|id| self.anchors.get(&id)).copied();
+        let (title, text, waits) = (step.title, step.text, step.advance_on.is_some());
+        let count = self.steps.len();
+
+        let mut area = egui::Area::new(egui::Id::new("tour_card")).order(egui::Order::Foreground);
+        match anchor {
+            Some(rect) => {
+                ctx.layer_painter(egui::LayerId::new(
+                    egui::Order::Foreground,
+                    egui::Id::new("tour_highlight"),
+                ))
+                .rect_stroke(
+                    rect.expand(4.0),
+                    4.0,
+                    egui::Stroke::new(2.0, ctx.style().visuals.selection.stroke.color),
+                );
+                let screen = ctx.screen_rect();
+                let pos = egui::pos2(
+                    rect.left().min(screen.right() - 320.0).max(screen.left()),
+                    if rect.bottom() + 160.0 < screen.bottom() {
+                        rect.bottom() + 8.0
+                    } else {
+                        (rect.top() - 168.0).max(screen.top())
+                    },
+                );
+                area = area.fixed_pos(pos);
+            }
+            None => area = area.anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO),
+        }
+
+        let mut action = None;
+        area.show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_max_width(300.0);
+                ui.strong(format!("{} ({}/{})", title, index + 1, count));
+                ui.label(text);
+                if waits {
+                    ui.weak("Doing it moves on to the next step.");
+                }
+                add_contents(ui, index);
+                ui.horizontal(|ui| {
+                    let next = if index + 1 == count { "Finish" } else { "Next" };
+                    if ui.button(next).clicked() {
+                        action = Some(false);
+                    }
+                    if ui.button("Skip tour").clicked() {
+                        action = Some(true);
+                    }
+                });
+            });
+        });
+        match action {
+            Some(true) => self.skip(),
+            Some(false) => self.next(),
+            None => {}
+        }
+        self.anchors.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(title: &'static str, advance_on: Option<Event>) -> Step {
+        Step {
+            title,
+            text: "",
+            anchor: None,
+            advance_on,
+        }
+    }
+
+    fn tour() -> Tour {
+        Tour::new(vec![
+            step("toolchain", None),
+            step("open", Some(Event::FileLoaded)),
+            step("drag", Some(Event::ValueEdited)),
+            step("run", Some(Event::RunFinished)),
+        ])
+    }
+
+    fn title(tour: &Tour) -> Option<&'static str> {
+        tour.current().map(|(_, step)| step.title)
+    }
+
+    #[test]
+    fn next_walks_the_steps_then_completes() {
+        let mut tour = tour();
+        for (index, expected) in ["toolchain", "open", "drag", "run"].into_iter().enumerate() {
+            assert_eq!(tour.status(), Status::Active);
+            assert_eq!(tour.current().map(|(i, _)| i), Some(index));
+            assert_eq!(title(&tour), Some(expected));
+            tour.next();
+        }
+        assert_eq!(tour.status(), Status::Completed);
+        assert!(tour.current().is_none());
+        tour.next();
+        assert_eq!(tour.status(), Status::Completed);
+    }
+
+    #[test]
+    fn events_only_advance_the_step_waiting_for_them() {
+        let mut tour = tour();
+        tour.notify(Event::FileLoaded);
+        assert_eq!(title(&tour), Some("toolchain"));
+        tour.next();
+        tour.notify(Event::RunFinished);
+        tour.notify(Event::ValueEdited);
+        assert_eq!(title(&tour), Some("open"));
+        tour.notify(Event::FileLoaded);
+        assert_eq!(title(&tour), Some("drag"));
+        tour.notify(Event::ValueEdited);
+        tour.notify(Event::ValueEdited);
+        assert_eq!(title(&tour), Some("run"));
+        tour.notify(Event::RunFinished);
+        assert_eq!(tour.status(), Status::Completed);
+    }
+
+    #[test]
+    fn skipping_ends_the_tour_for_good() {
+        let mut tour = tour();
+        tour.next();
+        tour.skip();
+        assert_eq!(tour.status(), Status::Skipped);
+        assert!(tour.current().is_none());
+        tour.next();
+        tour.notify(Event::ValueEdited);
+        assert_eq!(tour.status(), Status::Skipped);
+    }
+
+    #[test]
+    fn a_finished_tour_cant_be_skipped() {
+        let mut tour = Tour::new(vec![step("only", None)]);
+        tour.next();
+        tour.skip();
+        assert_eq!(tour.status(), Status::Completed);
+    }
+
+    #[test]
+    fn an_empty_tour_is_already_complete() {
+        let tour = Tour::new(Vec::new());
+        assert_eq!(tour.status(), Status::Completed);
+        assert!(tour.current().is_none());
+    }
+
+    #[test]
+    fn showing_a_step_uses_and_forgets_this_frames_anchors() {
+        let id = anchor_id("panel");
+        assert_eq!(id, anchor_id("panel"));
+        assert_ne!(id, anchor_id("output"));
+        let mut tour = Tour::new(vec![
+            Step {
+                anchor: Some(id),
+                ..step("anchored", None)
+            },
+            step("centered", None),
+        ]);
+        let ctx = egui::Context::default();
+        let mut shown = Vec::new();
+        for _ in 0..2 {
+            let _ = ctx.run(egui::RawInput::default(), |ctx| {
+                tour.anchor(
+                    id,
+                    egui::Rect::from_min_size(egui::pos2(10.0, 10.0), egui::vec2(50.0, 20.0)),
+                );
+                tour.show(ctx, |_, index| shown.push(index));
+            });
+            assert!(tour.anchors.is_empty());
+            tour.next();
+        }
+        assert_eq!(shown, vec![0, 1]);
+        assert_eq!(tour.status(), Status::Completed);
+    }
+}
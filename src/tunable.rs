@@ -0,0 +1,534 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+use crate::lexer::{tokenize, TokenKind};
+use crate::numeric;
+use crate::VariableValue;
+
+/// Types the panel can edit, offered when adding a tunable.
+pub const TYPES: [&str; 9] = [
+    "i32", "i64", "u32", "usize", "f32", "f64", "bool", "&str", "String",
+];
+
+/// Where a new binding goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    /// First statement of `fn main`.
+    MainTop,
+    /// Before the statement the cursor (a byte offset) is in, or after the
+    /// last one when it's past them.
+    Cursor(usize),
+}
+
+/// The buffer with the binding added.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Insertion {
+    pub code: String,
+    /// The name actually used, suffixed when the requested one was taken.
+    pub name: String,
+    /// Byte offset just past the inserted statement.
+    pub cursor: usize,
+}
+
+/// `value` as a literal of type `ty`, or why it isn't one. Numbers may be
+/// written the way the panel's fields accept them; integers keep the radix
+/// they were typed in.
+pub fn value_literal(ty: &str, value: &str) -> Result<String, String> {
+    let value = value.trim();
+    match ty {
+        "bool" => match value {
+            "true" | "false" => Ok(value.to_string()),
+            _ => Err("Expected true or false".to_string()),
+        },
+        "&str" => Ok(format!("{:?}", value)),
+        "String" => Ok(format!("{:?}.to_string()", value)),
+        _ => match numeric::parse_entry(if value.is_empty() { "0" } else { value }, ty)? {
+            VariableValue::Int(int) => Ok(numeric::Radix::of(value).format(int)),
+            VariableValue::UInt(uint) => Ok(numeric::Radix::of(value).format_unsigned(uint)),
+            VariableValue::Float(value) => Ok(format!("{:?}", value)),
+            _ => Err(format!("Not a {}", ty)),
+        },
+    }
+}
+
+/// Adds `let name: ty = literal;` to `code` at `location`, indented like the
+/// statements around it with `indent_unit` per level. A name already bound
+/// in the enclosing function gets a `_2`, `_3`, … suffix. An empty buffer
+/// gets a `fn main` to hold the binding.
+pub fn insert(
+    code: &str,
+    name: &str,
+    ty: &str,
+    literal: &str,
+    location: Location,
+    indent_unit: &str,
+) -> Result<Insertion, String> {
+    if syn::parse_str::<syn::Ident>(name).is_err() {
+        return Err(format!("`{}` isn't a valid variable name", name));
+    }
+    if code.trim().is_empty() {
+        let statement = format!("let {}: {} = {};", name, ty, literal);
+        let code = format!("fn main() {{\n{}{}\n}}\n", indent_unit, statement);
+        let cursor = code.find(&statement).map_or(0, |at| at + statement.len());
+        return Ok(Insertion {
+            code,
+            name: name.to_string(),
+            cursor,
+        });
+    }
+    let ast = crate::parse_rust_code(code)
+        .map_err(|_| "The code has to parse before a tunable can be added".to_string())?;
+
+    let function = match location {
+        Location::MainTop => ast
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Fn(function) if function.sig.ident == "main" => Some(function),
+                _ => None,
+            })
+            .ok_or("There's no `fn main` to add the tunable to")?,
+        Location::Cursor(offset) => {
+            let mut finder = FunctionFinder {
+                offset,
+                found: None,
+            };
+            finder.visit_file(&ast);
+            finder
+                .found
+                .ok_or("Put the cursor inside a function body first")?
+        }
+    };
+
+    let mut bindings = BindingCollector {
+        names: HashSet::new(),
+    };
+    bindings.visit_item_fn(function);
+    let name = unique_name(&bindings.names, name);
+    let statement = format!("let {}: {} = {};", name, ty, literal);
+
+    let block = match location {
+        Location::MainTop => &*function.block,
+        Location::Cursor(offset) => {
+            let mut finder = BlockFinder {
+                offset,
+                found: &function.block,
+            };
+            finder.visit_block(&function.block);
+            finder.found
+        }
+    };
+    let open = block.brace_token.span.open().byte_range().end;
+    let close = block.brace_token.span.close().byte_range().start;
+    let block_indent = line_indent(code, block.brace_token.span.open().byte_range().start);
+    let inner_indent = format!("{}{}", block_indent, indent_unit);
+
+    let statements: Vec<_> = block
+        .stmts
+        .iter()
+        .map(|stmt| stmt.span().byte_range())
+        .collect();
+    let before = match location {
+        Location::MainTop => statements.first(),
+        Location::Cursor(offset) => statements.iter().find(|span| span.end > offset),
+    };
+    let mut result = code.to_string();
+    let cursor = match (before, statements.last()) {
+        // On its own line above the statement, with the same indentation.
+        (Some(next), _) => {
+            let start = line_start(code, next.start);
+            let indent = line_indent(code, next.start);
+            result.insert_str(start, &format!("{}{}\n", indent, statement));
+            start + indent.len() + statement.len()
+        }
+        (None, Some(last)) => {
+            let indent = line_indent(code, last.start);
+            result.insert_str(last.end, &format!("\n{}{}", indent, statement));
+            last.end + 1 + indent.len() + statement.len()
+        }
+        // An empty block is opened up onto separate lines.
+        (None, None) => {
+            let inner = format!("\n{}{}\n{}", inner_indent, statement, block_indent);
+            result.replace_range(open..close, &inner);
+            open + 1 + inner_indent.len() + statement.len()
+        }
+    };
+    Ok(Insertion {
+        code: result,
+        name,
+        cursor,
+    })
+}
+
+/// Whether there's a literal at `offset` that [`extract`] can move out.
+pub fn can_extract(code: &str, offset: usize) -> bool {
+    crate::parse_rust_code(code)
+        .ok()
+        .and_then(|ast| literal_at(&ast, offset))
+        .is_some()
+}
+
+/// Moves the literal at `offset` in a function body into a new
+/// `let crowbar_vN = literal;` just before the statement it's in, and puts
+/// the name in its place. Negative numbers move with their sign. The
+/// binding has no type, so the literal takes the type its use gives it, as
+/// it did before.
+pub fn extract(code: &str, offset: usize) -> Result<Insertion, String> {
+    let ast = crate::parse_rust_code(code)
+        .map_err(|_| "The code has to parse before a literal can be extracted".to_string())?;
+    let (literal, statement) = literal_at(&ast, offset)
+        .ok_or("Only literals in expressions inside a function body can be extracted")?;
+
+    let taken: HashSet<String> = tokenize(code)
+        .iter()
+        .filter(|token| token.kind == TokenKind::Ident)
+        .map(|token| token.text(code).to_string())
+        .collect();
+    let name = (1..)
+        .map(|n| format!("crowbar_v{}", n))
+        .find(|name| !taken.contains(name))
+        .unwrap_or_default();
+    let binding = format!("let {} = {};", name, &code[literal.clone()]);
+
+    let mut result = code.to_string();
+    result.replace_range(literal.clone(), &name);
+    // On a line of its own above the statement, unless the statement
+    // shares its line with code before it.
+    let start = line_start(code, statement.start);
+    let inserted = if code[start..statement.start].trim().is_empty() {
+        let inserted = format!("{}{}\n", line_indent(code, statement.start), binding);
+        result.insert_str(start, &inserted);
+        inserted
+    } else {
+        let inserted = format!("{} ", binding);
+        result.insert_str(statement.start, &inserted);
+        inserted
+    };
+    Ok(Insertion {
+        code: result,
+        cursor: literal.start + inserted.len() + name.len(),
+        name,
+    })
+}
+
+/// The literal at `offset` that can be replaced by a variable, and the
+/// statement it's in.
+fn literal_at(ast: &syn::File, offset: usize) -> Option<(Range<usize>, Range<usize>)> {
+    let mut finder = LiteralFinder {
+        offset,
+        statements: Vec::new(),
+        found: None,
+    };
+    finder.visit_file(ast);
+    finder.found
+}
+
+/// `name`, or the first of `name_2`, `name_3`, … that isn't in `taken`.
+pub fn unique_name(taken: &HashSet<String>, name: &str) -> String {
+    if !taken.contains(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}_{}", name, n))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
+
+fn line_start(code: &str, offset: usize) -> usize {
+    code[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Leading whitespace of the line `offset` is on.
+fn line_indent(code: &str, offset: usize) -> String {
+    code[line_start(code, offset)..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+/// The innermost function whose body contains `offset`.
+struct FunctionFinder<'ast> {
+    offset: usize,
+    found: Option<&'ast syn::ItemFn>,
+}
+
+impl<'ast> Visit<'ast> for FunctionFinder<'ast> {
+    fn visit_item_fn(&mut self, function: &'ast syn::ItemFn) {
+        let body = function.block.span().byte_range();
+        if body.start < self.offset && self.offset < body.end {
+            self.found = Some(function);
+        }
+        visit::visit_item_fn(self, function);
+    }
+}
+
+/// The innermost block whose braces contain `offset`.
+struct BlockFinder<'ast> {
+    offset: usize,
+    found: &'ast syn::Block,
+}
+
+impl<'ast> Visit<'ast> for BlockFinder<'ast> {
+    fn visit_block(&mut self, block: &'ast syn::Block) {
+        let span = block.span().byte_range();
+        if span.start < self.offset && self.offset < span.end {
+            self.found = block;
+            visit::visit_block(self, block);
+        }
+    }
+
+    // Nested functions are found on their own.
+    fn visit_item_fn(&mut self, _: &'ast syn::ItemFn) {}
+}
+
+/// The literal at `offset` in a function body's statements. Literals where
+/// only a constant will do, like array lengths, patterns and the values of
+/// nested items, aren't found.
+struct LiteralFinder {
+    offset: usize,
+    /// Statements the visit is in, innermost last.
+    statements: Vec<Range<usize>>,
+    found: Option<(Range<usize>, Range<usize>)>,
+}
+
+impl<'ast> Visit<'ast> for LiteralFinder {
+    fn visit_stmt(&mut self, stmt: &'ast syn::Stmt) {
+        self.statements.push(stmt.span().byte_range());
+        visit::visit_stmt(self, stmt);
+        self.statements.pop();
+    }
+
+    fn visit_item(&mut self, item: &'ast syn::Item) {
+        let outer = std::mem::take(&mut self.statements);
+        visit::visit_item(self, item);
+        self.statements = outer;
+    }
+
+    fn visit_expr(&mut self, expr: &'ast syn::Expr) {
+        let literal = match expr {
+            syn::Expr::Lit(_) => true,
+            syn::Expr::Unary(syn::ExprUnary {
+                op: syn::UnOp::Neg(_),
+                expr,
+                ..
+            }) => matches!(
+                &**expr,
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(_) | syn::Lit::Float(_),
+                    ..
+                })
+            ),
+            _ => false,
+        };
+        let span = expr.span().byte_range();
+        if literal && span.start <= self.offset && self.offset < span.end {
+            if let Some(statement) = self.statements.last() {
+                self.found = Some((span, statement.clone()));
+            }
+            return;
+        }
+        visit::visit_expr(self, expr);
+    }
+
+    fn visit_expr_repeat(&mut self, repeat: &'ast syn::ExprRepeat) {
+        self.visit_expr(&repeat.expr);
+    }
+
+    fn visit_pat(&mut self, _: &'ast syn::Pat) {}
+
+    fn visit_type(&mut self, _: &'ast syn::Type) {}
+
+    fn visit_angle_bracketed_generic_arguments(
+        &mut self,
+        _: &'ast syn::AngleBracketedGenericArguments,
+    ) {
+    }
+}
+
+/// Names of the parameters and bindings in a function.
+struct BindingCollector {
+    names: HashSet<String>,
+}
+
+impl<'ast> Visit<'ast> for BindingCollector {
+    fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+        self.names.insert(pat.ident.to_string());
+        visit::visit_pat_ident(self, pat);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_main(code: &str, name: &str) -> Result<Insertion, String> {
+        insert(code, name, "i32", "5", Location::MainTop, "    ")
+    }
+
+    /// `code` with the cursor at the `|`, which is removed.
+    fn at_cursor(code: &str, name: &str) -> Result<Insertion, String> {
+        let offset = code.find('|').unwrap();
+        let code = code.replacen('|', "", 1);
+        insert(&code, name, "i32", "5", Location::Cursor(offset), "    ")
+    }
+
+    /// The inserted code with a `|` where the cursor ends up.
+    fn shown(insertion: &Insertion) -> String {
+        let mut code = insertion.code.clone();
+        code.insert(insertion.cursor, '|');
+        code
+    }
+
+    #[test]
+    fn an_empty_buffer_gets_a_main() {
+        let insertion = at_main("  \n", "speed").unwrap();
+        assert_eq!(
+            shown(&insertion),
+            "fn main() {\n    let speed: i32 = 5;|\n}\n"
+        );
+        assert_eq!(insertion.name, "speed");
+    }
+
+    #[test]
+    fn an_empty_main_is_opened_up() {
+        let insertion = at_main("fn main() {}\n", "speed").unwrap();
+        assert_eq!(
+            shown(&insertion),
+            "fn main() {\n    let speed: i32 = 5;|\n}\n"
+        );
+        let insertion = insert("fn main() {}\n", "x", "f64", "1.0", Location::MainTop, "\t");
+        assert_eq!(
+            insertion.unwrap().code,
+            "fn main() {\n\tlet x: f64 = 1.0;\n}\n"
+        );
+    }
+
+    #[test]
+    fn main_with_statements_gets_it_first() {
+        let code = "use std::fmt;\n\nfn helper() {}\n\nfn main() {\n  let a = 1;\n  helper();\n}\n";
+        let insertion = at_main(code, "speed").unwrap();
+        assert_eq!(
+            shown(&insertion),
+            "use std::fmt;\n\nfn helper() {}\n\nfn main() {\n  let speed: i32 = 5;|\n  let a = 1;\n  helper();\n}\n"
+        );
+    }
+
+    #[test]
+    fn no_main_is_an_error() {
+        assert_eq!(
+            at_main("fn helper() {}\n", "speed"),
+            Err("There's no `fn main` to add the tunable to".to_string())
+        );
+        assert_eq!(
+            at_main("fn main( {\n", "speed"),
+            Err("The code has to parse before a tunable can be added".to_string())
+        );
+        assert_eq!(
+            at_main("fn main() {}\n", "2fast"),
+            Err("`2fast` isn't a valid variable name".to_string())
+        );
+    }
+
+    #[test]
+    fn taken_names_get_a_suffix() {
+        let code = "fn main() {\n    let speed = 1;\n    let speed_2 = 2;\n}\n";
+        let insertion = at_main(code, "speed").unwrap();
+        assert_eq!(insertion.name, "speed_3");
+        assert!(insertion.code.contains("let speed_3: i32 = 5;"));
+        // Only names bound in the same function count.
+        let code = "fn other(speed: i32) {}\nfn main() {}\n";
+        assert_eq!(at_main(code, "speed").unwrap().name, "speed");
+        let code = "fn main() {\n    for speed in 0..3 {}\n}\n";
+        assert_eq!(at_main(code, "speed").unwrap().name, "speed_2");
+    }
+
+    #[test]
+    fn the_cursor_picks_the_statement_and_block() {
+        let code = "fn main() {\n    let a = 1;\n    if a > 0 {\n        fo|o();\n    }\n}\n";
+        assert_eq!(
+            shown(&at_cursor(code, "x").unwrap()),
+            "fn main() {\n    let a = 1;\n    if a > 0 {\n        let x: i32 = 5;|\n        foo();\n    }\n}\n"
+        );
+        // Past the last statement: after it.
+        let code = "fn main() {\n    let a = 1;\n    |\n}\n";
+        assert_eq!(
+            shown(&at_cursor(code, "x").unwrap()),
+            "fn main() {\n    let a = 1;\n    let x: i32 = 5;|\n    \n}\n"
+        );
+        // Any function works, not just main.
+        let code = "fn main() {}\nfn helper(x: i32) {\n    |\n}\n";
+        let insertion = at_cursor(code, "x").unwrap();
+        assert_eq!(insertion.name, "x_2");
+        assert_eq!(
+            insertion.code,
+            "fn main() {}\nfn helper(x: i32) {\n    let x_2: i32 = 5;\n}\n"
+        );
+        assert_eq!(
+            at_cursor("fn main() {}\n|", "x"),
+            Err("Put the cursor inside a function body first".to_string())
+        );
+    }
+
+    #[test]
+    fn values_become_literals_of_the_type() {
+        assert_eq!(value_literal("i32", ""), Ok("0".to_string()));
+        assert_eq!(value_literal("i32", "0x1F"), Ok("0x1F".to_string()));
+        assert_eq!(value_literal("i64", "1 << 20"), Ok("1048576".to_string()));
+        assert_eq!(value_literal("usize", "0b101"), Ok("0b101".to_string()));
+        assert_eq!(value_literal("f64", "1e3"), Ok("1000.0".to_string()));
+        assert_eq!(value_literal("f32", "2"), Ok("2.0".to_string()));
+        assert_eq!(value_literal("bool", " true "), Ok("true".to_string()));
+        assert_eq!(
+            value_literal("&str", "say \"hi\""),
+            Ok("\"say \\\"hi\\\"\"".to_string())
+        );
+        assert_eq!(
+            value_literal("String", "a\\b"),
+            Ok("\"a\\\\b\".to_string()".to_string())
+        );
+        assert_eq!(
+            value_literal("bool", "yes"),
+            Err("Expected true or false".to_string())
+        );
+        assert_eq!(
+            value_literal("u32", "-1"),
+            Err("Out of range for u32".to_string())
+        );
+        assert!(value_literal("f64", "1.0 / 0.0").is_err());
+    }
+
+    #[test]
+    fn literals_are_extracted_above_their_statement() {
+        let code = "fn main() {\n    let area = 3.5 * r * r;\n}\n";
+        let offset = code.find("3.5").unwrap() + 1;
+        assert!(can_extract(code, offset));
+        let insertion = extract(code, offset).unwrap();
+        assert_eq!(
+            shown(&insertion),
+            "fn main() {\n    let crowbar_v1 = 3.5;\n    let area = crowbar_v1| * r * r;\n}\n"
+        );
+
+        let code = "fn main() { let crowbar_v1 = 0; f(-2); }\n";
+        let insertion = extract(code, code.find("-2").unwrap()).unwrap();
+        assert_eq!(insertion.name, "crowbar_v2");
+        assert_eq!(
+            insertion.code,
+            "fn main() { let crowbar_v1 = 0; let crowbar_v2 = -2; f(crowbar_v2); }\n"
+        );
+    }
+
+    #[test]
+    fn constants_cannot_be_extracted() {
+        let code = "const N: usize = 4;\nfn main() {\n    let a = [0u8; 16];\n    match a[0] { 1 => {}, _ => {} }\n    let v: Vec<[u8; 3]> = vec![];\n}\n";
+        for needle in ["4;", "16]", "1 =>", "3]>"] {
+            let offset = code.find(needle).unwrap();
+            assert!(!can_extract(code, offset), "{}", needle);
+        }
+        assert!(can_extract(code, code.find("0u8").unwrap()));
+        assert!(!can_extract(code, code.find("let").unwrap()));
+        assert!(extract(code, code.find("16]").unwrap()).is_err());
+    }
+}
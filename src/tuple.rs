@@ -0,0 +1,83 @@
+use syn::Expr;
+
+use crate::{
+    default_value, literal_like, number_value, numeric_literal, typed_literal, VariableValue,
+};
+
+/// Element types of a tuple type as [`crate::extract_type`] writes it.
+pub fn element_types(var_type: &str) -> Option<Vec<String>> {
+    match syn::parse_str::<syn::Type>(var_type).ok()? {
+        syn::Type::Tuple(tuple) if !tuple.elems.is_empty() => {
+            Some(tuple.elems.iter().map(crate::extract_type).collect())
+        }
+        _ => None,
+    }
+}
+
+/// A tuple of `elements`, types or values, as it's written, with the
+/// trailing comma a single element needs.
+pub fn written(elements: &[String]) -> String {
+    match elements {
+        [single] => format!("({},)", single),
+        _ => format!("({})", elements.join(", ")),
+    }
+}
+
+/// Element types and values of a tuple of literals. Elements take their
+/// type from `element_types` when the declaration gives them, otherwise
+/// from their literal. Tuples with anything but plain literals in them,
+/// nested lists and tuples included, aren't editable.
+pub fn parse(
+    expr: &Expr,
+    element_types: Option<&[String]>,
+) -> Option<(Vec<String>, Vec<VariableValue>)> {
+    let Expr::Tuple(tuple) = expr else {
+        return None;
+    };
+    if tuple.elems.is_empty() || element_types.is_some_and(|types| types.len() != tuple.elems.len())
+    {
+        return None;
+    }
+    let mut types = Vec::with_capacity(tuple.elems.len());
+    let mut values = Vec::with_capacity(tuple.elems.len());
+    for (index, element) in tuple.elems.iter().enumerate() {
+        let declared = element_types.map(|types| types[index].clone());
+        let (ty, value) = match (declared, numeric_literal(element)) {
+            (Some(ty), Some((lit, negative))) => {
+                let value = number_value(lit, negative, &ty);
+                (ty, value)
+            }
+            (Some(ty), None) => (ty, typed_literal(element)?.1),
+            (None, _) => typed_literal(element)?,
+        };
+        let scalar = !matches!(
+            value,
+            VariableValue::List(_) | VariableValue::Tuple(_) | VariableValue::Unknown
+        );
+        if !scalar || std::mem::discriminant(&value) != std::mem::discriminant(&default_value(&ty))
+        {
+            return None;
+        }
+        types.push(ty);
+        values.push(value);
+    }
+    Some((types, values))
+}
+
+/// `items` written over the tuple literal `source`, each element keeping
+/// the suffix, radix and way of building a `String` it's written with.
+pub fn literal(items: &[VariableValue], element_types: &[String], source: &str) -> Option<String> {
+    let Ok(Expr::Tuple(tuple)) = syn::parse_str::<Expr>(source) else {
+        return None;
+    };
+    if tuple.elems.len() != items.len() || element_types.len() != items.len() {
+        return None;
+    }
+    let elements = items
+        .iter()
+        .zip(element_types)
+        .zip(&tuple.elems)
+        .map(|((item, ty), element)| literal_like(item, ty, element))
+        .collect::<Option<Vec<_>>>()?;
+    Some(written(&elements))
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use quote::ToTokens;
+use syn::Type;
+
+/// A declared type, as much of it as the panel understands.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeDesc {
+    /// A type named by one identifier, like `i32`, or a qualified path to
+    /// one the panel edits, like `std::string::String`.
+    Named(String),
+    /// A shared reference to `str`, whatever its lifetime.
+    Str,
+    /// Any other reference, listed by what it refers to but not edited.
+    Reference {
+        mutable: bool,
+        inner: Box<TypeDesc>,
+    },
+    Option(Box<TypeDesc>),
+    Vec(Box<TypeDesc>),
+    Tuple(Vec<TypeDesc>),
+    /// `[T; N]`, with the length as written.
+    Array(Box<TypeDesc>, String),
+    /// Anything else, as written.
+    Other(String),
+}
+
+impl TypeDesc {
+    /// `is_editable` tells which named types a qualified path may resolve
+    /// to.
+    pub fn of(ty: &Type, is_editable: &dyn Fn(&str) -> bool) -> Self {
+        match ty {
+            Type::Path(path) if path.qself.is_none() => {
+                if let Some(ident) = path.path.get_ident() {
+                    return TypeDesc::Named(ident.to_string());
+                }
+                let Some(last) = path.path.segments.last() else {
+                    return TypeDesc::Other(written(ty));
+                };
+                let generic = |name: &str| match &last.arguments {
+                    syn::PathArguments::AngleBracketed(args) if last.ident == name => {
+                        match args.args.iter().collect::<Vec<_>>().as_slice() {
+                            [syn::GenericArgument::Type(inner)] => {
+                                Some(Box::new(TypeDesc::of(inner, is_editable)))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(inner) = generic("Option") {
+                    TypeDesc::Option(inner)
+                } else if let Some(inner) = generic("Vec") {
+                    TypeDesc::Vec(inner)
+                } else if path
+                    .path
+                    .segments
+                    .iter()
+                    .all(|segment| segment.arguments.is_none())
+                    && is_editable(&last.ident.to_string())
+                {
+                    TypeDesc::Named(last.ident.to_string())
+                } else {
+                    TypeDesc::Other(written(ty))
+                }
+            }
+            Type::Reference(reference) => {
+                let inner = TypeDesc::of(&reference.elem, is_editable);
+                if reference.mutability.is_none() && inner == TypeDesc::Named("str".to_string()) {
+                    TypeDesc::Str
+                } else {
+                    TypeDesc::Reference {
+                        mutable: reference.mutability.is_some(),
+                        inner: Box::new(inner),
+                    }
+                }
+            }
+            Type::Array(array) => TypeDesc::Array(
+                Box::new(TypeDesc::of(&array.elem, is_editable)),
+                array.len.to_token_stream().to_string(),
+            ),
+            Type::Paren(paren) => TypeDesc::of(&paren.elem, is_editable),
+            Type::Tuple(tuple) if !tuple.elems.is_empty() => TypeDesc::Tuple(
+                tuple
+                    .elems
+                    .iter()
+                    .map(|elem| TypeDesc::of(elem, is_editable))
+                    .collect(),
+            ),
+            _ => TypeDesc::Other(written(ty)),
+        }
+    }
+}
+
+impl fmt::Display for TypeDesc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeDesc::Named(name) | TypeDesc::Other(name) => f.write_str(name),
+            TypeDesc::Str => f.write_str("&str"),
+            TypeDesc::Reference {
+                mutable: false,
+                inner,
+            } => write!(f, "&{}", inner),
+            TypeDesc::Reference {
+                mutable: true,
+                inner,
+            } => write!(f, "&mut {}", inner),
+            TypeDesc::Option(inner) => write!(f, "Option<{}>", inner),
+            TypeDesc::Vec(inner) => write!(f, "Vec<{}>", inner),
+            TypeDesc::Array(inner, len) => write!(f, "[{}; {}]", inner, len),
+            TypeDesc::Tuple(elems) => {
+                let elems: Vec<String> = elems.iter().map(ToString::to_string).collect();
+                if elems.len() == 1 {
+                    write!(f, "({},)", elems[0])
+                } else {
+                    write!(f, "({})", elems.join(", "))
+                }
+            }
+        }
+    }
+}
+
+/// `ty` roughly as it would be written, without the spaces token printing
+/// puts around punctuation.
+fn written(ty: &Type) -> String {
+    let mut text = ty.to_token_stream().to_string();
+    for (spaced, tight) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" < ", "<"),
+        ("< ", "<"),
+        (" <", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" ;", ";"),
+        ("[ ", "["),
+        (" ]", "]"),
+        ("( ", "("),
+        (" )", ")"),
+        ("& ", "&"),
+    ] {
+        text = text.replace(spaced, tight);
+    }
+    text
+}